@@ -1,5 +1,10 @@
 use {
-    crate::manifest::Manifest,
+    crate::{
+        editor::ManifestEditor,
+        manifest::Manifest,
+        mock::MockProviders,
+        prompt::PromptMode,
+    },
     anyhow::{
         Context,
         Result,
@@ -18,12 +23,27 @@ pub(crate) enum ManualFormat {
     Markdown,
 }
 
+/// Where `autocomplete` puts the completion script.
+#[derive(Debug)]
+pub(crate) enum CompletionTarget {
+    Directory(PathBuf),
+    Install,
+    Print,
+}
+
 pub(crate) enum SealInput {
     Pointer(String),
     Direct(Zeroizing<String>),
     Stdin,
 }
 
+/// A `--recipient` key to locate instead of using the configured one.
+pub(crate) struct RecipientQuery {
+    pub(crate) email: String,
+    pub(crate) fingerprint: Option<String>,
+    pub(crate) keyserver: String,
+}
+
 pub(crate) enum SealTarget {
     Document(String),
     EnvironmentVariable(String),
@@ -51,8 +71,93 @@ impl ChildCommand {
     }
 }
 
+/// A `--var` or `--var-file` flag, applied after all profile values.
+pub(crate) enum VariableOverride {
+    Value { name: String, value: Zeroizing<String> },
+    File(PathBuf),
+}
+
+/// `--only` and `--exclude` glob filters on variable names.
+#[derive(Default)]
+pub(crate) struct VariableFilter {
+    only: Vec<regex::Regex>,
+    exclude: Vec<regex::Regex>,
+}
+
+impl VariableFilter {
+    pub(crate) fn new<'a>(
+        only: impl IntoIterator<Item=&'a String>,
+        exclude: impl IntoIterator<Item=&'a String>,
+    ) -> Result<Self> {
+        let compile = |patterns: &mut dyn Iterator<Item=&'a String>| {
+            patterns
+                .map(|pattern| {
+                    crate::manifest::glob(pattern).with_context(|| format!("Invalid variable pattern '{}'", pattern))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            only: compile(&mut only.into_iter())?,
+            exclude: compile(&mut exclude.into_iter())?,
+        })
+    }
+
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|pattern| pattern.is_match(name)))
+            && !self.exclude.iter().any(|pattern| pattern.is_match(name))
+    }
+}
+
+/// What `unlock` resolves, set by `--no-files` and `--files-only`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Materialization {
+    All,
+    Environment,
+    Files,
+}
+
+impl Materialization {
+    pub(crate) fn environment(self) -> bool {
+        self != Self::Files
+    }
+
+    pub(crate) fn files(self) -> bool {
+        self != Self::Environment
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    Shell,
+    Json,
+    Dotenv,
+    GitlabDotenv,
+    SystemdCreds,
+}
+
+impl OutputFormat {
+    fn parse(format: Option<&str>) -> Self {
+        match format {
+            | Some("json") => Self::Json,
+            | Some("dotenv") => Self::Dotenv,
+            | Some("gitlab-dotenv") => Self::GitlabDotenv,
+            | Some("systemd-creds") => Self::SystemdCreds,
+            | _ => Self::Shell,
+        }
+    }
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            | Self::Shell => "sh",
+            | Self::Json => "json",
+            | Self::Dotenv | Self::GitlabDotenv => "env",
+            | Self::SystemdCreds => "conf",
+        }
+    }
+}
+
 pub(crate) enum UnlockAction {
-    Print,
+    Print { format: OutputFormat, reveal: bool },
     Run(ChildCommand),
 }
 
@@ -62,7 +167,7 @@ pub(crate) enum Command {
         format: ManualFormat,
     },
     Autocomplete {
-        path: PathBuf,
+        target: CompletionTarget,
         shell: clap_complete::Shell,
     },
     Unlock {
@@ -71,17 +176,127 @@ pub(crate) enum Command {
         action: UnlockAction,
         force: bool,
         timeout: Option<std::time::Duration>,
+        prompt: PromptMode,
+        overrides: Vec<VariableOverride>,
+        filter: VariableFilter,
+        mock_providers: Option<MockProviders>,
+        allow_nested: bool,
+        allow_expired: bool,
+        experimental: bool,
+        confirm: Option<String>,
+        strict: bool,
+        snapshot: Option<PathBuf>,
+        materialization: Materialization,
+    },
+    Snapshot {
+        manifest: Manifest,
+        profile_name: String,
+        out: PathBuf,
+        recipient: String,
+        force: bool,
+        prompt: PromptMode,
+        mock_providers: Option<MockProviders>,
+        allow_expired: bool,
+        confirm: Option<String>,
+        strict: bool,
+    },
+    ResolveAll {
+        manifest: Manifest,
+        profiles: Vec<String>,
+        output_dir: PathBuf,
+        format: OutputFormat,
+        force: bool,
+        prompt: PromptMode,
+        mock_providers: Option<MockProviders>,
+        allow_expired: bool,
+        confirm: Option<String>,
+        strict: bool,
+    },
+    Serve {
+        manifest: Manifest,
+        profile_name: String,
+        listen: std::net::SocketAddr,
+        token_file: PathBuf,
+        command: Option<ChildCommand>,
+        prompt: PromptMode,
+        mock_providers: Option<MockProviders>,
+        allow_expired: bool,
+        confirm: Option<String>,
+        metrics_listen: Option<std::net::SocketAddr>,
+        strict: bool,
     },
     Seal {
         manifest: Manifest,
         profile_name: String,
         target: SealTarget,
         input: SealInput,
+        recipient: Option<RecipientQuery>,
+        prompt: PromptMode,
+        mock_providers: Option<MockProviders>,
+    },
+    Audit {
+        manifest: Manifest,
+        profiles: Vec<String>,
+        strict: bool,
+    },
+    VerifyFiles {
+        manifest: Manifest,
+        profile_name: String,
+        prompt: PromptMode,
+        mock_providers: Option<MockProviders>,
+        allow_expired: bool,
+        confirm: Option<String>,
+        strict: bool,
+    },
+    Explain {
+        manifest: Manifest,
+        profile_name: String,
+        variable: String,
+    },
+    Search {
+        manifest: Manifest,
+        pattern: regex::Regex,
+    },
+    Set {
+        manifest: Manifest,
+        profile_name: String,
+        key: Vec<String>,
+        value: String,
+    },
+    Remove {
+        manifest: Manifest,
+        profile_name: String,
+        key: Vec<String>,
+    },
+    CompleteKeys {
+        /// `None` lets the shell complete the key itself.
+        target: Option<(Manifest, String)>,
+    },
+    Import {
+        format: crate::import::ImportFormat,
+        source: PathBuf,
+        path: PathBuf,
+        force: bool,
+        profile_name: String,
+        public_key: Option<PathBuf>,
+        recipient: Option<RecipientQuery>,
+        key_source: Option<String>,
+    },
+    GenerateKey {
+        name: String,
+        email: String,
+        store: crate::keys::KeyStore,
+        protect: bool,
+        prompt: PromptMode,
     },
     Init {
         path: PathBuf,
         force: bool,
     },
+    Clean {
+        dry_run: bool,
+    },
+    Schema,
 }
 
 pub(crate) struct ClapArgumentLoader {}
@@ -99,6 +314,92 @@ impl ClapArgumentLoader {
         }
     }
 
+    fn get_config_path(matches: &clap::ArgMatches) -> Result<PathBuf> {
+        if matches.value_source("config") == Some(clap::parser::ValueSource::DefaultValue) {
+            return Ok(Manifest::locate_default(&std::env::current_dir()?));
+        }
+        Self::get_absolute_path(matches, "config")
+    }
+
+    /// The manifest and profile when the next word is the key of `set` or `rm`.
+    fn key_completion_target(words: &[&String]) -> Option<(Manifest, String)> {
+        let matches = Self::root_command()
+            .ignore_errors(true)
+            .try_get_matches_from(words)
+            .ok()?;
+        let (name, subc) = matches.subcommand()?;
+        if !matches!(name, "set" | "rm") || subc.contains_id("key") {
+            return None;
+        }
+        // The last word may be an option still waiting for its value.
+        let last = words.last()?;
+        let root = Self::root_command();
+        let mut arguments = root.get_arguments().chain(root.find_subcommand(name)?.get_arguments());
+        let pending = arguments.any(|argument| {
+            argument.get_action().takes_values()
+                && (argument
+                    .get_long()
+                    .is_some_and(|long| last.strip_prefix("--") == Some(long))
+                    || argument
+                        .get_short()
+                        .is_some_and(|short| last.as_str() == format!("-{}", short)))
+        });
+        if pending {
+            return None;
+        }
+        let manifest = Manifest::load(Self::get_config_path(subc).ok()?).ok()?;
+        let profile_name = subc.get_one::<String>("profile")?.clone();
+        Some((manifest, profile_name))
+    }
+
+    fn get_variable_overrides(matches: &clap::ArgMatches) -> Result<Vec<VariableOverride>> {
+        let mut overrides = Vec::new();
+        if let (Some(values), Some(indices)) = (matches.get_many::<String>("var"), matches.indices_of("var")) {
+            for (value, index) in values.zip(indices) {
+                let (name, value) = value
+                    .split_once('=')
+                    .with_context(|| format!("Invalid --var '{}': expected KEY=VALUE", value))?;
+                overrides.push((index, VariableOverride::Value {
+                    name: name.to_string(),
+                    value: Zeroizing::new(value.to_string()),
+                }));
+            }
+        }
+        if let (Some(paths), Some(indices)) = (matches.get_many::<String>("var_file"), matches.indices_of("var_file")) {
+            for (path, index) in paths.zip(indices) {
+                let path = std::path::Path::new(path);
+                let path = if path.is_absolute() {
+                    path.to_path_buf().clean()
+                } else {
+                    std::env::current_dir()?.join(path).clean()
+                };
+                overrides.push((index, VariableOverride::File(path)));
+            }
+        }
+        overrides.sort_by_key(|(index, _)| *index);
+        Ok(overrides.into_iter().map(|(_, value)| value).collect())
+    }
+
+    fn get_prompt_mode(matches: &clap::ArgMatches) -> Result<PromptMode> {
+        let value = match matches.get_one::<String>("prompt") {
+            | Some(value) => value.clone(),
+            | None => {
+                match std::env::var("SECENV_PROMPT") {
+                    | Ok(value) if !value.is_empty() => value,
+                    | _ => return Ok(PromptMode::Auto),
+                }
+            },
+        };
+        value.parse().context("Invalid --prompt value")
+    }
+
+    fn get_mock_providers(matches: &clap::ArgMatches) -> Result<Option<MockProviders>> {
+        if matches.get_one::<String>("mock_providers").is_none() {
+            return Ok(None);
+        }
+        MockProviders::load(&Self::get_absolute_path(matches, "mock_providers")?).map(Some)
+    }
+
     pub(crate) fn root_command() -> clap::Command {
         clap::Command::new(env!("CARGO_PKG_NAME"))
             .version(env!("CARGO_PKG_VERSION"))
@@ -106,6 +407,46 @@ impl ClapArgumentLoader {
             .author("cchexcode <alexanderh.weber@outlook.com>")
             .propagate_version(true)
             .subcommand_required(false)
+            .arg(
+                clap::Arg::new("plain")
+                    .long("plain")
+                    .global(true)
+                    .action(clap::ArgAction::SetTrue)
+                    .help("Print without colors or table headers, as when not writing to a terminal"),
+            )
+            .arg(
+                clap::Arg::new("max_concurrency")
+                    .long("max-concurrency")
+                    .global(true)
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .help(format!(
+                        "Send at most N requests to secret providers at once [default: {}]",
+                        crate::throttle::DEFAULT_MAX_CONCURRENCY
+                    )),
+            )
+            .arg(
+                clap::Arg::new("gcp_client")
+                    .long("gcp-client")
+                    .global(true)
+                    .value_parser(["auto", "rest", "gcloud"])
+                    .help(
+                        "Read GCP Secret Manager through its REST API and application-default credentials, with \
+                         gcloud, or with the REST API only when such credentials are found [default: \
+                         providers.gcp.client, or auto]",
+                    ),
+            )
+            .arg(
+                clap::Arg::new("aws_client")
+                    .long("aws-client")
+                    .global(true)
+                    .value_parser(["auto", "rest", "cli"])
+                    .help(
+                        "Read AWS Secrets Manager with curl through its API and the standard credential chain, with \
+                         the aws CLI, or with the API only when a region and such credentials are found [default: \
+                         providers.aws.client, or auto]",
+                    ),
+            )
             .subcommand(
                 clap::Command::new("man")
                     .about("Renders the manual.")
@@ -121,13 +462,36 @@ impl ClapArgumentLoader {
             .subcommand(
                 clap::Command::new("autocomplete")
                     .about("Renders shell completion scripts.")
-                    .arg(clap::Arg::new("out").short('o').long("out").required(true))
+                    .arg(
+                        clap::Arg::new("out")
+                            .short('o')
+                            .long("out")
+                            .value_name("DIR")
+                            .help("Write the script into this directory"),
+                    )
+                    .arg(
+                        clap::Arg::new("install")
+                            .long("install")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Write the script to the shell's per-user completion directory"),
+                    )
+                    .arg(
+                        clap::Arg::new("print")
+                            .long("print")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Print the script to stdout, e.g. for `source <(secenv autocomplete --print)`"),
+                    )
+                    .group(
+                        clap::ArgGroup::new("target")
+                            .args(["out", "install", "print"])
+                            .required(true),
+                    )
                     .arg(
                         clap::Arg::new("shell")
                             .short('s')
                             .long("shell")
                             .value_parser(["bash", "zsh", "fish", "elvish", "powershell"])
-                            .required(true),
+                            .help("Shell to complete for; detected from $SHELL when omitted"),
                     ),
             )
             .subcommand(
@@ -138,7 +502,8 @@ impl ClapArgumentLoader {
                             .short('c')
                             .long("config")
                             .required(false)
-                            .default_value("secenv.conf"),
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
                     )
                     .arg(
                         clap::Arg::new("profile")
@@ -154,6 +519,116 @@ impl ClapArgumentLoader {
                             .action(clap::ArgAction::SetTrue)
                             .help("Overwrite existing files defined in the manifest"),
                     )
+                    .arg(
+                        clap::Arg::new("allow_nested")
+                            .long("allow-nested")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Allow running inside a command started by another secenv unlock"),
+                    )
+                    .arg(
+                        clap::Arg::new("allow_expired")
+                            .long("allow-expired")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Unlock profiles and variables past their 'expires' time"),
+                    )
+                    .arg(
+                        clap::Arg::new("experimental")
+                            .long("experimental")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Run commands outside the profile's exec.allow list"),
+                    )
+                    .arg(
+                        clap::Arg::new("confirm")
+                            .long("confirm")
+                            .value_name("ANSWER")
+                            .help("Answer the profile's confirmation without a prompt: yes, or its phrase"),
+                    )
+                    .arg(
+                        clap::Arg::new("strict")
+                            .long("strict")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Reject PGP values not signed by one of the manifest's signers"),
+                    )
+                    .arg(
+                        clap::Arg::new("from_snapshot")
+                            .long("from-snapshot")
+                            .value_name("PATH")
+                            .help("Replay the values and files of a snapshot instead of resolving the profile"),
+                    )
+                    .arg(clap::Arg::new("prompt").long("prompt").value_name("MODE").help(
+                        "Where to ask for key passwords: auto, tty, never, pinentry[:<program>], or fd:<number> [env: \
+                         SECENV_PROMPT]",
+                    ))
+                    .arg(
+                        clap::Arg::new("mock_providers")
+                            .long("mock-providers")
+                            .value_name("FIXTURES")
+                            .help("Answer provider, password-store, and GPG lookups from a JSON fixture file instead"),
+                    )
+                    .arg(
+                        clap::Arg::new("var")
+                            .long("var")
+                            .value_name("KEY=VALUE")
+                            .action(clap::ArgAction::Append)
+                            .help("Set a variable, overriding the profile; repeatable"),
+                    )
+                    .arg(
+                        clap::Arg::new("var_file")
+                            .long("var-file")
+                            .value_name("PATH")
+                            .action(clap::ArgAction::Append)
+                            .help("Set variables from a KEY=VALUE file, overriding the profile; repeatable"),
+                    )
+                    .arg(
+                        clap::Arg::new("no_files")
+                            .long("no-files")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Resolve only variables; skip the profile's files and sealed documents"),
+                    )
+                    .arg(
+                        clap::Arg::new("files_only")
+                            .long("files-only")
+                            .action(clap::ArgAction::SetTrue)
+                            .conflicts_with_all(["no_files", "var", "var_file", "only", "exclude"])
+                            .requires("command")
+                            .help("Create only the profile's files and sealed documents; set no profile variables"),
+                    )
+                    .arg(
+                        clap::Arg::new("only")
+                            .long("only")
+                            .value_name("GLOB")
+                            .action(clap::ArgAction::Append)
+                            .help(
+                                "Only set variables matching the pattern; env.from sources are still read in full; \
+                                 repeatable",
+                            ),
+                    )
+                    .arg(
+                        clap::Arg::new("exclude")
+                            .long("exclude")
+                            .value_name("GLOB")
+                            .action(clap::ArgAction::Append)
+                            .help("Skip variables matching the pattern; repeatable"),
+                    )
+                    .arg(
+                        clap::Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_parser(["shell", "json", "dotenv", "gitlab-dotenv", "systemd-creds"])
+                            .default_value("shell")
+                            .conflicts_with("command")
+                            .help(
+                                "Print variables as shell exports, as JSON with their sources, as dotenv lines, as a \
+                                 GitLab dotenv report, or as encrypted systemd credentials",
+                            ),
+                    )
+                    .arg(
+                        clap::Arg::new("reveal")
+                            .long("reveal")
+                            .action(clap::ArgAction::SetTrue)
+                            .conflicts_with("command")
+                            .help("Include plaintext values in JSON output"),
+                    )
                     .arg(
                         clap::Arg::new("timeout")
                             .long("timeout")
@@ -170,6 +645,235 @@ impl ClapArgumentLoader {
                             .value_name("COMMAND"),
                     ),
             )
+            .subcommand(
+                clap::Command::new("serve")
+                    .about("Serves unlocked values over a token-authenticated HTTP API on a loopback address.")
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .required(false)
+                            .default_value("default"),
+                    )
+                    .arg(
+                        clap::Arg::new("listen")
+                            .long("listen")
+                            .value_name("ADDRESS")
+                            .value_parser(clap::value_parser!(std::net::SocketAddr))
+                            .default_value("127.0.0.1:0")
+                            .help("Loopback address and port to listen on; port 0 picks a free one"),
+                    )
+                    .arg(
+                        clap::Arg::new("token_file")
+                            .long("token-file")
+                            .value_name("PATH")
+                            .required(true)
+                            .help("Where to write the bearer token; removed when serving stops"),
+                    )
+                    .arg(
+                        clap::Arg::new("allow_expired")
+                            .long("allow-expired")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Serve profiles and variables past their 'expires' time"),
+                    )
+                    .arg(
+                        clap::Arg::new("confirm")
+                            .long("confirm")
+                            .value_name("ANSWER")
+                            .help("Answer the profile's confirmation without a prompt: yes, or its phrase"),
+                    )
+                    .arg(
+                        clap::Arg::new("strict")
+                            .long("strict")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Reject PGP values not signed by one of the manifest's signers"),
+                    )
+                    .arg(clap::Arg::new("prompt").long("prompt").value_name("MODE").help(
+                        "Where to ask for key passwords: auto, tty, never, pinentry[:<program>], or fd:<number> [env: \
+                         SECENV_PROMPT]",
+                    ))
+                    .arg(
+                        clap::Arg::new("mock_providers")
+                            .long("mock-providers")
+                            .value_name("FIXTURES")
+                            .help("Answer provider, password-store, and GPG lookups from a JSON fixture file instead"),
+                    )
+                    .arg(
+                        clap::Arg::new("metrics_listen")
+                            .long("metrics-listen")
+                            .value_name("ADDRESS")
+                            .value_parser(clap::value_parser!(std::net::SocketAddr))
+                            .help(
+                                "Also serve Prometheus metrics on /metrics at this loopback address, without the token",
+                            ),
+                    )
+                    .arg(
+                        clap::Arg::new("command")
+                            .help("Command to run while serving; serves until interrupted when omitted")
+                            .num_args(1..)
+                            .last(true)
+                            .value_name("COMMAND"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("snapshot")
+                    .about("Captures resolved profiles for replay without provider access.")
+                    .subcommand_required(true)
+                    .subcommand(
+                        clap::Command::new("create")
+                            .about("Resolves a profile and writes its values and files, encrypted to a GPG key.")
+                            .arg(
+                                clap::Arg::new("config")
+                                    .short('c')
+                                    .long("config")
+                                    .required(false)
+                                    .default_value("secenv.conf")
+                                    .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
+                            )
+                            .arg(
+                                clap::Arg::new("profile")
+                                    .short('p')
+                                    .long("profile")
+                                    .required(false)
+                                    .default_value("default"),
+                            )
+                            .arg(
+                                clap::Arg::new("out")
+                                    .short('o')
+                                    .long("out")
+                                    .value_name("PATH")
+                                    .required(true)
+                                    .help("Path of the snapshot file"),
+                            )
+                            .arg(
+                                clap::Arg::new("recipient")
+                                    .long("recipient")
+                                    .value_name("FINGERPRINT")
+                                    .required(true)
+                                    .help(
+                                        "GPG key to encrypt the snapshot to; its public key is read from the keyring",
+                                    ),
+                            )
+                            .arg(
+                                clap::Arg::new("force")
+                                    .short('f')
+                                    .long("force")
+                                    .action(clap::ArgAction::SetTrue)
+                                    .help("Overwrite an existing snapshot"),
+                            )
+                            .arg(
+                                clap::Arg::new("allow_expired")
+                                    .long("allow-expired")
+                                    .action(clap::ArgAction::SetTrue)
+                                    .help("Capture profiles and variables past their 'expires' time"),
+                            )
+                            .arg(
+                                clap::Arg::new("confirm")
+                                    .long("confirm")
+                                    .value_name("ANSWER")
+                                    .help("Answer the profile's confirmation without a prompt: yes, or its phrase"),
+                            )
+                            .arg(
+                                clap::Arg::new("strict")
+                                    .long("strict")
+                                    .action(clap::ArgAction::SetTrue)
+                                    .help("Reject PGP values not signed by one of the manifest's signers"),
+                            )
+                            .arg(clap::Arg::new("prompt").long("prompt").value_name("MODE").help(
+                                "Where to ask for key passwords: auto, tty, never, pinentry[:<program>], or \
+                                 fd:<number> [env: SECENV_PROMPT]",
+                            ))
+                            .arg(
+                                clap::Arg::new("mock_providers")
+                                    .long("mock-providers")
+                                    .value_name("FIXTURES")
+                                    .help(
+                                        "Answer provider, password-store, and GPG lookups from a JSON fixture file \
+                                         instead",
+                                    ),
+                            ),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("resolve-all")
+                    .about("Resolves the variables of several profiles in one run and writes a file per profile.")
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .value_name("PATTERN")
+                            .action(clap::ArgAction::Append)
+                            .help("Only resolve profiles matching the glob, e.g. 'prod/*'; repeatable"),
+                    )
+                    .arg(
+                        clap::Arg::new("output_dir")
+                            .long("output-dir")
+                            .value_name("DIR")
+                            .required(true)
+                            .help("Directory to write <profile>.<extension> files to"),
+                    )
+                    .arg(
+                        clap::Arg::new("format")
+                            .long("format")
+                            .value_parser(["shell", "json", "dotenv", "gitlab-dotenv", "systemd-creds"])
+                            .default_value("dotenv")
+                            .help(
+                                "Write shell exports, JSON with sources and values, dotenv lines, GitLab dotenv, or \
+                                 encrypted systemd credentials",
+                            ),
+                    )
+                    .arg(
+                        clap::Arg::new("force")
+                            .short('f')
+                            .long("force")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Overwrite existing output files"),
+                    )
+                    .arg(
+                        clap::Arg::new("allow_expired")
+                            .long("allow-expired")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Resolve profiles and variables past their 'expires' time"),
+                    )
+                    .arg(
+                        clap::Arg::new("confirm")
+                            .long("confirm")
+                            .value_name("ANSWER")
+                            .help("Answer every profile's confirmation without a prompt: yes, or its phrase"),
+                    )
+                    .arg(
+                        clap::Arg::new("strict")
+                            .long("strict")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Reject PGP values not signed by one of the manifest's signers"),
+                    )
+                    .arg(clap::Arg::new("prompt").long("prompt").value_name("MODE").help(
+                        "Where to ask for key passwords: auto, tty, never, pinentry[:<program>], or fd:<number> [env: \
+                         SECENV_PROMPT]",
+                    ))
+                    .arg(
+                        clap::Arg::new("mock_providers")
+                            .long("mock-providers")
+                            .value_name("FIXTURES")
+                            .help("Answer provider, password-store, and GPG lookups from a JSON fixture file instead"),
+                    ),
+            )
             .subcommand(
                 clap::Command::new("seal")
                     .about("Encrypts a value for a configured sealed document or profile environment variable.")
@@ -178,7 +882,8 @@ impl ClapArgumentLoader {
                             .short('c')
                             .long("config")
                             .required(false)
-                            .default_value("secenv.conf"),
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
                     )
                     .arg(
                         clap::Arg::new("profile")
@@ -187,6 +892,16 @@ impl ClapArgumentLoader {
                             .required(false)
                             .default_value("default"),
                     )
+                    .arg(clap::Arg::new("prompt").long("prompt").value_name("MODE").help(
+                        "Where to ask for key passwords: auto, tty, never, pinentry[:<program>], or fd:<number> [env: \
+                         SECENV_PROMPT]",
+                    ))
+                    .arg(
+                        clap::Arg::new("mock_providers")
+                            .long("mock-providers")
+                            .value_name("FIXTURES")
+                            .help("Answer provider, password-store, and GPG lookups from a JSON fixture file instead"),
+                    )
                     .arg(
                         clap::Arg::new("configured_file")
                             .long("for")
@@ -208,6 +923,27 @@ impl ClapArgumentLoader {
                             .conflicts_with("environment_variable")
                             .help("RFC 6901 JSON Pointer to replace in the configured source document"),
                     )
+                    .arg(
+                        clap::Arg::new("recipient")
+                            .long("recipient")
+                            .value_name("EMAIL")
+                            .help("Seal to the PGP key of EMAIL, located via WKD or a keyserver"),
+                    )
+                    .arg(
+                        clap::Arg::new("fingerprint")
+                            .long("fingerprint")
+                            .value_name("FINGERPRINT")
+                            .requires("recipient")
+                            .help("Fingerprint the located key must have; confirms on the terminal when omitted"),
+                    )
+                    .arg(
+                        clap::Arg::new("keyserver")
+                            .long("keyserver")
+                            .value_name("URL")
+                            .requires("recipient")
+                            .default_value(crate::recipient::DEFAULT_KEYSERVER)
+                            .help("Keyserver asked when EMAIL publishes no WKD key"),
+                    )
                     .arg(
                         clap::Arg::new("value")
                             .value_name("VALUE")
@@ -221,6 +957,346 @@ impl ClapArgumentLoader {
                             .multiple(false),
                     ),
             )
+            .subcommand(
+                clap::Command::new("audit")
+                    .about("Reports expired profiles and variables, and variables past their rotation window.")
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .value_name("PATTERN")
+                            .action(clap::ArgAction::Append)
+                            .help("Only audit profiles matching the glob, e.g. 'prod/*'; repeatable"),
+                    )
+                    .arg(
+                        clap::Arg::new("strict")
+                            .long("strict")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Exit with status 1 when anything is reported"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("verify-files")
+                    .about("Reports persisted files of a profile whose content no longer matches the manifest.")
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .required(false)
+                            .default_value("default"),
+                    )
+                    .arg(
+                        clap::Arg::new("allow_expired")
+                            .long("allow-expired")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Verify a profile past its 'expires' time"),
+                    )
+                    .arg(
+                        clap::Arg::new("confirm")
+                            .long("confirm")
+                            .value_name("ANSWER")
+                            .help("Answer the profile's confirmation without a prompt: yes, or its phrase"),
+                    )
+                    .arg(
+                        clap::Arg::new("strict")
+                            .long("strict")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Reject PGP values not signed by one of the manifest's signers"),
+                    )
+                    .arg(clap::Arg::new("prompt").long("prompt").value_name("MODE").help(
+                        "Where to ask for key passwords: auto, tty, never, pinentry[:<program>], or fd:<number> [env: \
+                         SECENV_PROMPT]",
+                    ))
+                    .arg(
+                        clap::Arg::new("mock_providers")
+                            .long("mock-providers")
+                            .value_name("FIXTURES")
+                            .help("Answer provider, password-store, and GPG lookups from a JSON fixture file instead"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("explain")
+                    .about("Shows how a variable of a profile is resolved, without unlocking anything.")
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .required(false)
+                            .default_value("default"),
+                    )
+                    .arg(
+                        clap::Arg::new("variable")
+                            .value_name("VARIABLE")
+                            .required(true)
+                            .help("Environment variable name"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("search")
+                    .about(
+                        "Lists variables, files, and sources of every profile whose name, path, or secret reference \
+                         matches a pattern. Values are never searched.",
+                    )
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON/JSON, or YAML/TOML by extension"),
+                    )
+                    .arg(
+                        clap::Arg::new("ignore_case")
+                            .short('i')
+                            .long("ignore-case")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Match case-insensitively"),
+                    )
+                    .arg(
+                        clap::Arg::new("pattern")
+                            .value_name("PATTERN")
+                            .required(true)
+                            .help("Regular expression, matched anywhere unless anchored"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("set")
+                    .about(
+                        "Sets a key of a profile in the manifest, keeping the comments and formatting of the rest of \
+                         the file.",
+                    )
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON or JSON"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .required(false)
+                            .default_value("default"),
+                    )
+                    .arg(
+                        clap::Arg::new("key")
+                            .value_name("KEY")
+                            .required(true)
+                            .help("Dotted key within the profile, e.g. env.vars.TOKEN"),
+                    )
+                    .arg(
+                        clap::Arg::new("value")
+                            .value_name("VALUE")
+                            .required_unless_present("from_file")
+                            .help(
+                                "HOCON value, e.g. '{ gcs.secret = \"projects/p/secrets/token\" }', or the content to \
+                                 compress with --compress",
+                            ),
+                    )
+                    .arg(
+                        clap::Arg::new("compress")
+                            .long("compress")
+                            .value_name("CODEC")
+                            .value_parser(["gzip", "zstd"])
+                            .help("Store the content compressed as a plain.gzip or plain.zstd value"),
+                    )
+                    .arg(
+                        clap::Arg::new("from_file")
+                            .long("from-file")
+                            .value_name("PATH")
+                            .requires("compress")
+                            .conflicts_with("value")
+                            .help("Read the content to compress from PATH"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("rm")
+                    .about(
+                        "Removes a key of a profile from the manifest, keeping the comments and formatting of the \
+                         rest of the file.",
+                    )
+                    .arg(
+                        clap::Arg::new("config")
+                            .short('c')
+                            .long("config")
+                            .required(false)
+                            .default_value("secenv.conf")
+                            .help("Manifest path; HOCON or JSON"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .required(false)
+                            .default_value("default"),
+                    )
+                    .arg(
+                        clap::Arg::new("key")
+                            .value_name("KEY")
+                            .required(true)
+                            .help("Dotted key within the profile, e.g. env.vars.TOKEN"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("complete-keys")
+                    .about("Prints the variable keys of the profile a partial `set` or `rm` command line selects.")
+                    .hide(true)
+                    .arg(
+                        clap::Arg::new("words")
+                            .value_name("WORD")
+                            .num_args(0..)
+                            .trailing_var_arg(true)
+                            .allow_hyphen_values(true)
+                            .help("The words of the command line before the one being completed"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("import-from")
+                    .about("Converts a SOPS file, chamber export, or teller configuration into a new manifest.")
+                    .arg(
+                        clap::Arg::new("format")
+                            .value_name("FORMAT")
+                            .required(true)
+                            .value_parser(["sops", "chamber", "teller"]),
+                    )
+                    .arg(
+                        clap::Arg::new("source")
+                            .value_name("PATH")
+                            .required(true)
+                            .help("SOPS file, `chamber export --format json` output, or .teller.yml"),
+                    )
+                    .arg(
+                        clap::Arg::new("out")
+                            .short('o')
+                            .long("out")
+                            .default_value("secenv.conf")
+                            .help("Path for the new manifest; HOCON/JSON, or YAML/TOML by extension"),
+                    )
+                    .arg(
+                        clap::Arg::new("force")
+                            .short('f')
+                            .long("force")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Overwrite existing file"),
+                    )
+                    .arg(
+                        clap::Arg::new("profile")
+                            .short('p')
+                            .long("profile")
+                            .default_value("default")
+                            .help("Name of the profile holding the imported variables"),
+                    )
+                    .arg(
+                        clap::Arg::new("public_key")
+                            .long("public-key")
+                            .value_name("PATH")
+                            .conflicts_with("recipient")
+                            .help("Armored public key to encrypt imported values to"),
+                    )
+                    .arg(
+                        clap::Arg::new("recipient")
+                            .long("recipient")
+                            .value_name("EMAIL")
+                            .help("Encrypt imported values to the PGP key of EMAIL, located via WKD or a keyserver"),
+                    )
+                    .arg(
+                        clap::Arg::new("fingerprint")
+                            .long("fingerprint")
+                            .value_name("FINGERPRINT")
+                            .requires("recipient")
+                            .help("Fingerprint the located key must have; confirms on the terminal when omitted"),
+                    )
+                    .arg(
+                        clap::Arg::new("keyserver")
+                            .long("keyserver")
+                            .value_name("URL")
+                            .requires("recipient")
+                            .default_value(crate::recipient::DEFAULT_KEYSERVER)
+                            .help("Keyserver asked when EMAIL publishes no WKD key"),
+                    )
+                    .arg(
+                        clap::Arg::new("key_source")
+                            .long("key-source")
+                            .value_name("KIND:LOCATION")
+                            .help(
+                                "Where the manifest reads the private key: file:<path>, env:<name>, \
+                                 gpg:<fingerprint>, gcs:<secret>, aws:<secret>, azure:<vault>/<secret>, \
+                                 op://<vault>/<item>/<field>, pass:<entry>, gopass:<entry>, or \
+                                 keyring:<service>/<account>; defaults to the GPG keyring",
+                            ),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("keys")
+                    .about("Manages PGP keys for use in manifests.")
+                    .subcommand_required(true)
+                    .subcommand(
+                        clap::Command::new("generate")
+                            .about("Generates an Ed25519/Cv25519 key pair and prints its public key.")
+                            .arg(clap::Arg::new("name").long("name").value_name("NAME").required(true))
+                            .arg(clap::Arg::new("email").long("email").value_name("EMAIL").required(true))
+                            .arg(
+                                clap::Arg::new("out")
+                                    .long("out")
+                                    .value_name("PATH")
+                                    .help("Write the private key to a new file, readable only by you"),
+                            )
+                            .arg(
+                                clap::Arg::new("gcp_secret")
+                                    .long("gcp-secret")
+                                    .value_name("projects/PROJECT/secrets/SECRET")
+                                    .help("Store the private key as a new version of a GCP secret"),
+                            )
+                            .arg(
+                                clap::Arg::new("keyring")
+                                    .long("keyring")
+                                    .action(clap::ArgAction::SetTrue)
+                                    .help("Import the private key into the local GPG keyring"),
+                            )
+                            .arg(
+                                clap::Arg::new("protect")
+                                    .long("protect")
+                                    .action(clap::ArgAction::SetTrue)
+                                    .help("Encrypt the private key with a password"),
+                            )
+                            .arg(clap::Arg::new("prompt").long("prompt").value_name("MODE").help(
+                                "Where to ask for the password: auto, tty, never, pinentry[:<program>], or \
+                                 fd:<number> [env: SECENV_PROMPT]",
+                            ))
+                            .group(
+                                clap::ArgGroup::new("key_store")
+                                    .args(["out", "gcp_secret", "keyring"])
+                                    .required(true)
+                                    .multiple(false),
+                            ),
+                    ),
+            )
             .subcommand(
                 clap::Command::new("init")
                     .about("Initialize a new secenv configuration file.")
@@ -240,10 +1316,42 @@ impl ClapArgumentLoader {
                             .help("Overwrite existing file"),
                     ),
             )
+            .subcommand(
+                clap::Command::new("clean")
+                    .about("Removes temporary files left behind by crashed or killed runs.")
+                    .arg(
+                        clap::Arg::new("dry_run")
+                            .long("dry-run")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Only print what would be cleaned up"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("schema")
+                    .about("Prints the JSON Schema of manifests for editors to validate them with."),
+            )
     }
 
     pub(crate) fn load() -> Result<Command> {
         let mut command = Self::root_command().get_matches();
+        crate::output::set_plain(command.get_flag("plain"));
+        if let Some(max) = command.get_one::<u64>("max_concurrency") {
+            crate::throttle::set_max_concurrency(usize::try_from(*max).unwrap_or(usize::MAX));
+        }
+        if let Some(client) = command.get_one::<String>("gcp_client") {
+            crate::gcp::GcpClient::set_flag(match client.as_str() {
+                | "rest" => crate::gcp::GcpClient::Rest,
+                | "gcloud" => crate::gcp::GcpClient::Gcloud,
+                | _ => crate::gcp::GcpClient::Auto,
+            });
+        }
+        if let Some(client) = command.get_one::<String>("aws_client") {
+            crate::aws::AwsClient::set_flag(match client.as_str() {
+                | "rest" => crate::aws::AwsClient::Rest,
+                | "cli" => crate::aws::AwsClient::Cli,
+                | _ => crate::aws::AwsClient::Auto,
+            });
+        }
 
         let command = if let Some(subc) = command.subcommand_matches("man") {
             Command::Manual {
@@ -259,23 +1367,32 @@ impl ClapArgumentLoader {
                 },
             }
         } else if let Some(subc) = command.subcommand_matches("autocomplete") {
-            Command::Autocomplete {
-                path: Self::get_absolute_path(subc, "out")?,
-                shell: clap_complete::Shell::from_str(
-                    subc.get_one::<String>("shell")
-                        .context("Missing completion shell")?
-                        .as_str(),
-                )
-                .map_err(|error| anyhow::anyhow!("Invalid completion shell: {}", error))?,
-            }
+            let target = if subc.get_flag("install") {
+                CompletionTarget::Install
+            } else if subc.get_flag("print") {
+                CompletionTarget::Print
+            } else {
+                CompletionTarget::Directory(Self::get_absolute_path(subc, "out")?)
+            };
+            let shell = match subc.get_one::<String>("shell") {
+                | Some(shell) => {
+                    clap_complete::Shell::from_str(shell)
+                        .map_err(|error| anyhow::anyhow!("Invalid completion shell: {}", error))?
+                },
+                | None => {
+                    clap_complete::Shell::from_env().context("Cannot detect the shell from $SHELL; pass --shell")?
+                },
+            };
+            Command::Autocomplete { target, shell }
         } else if let Some(subc) = command.subcommand_matches("unlock") {
-            let config_path = Self::get_absolute_path(subc, "config")?;
+            let config_path = Self::get_config_path(subc)?;
             let cfg = Manifest::load(config_path)?;
 
             let profile_name = subc.get_one::<String>("profile").context("Missing profile name")?;
 
             if !cfg.profiles.contains_key(profile_name) {
-                return Err(anyhow::anyhow!("Profile '{}' not found in config", profile_name));
+                return Err(crate::failure::Failure::ProfileNotFound
+                    .error(format!("Profile '{}' not found in config", profile_name)));
             }
 
             let action = match subc.get_many::<String>("command") {
@@ -283,12 +1400,24 @@ impl ClapArgumentLoader {
                     let program = values.next().context("Command is missing its program")?.clone();
                     UnlockAction::Run(ChildCommand::new(program, values.cloned().collect())?)
                 },
-                | None => UnlockAction::Print,
+                | None => {
+                    UnlockAction::Print {
+                        format: OutputFormat::parse(subc.get_one::<String>("output").map(String::as_str)),
+                        reveal: subc.get_flag("reveal"),
+                    }
+                },
             };
             let force = subc.get_flag("force");
             let timeout = subc
                 .get_one::<u64>("timeout")
                 .map(|seconds| std::time::Duration::from_secs(*seconds));
+            let prompt = Self::get_prompt_mode(subc)?;
+            let overrides = Self::get_variable_overrides(subc)?;
+            let filter = VariableFilter::new(
+                subc.get_many::<String>("only").into_iter().flatten(),
+                subc.get_many::<String>("exclude").into_iter().flatten(),
+            )?;
+            let mock_providers = Self::get_mock_providers(subc)?;
 
             Command::Unlock {
                 manifest: cfg,
@@ -296,6 +1425,100 @@ impl ClapArgumentLoader {
                 action,
                 force,
                 timeout,
+                prompt,
+                overrides,
+                filter,
+                mock_providers,
+                allow_nested: subc.get_flag("allow_nested"),
+                allow_expired: subc.get_flag("allow_expired"),
+                experimental: subc.get_flag("experimental"),
+                confirm: subc.get_one::<String>("confirm").cloned(),
+                strict: subc.get_flag("strict"),
+                snapshot: match subc.get_one::<String>("from_snapshot") {
+                    | Some(_) => Some(Self::get_absolute_path(subc, "from_snapshot")?),
+                    | None => None,
+                },
+                materialization: if subc.get_flag("no_files") {
+                    Materialization::Environment
+                } else if subc.get_flag("files_only") {
+                    Materialization::Files
+                } else {
+                    Materialization::All
+                },
+            }
+        } else if let Some(subc) = command
+            .subcommand_matches("snapshot")
+            .and_then(|snapshot| snapshot.subcommand_matches("create"))
+        {
+            let cfg = Manifest::load(Self::get_config_path(subc)?)?;
+            let profile_name = subc.get_one::<String>("profile").context("Missing profile name")?;
+            if !cfg.profiles.contains_key(profile_name) {
+                return Err(crate::failure::Failure::ProfileNotFound
+                    .error(format!("Profile '{}' not found in config", profile_name)));
+            }
+
+            Command::Snapshot {
+                manifest: cfg,
+                profile_name: profile_name.clone(),
+                out: Self::get_absolute_path(subc, "out")?,
+                recipient: subc
+                    .get_one::<String>("recipient")
+                    .context("Missing recipient")?
+                    .clone(),
+                force: subc.get_flag("force"),
+                prompt: Self::get_prompt_mode(subc)?,
+                mock_providers: Self::get_mock_providers(subc)?,
+                allow_expired: subc.get_flag("allow_expired"),
+                confirm: subc.get_one::<String>("confirm").cloned(),
+                strict: subc.get_flag("strict"),
+            }
+        } else if let Some(subc) = command.subcommand_matches("serve") {
+            let cfg = Manifest::load(Self::get_config_path(subc)?)?;
+            let profile_name = subc.get_one::<String>("profile").context("Missing profile name")?;
+            if !cfg.profiles.contains_key(profile_name) {
+                return Err(crate::failure::Failure::ProfileNotFound
+                    .error(format!("Profile '{}' not found in config", profile_name)));
+            }
+            let command = match subc.get_many::<String>("command") {
+                | Some(mut values) => {
+                    let program = values.next().context("Command is missing its program")?.clone();
+                    Some(ChildCommand::new(program, values.cloned().collect())?)
+                },
+                | None => None,
+            };
+
+            Command::Serve {
+                manifest: cfg,
+                profile_name: profile_name.clone(),
+                listen: *subc
+                    .get_one::<std::net::SocketAddr>("listen")
+                    .context("Missing listen address")?,
+                token_file: Self::get_absolute_path(subc, "token_file")?,
+                command,
+                prompt: Self::get_prompt_mode(subc)?,
+                mock_providers: Self::get_mock_providers(subc)?,
+                allow_expired: subc.get_flag("allow_expired"),
+                confirm: subc.get_one::<String>("confirm").cloned(),
+                metrics_listen: subc.get_one::<std::net::SocketAddr>("metrics_listen").copied(),
+                strict: subc.get_flag("strict"),
+            }
+        } else if let Some(subc) = command.subcommand_matches("resolve-all") {
+            Command::ResolveAll {
+                manifest: Manifest::load(Self::get_config_path(subc)?)?,
+                profiles: subc
+                    .get_many::<String>("profile")
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect(),
+                output_dir: Self::get_absolute_path(subc, "output_dir")?,
+                format: OutputFormat::parse(subc.get_one::<String>("format").map(String::as_str)),
+                force: subc.get_flag("force"),
+                prompt: Self::get_prompt_mode(subc)?,
+                mock_providers: Self::get_mock_providers(subc)?,
+                allow_expired: subc.get_flag("allow_expired"),
+                confirm: subc.get_one::<String>("confirm").cloned(),
+                strict: subc.get_flag("strict"),
             }
         } else if command.subcommand_name() == Some("seal") {
             let (_, mut subc) = command.remove_subcommand().context("Missing seal arguments")?;
@@ -313,7 +1536,7 @@ impl ClapArgumentLoader {
                 | (None, None) => SealInput::Stdin,
                 | (Some(_), Some(_)) => anyhow::bail!("VALUE conflicts with --path"),
             };
-            let config_path = Self::get_absolute_path(&subc, "config")?;
+            let config_path = Self::get_config_path(&subc)?;
             let cfg = Manifest::load(config_path)?;
 
             let profile_name = subc
@@ -321,14 +1544,174 @@ impl ClapArgumentLoader {
                 .context("Missing profile name")?
                 .clone();
             if !cfg.profiles.contains_key(&profile_name) {
-                return Err(anyhow::anyhow!("Profile '{}' not found in config", profile_name));
+                return Err(crate::failure::Failure::ProfileNotFound
+                    .error(format!("Profile '{}' not found in config", profile_name)));
             }
 
+            let prompt = Self::get_prompt_mode(&subc)?;
+            let mock_providers = Self::get_mock_providers(&subc)?;
+            let recipient = match subc.remove_one::<String>("recipient") {
+                | Some(email) => {
+                    Some(RecipientQuery {
+                        email,
+                        fingerprint: subc.remove_one::<String>("fingerprint"),
+                        keyserver: subc.remove_one::<String>("keyserver").context("Missing keyserver")?,
+                    })
+                },
+                | None => None,
+            };
+
             Command::Seal {
                 manifest: cfg,
                 profile_name,
                 target,
                 input,
+                recipient,
+                prompt,
+                mock_providers,
+            }
+        } else if let Some(subc) = command.subcommand_matches("audit") {
+            Command::Audit {
+                manifest: Manifest::load(Self::get_config_path(subc)?)?,
+                profiles: subc
+                    .get_many::<String>("profile")
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect(),
+                strict: subc.get_flag("strict"),
+            }
+        } else if let Some(subc) = command.subcommand_matches("verify-files") {
+            Command::VerifyFiles {
+                manifest: Manifest::load(Self::get_config_path(subc)?)?,
+                profile_name: subc
+                    .get_one::<String>("profile")
+                    .context("Missing profile name")?
+                    .clone(),
+                prompt: Self::get_prompt_mode(subc)?,
+                mock_providers: Self::get_mock_providers(subc)?,
+                allow_expired: subc.get_flag("allow_expired"),
+                confirm: subc.get_one::<String>("confirm").cloned(),
+                strict: subc.get_flag("strict"),
+            }
+        } else if let Some(subc) = command.subcommand_matches("explain") {
+            let cfg = Manifest::load(Self::get_config_path(subc)?)?;
+            let profile_name = subc.get_one::<String>("profile").context("Missing profile name")?;
+            if !cfg.profiles.contains_key(profile_name) {
+                return Err(crate::failure::Failure::ProfileNotFound
+                    .error(format!("Profile '{}' not found in config", profile_name)));
+            }
+
+            Command::Explain {
+                manifest: cfg,
+                profile_name: profile_name.clone(),
+                variable: subc
+                    .get_one::<String>("variable")
+                    .context("Missing variable name")?
+                    .clone(),
+            }
+        } else if let Some(subc) = command.subcommand_matches("search") {
+            let pattern = subc.get_one::<String>("pattern").context("Missing search pattern")?;
+            Command::Search {
+                manifest: Manifest::load(Self::get_config_path(subc)?)?,
+                pattern: regex::RegexBuilder::new(pattern)
+                    .case_insensitive(subc.get_flag("ignore_case"))
+                    .build()
+                    .with_context(|| format!("Invalid search pattern '{}'", pattern))?,
+            }
+        } else if let Some(subc) = command.subcommand_matches("set") {
+            Command::Set {
+                manifest: Manifest::load(Self::get_config_path(subc)?)?,
+                profile_name: subc
+                    .get_one::<String>("profile")
+                    .context("Missing profile name")?
+                    .clone(),
+                key: ManifestEditor::parse_path(subc.get_one::<String>("key").context("Missing key")?)?,
+                value: match subc.get_one::<String>("compress") {
+                    | Some(codec) => {
+                        let content = match subc.get_one::<String>("value") {
+                            | Some(value) => value.clone().into_bytes(),
+                            | None => {
+                                let path = Self::get_absolute_path(subc, "from_file")?;
+                                std::fs::read(&path).with_context(|| format!("Failed to read '{}'", path.display()))?
+                            },
+                        };
+                        std::str::from_utf8(&content).context("Content to compress is not valid UTF-8")?;
+                        let value = crate::manifest::EncodedValue::compress(codec, &content)?;
+                        serde_json::to_string(&crate::manifest::Content::Plain(value))
+                            .context("Failed to serialize compressed value")?
+                    },
+                    | None => subc.get_one::<String>("value").context("Missing value")?.clone(),
+                },
+            }
+        } else if let Some(subc) = command.subcommand_matches("rm") {
+            let cfg = Manifest::load(Self::get_config_path(subc)?)?;
+            let profile_name = subc.get_one::<String>("profile").context("Missing profile name")?;
+            if !cfg.profiles.contains_key(profile_name) {
+                return Err(crate::failure::Failure::ProfileNotFound
+                    .error(format!("Profile '{}' not found in config", profile_name)));
+            }
+
+            Command::Remove {
+                manifest: cfg,
+                profile_name: profile_name.clone(),
+                key: ManifestEditor::parse_path(subc.get_one::<String>("key").context("Missing key")?)?,
+            }
+        } else if let Some(subc) = command.subcommand_matches("complete-keys") {
+            let words: Vec<_> = subc.get_many::<String>("words").into_iter().flatten().collect();
+            Command::CompleteKeys {
+                target: Self::key_completion_target(&words),
+            }
+        } else if let Some(subc) = command.subcommand_matches("import-from") {
+            let recipient = match subc.get_one::<String>("recipient") {
+                | Some(email) => {
+                    Some(RecipientQuery {
+                        email: email.clone(),
+                        fingerprint: subc.get_one::<String>("fingerprint").cloned(),
+                        keyserver: subc
+                            .get_one::<String>("keyserver")
+                            .context("Missing keyserver")?
+                            .clone(),
+                    })
+                },
+                | None => None,
+            };
+            Command::Import {
+                format: subc
+                    .get_one::<String>("format")
+                    .context("Missing import format")?
+                    .parse()?,
+                source: Self::get_absolute_path(subc, "source")?,
+                path: Self::get_absolute_path(subc, "out")?,
+                force: subc.get_flag("force"),
+                profile_name: subc
+                    .get_one::<String>("profile")
+                    .context("Missing profile name")?
+                    .clone(),
+                public_key: match subc.get_one::<String>("public_key") {
+                    | Some(_) => Some(Self::get_absolute_path(subc, "public_key")?),
+                    | None => None,
+                },
+                recipient,
+                key_source: subc.get_one::<String>("key_source").cloned(),
+            }
+        } else if let Some(subc) = command
+            .subcommand_matches("keys")
+            .and_then(|keys| keys.subcommand_matches("generate"))
+        {
+            let store = if subc.get_one::<String>("out").is_some() {
+                crate::keys::KeyStore::File(Self::get_absolute_path(subc, "out")?)
+            } else if let Some(secret) = subc.get_one::<String>("gcp_secret") {
+                crate::keys::KeyStore::Gcp(secret.clone())
+            } else {
+                crate::keys::KeyStore::Keyring
+            };
+            Command::GenerateKey {
+                name: subc.get_one::<String>("name").context("Missing name")?.clone(),
+                email: subc.get_one::<String>("email").context("Missing email")?.clone(),
+                store,
+                protect: subc.get_flag("protect"),
+                prompt: Self::get_prompt_mode(subc)?,
             }
         } else if let Some(subc) = command.subcommand_matches("init") {
             let config_path = Self::get_absolute_path(subc, "path")?;
@@ -338,6 +1721,12 @@ impl ClapArgumentLoader {
                 path: config_path,
                 force,
             }
+        } else if let Some(subc) = command.subcommand_matches("clean") {
+            Command::Clean {
+                dry_run: subc.get_flag("dry_run"),
+            }
+        } else if command.subcommand_matches("schema").is_some() {
+            Command::Schema
         } else {
             anyhow::bail!("unknown command")
         };
@@ -348,7 +1737,11 @@ impl ClapArgumentLoader {
 
 #[cfg(test)]
 mod tests {
-    use super::ClapArgumentLoader;
+    use super::{
+        ClapArgumentLoader,
+        VariableFilter,
+        VariableOverride,
+    };
 
     #[test]
     fn seal_accepts_a_direct_value_for_a_configured_file() {
@@ -413,6 +1806,54 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn unlock_variable_overrides_keep_command_line_order() -> anyhow::Result<()> {
+        let matches = ClapArgumentLoader::root_command().try_get_matches_from([
+            "secenv",
+            "unlock",
+            "--var",
+            "FIRST=1",
+            "--var-file",
+            "/tmp/overrides.env",
+            "--var",
+            "LAST=a=b",
+            "--",
+            "true",
+        ])?;
+        let unlock = matches.subcommand_matches("unlock").unwrap();
+        let overrides = ClapArgumentLoader::get_variable_overrides(unlock)?;
+
+        assert!(matches!(
+            &overrides[..],
+            [
+                VariableOverride::Value { name: first, .. },
+                VariableOverride::File(path),
+                VariableOverride::Value { name: last, value },
+            ] if first == "FIRST" && path.ends_with("overrides.env") && last == "LAST" && value.as_str() == "a=b"
+        ));
+
+        let invalid =
+            ClapArgumentLoader::root_command().try_get_matches_from(["secenv", "unlock", "--var", "MISSING"])?;
+        assert!(ClapArgumentLoader::get_variable_overrides(invalid.subcommand_matches("unlock").unwrap()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn variable_filters_match_globs() -> anyhow::Result<()> {
+        let only = ["DB_*".to_string(), "API_TOKEN".to_string()];
+        let exclude = ["*_DEBUG".to_string()];
+        let filter = VariableFilter::new(&only, &exclude)?;
+
+        assert!(filter.matches("DB_HOST"));
+        assert!(filter.matches("API_TOKEN"));
+        assert!(!filter.matches("DB_DEBUG"));
+        assert!(!filter.matches("API_TOKEN_2"));
+        assert!(!filter.matches("XDB_HOST"));
+        assert!(VariableFilter::default().matches("ANYTHING"));
+        assert!(VariableFilter::new(&[], &["A.B".to_string()])?.matches("AXB"));
+        Ok(())
+    }
+
     #[test]
     fn unlock_timeout_requires_a_command_and_positive_seconds() {
         assert!(ClapArgumentLoader::root_command()
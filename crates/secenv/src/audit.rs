@@ -0,0 +1,161 @@
+use {
+    crate::manifest::{
+        Manifest,
+        ManifestProfile,
+        VariableMetadata,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    chrono::{
+        DateTime,
+        Utc,
+    },
+};
+
+/// A lifecycle problem of a profile or one of its variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum AuditIssue {
+    RotationOverdue { due: DateTime<Utc> },
+    Expired { at: DateTime<Utc> },
+}
+
+impl AuditIssue {
+    pub(crate) fn check(metadata: &VariableMetadata, now: DateTime<Utc>) -> Result<Vec<Self>> {
+        let mut issues = Vec::new();
+        if let Some(at) = metadata.expires()?.filter(|at| *at <= now) {
+            issues.push(Self::Expired { at });
+        }
+        if let Some(due) = metadata.rotation_due()?.filter(|due| *due <= now) {
+            issues.push(Self::RotationOverdue { due });
+        }
+        Ok(issues)
+    }
+
+    /// Warn, or fail for an expired value unless `allow_expired`.
+    pub(crate) fn enforce(&self, subject: &str, allow_expired: bool) -> Result<()> {
+        match self {
+            | Self::Expired { .. } if !allow_expired => {
+                anyhow::bail!("{} {}. Use --allow-expired to unlock it anyway.", subject, self)
+            },
+            | _ => {
+                crate::output::warning!("{}: {}", subject, self);
+                Ok(())
+            },
+        }
+    }
+
+    pub(crate) fn check_profile(profile: &ManifestProfile, now: DateTime<Utc>) -> Result<Vec<Self>> {
+        Ok(profile
+            .expires()?
+            .filter(|at| *at <= now)
+            .map(|at| Self::Expired { at })
+            .into_iter()
+            .collect())
+    }
+}
+
+impl std::fmt::Display for AuditIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::RotationOverdue { due } => write!(f, "rotation overdue since {}", due.format("%Y-%m-%d")),
+            | Self::Expired { at } => write!(f, "expired at {}", at.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AuditFinding {
+    pub(crate) profile: String,
+    /// `None` for issues of the profile itself.
+    pub(crate) variable: Option<String>,
+    pub(crate) issue: AuditIssue,
+}
+
+impl std::fmt::Display for AuditFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.variable {
+            | Some(variable) => write!(f, "{}.{}: {}", self.profile, variable, self.issue),
+            | None => write!(f, "{}: {}", self.profile, self.issue),
+        }
+    }
+}
+
+/// Every issue in the profiles matching `patterns`, sorted by profile and name.
+pub(crate) fn audit(manifest: &Manifest, patterns: &[String], now: DateTime<Utc>) -> Result<Vec<AuditFinding>> {
+    let mut findings = Vec::new();
+    for profile_name in manifest.profile_names(patterns)? {
+        let profile = manifest.profile(profile_name)?;
+        let issues =
+            AuditIssue::check_profile(&profile, now).with_context(|| format!("Invalid profile '{}'", profile_name))?;
+        findings.extend(issues.into_iter().map(|issue| {
+            AuditFinding {
+                profile: profile_name.to_string(),
+                variable: None,
+                issue,
+            }
+        }));
+        for (name, value) in &profile.env.vars {
+            let issues = AuditIssue::check(&value.metadata, now)
+                .with_context(|| format!("Invalid environment variable '{}.{}'", profile_name, name))?;
+            findings.extend(issues.into_iter().map(|issue| {
+                AuditFinding {
+                    profile: profile_name.to_string(),
+                    variable: Some(name.clone()),
+                    issue,
+                }
+            }));
+        }
+    }
+    findings.sort_by(|left, right| (&left.profile, &left.variable).cmp(&(&right.profile, &right.variable)));
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::manifest::ManifestFormat,
+    };
+
+    #[test]
+    fn reports_overdue_and_expired_values() -> Result<()> {
+        let manifest = Manifest::parse(
+            r#"
+version = "0.0.0"
+profiles.default.env.vars {
+  STALE { plain.literal = "a", rotate_after = "90d", last_rotated = "2025-01-01" }
+  FRESH { plain.literal = "b", rotate_after = "1y", last_rotated = "2025-05-01T08:00:00+02:00" }
+  UNTRACKED.plain.literal = "c"
+  CONTRACTOR { plain.literal = "d", expires = "2025-05-31T18:00:00Z" }
+}
+profiles.ci = ${profiles.default}
+profiles.ci.expires = "2025-06-01"
+"#,
+            ManifestFormat::Hocon,
+        )?;
+        let now = VariableMetadata::timestamp("2025-06-01")?;
+        let findings = audit(&manifest, &[], now)?;
+
+        assert_eq!(findings.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+            "ci: expired at 2025-06-01T00:00:00+00:00",
+            "ci.CONTRACTOR: expired at 2025-05-31T18:00:00+00:00",
+            "ci.STALE: rotation overdue since 2025-04-01",
+            "default.CONTRACTOR: expired at 2025-05-31T18:00:00+00:00",
+            "default.STALE: rotation overdue since 2025-04-01",
+        ]);
+
+        for invalid in [
+            r#"rotate_after = "90d""#,
+            r#"rotate_after = "soon", last_rotated = "2025-01-01""#,
+        ] {
+            let content = format!(
+                "version = \"0.0.0\"\nprofiles.default.env.vars.X {{ plain.literal = \"a\", {} }}\n",
+                invalid
+            );
+            assert!(audit(&Manifest::parse(&content, ManifestFormat::Hocon)?, &[], now).is_err());
+        }
+        Ok(())
+    }
+}
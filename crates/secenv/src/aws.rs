@@ -1,13 +1,48 @@
 use {
+    crate::{
+        failure::{
+            Categorize,
+            Failure,
+        },
+        http::{
+            percent_encode,
+            HttpClient,
+            HttpMethod,
+        },
+    },
     anyhow::{
         Context,
         Result,
     },
-    std::process::{
-        Command,
-        Stdio,
+    base64::Engine,
+    chrono::{
+        DateTime,
+        Utc,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    sha2::{
+        Digest,
+        Sha256,
+    },
+    std::{
+        collections::HashMap,
+        path::PathBuf,
+        process::{
+            Command,
+            Stdio,
+        },
+        sync::{
+            Mutex,
+            OnceLock,
+        },
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
     },
-    zeroize::Zeroize,
 };
 
 #[derive(Debug, Clone)]
@@ -38,10 +73,779 @@ impl AwsSecretSpec {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AwsClient {
+    /// REST when it finds a region and credentials, the aws CLI otherwise.
+    #[default]
+    Auto,
+    Rest,
+    Cli,
+}
+
+/// Set by `--aws-client`, which overrides the manifest.
+static CLIENT_FLAG: OnceLock<AwsClient> = OnceLock::new();
+static CLIENT: Mutex<Option<AwsClient>> = Mutex::new(None);
+static CREDENTIALS: Mutex<Option<CachedCredentials>> = Mutex::new(None);
+
+type Headers = Vec<(String, Zeroizing<String>)>;
+type Settings = HashMap<String, Zeroizing<String>>;
+
+enum CachedCredentials {
+    /// Reused until shortly before they expire.
+    Found(AwsCredentials),
+    Missing,
+}
+
+impl AwsClient {
+    pub(crate) fn set_flag(client: AwsClient) {
+        let _ = CLIENT_FLAG.set(client);
+    }
+
+    pub(crate) fn configure(client: Option<AwsClient>) {
+        *CLIENT.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = client;
+    }
+
+    fn current() -> AwsClient {
+        CLIENT_FLAG
+            .get()
+            .copied()
+            .or(*CLIENT.lock().unwrap_or_else(std::sync::PoisonError::into_inner))
+            .unwrap_or_default()
+    }
+}
+
+/// Without the variables secret sources use.
+struct AwsEnvironment<'a> {
+    removed_env_vars: &'a [String],
+}
+
+impl AwsEnvironment<'_> {
+    fn var(&self, name: &str) -> Option<String> {
+        if self.removed_env_vars.iter().any(|removed| removed == name) {
+            return None;
+        }
+        std::env::var(name).ok().filter(|value| !value.is_empty())
+    }
+
+    fn shared_file(&self, variable: &str, name: &str) -> Option<PathBuf> {
+        if let Some(path) = self.var(variable) {
+            return Some(PathBuf::from(path));
+        }
+        let home = self.var("HOME").or_else(|| self.var("USERPROFILE"))?;
+        Some(PathBuf::from(home).join(".aws").join(name))
+    }
+
+    /// Profiles other than `default` are `profile <name>` in the config.
+    fn profile(&self) -> Result<[Option<Settings>; 2]> {
+        let profile = self.var("AWS_PROFILE").unwrap_or_else(|| "default".to_string());
+        let read = |path: Option<PathBuf>, section: &str| -> Result<_> {
+            let Some(path) = path.filter(|path| path.is_file()) else {
+                return Ok(None);
+            };
+            let content = Zeroizing::new(
+                std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read AWS configuration '{}'", path.display()))?,
+            );
+            Ok(Self::section(&content, section))
+        };
+        let config_section = if profile == "default" {
+            profile.clone()
+        } else {
+            format!("profile {}", profile)
+        };
+        Ok([
+            read(self.shared_file("AWS_SHARED_CREDENTIALS_FILE", "credentials"), &profile)?,
+            read(self.shared_file("AWS_CONFIG_FILE", "config"), &config_section)?,
+        ])
+    }
+
+    fn section(content: &str, section: &str) -> Option<Settings> {
+        let mut settings = None;
+        let mut current = false;
+        for line in content.lines().map(str::trim) {
+            if line.starts_with(['#', ';']) || line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                current = name.trim() == section;
+                if current {
+                    settings.get_or_insert_with(HashMap::new);
+                }
+                continue;
+            }
+            if let (true, Some(settings), Some((key, value))) = (current, settings.as_mut(), line.split_once('=')) {
+                settings.insert(key.trim().to_string(), Zeroizing::new(value.trim().to_string()));
+            }
+        }
+        settings
+    }
+
+    /// `explicit`, `AWS_REGION`, `AWS_DEFAULT_REGION`, or the profile's.
+    fn region(&self, explicit: Option<&str>) -> Result<Option<String>> {
+        let region = match explicit.map(str::to_string) {
+            | Some(region) => Some(region),
+            | None => {
+                match self.var("AWS_REGION").or_else(|| self.var("AWS_DEFAULT_REGION")) {
+                    | Some(region) => Some(region),
+                    | None => {
+                        let [_, config] = self.profile()?;
+                        config.and_then(|config| config.get("region").map(|region| region.to_string()))
+                    },
+                }
+            },
+        };
+        if let Some(region) = &region {
+            if region.is_empty()
+                || !region
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric() || character == '-')
+            {
+                anyhow::bail!("Invalid AWS region '{}'", region);
+            }
+        }
+        Ok(region)
+    }
+}
+
+#[derive(Clone)]
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: Zeroizing<String>,
+    session_token: Option<Zeroizing<String>>,
+    expires: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CredentialsResponse {
+    access_key_id: String,
+    secret_access_key: Zeroizing<String>,
+    #[serde(alias = "SessionToken")]
+    token: Option<Zeroizing<String>>,
+    /// An RFC 3339 date, or seconds since the epoch from STS.
+    expiration: Option<serde_json::Value>,
+}
+
+impl From<CredentialsResponse> for AwsCredentials {
+    fn from(response: CredentialsResponse) -> Self {
+        let expires = match response.expiration {
+            | Some(serde_json::Value::Number(seconds)) => {
+                seconds
+                    .as_f64()
+                    .and_then(|seconds| DateTime::from_timestamp(seconds as i64, 0))
+            },
+            | Some(serde_json::Value::String(date)) => {
+                DateTime::parse_from_rfc3339(&date)
+                    .ok()
+                    .map(|date| date.with_timezone(&Utc))
+            },
+            | _ => None,
+        };
+        Self {
+            access_key_id: response.access_key_id,
+            secret_access_key: response.secret_access_key,
+            session_token: response.token,
+            expires,
+        }
+    }
+}
+
+impl AwsCredentials {
+    const CONTAINER_HOST: &'static str = "http://169.254.170.2";
+    const INSTANCE_HOST: &'static str = "http://169.254.169.254";
+
+    fn current(environment: &AwsEnvironment) -> Result<Option<Self>> {
+        let mut cached = CREDENTIALS.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let credentials = match cached.take() {
+            | Some(CachedCredentials::Missing) => None,
+            | Some(CachedCredentials::Found(credentials))
+                if credentials
+                    .expires
+                    .is_none_or(|expires| expires - chrono::Duration::minutes(5) > Utc::now()) =>
+            {
+                Some(credentials)
+            },
+            | _ => Self::find(environment).context("Failed to get AWS credentials")?,
+        };
+        *cached = Some(match &credentials {
+            | Some(credentials) => CachedCredentials::Found(credentials.clone()),
+            | None => CachedCredentials::Missing,
+        });
+        Ok(credentials)
+    }
+
+    /// `None` for SSO, role chaining, and credential processes.
+    fn find(environment: &AwsEnvironment) -> Result<Option<Self>> {
+        if let (Some(access_key_id), Some(secret_access_key)) = (
+            environment.var("AWS_ACCESS_KEY_ID"),
+            environment.var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Ok(Some(Self {
+                access_key_id,
+                secret_access_key: Zeroizing::new(secret_access_key),
+                session_token: environment.var("AWS_SESSION_TOKEN").map(Zeroizing::new),
+                expires: None,
+            }));
+        }
+        let profile = environment.profile()?;
+        for settings in profile.iter().flatten() {
+            if ["role_arn", "sso_start_url", "sso_session", "credential_process"]
+                .iter()
+                .any(|key| settings.contains_key(*key))
+            {
+                return Ok(None);
+            }
+            if let (Some(access_key_id), Some(secret_access_key)) =
+                (settings.get("aws_access_key_id"), settings.get("aws_secret_access_key"))
+            {
+                return Ok(Some(Self {
+                    access_key_id: access_key_id.to_string(),
+                    secret_access_key: secret_access_key.clone(),
+                    session_token: settings.get("aws_session_token").cloned(),
+                    expires: None,
+                }));
+            }
+        }
+        if let Some(credentials) = Self::from_web_identity(environment)? {
+            return Ok(Some(credentials));
+        }
+        if let Some(credentials) = Self::from_container(environment)? {
+            return Ok(Some(credentials));
+        }
+        Self::from_instance(environment)
+    }
+
+    fn from_web_identity(environment: &AwsEnvironment) -> Result<Option<Self>> {
+        let (Some(token_file), Some(role_arn)) = (
+            environment.var("AWS_WEB_IDENTITY_TOKEN_FILE"),
+            environment.var("AWS_ROLE_ARN"),
+        ) else {
+            return Ok(None);
+        };
+        let token = Zeroizing::new(
+            std::fs::read_to_string(&token_file)
+                .with_context(|| format!("Failed to read the web identity token '{}'", token_file))?,
+        );
+        let session_name = environment
+            .var("AWS_ROLE_SESSION_NAME")
+            .unwrap_or_else(|| "secenv".to_string());
+        let url = match environment.region(None)? {
+            | Some(region) => format!("https://sts.{}.amazonaws.com/", region),
+            | None => "https://sts.amazonaws.com/".to_string(),
+        };
+        let mut body = Zeroizing::new(format!(
+            "Action=AssumeRoleWithWebIdentity&Version=2011-06-15&RoleArn={}&RoleSessionName={}&WebIdentityToken=",
+            percent_encode(&role_arn),
+            percent_encode(&session_name)
+        ));
+        body.push_str(&Zeroizing::new(percent_encode(token.trim())));
+        let headers = [
+            (
+                "Content-Type".to_string(),
+                Zeroizing::new("application/x-www-form-urlencoded".to_string()),
+            ),
+            ("Accept".to_string(), Zeroizing::new("application/json".to_string())),
+        ];
+        let response = crate::throttle::Provider::Aws
+            .request(|| HttpClient.send(&url, HttpMethod::Post, &headers, Some(&body)))?;
+        let mut document: serde_json::Value =
+            serde_json::from_str(&response).context("STS response is not valid JSON")?;
+        let credentials = document
+            .pointer("/AssumeRoleWithWebIdentityResponse/AssumeRoleWithWebIdentityResult/Credentials")
+            .cloned()
+            .map(serde_json::from_value::<CredentialsResponse>);
+        crate::sealed::SealedDocument::zeroize_value(&mut document);
+        let credentials = credentials
+            .context("STS response has no credentials")?
+            .context("STS credentials are not valid")?;
+        Ok(Some(credentials.into()))
+    }
+
+    fn from_container(environment: &AwsEnvironment) -> Result<Option<Self>> {
+        let url = match (
+            environment.var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI"),
+            environment.var("AWS_CONTAINER_CREDENTIALS_FULL_URI"),
+        ) {
+            | (Some(relative), _) => format!("{}{}", Self::CONTAINER_HOST, relative),
+            | (None, Some(full)) => full,
+            | (None, None) => return Ok(None),
+        };
+        let token = match environment.var("AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE") {
+            | Some(path) => {
+                let token = Zeroizing::new(
+                    std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read the container authorization token '{}'", path))?,
+                );
+                Some(Zeroizing::new(token.trim().to_string()))
+            },
+            | None => environment.var("AWS_CONTAINER_AUTHORIZATION_TOKEN").map(Zeroizing::new),
+        };
+        let headers: Vec<_> = token
+            .into_iter()
+            .map(|token| ("Authorization".to_string(), token))
+            .collect();
+        let response = Self::local_request("GET", &url, &headers, false, environment.removed_env_vars)?
+            .context("The container credentials endpoint does not answer")?;
+        let credentials: CredentialsResponse =
+            serde_json::from_str(&response).context("Container credentials are not valid")?;
+        Ok(Some(credentials.into()))
+    }
+
+    fn from_instance(environment: &AwsEnvironment) -> Result<Option<Self>> {
+        if environment
+            .var("AWS_EC2_METADATA_DISABLED")
+            .is_some_and(|disabled| disabled.eq_ignore_ascii_case("true"))
+        {
+            return Ok(None);
+        }
+        let host = environment
+            .var("AWS_EC2_METADATA_SERVICE_ENDPOINT")
+            .unwrap_or_else(|| Self::INSTANCE_HOST.to_string());
+        let host = host.trim_end_matches('/');
+        let removed_env_vars = environment.removed_env_vars;
+        let ttl = [(
+            "X-aws-ec2-metadata-token-ttl-seconds".to_string(),
+            Zeroizing::new("21600".to_string()),
+        )];
+        let Some(token) = Self::local_request(
+            "PUT",
+            &format!("{}/latest/api/token", host),
+            &ttl,
+            true,
+            removed_env_vars,
+        )?
+        else {
+            return Ok(None);
+        };
+        let headers = [("X-aws-ec2-metadata-token".to_string(), token)];
+        let roles_url = format!("{}/latest/meta-data/iam/security-credentials/", host);
+        let Some(roles) = Self::local_request("GET", &roles_url, &headers, true, removed_env_vars)? else {
+            return Ok(None);
+        };
+        let Some(role) = roles.lines().map(str::trim).find(|role| !role.is_empty()) else {
+            return Ok(None);
+        };
+        let response = Self::local_request(
+            "GET",
+            &format!("{}{}", roles_url, percent_encode(role)),
+            &headers,
+            false,
+            removed_env_vars,
+        )?
+        .context("The instance metadata service does not answer")?;
+        let credentials: CredentialsResponse =
+            serde_json::from_str(&response).context("Instance credentials are not valid")?;
+        Ok(Some(credentials.into()))
+    }
+
+    /// `None` when nothing answers, or an error with `optional`.
+    fn local_request(
+        method: &str,
+        url: &str,
+        headers: &[(String, Zeroizing<String>)],
+        optional: bool,
+        removed_env_vars: &[String],
+    ) -> Result<Option<Zeroizing<String>>> {
+        if !url.starts_with("http://") && !url.starts_with("https://") || url.chars().any(char::is_whitespace) {
+            anyhow::bail!("Invalid AWS credentials endpoint '{}'", url);
+        }
+        let mut cmd = Command::new("curl");
+        cmd.args([
+            "--silent",
+            "--fail",
+            "--proto",
+            "=http,https",
+            "--connect-timeout",
+            "1",
+            "--max-time",
+            "5",
+            "--config",
+            "-",
+            "--request",
+            method,
+            "--url",
+            url,
+        ]);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            | Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            | child => child.context("Failed to execute curl").categorize(Failure::Provider)?,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            stdin
+                .write_all(HttpClient::config(headers, None).as_bytes())
+                .context("Failed to pass the request to curl")?;
+        }
+        let mut output = child.wait_with_output().context("Failed to wait for curl")?;
+        if !output.status.success() {
+            output.stdout.zeroize();
+            // curl could not resolve, connect to, or hear back from the host,
+            // or with 22, the host answered with an error.
+            return match output.status.code() {
+                | Some(6 | 7 | 28) => Ok(None),
+                | Some(22) if optional => Ok(None),
+                | _ => {
+                    Err(Failure::Provider.error(format!(
+                        "Failed to request AWS credentials from '{}': curl exited with {}",
+                        url, output.status
+                    )))
+                },
+            };
+        }
+        String::from_utf8(std::mem::take(&mut output.stdout))
+            .map(|body| Some(Zeroizing::new(body)))
+            .map_err(|error| {
+                error.into_bytes().zeroize();
+                anyhow::anyhow!("Response of '{}' is not valid UTF-8", url)
+            })
+    }
+}
+
+struct SigV4<'a> {
+    credentials: &'a AwsCredentials,
+    region: &'a str,
+    service: &'a str,
+}
+
+impl SigV4<'_> {
+    fn sign(
+        &self,
+        method: &str,
+        host: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Headers> {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = &amz_date[..8];
+        let mut sent: Headers = headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), Zeroizing::new(value.to_string())))
+            .collect();
+        sent.push(("X-Amz-Date".to_string(), Zeroizing::new(amz_date.clone())));
+        if let Some(token) = &self.credentials.session_token {
+            sent.push(("X-Amz-Security-Token".to_string(), token.clone()));
+        }
+
+        let mut signed: Vec<(String, &str)> = sent
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.trim()))
+            .collect();
+        signed.push(("host".to_string(), host));
+        signed.sort();
+        let signed_headers = signed
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let mut canonical = Zeroizing::new(format!("{}\n{}\n\n", method, path));
+        for (name, value) in &signed {
+            canonical.push_str(name);
+            canonical.push(':');
+            canonical.push_str(value);
+            canonical.push('\n');
+        }
+        canonical.push('\n');
+        canonical.push_str(&signed_headers);
+        canonical.push('\n');
+        canonical.push_str(&hex(&Sha256::digest(body.as_bytes())));
+
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical.as_bytes()))
+        );
+        let secret = Zeroizing::new(format!("AWS4{}", self.credentials.secret_access_key.as_str()));
+        let mut key = hmac(secret.as_bytes(), date.as_bytes())?;
+        for part in [self.region, self.service, "aws4_request"] {
+            key = hmac(&key, part.as_bytes())?;
+        }
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes())?);
+        sent.push((
+            "Authorization".to_string(),
+            Zeroizing::new(format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.credentials.access_key_id, scope, signed_headers, signature
+            )),
+        ));
+        Ok(sent)
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    use openssl::{
+        hash::MessageDigest,
+        pkey::PKey,
+        sign::Signer,
+    };
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(data)?;
+    Ok(Zeroizing::new(signer.sign_to_vec()?))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SecretValue {
+    secret_string: Option<Zeroizing<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListPage {
+    #[serde(default)]
+    secret_list: Vec<ListedSecret>,
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedSecret {
+    name: String,
+    #[serde(default)]
+    tags: Option<Vec<ListedTag>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedTag {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Parameter {
+    name: String,
+    value: Zeroizing<String>,
+}
+
 pub(crate) struct AwsSecretManager;
 
 impl AwsSecretManager {
+    const MAX_PAGES: usize = 100;
+
+    /// With `expand_json`, one variable per key of each secret's JSON object.
+    pub(crate) fn access_all(
+        &self,
+        filter_tags: &HashMap<String, String>,
+        region: Option<&str>,
+        expand_json: bool,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        let mut variables = Vec::new();
+        for secret in self.list_secrets(filter_tags, region, removed_env_vars)? {
+            let spec = AwsSecretSpec {
+                secret: secret.clone(),
+                version: None,
+                region: region.map(str::to_string),
+            };
+            let value = Zeroizing::new(
+                self.access_secret(&spec, removed_env_vars)
+                    .with_context(|| format!("Failed to access AWS secret '{}'", secret))?,
+            );
+            if expand_json {
+                variables.extend(
+                    Self::expand_json(&value).with_context(|| format!("Failed to expand AWS secret '{}'", secret))?,
+                );
+            } else {
+                variables.push((crate::process::environment_variable_name(&secret), value));
+            }
+        }
+        Ok(variables)
+    }
+
+    fn list_secrets(
+        &self,
+        filter_tags: &HashMap<String, String>,
+        region: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<String>> {
+        if filter_tags.is_empty() {
+            anyhow::bail!("AWS bulk import requires at least one tag in 'filter_tags'");
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return Ok(fixtures.aws_secret_names(filter_tags));
+        }
+        // The service matches tag keys and values independently, so exact
+        // pairs are checked again afterwards.
+        let mut sorted_tags: Vec<_> = filter_tags.iter().collect();
+        sorted_tags.sort();
+        if let Some((region, credentials)) = Self::rest_session(region, removed_env_vars)? {
+            let filters: Vec<_> = sorted_tags
+                .iter()
+                .flat_map(|(key, value)| {
+                    [
+                        serde_json::json!({ "Key": "tag-key", "Values": [key] }),
+                        serde_json::json!({ "Key": "tag-value", "Values": [value] }),
+                    ]
+                })
+                .collect();
+            let mut secrets = Vec::new();
+            let mut next_token: Option<String> = None;
+            for _ in 0..Self::MAX_PAGES {
+                let mut body = serde_json::json!({ "Filters": filters, "MaxResults": 100 });
+                if let Some(next_token) = &next_token {
+                    body["NextToken"] = serde_json::Value::from(next_token.as_str());
+                }
+                let response = self.call("ListSecrets", &region, &body, &credentials)?;
+                let page: ListPage =
+                    serde_json::from_str(&response).context("Secrets Manager list response is not valid")?;
+                secrets.extend(page.secret_list);
+                next_token = page.next_token.filter(|token| !token.is_empty());
+                if next_token.is_none() {
+                    return Ok(Self::select(secrets, filter_tags));
+                }
+            }
+            anyhow::bail!("AWS has more than {} pages of matching secrets", Self::MAX_PAGES);
+        }
+        let mut cmd = Command::new("aws");
+        cmd.args(["secretsmanager", "list-secrets", "--output", "json"])
+            .arg("--query")
+            .arg("SecretList[].{Name:Name,Tags:Tags}")
+            .arg("--filters");
+        for (key, value) in sorted_tags {
+            cmd.arg(format!("Key=tag-key,Values={}", key))
+                .arg(format!("Key=tag-value,Values={}", value));
+        }
+        if let Some(region) = region {
+            cmd.arg("--region").arg(region);
+        }
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let output = crate::throttle::Provider::Aws
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute aws CLI to list secrets")
+            .categorize(Failure::Provider)?;
+        if !output.status.success() {
+            return Err(Failure::Provider.error(format!("aws CLI failed: {}", String::from_utf8_lossy(&output.stderr))));
+        }
+        Self::matching_secrets(&output.stdout, filter_tags)
+    }
+
+    fn matching_secrets(listing: &[u8], filter_tags: &HashMap<String, String>) -> Result<Vec<String>> {
+        let secrets: Option<Vec<ListedSecret>> =
+            serde_json::from_slice(listing).context("AWS list-secrets output is not valid JSON")?;
+        Ok(Self::select(secrets.unwrap_or_default(), filter_tags))
+    }
+
+    fn select(secrets: Vec<ListedSecret>, filter_tags: &HashMap<String, String>) -> Vec<String> {
+        let mut names: Vec<_> = secrets
+            .into_iter()
+            .filter(|secret| {
+                let tags = secret.tags.as_deref().unwrap_or_default();
+                filter_tags
+                    .iter()
+                    .all(|(key, value)| tags.iter().any(|tag| &tag.key == key && &tag.value == value))
+            })
+            .map(|secret| secret.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// `None` when secrets are read with the aws CLI.
+    fn rest_session(region: Option<&str>, removed_env_vars: &[String]) -> Result<Option<(String, AwsCredentials)>> {
+        let client = AwsClient::current();
+        if client == AwsClient::Cli {
+            return Ok(None);
+        }
+        let environment = AwsEnvironment { removed_env_vars };
+        let session = match environment.region(region)? {
+            | Some(region) => AwsCredentials::current(&environment)?.map(|credentials| (region, credentials)),
+            | None => None,
+        };
+        match session {
+            | None if client == AwsClient::Rest => {
+                Err(Failure::Provider.error(
+                    "No AWS region and credentials the REST client supports were found: set a region and static keys, \
+                     a web identity, or run with an ECS, EKS, or EC2 role, or read secrets with the aws CLI by \
+                     setting 'providers.aws.client = cli'",
+                ))
+            },
+            | session => Ok(session),
+        }
+    }
+
+    fn call(
+        &self,
+        target: &str,
+        region: &str,
+        body: &serde_json::Value,
+        credentials: &AwsCredentials,
+    ) -> Result<Zeroizing<String>> {
+        let host = format!("secretsmanager.{}.amazonaws.com", region);
+        let body = body.to_string();
+        let target = format!("secretsmanager.{}", target);
+        let signer = SigV4 {
+            credentials,
+            region,
+            service: "secretsmanager",
+        };
+        let headers = signer.sign(
+            "POST",
+            &host,
+            "/",
+            &[
+                ("Content-Type", "application/x-amz-json-1.1"),
+                ("X-Amz-Target", &target),
+            ],
+            &body,
+            Utc::now(),
+        )?;
+        crate::throttle::Provider::Aws
+            .request(|| HttpClient.send(&format!("https://{}/", host), HttpMethod::Post, &headers, Some(&body)))
+    }
+
+    fn expand_json(value: &str) -> Result<Vec<(String, Zeroizing<String>)>> {
+        let mut values = crate::process::json_variables(value)?;
+        for (key, _) in &mut values {
+            *key = crate::process::environment_variable_name(key);
+        }
+        Ok(values)
+    }
+
     pub(crate) fn access_secret(&self, spec: &AwsSecretSpec, removed_env_vars: &[String]) -> Result<String> {
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.aws_secret(&spec.secret, spec.version.as_deref());
+        }
+        if let Some((region, credentials)) = Self::rest_session(spec.region.as_deref(), removed_env_vars)? {
+            let mut body = serde_json::json!({ "SecretId": spec.secret });
+            if let Some(version) = &spec.version {
+                let key = if AwsSecretSpec::is_version_stage(version) {
+                    "VersionStage"
+                } else {
+                    "VersionId"
+                };
+                body[key] = serde_json::Value::from(version.as_str());
+            }
+            let response = self.call("GetSecretValue", &region, &body, &credentials)?;
+            let value: SecretValue =
+                serde_json::from_str(&response).context("Secrets Manager response is not valid")?;
+            let mut value = value
+                .secret_string
+                .context("AWS secret does not contain SecretString")?;
+            return Ok(std::mem::take(&mut *value));
+        }
         let mut cmd = Command::new("aws");
         cmd.args(["secretsmanager", "get-secret-value"])
             .arg("--secret-id")
@@ -58,16 +862,19 @@ impl AwsSecretManager {
         }
         crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
 
-        let mut output = cmd
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .context("Failed to execute aws CLI to access secret")?;
+        let mut output = crate::throttle::Provider::Aws
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute aws CLI to access secret")
+            .categorize(Failure::Provider)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            let error = anyhow::anyhow!("aws CLI failed: {}", stderr);
+            let error = Failure::Provider.error(format!("aws CLI failed: {}", stderr));
             output.stdout.zeroize();
             return Err(error);
         }
@@ -79,3 +886,363 @@ impl AwsSecretManager {
             .context("AWS secret does not contain SecretString")
     }
 }
+
+pub(crate) struct AwsKms;
+
+impl AwsKms {
+    /// Requires that `ciphertext` was encrypted under `key_id`.
+    pub(crate) fn decrypt(
+        &self,
+        key_id: &str,
+        region: Option<&str>,
+        ciphertext: &str,
+        removed_env_vars: &[String],
+    ) -> Result<String> {
+        if key_id.is_empty() || key_id.starts_with('-') {
+            anyhow::bail!("Invalid KMS key id: '{}'", key_id);
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.kms_plaintext(ciphertext.trim());
+        }
+        let blob = base64::engine::general_purpose::STANDARD
+            .decode(ciphertext.trim())
+            .context("KMS ciphertext is not valid base64")?;
+        let mut cmd = Command::new("aws");
+        // The blob is read as raw bytes from stdin, which works with both
+        // major versions of the CLI.
+        cmd.args(["kms", "decrypt", "--output", "text", "--query", "Plaintext"])
+            .arg("--key-id")
+            .arg(key_id)
+            .arg("--ciphertext-blob")
+            .arg("fileb:///dev/stdin");
+        if let Some(region) = region {
+            cmd.arg("--region").arg(region);
+        }
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = crate::throttle::Provider::Aws
+            .request(|| {
+                let mut child = cmd
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .context("Failed to execute aws CLI to decrypt with KMS")
+                    .categorize(Failure::Provider)?;
+                if let Some(mut input) = child.stdin.take() {
+                    use std::io::Write;
+                    input
+                        .write_all(&blob)
+                        .context("Failed to pass the ciphertext to the aws CLI")?;
+                }
+                child.wait_with_output().context("Failed to wait for the aws CLI")
+            })
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Decryption.error(format!("aws CLI failed to decrypt with KMS: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let plaintext = base64::engine::general_purpose::STANDARD.decode(output.stdout.trim_ascii());
+        output.stdout.zeroize();
+        String::from_utf8(plaintext.context("KMS plaintext output is not valid base64")?).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("KMS plaintext is not valid UTF-8")
+        })
+    }
+}
+
+pub(crate) struct AwsS3;
+
+impl AwsS3 {
+    pub(crate) fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        region: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<Zeroizing<String>> {
+        if bucket.is_empty()
+            || !bucket.chars().all(|character| {
+                character.is_ascii_lowercase() || character.is_ascii_digit() || ".-".contains(character)
+            })
+        {
+            anyhow::bail!("Invalid S3 bucket: '{}'", bucket);
+        }
+        if key.is_empty() {
+            anyhow::bail!("S3 object key of bucket '{}' is empty", bucket);
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.s3_object(bucket, key);
+        }
+        if let Some((region, credentials)) = AwsSecretManager::rest_session(region, removed_env_vars)? {
+            let (host, path) = Self::location(bucket, key, &region);
+            let signer = SigV4 {
+                credentials: &credentials,
+                region: &region,
+                service: "s3",
+            };
+            let headers = signer.sign(
+                "GET",
+                &host,
+                &path,
+                &[("X-Amz-Content-Sha256", &hex(&Sha256::digest(b"")))],
+                "",
+                Utc::now(),
+            )?;
+            return crate::throttle::Provider::Aws
+                .request(|| HttpClient.send(&format!("https://{}{}", host, path), HttpMethod::Get, &headers, None))
+                .with_context(|| format!("Failed to download 's3://{}/{}'", bucket, key));
+        }
+
+        let mut cmd = Command::new("aws");
+        cmd.args(["s3", "cp", "--quiet"])
+            .arg(format!("s3://{}/{}", bucket, key))
+            .arg("-");
+        if let Some(region) = region {
+            cmd.arg("--region").arg(region);
+        }
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = crate::throttle::Provider::Aws
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute aws CLI to download an S3 object")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("aws CLI failed: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        String::from_utf8(std::mem::take(&mut output.stdout))
+            .map(Zeroizing::new)
+            .map_err(|error| {
+                error.into_bytes().zeroize();
+                anyhow::anyhow!("S3 object 's3://{}/{}' is not valid UTF-8", bucket, key)
+            })
+    }
+
+    /// Virtual-hosted, unless dots in the bucket name break the certificate.
+    fn location(bucket: &str, key: &str, region: &str) -> (String, String) {
+        let key = key.split('/').map(percent_encode).collect::<Vec<_>>().join("/");
+        if bucket.contains('.') {
+            (format!("s3.{}.amazonaws.com", region), format!("/{}/{}", bucket, key))
+        } else {
+            (format!("{}.s3.{}.amazonaws.com", bucket, region), format!("/{}", key))
+        }
+    }
+}
+
+pub(crate) struct AwsParameterStore;
+
+impl AwsParameterStore {
+    /// Without `recursive`, only the direct children are read.
+    pub(crate) fn access_path(
+        &self,
+        path: &str,
+        recursive: bool,
+        decrypt: bool,
+        region: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        if !path.starts_with('/') {
+            anyhow::bail!("Invalid SSM parameter path '{}': expected an absolute path", path);
+        }
+        let parameters = match crate::mock::MockProviders::active() {
+            | Some(fixtures) => fixtures.ssm_parameters(path, recursive),
+            | None => self.parameters_by_path(path, recursive, decrypt, region, removed_env_vars)?,
+        };
+        Ok(Self::variables(path, parameters))
+    }
+
+    fn parameters_by_path(
+        &self,
+        path: &str,
+        recursive: bool,
+        decrypt: bool,
+        region: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        let mut cmd = Command::new("aws");
+        cmd.args(["ssm", "get-parameters-by-path", "--output", "json"])
+            .arg("--path")
+            .arg(path)
+            .arg("--query")
+            .arg("Parameters[].{Name:Name,Value:Value}");
+        if recursive {
+            cmd.arg("--recursive");
+        }
+        if decrypt {
+            cmd.arg("--with-decryption");
+        }
+        if let Some(region) = region {
+            cmd.arg("--region").arg(region);
+        }
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = crate::throttle::Provider::Aws
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute aws CLI to get parameters")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("aws CLI failed: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let parameters = serde_json::from_slice::<Option<Vec<Parameter>>>(&output.stdout);
+        output.stdout.zeroize();
+        Ok(parameters
+            .context("AWS get-parameters-by-path output is not valid JSON")?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|parameter| (parameter.name, parameter.value))
+            .collect())
+    }
+
+    fn variables(path: &str, parameters: Vec<(String, Zeroizing<String>)>) -> Vec<(String, Zeroizing<String>)> {
+        let prefix = path.trim_end_matches('/');
+        let mut variables: Vec<_> = parameters
+            .into_iter()
+            .map(|(name, value)| {
+                let relative = name.strip_prefix(prefix).unwrap_or(&name).trim_start_matches('/');
+                (crate::process::environment_variable_name(relative), value)
+            })
+            .collect();
+        variables.sort_by(|(left, _), (right, _)| left.cmp(right));
+        variables
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_requests_like_the_sigv4_test_suite() -> Result<()> {
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: Zeroizing::new("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string()),
+            session_token: None,
+            expires: None,
+        };
+        let signer = SigV4 {
+            credentials: &credentials,
+            region: "us-east-1",
+            service: "service",
+        };
+        let now = DateTime::parse_from_rfc3339("2015-08-30T12:36:00Z")?.with_timezone(&Utc);
+        let headers = signer.sign("GET", "example.amazonaws.com", "/", &[], "", now)?;
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(header("X-Amz-Date"), Some("20150830T123600Z"));
+        assert_eq!(
+            header("Authorization"),
+            Some(
+                "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+                 SignedHeaders=host;x-amz-date, \
+                 Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn locates_s3_objects() {
+        assert_eq!(
+            AwsS3::location("config", "env/prod file.env", "eu-west-1"),
+            (
+                "config.s3.eu-west-1.amazonaws.com".to_string(),
+                "/env/prod%20file.env".to_string()
+            )
+        );
+        assert_eq!(
+            AwsS3::location("config.example.com", "prod.env", "eu-west-1"),
+            (
+                "s3.eu-west-1.amazonaws.com".to_string(),
+                "/config.example.com/prod.env".to_string()
+            )
+        );
+        assert!(AwsS3.get_object("--region", "prod.env", None, &[]).is_err());
+    }
+
+    #[test]
+    fn reads_profiles_and_temporary_credentials() -> Result<()> {
+        let content = "[default]\naws_access_key_id = A\n# comment\n[profile ci]\nregion=eu-west-1\nrole_arn = r\n";
+        let ci = AwsEnvironment::section(content, "profile ci").context("profile is missing")?;
+        assert_eq!(ci.get("region").map(|region| region.as_str()), Some("eu-west-1"));
+        assert!(ci.contains_key("role_arn") && !ci.contains_key("aws_access_key_id"));
+        assert!(AwsEnvironment::section(content, "profile other").is_none());
+
+        for expiration in [r#""2024-05-01T10:00:00Z""#, "1714557600"] {
+            let response: CredentialsResponse = serde_json::from_str(&format!(
+                r#"{{"AccessKeyId": "A", "SecretAccessKey": "S", "Token": "T", "Expiration": {}}}"#,
+                expiration
+            ))?;
+            let credentials = AwsCredentials::from(response);
+            assert_eq!(credentials.expires.map(|expires| expires.timestamp()), Some(1714557600));
+            assert_eq!(credentials.session_token.as_deref().map(String::as_str), Some("T"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn selects_and_expands_tagged_secrets() -> Result<()> {
+        let listing = br#"[
+            {"Name": "prod/payments/db", "Tags": [{"Key": "team", "Value": "payments"}]},
+            {"Name": "prod/search/db", "Tags": [{"Key": "team", "Value": "search"}, {"Key": "x", "Value": "payments"}]},
+            {"Name": "untagged"}
+        ]"#;
+        let filter_tags = HashMap::from([("team".to_string(), "payments".to_string())]);
+        assert_eq!(AwsSecretManager::matching_secrets(listing, &filter_tags)?, vec![
+            "prod/payments/db"
+        ]);
+        assert!(AwsSecretManager::matching_secrets(b"null", &filter_tags)?.is_empty());
+
+        let expanded = AwsSecretManager::expand_json(r#"{"username": "app", "port": 5432, "db-name": "main"}"#)?;
+        let expanded: Vec<_> = expanded
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(expanded, vec![
+            ("DB_NAME", "main"),
+            ("PORT", "5432"),
+            ("USERNAME", "app")
+        ]);
+        assert!(AwsSecretManager::expand_json(r#"{"nested": {"key": "value"}}"#).is_err());
+        assert!(AwsSecretManager::expand_json("plain text").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn names_parameters_after_their_path() {
+        let parameters = vec![
+            ("/app/prod/db/password".to_string(), Zeroizing::new("p4ss".to_string())),
+            ("/app/prod/api-key".to_string(), Zeroizing::new("key".to_string())),
+        ];
+        let variables = AwsParameterStore::variables("/app/prod/", parameters);
+        let variables: Vec<_> = variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(variables, [("API_KEY", "key"), ("DB_PASSWORD", "p4ss")]);
+    }
+}
@@ -0,0 +1,216 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::Zeroize,
+};
+
+#[derive(Debug, Clone)]
+pub(crate) struct AzureSecretSpec {
+    // Key Vault name, as in https://{vault}.vault.azure.net
+    pub(crate) vault: String,
+    // Secret name within the vault
+    pub(crate) secret: String,
+    // Optional version identifier (defaults to the current version)
+    pub(crate) version: Option<String>,
+}
+
+impl AzureSecretSpec {
+    /// Key Vault's naming rules, which also keep names from reading as options.
+    fn validate(&self) -> Result<()> {
+        let vault = self.vault.as_bytes();
+        if !(3..=24).contains(&vault.len())
+            || !vault[0].is_ascii_alphabetic()
+            || !vault[vault.len() - 1].is_ascii_alphanumeric()
+            || !vault.iter().all(|c| c.is_ascii_alphanumeric() || *c == b'-')
+            || self.vault.contains("--")
+        {
+            anyhow::bail!("Invalid Azure Key Vault name: '{}'", self.vault);
+        }
+        if !(1..=127).contains(&self.secret.len())
+            || !self.secret.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-')
+            || self.secret.starts_with('-')
+        {
+            anyhow::bail!("Invalid Azure secret name: '{}'", self.secret);
+        }
+        if let Some(version) = &self.version {
+            if version.is_empty() || !version.bytes().all(|c| c.is_ascii_alphanumeric()) {
+                anyhow::bail!("Invalid Azure secret version: '{}'", version);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn path(&self) -> String {
+        format!("{}/{}", self.vault, self.secret)
+    }
+}
+
+pub(crate) struct AzureKeyVault;
+
+impl AzureKeyVault {
+    pub(crate) fn access_secret(&self, spec: &AzureSecretSpec, removed_env_vars: &[String]) -> Result<String> {
+        spec.validate()?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.azure_secret(&spec.path(), spec.version.as_deref());
+        }
+        let mut cmd = Command::new("az");
+        cmd.args(["keyvault", "secret", "show"])
+            .arg("--vault-name")
+            .arg(&spec.vault)
+            .arg("--name")
+            .arg(&spec.secret)
+            .arg("--query")
+            .arg("value")
+            .arg("--output")
+            .arg("json");
+        if let Some(version) = &spec.version {
+            cmd.arg("--version").arg(version);
+        }
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = crate::throttle::Provider::Azure
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute az CLI to access secret")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("az CLI failed: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+
+        let value = serde_json::from_slice::<Option<String>>(&output.stdout);
+        output.stdout.zeroize();
+        value
+            .context("Azure secret output is not valid JSON")?
+            .context("Azure secret has no value")
+    }
+}
+
+pub(crate) struct AzureAccessToken;
+
+impl AzureAccessToken {
+    fn validate(scope: Option<&str>, resource: Option<&str>, tenant: Option<&str>) -> Result<()> {
+        if scope.is_some() && resource.is_some() {
+            anyhow::bail!("An Azure token takes either a scope or a resource, not both");
+        }
+        for (name, value) in [("scope", scope), ("resource", resource), ("tenant", tenant)] {
+            if value.is_some_and(|value| value.is_empty() || value.starts_with('-') || value.contains(' ')) {
+                anyhow::bail!("Invalid Azure token {}: '{}'", name, value.unwrap_or_default());
+            }
+        }
+        Ok(())
+    }
+
+    /// A token for `scope` or `resource`, Azure Resource Manager by default.
+    pub(crate) fn token(
+        &self,
+        scope: Option<&str>,
+        resource: Option<&str>,
+        tenant: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<String> {
+        Self::validate(scope, resource, tenant)?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.azure_token(scope.or(resource).unwrap_or("default"));
+        }
+        let mut cmd = Command::new("az");
+        cmd.args([
+            "account",
+            "get-access-token",
+            "--query",
+            "accessToken",
+            "--output",
+            "json",
+        ]);
+        if let Some(scope) = scope {
+            cmd.arg("--scope").arg(scope);
+        }
+        if let Some(resource) = resource {
+            cmd.arg("--resource").arg(resource);
+        }
+        if let Some(tenant) = tenant {
+            cmd.arg("--tenant").arg(tenant);
+        }
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = crate::throttle::Provider::Azure
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute az CLI to get an access token")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("az CLI failed: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+
+        let token = serde_json::from_slice::<String>(&output.stdout);
+        output.stdout.zeroize();
+        token.context("Azure access token output is not a JSON string")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_vault_and_secret_names() {
+        let spec = |vault: &str, secret: &str, version: Option<&str>| {
+            AzureSecretSpec {
+                vault: vault.to_string(),
+                secret: secret.to_string(),
+                version: version.map(str::to_string),
+            }
+        };
+        assert!(spec("payments-kv", "db-password", None).validate().is_ok());
+        assert!(spec("kv1", "token", Some("4387e9f3d6e14c459867679a90fd0f79"))
+            .validate()
+            .is_ok());
+        assert_eq!(spec("kv1", "token", None).path(), "kv1/token");
+
+        for (vault, secret, version) in [
+            ("kv", "token", None),
+            ("1kv", "token", None),
+            ("kv-", "token", None),
+            ("pay--kv", "token", None),
+            ("kv1", "--help", None),
+            ("kv1", "db_password", None),
+            ("kv1", "token", Some("--help")),
+        ] {
+            assert!(spec(vault, secret, version).validate().is_err(), "{}/{}", vault, secret);
+        }
+    }
+
+    #[test]
+    fn validates_token_requests() {
+        let scope = Some("https://ossrdbms-aad.database.windows.net/.default");
+        assert!(AzureAccessToken::validate(scope, None, Some("contoso.onmicrosoft.com")).is_ok());
+        assert!(AzureAccessToken::validate(None, None, None).is_ok());
+        assert!(AzureAccessToken::validate(scope, Some("https://vault.azure.net"), None).is_err());
+        assert!(AzureAccessToken::validate(None, Some("--help"), None).is_err());
+    }
+}
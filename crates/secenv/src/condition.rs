@@ -0,0 +1,258 @@
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::collections::HashMap,
+};
+
+/// A `when` expression, e.g. `env.CI == 'true' && os != 'windows'`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Condition {
+    Set(Operand),
+    Equals(Operand, Operand),
+    NotEquals(Operand, Operand),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Operand {
+    Literal(String),
+    Env(String),
+    Os,
+    Hostname,
+    Profile,
+}
+
+pub(crate) struct ConditionContext {
+    profile: String,
+    os: String,
+    hostname: String,
+    env: HashMap<String, String>,
+}
+
+impl ConditionContext {
+    pub(crate) fn current(profile: &str) -> Self {
+        Self {
+            profile: profile.to_string(),
+            os: std::env::consts::OS.to_string(),
+            hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+            env: std::env::vars_os()
+                .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+                .collect(),
+        }
+    }
+
+    fn value(&self, operand: &Operand) -> String {
+        match operand {
+            | Operand::Literal(value) => value.clone(),
+            | Operand::Env(name) => self.env.get(name).cloned().unwrap_or_default(),
+            | Operand::Os => self.os.clone(),
+            | Operand::Hostname => self.hostname.clone(),
+            | Operand::Profile => self.profile.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Text(String),
+    Word(String),
+    Equals,
+    NotEquals,
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl Condition {
+    pub(crate) fn parse(expression: &str) -> Result<Self> {
+        let tokens = Self::tokenize(expression).with_context(|| format!("Invalid condition '{}'", expression))?;
+        let mut parser = ConditionParser { tokens, position: 0 };
+        let condition = parser
+            .or()
+            .with_context(|| format!("Invalid condition '{}'", expression))?;
+        if parser.position != parser.tokens.len() {
+            anyhow::bail!("Invalid condition '{}': unexpected trailing input", expression);
+        }
+        Ok(condition)
+    }
+
+    pub(crate) fn evaluate(&self, context: &ConditionContext) -> bool {
+        match self {
+            | Self::Set(operand) => !context.value(operand).is_empty(),
+            | Self::Equals(left, right) => context.value(left) == context.value(right),
+            | Self::NotEquals(left, right) => context.value(left) != context.value(right),
+            | Self::Not(condition) => !condition.evaluate(context),
+            | Self::And(left, right) => left.evaluate(context) && right.evaluate(context),
+            | Self::Or(left, right) => left.evaluate(context) || right.evaluate(context),
+        }
+    }
+
+    pub(crate) fn applies(when: Option<&str>, context: &ConditionContext) -> Result<bool> {
+        when.map_or(Ok(true), |when| Ok(Self::parse(when)?.evaluate(context)))
+    }
+
+    fn tokenize(expression: &str) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        let mut rest = expression;
+        while let Some(character) = rest.chars().next() {
+            let (token, length) = match character {
+                | _ if character.is_whitespace() => {
+                    rest = &rest[character.len_utf8()..];
+                    continue;
+                },
+                | '(' => (Token::Open, 1),
+                | ')' => (Token::Close, 1),
+                | '=' if rest.starts_with("==") => (Token::Equals, 2),
+                | '!' if rest.starts_with("!=") => (Token::NotEquals, 2),
+                | '!' => (Token::Not, 1),
+                | '&' if rest.starts_with("&&") => (Token::And, 2),
+                | '|' if rest.starts_with("||") => (Token::Or, 2),
+                | '\'' | '"' => {
+                    let end = rest[1..]
+                        .find(character)
+                        .with_context(|| format!("Unterminated string starting at '{}'", rest))?;
+                    (Token::Text(rest[1..end + 1].to_string()), end + 2)
+                },
+                | _ if character.is_ascii_alphanumeric() || character == '_' => {
+                    let length = rest
+                        .find(|character: char| !(character.is_ascii_alphanumeric() || "_.-".contains(character)))
+                        .unwrap_or(rest.len());
+                    (Token::Word(rest[..length].to_string()), length)
+                },
+                | _ => anyhow::bail!("Unexpected '{}'", character),
+            };
+            tokens.push(token);
+            rest = &rest[length..];
+        }
+        Ok(tokens)
+    }
+}
+
+struct ConditionParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl ConditionParser {
+    fn next_if(&mut self, token: &Token) -> bool {
+        let matches = self.tokens.get(self.position) == Some(token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn or(&mut self) -> Result<Condition> {
+        let mut condition = self.and()?;
+        while self.next_if(&Token::Or) {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition> {
+        let mut condition = self.unary()?;
+        while self.next_if(&Token::And) {
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> Result<Condition> {
+        if self.next_if(&Token::Not) {
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+        if self.next_if(&Token::Open) {
+            let condition = self.or()?;
+            if !self.next_if(&Token::Close) {
+                anyhow::bail!("Missing ')'");
+            }
+            return Ok(condition);
+        }
+        let left = self.operand()?;
+        if self.next_if(&Token::Equals) {
+            return Ok(Condition::Equals(left, self.operand()?));
+        }
+        if self.next_if(&Token::NotEquals) {
+            return Ok(Condition::NotEquals(left, self.operand()?));
+        }
+        Ok(Condition::Set(left))
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .context("Unexpected end of condition")?;
+        self.position += 1;
+        match token {
+            | Token::Text(value) => Ok(Operand::Literal(value)),
+            | Token::Word(word) => {
+                match word.as_str() {
+                    | "os" => Ok(Operand::Os),
+                    | "hostname" => Ok(Operand::Hostname),
+                    | "profile" => Ok(Operand::Profile),
+                    | _ => {
+                        word.strip_prefix("env.")
+                            .filter(|name| !name.is_empty())
+                            .map(|name| Operand::Env(name.to_string()))
+                            .with_context(|| {
+                                format!(
+                                    "Unknown operand '{}'; expected env.NAME, os, hostname, profile, or a quoted \
+                                     string",
+                                    word
+                                )
+                            })
+                    },
+                }
+            },
+            | token => anyhow::bail!("Expected an operand, found {:?}", token),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_conditions() -> Result<()> {
+        let context = ConditionContext {
+            profile: "ci".to_string(),
+            os: "linux".to_string(),
+            hostname: "build-01".to_string(),
+            env: HashMap::from([
+                ("CI".to_string(), "true".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]),
+        };
+        let evaluate = |expression: &str| Condition::parse(expression).map(|condition| condition.evaluate(&context));
+
+        assert!(evaluate("env.CI == 'true'")?);
+        assert!(!evaluate("env.CI != \"true\"")?);
+        assert!(evaluate("env.CI && !env.EMPTY && !env.MISSING")?);
+        assert!(evaluate("os == 'macos' || profile == 'ci' && hostname == 'build-01'")?);
+        assert!(!evaluate("(os == 'macos' || profile == 'ci') && hostname == 'other'")?);
+        assert!(Condition::applies(None, &context)?);
+
+        for invalid in [
+            "",
+            "env.CI ==",
+            "env.CI = 'true'",
+            "(env.CI",
+            "user == 'x'",
+            "'open",
+            "env. == ''",
+        ] {
+            assert!(Condition::parse(invalid).is_err(), "{}", invalid);
+        }
+        Ok(())
+    }
+}
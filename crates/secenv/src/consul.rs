@@ -0,0 +1,144 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    base64::Engine,
+    serde::Deserialize,
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+#[derive(Deserialize)]
+struct ExportedKey {
+    key: String,
+    /// Base64; empty or missing for folders.
+    value: Option<Zeroizing<String>>,
+}
+
+pub(crate) struct ConsulKv;
+
+impl ConsulKv {
+    /// Read every key under `prefix`, named after the rest of the key.
+    pub(crate) fn read_prefix(
+        &self,
+        prefix: &str,
+        datacenter: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        if prefix.starts_with('-') {
+            anyhow::bail!("Invalid Consul prefix: '{}'", prefix);
+        }
+        if let Some(datacenter) = datacenter {
+            if datacenter.is_empty() || datacenter.starts_with('-') {
+                anyhow::bail!("Invalid Consul datacenter: '{}'", datacenter);
+            }
+        }
+        let keys = match crate::mock::MockProviders::active() {
+            | Some(fixtures) => fixtures.consul_keys(prefix)?,
+            | None => self.export(prefix, datacenter, removed_env_vars)?,
+        };
+        Ok(Self::variables(prefix, keys))
+    }
+
+    /// Skips keys that merely share the prefix, such as `app/configs`.
+    fn variables(prefix: &str, keys: Vec<(String, Zeroizing<String>)>) -> Vec<(String, Zeroizing<String>)> {
+        let prefix = prefix.trim_end_matches('/');
+        keys.into_iter()
+            .filter_map(|(key, value)| {
+                let relative = key.strip_prefix(prefix)?;
+                let relative = match relative.strip_prefix('/') {
+                    | Some(relative) => relative,
+                    | None if prefix.is_empty() => relative,
+                    | None => return None,
+                };
+                if relative.is_empty() || relative.ends_with('/') {
+                    return None;
+                }
+                Some((crate::process::environment_variable_name(relative), value))
+            })
+            .collect()
+    }
+
+    fn export(
+        &self,
+        prefix: &str,
+        datacenter: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        let mut cmd = Command::new("consul");
+        cmd.args(["kv", "export"]);
+        if let Some(datacenter) = datacenter {
+            cmd.arg(format!("-datacenter={}", datacenter));
+        }
+        cmd.arg(prefix);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute consul CLI to export keys")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("consul CLI failed: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let keys = serde_json::from_slice::<Vec<ExportedKey>>(&output.stdout);
+        output.stdout.zeroize();
+        keys.context("consul kv export output is not valid JSON")?
+            .into_iter()
+            .map(|exported| {
+                let encoded = exported.value.unwrap_or_default();
+                let decoded = Zeroizing::new(
+                    base64::engine::general_purpose::STANDARD
+                        .decode(encoded.as_bytes())
+                        .with_context(|| format!("Consul key '{}' is not valid base64", exported.key))?,
+                );
+                let value = String::from_utf8(decoded.to_vec()).map_err(|error| {
+                    error.into_bytes().zeroize();
+                    anyhow::anyhow!("Consul key '{}' is not valid UTF-8", exported.key)
+                })?;
+                Ok((exported.key, Zeroizing::new(value)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_keys_below_the_prefix() {
+        let keys = [
+            "app/config/",
+            "app/config/db/host",
+            "app/config/feature-flags",
+            "app/configs",
+        ]
+        .into_iter()
+        .map(|key| (key.to_string(), Zeroizing::new(String::new())))
+        .collect();
+        let names: Vec<_> = ConsulKv::variables("app/config/", keys)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["DB_HOST", "FEATURE_FLAGS"]);
+        assert!(ConsulKv.read_prefix("-http-addr=evil", None, &[]).is_err());
+    }
+}
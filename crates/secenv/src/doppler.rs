@@ -0,0 +1,135 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::{
+        collections::BTreeMap,
+        process::{
+            Command,
+            Stdio,
+        },
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+pub(crate) struct DopplerManager;
+
+impl DopplerManager {
+    pub(crate) fn download(
+        &self,
+        project: &str,
+        config: &str,
+        only: &[String],
+        exclude: &[String],
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        for (name, value) in [("project", project), ("config", config)] {
+            if value.is_empty() || value.starts_with('-') {
+                anyhow::bail!("Invalid Doppler {}: '{}'", name, value);
+            }
+        }
+        let only = Self::patterns(only)?;
+        let exclude = Self::patterns(exclude)?;
+        let secrets = match crate::mock::MockProviders::active() {
+            | Some(fixtures) => fixtures.doppler_config(project, config)?,
+            | None => self.fetch(project, config, removed_env_vars)?,
+        };
+        Ok(Self::filter(secrets, &only, &exclude))
+    }
+
+    fn filter(
+        secrets: Vec<(String, Zeroizing<String>)>,
+        only: &[regex::Regex],
+        exclude: &[regex::Regex],
+    ) -> Vec<(String, Zeroizing<String>)> {
+        secrets
+            .into_iter()
+            .filter(|(name, _)| {
+                (only.is_empty() || only.iter().any(|pattern| pattern.is_match(name)))
+                    && !exclude.iter().any(|pattern| pattern.is_match(name))
+            })
+            .collect()
+    }
+
+    fn patterns(patterns: &[String]) -> Result<Vec<regex::Regex>> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                crate::manifest::glob(pattern).with_context(|| format!("Invalid Doppler key filter '{}'", pattern))
+            })
+            .collect()
+    }
+
+    fn fetch(
+        &self,
+        project: &str,
+        config: &str,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        let mut cmd = Command::new("doppler");
+        cmd.args(["secrets", "download", "--no-file", "--format", "json"])
+            .arg("--project")
+            .arg(project)
+            .arg("--config")
+            .arg(config);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = crate::throttle::Provider::Doppler
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute doppler CLI to download secrets")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("doppler CLI failed: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let secrets = serde_json::from_slice::<BTreeMap<String, Zeroizing<String>>>(&output.stdout);
+        output.stdout.zeroize();
+        Ok(secrets
+            .context("Doppler secrets download output is not a JSON object of strings")?
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_downloaded_keys() -> Result<()> {
+        let names = |only: &[&str], exclude: &[&str]| -> Result<Vec<String>> {
+            let only: Vec<_> = only.iter().map(|pattern| pattern.to_string()).collect();
+            let exclude: Vec<_> = exclude.iter().map(|pattern| pattern.to_string()).collect();
+            let secrets = ["API_KEY", "DB_PASSWORD", "DB_USER", "DOPPLER_CONFIG"]
+                .into_iter()
+                .map(|name| (name.to_string(), Zeroizing::new(String::new())))
+                .collect();
+            let filtered = DopplerManager::filter(
+                secrets,
+                &DopplerManager::patterns(&only)?,
+                &DopplerManager::patterns(&exclude)?,
+            );
+            Ok(filtered.into_iter().map(|(name, _)| name).collect())
+        };
+        assert_eq!(names(&[], &["DOPPLER_*"])?, ["API_KEY", "DB_PASSWORD", "DB_USER"]);
+        assert_eq!(names(&["DB_*"], &["*_USER"])?, ["DB_PASSWORD"]);
+        assert!(DopplerManager.download("--help", "prd", &[], &[], &[]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use {
+    anyhow::Result,
+    zeroize::Zeroizing,
+};
+
+pub(crate) struct Entry {
+    pub(crate) line: usize,
+    pub(crate) name: String,
+    pub(crate) value: Zeroizing<String>,
+}
+
+/// Parse a dotenv document of `KEY=VALUE` lines; errors never contain values.
+pub(crate) fn parse(document: &str) -> Result<Vec<Entry>> {
+    let mut parser = Parser {
+        rest: document,
+        line: 1,
+    };
+    let mut entries = Vec::new();
+    while let Some(entry) = parser.entry()? {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn entry(&mut self) -> Result<Option<Entry>> {
+        loop {
+            self.skip_blanks();
+            match self.rest.chars().next() {
+                | None => return Ok(None),
+                | Some('\n') => self.advance(1),
+                | Some('#') => self.skip_line(),
+                | Some(_) => break,
+            }
+        }
+        let line = self.line;
+        let end = self.rest.find(['=', '\n']).unwrap_or(self.rest.len());
+        let statement = self.rest[..end].trim();
+        if !self.rest[end..].starts_with('=') {
+            anyhow::bail!("Malformed line {} (missing '=')", line);
+        }
+        let name = statement
+            .strip_prefix("export")
+            .filter(|name| name.starts_with([' ', '\t']))
+            .map_or(statement, str::trim_start);
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            anyhow::bail!("Malformed line {} (invalid variable name)", line);
+        }
+        let name = name.to_string();
+        self.advance(end + 1);
+        self.skip_blanks();
+        let value = match self.rest.chars().next() {
+            | Some('\'') => self.single_quoted(line)?,
+            | Some('"') => self.double_quoted(line)?,
+            | _ => self.unquoted(),
+        };
+        Ok(Some(Entry { line, name, value }))
+    }
+
+    fn unquoted(&mut self) -> Zeroizing<String> {
+        let end = self.rest.find('\n').unwrap_or(self.rest.len());
+        let line = &self.rest[..end];
+        let comment = line
+            .match_indices('#')
+            .map(|(index, _)| index)
+            .find(|&index| index == 0 || line[..index].ends_with([' ', '\t']));
+        let value = Zeroizing::new(line[..comment.unwrap_or(end)].trim().to_string());
+        self.advance(end);
+        value
+    }
+
+    fn single_quoted(&mut self, line: usize) -> Result<Zeroizing<String>> {
+        let Some(end) = self.rest[1..].find('\'') else {
+            anyhow::bail!("Malformed line {} (unterminated quoted value)", line);
+        };
+        let value = Zeroizing::new(self.rest[1..=end].to_string());
+        self.advance(end + 2);
+        self.end_of_value(line)?;
+        Ok(value)
+    }
+
+    fn double_quoted(&mut self, line: usize) -> Result<Zeroizing<String>> {
+        let mut value = Zeroizing::new(String::new());
+        let mut chars = self.rest.char_indices().skip(1);
+        let end = loop {
+            match chars.next() {
+                | None => anyhow::bail!("Malformed line {} (unterminated quoted value)", line),
+                | Some((index, '"')) => break index,
+                | Some((_, '\\')) => {
+                    match chars.next().map(|(_, escaped)| escaped) {
+                        | Some('n') => value.push('\n'),
+                        | Some('r') => value.push('\r'),
+                        | Some('t') => value.push('\t'),
+                        | Some(escaped @ ('"' | '\\' | '$')) => value.push(escaped),
+                        | Some(other) => {
+                            value.push('\\');
+                            value.push(other);
+                        },
+                        | None => anyhow::bail!("Malformed line {} (unterminated quoted value)", line),
+                    }
+                },
+                | Some((_, other)) => value.push(other),
+            }
+        };
+        self.advance(end + 1);
+        self.end_of_value(line)?;
+        Ok(value)
+    }
+
+    fn end_of_value(&mut self, line: usize) -> Result<()> {
+        self.skip_blanks();
+        match self.rest.chars().next() {
+            | None | Some('\n') => Ok(()),
+            | Some('#') => {
+                self.skip_line();
+                Ok(())
+            },
+            | Some(_) => anyhow::bail!("Malformed line {} (unexpected characters after the quoted value)", line),
+        }
+    }
+
+    fn skip_blanks(&mut self) {
+        let blanks = self.rest.len() - self.rest.trim_start_matches([' ', '\t', '\r']).len();
+        self.advance(blanks);
+    }
+
+    fn skip_line(&mut self) {
+        self.advance(self.rest.find('\n').unwrap_or(self.rest.len()));
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.line += self.rest[..bytes].matches('\n').count();
+        self.rest = &self.rest[bytes..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dotenv_documents() -> Result<()> {
+        let document = "# comment\nPLAIN=value\n\x20 SPACED = padded value  \nexport \
+                        EXPORTED=yes\nexported_name=kept\nINLINE=value # comment\nHASH=a#b\nEMPTY=\nCOMMENTED= # \
+                        comment\nSINGLE='raw \\n $HOME # not a comment'\nDOUBLE=\"line\\nnext \\\"quoted\\\" \\\\ \
+                        \\$HOME \\x\" # comment\nMULTILINE=\"first\nsecond\"\nCRLF=windows\r\nEQUALS=a=b\nLAST=1";
+        let entries = parse(document)?;
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.line, entry.name.as_str(), entry.value.as_str()))
+            .collect();
+        assert_eq!(entries, [
+            (2, "PLAIN", "value"),
+            (3, "SPACED", "padded value"),
+            (4, "EXPORTED", "yes"),
+            (5, "exported_name", "kept"),
+            (6, "INLINE", "value"),
+            (7, "HASH", "a#b"),
+            (8, "EMPTY", ""),
+            (9, "COMMENTED", ""),
+            (10, "SINGLE", "raw \\n $HOME # not a comment"),
+            (11, "DOUBLE", "line\nnext \"quoted\" \\ $HOME \\x"),
+            (12, "MULTILINE", "first\nsecond"),
+            (14, "CRLF", "windows"),
+            (15, "EQUALS", "a=b"),
+            (16, "LAST", "1"),
+        ]);
+
+        for (document, error) in [
+            ("A=1\nhunter2", "Malformed line 2 (missing '=')"),
+            ("A=1\n\nB='hunter2\n", "Malformed line 3 (unterminated quoted value)"),
+            ("A=\"hunter2", "Malformed line 1 (unterminated quoted value)"),
+            (
+                "A='hunter2' trailing",
+                "Malformed line 1 (unexpected characters after the quoted value)",
+            ),
+            ("MY KEY=hunter2", "Malformed line 1 (invalid variable name)"),
+            ("=hunter2", "Malformed line 1 (invalid variable name)"),
+        ] {
+            assert_eq!(
+                parse(document).err().map(|error| error.to_string()).as_deref(),
+                Some(error)
+            );
+        }
+        Ok(())
+    }
+}
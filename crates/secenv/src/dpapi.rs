@@ -0,0 +1,88 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    base64::Engine,
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+pub(crate) struct Dpapi;
+
+impl Dpapi {
+    /// Prints the plaintext as base64 so the console encoding cannot alter it.
+    const SCRIPT: &'static str = "Add-Type -AssemblyName System.Security; $blob = \
+                                  [Convert]::FromBase64String([Console]::In.ReadToEnd().Trim()); $plain = \
+                                  [Security.Cryptography.ProtectedData]::Unprotect($blob, $null, 'CurrentUser'); \
+                                  [Console]::Out.Write([Convert]::ToBase64String($plain))";
+
+    pub(crate) fn read(&self, path: &str, removed_env_vars: &[String]) -> Result<String> {
+        let blob = Zeroizing::new(
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read DPAPI file: {}", path))?,
+        );
+        if !cfg!(windows) {
+            return Err(Failure::Provider.error(format!("DPAPI file '{}' can only be unprotected on Windows", path)));
+        }
+        let mut cmd = Command::new("powershell.exe");
+        cmd.args(["-NoProfile", "-NonInteractive", "-Command", Self::SCRIPT]);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to execute PowerShell to unprotect a DPAPI file")
+            .categorize(Failure::Provider)?;
+        if let Some(mut input) = child.stdin.take() {
+            use std::io::Write;
+            input
+                .write_all(blob.trim().as_bytes())
+                .context("Failed to pass the DPAPI blob to PowerShell")?;
+        }
+        let mut output = child.wait_with_output().context("Failed to wait for PowerShell")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error =
+                Failure::Decryption.error(format!("Failed to unprotect DPAPI file '{}': {}", path, stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let plaintext = base64::engine::general_purpose::STANDARD.decode(output.stdout.trim_ascii());
+        output.stdout.zeroize();
+        String::from_utf8(plaintext.context("PowerShell printed invalid base64")?).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("DPAPI file '{}' is not valid UTF-8", path)
+        })
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unprotects_only_on_windows() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("key.dpapi");
+        std::fs::write(&path, "AQAAANCMnd8BFdERjHoAwE/Cl+s=")?;
+        let error = Dpapi
+            .read(path.to_str().context("path is not valid UTF-8")?, &[])
+            .unwrap_err();
+        assert!(error.to_string().contains("only be unprotected on Windows"));
+        assert!(Dpapi.read("/nonexistent/key.dpapi", &[]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,647 @@
+use {
+    crate::manifest::{
+        Manifest,
+        ManifestFormat,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::{
+        io::Write,
+        ops::Range,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// Edits a HOCON manifest in place, keeping everything an edit does not touch.
+pub(crate) struct ManifestEditor {
+    path: PathBuf,
+    text: String,
+}
+
+struct Object {
+    /// `None` for a braceless root.
+    open: Option<usize>,
+    close: usize,
+    fields: Vec<Field>,
+}
+
+struct Field {
+    path: Vec<String>,
+    start: usize,
+    key_end: usize,
+    quoted: bool,
+    value: Range<usize>,
+    comma: Option<usize>,
+    object: Option<Object>,
+}
+
+impl Field {
+    fn end(&self) -> usize {
+        self.comma.unwrap_or(self.value.end)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn at_comment(&self) -> bool {
+        self.rest().starts_with('#') || self.rest().starts_with("//")
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn skip_to_line_end(&mut self) {
+        self.position = self
+            .rest()
+            .find('\n')
+            .map_or(self.text.len(), |end| self.position + end);
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                | Some(byte) if byte.is_ascii_whitespace() || byte == b',' => self.position += 1,
+                | Some(_) if self.at_comment() => self.skip_to_line_end(),
+                | _ => return,
+            }
+        }
+    }
+
+    fn parse_root(&mut self) -> Result<Object> {
+        self.skip_trivia();
+        if self.peek() == Some(b'{') {
+            let open = self.position;
+            self.position += 1;
+            let root = self.parse_object(Some(open))?;
+            self.skip_trivia();
+            if self.position < self.text.len() {
+                anyhow::bail!("Unexpected content after the root object at offset {}", self.position);
+            }
+            return Ok(root);
+        }
+        self.parse_object(None)
+    }
+
+    fn parse_object(&mut self, open: Option<usize>) -> Result<Object> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                | None if open.is_some() => anyhow::bail!("Unclosed '{{' at offset {}", open.unwrap_or_default()),
+                | None => {
+                    return Ok(Object {
+                        open,
+                        close: self.text.len(),
+                        fields,
+                    });
+                },
+                | Some(b'}') if open.is_some() => {
+                    let close = self.position;
+                    self.position += 1;
+                    return Ok(Object { open, close, fields });
+                },
+                | Some(b'}') => anyhow::bail!("Unexpected '}}' at offset {}", self.position),
+                | Some(_) if self.rest().starts_with("include ") => self.skip_to_line_end(),
+                | Some(_) => fields.push(self.parse_field()?),
+            }
+        }
+    }
+
+    fn parse_field(&mut self) -> Result<Field> {
+        let start = self.position;
+        let quoted = self.peek() == Some(b'"');
+        let path = self.parse_key()?;
+        let key_end = self.position;
+        self.skip_inline_whitespace();
+        if self.rest().starts_with("+=") {
+            self.position += 2;
+        } else if matches!(self.peek(), Some(b'=' | b':')) {
+            self.position += 1;
+        } else if self.peek() != Some(b'{') {
+            anyhow::bail!("Expected '=', ':', or '{{' after key '{}'", path.join("."));
+        }
+        self.skip_trivia_before_value();
+        let value_start = self.position;
+        let (value_end, object) = self.parse_value()?;
+        self.skip_inline_whitespace();
+        let comma = (self.peek() == Some(b',')).then(|| {
+            self.position += 1;
+            self.position
+        });
+        Ok(Field {
+            path,
+            start,
+            key_end,
+            quoted,
+            value: value_start..value_end,
+            comma,
+            object,
+        })
+    }
+
+    fn skip_trivia_before_value(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn parse_key(&mut self) -> Result<Vec<String>> {
+        let mut path = Vec::new();
+        loop {
+            if self.peek() == Some(b'"') {
+                let start = self.position;
+                self.skip_string()?;
+                path.push(
+                    serde_json::from_str(&self.text[start..self.position])
+                        .with_context(|| format!("Invalid quoted key at offset {}", start))?,
+                );
+            } else {
+                let length = self
+                    .rest()
+                    .find(|character: char| character.is_whitespace() || "\".=:{}[],#$+".contains(character))
+                    .unwrap_or(self.rest().len());
+                if length == 0 {
+                    anyhow::bail!("Expected a key at offset {}", self.position);
+                }
+                path.push(self.rest()[..length].to_string());
+                self.position += length;
+            }
+            if self.peek() != Some(b'.') {
+                return Ok(path);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<(usize, Option<Object>)> {
+        let mut end = self.position;
+        let mut parts = 0;
+        let mut object = None;
+        loop {
+            match self.peek() {
+                | None | Some(b'\n' | b',' | b'}' | b']') => break,
+                | Some(_) if self.at_comment() => break,
+                | Some(b' ' | b'\t' | b'\r') => {
+                    self.position += 1;
+                    continue;
+                },
+                | Some(b'{') => {
+                    let open = self.position;
+                    self.position += 1;
+                    object = Some(self.parse_object(Some(open))?);
+                },
+                | Some(b'[') => self.skip_array()?,
+                | Some(b'"') => self.skip_string()?,
+                | Some(_) if self.rest().starts_with("${") => {
+                    let length = self
+                        .rest()
+                        .find('}')
+                        .with_context(|| format!("Unclosed substitution at offset {}", self.position))?;
+                    self.position += length + 1;
+                },
+                | Some(_) => {
+                    let rest = self.rest();
+                    let length = rest
+                        .char_indices()
+                        .find(|(index, character)| {
+                            character.is_whitespace()
+                                || "\",{}[]#".contains(*character)
+                                || rest[*index..].starts_with("//")
+                                || rest[*index..].starts_with("${")
+                        })
+                        .map_or(rest.len(), |(index, _)| index);
+                    if length == 0 {
+                        anyhow::bail!("Unexpected character at offset {}", self.position);
+                    }
+                    self.position += length;
+                },
+            }
+            parts += 1;
+            end = self.position;
+        }
+        if parts == 0 {
+            anyhow::bail!("Missing value at offset {}", self.position);
+        }
+        Ok((end, if parts == 1 { object } else { None }))
+    }
+
+    fn skip_array(&mut self) -> Result<()> {
+        let start = self.position;
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                | None => anyhow::bail!("Unclosed '[' at offset {}", start),
+                | Some(b'"') => self.skip_string()?,
+                | Some(_) if self.at_comment() => self.skip_to_line_end(),
+                | Some(b'[' | b'{') => {
+                    depth += 1;
+                    self.position += 1;
+                },
+                | Some(b']' | b'}') => {
+                    depth -= 1;
+                    self.position += 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                },
+                | Some(_) => self.position += 1,
+            }
+        }
+    }
+
+    fn skip_string(&mut self) -> Result<()> {
+        let start = self.position;
+        if let Some(after) = self.rest().strip_prefix("\"\"\"") {
+            let length = after
+                .find("\"\"\"")
+                .with_context(|| format!("Unterminated string at offset {}", start))?;
+            self.position += 3 + length + 3;
+            while self.peek() == Some(b'"') {
+                self.position += 1;
+            }
+            return Ok(());
+        }
+        self.position += 1;
+        loop {
+            match self.peek() {
+                | None | Some(b'\n') => anyhow::bail!("Unterminated string at offset {}", start),
+                | Some(b'\\') => self.position += 2,
+                | Some(b'"') => {
+                    self.position += 1;
+                    return Ok(());
+                },
+                | Some(_) => self.position += 1,
+            }
+        }
+    }
+}
+
+impl ManifestEditor {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        if ManifestFormat::detect(path) != ManifestFormat::Hocon {
+            anyhow::bail!(
+                "Editing '{}' is not supported; only HOCON and JSON manifests can be edited",
+                path.display()
+            );
+        }
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Self::new(path.to_path_buf(), text)
+    }
+
+    fn new(path: PathBuf, text: String) -> Result<Self> {
+        Self::parse(&text).with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        Ok(Self { path, text })
+    }
+
+    fn parse(text: &str) -> Result<Object> {
+        Parser { text, position: 0 }.parse_root()
+    }
+
+    /// Split a dotted key such as `files."./cert.pem".gcs` into its segments.
+    pub(crate) fn parse_path(path: &str) -> Result<Vec<String>> {
+        let mut parser = Parser {
+            text: path,
+            position: 0,
+        };
+        let segments = parser.parse_key()?;
+        if parser.position != path.len() {
+            anyhow::bail!("Invalid key '{}'", path);
+        }
+        Ok(segments)
+    }
+
+    pub(crate) fn set(&mut self, path: &[String], value: &str) -> Result<()> {
+        let root = Self::parse(&self.text)?;
+        let mut covered = Vec::new();
+        Self::covered(&root, path, &mut covered);
+        covered.sort_by_key(|(field, _)| field.start);
+        let Some((last, true)) = covered.pop() else {
+            self.remove(path)?;
+            return self.insert(path, value);
+        };
+
+        let mut edits: Vec<_> = covered
+            .iter()
+            .map(|(field, _)| (self.removal_span(field), String::new()))
+            .collect();
+        if self.text[last.key_end..last.value.start].contains(['=', ':']) {
+            edits.push((last.value.clone(), value.to_string()));
+        } else {
+            edits.push((last.key_end..last.value.end, format!(" = {}", value)));
+        }
+        self.apply(edits);
+        Ok(())
+    }
+
+    pub(crate) fn remove(&mut self, path: &[String]) -> Result<bool> {
+        let root = Self::parse(&self.text)?;
+        let mut covered = Vec::new();
+        Self::covered(&root, path, &mut covered);
+        let edits: Vec<_> = covered
+            .iter()
+            .map(|(field, _)| (self.removal_span(field), String::new()))
+            .collect();
+        let removed = !edits.is_empty();
+        self.apply(edits);
+        Ok(removed)
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        Manifest::from_source(&self.text, self.path.clone())
+            .context("The edited manifest is invalid and was not written")?;
+        let parent = self
+            .path
+            .parent()
+            .with_context(|| format!("Config file '{}' has no parent directory", self.path.display()))?;
+        let permissions = std::fs::metadata(&self.path)
+            .with_context(|| format!("Failed to read metadata of '{}'", self.path.display()))?
+            .permissions();
+        let mut temporary = tempfile::NamedTempFile::new_in(parent)
+            .with_context(|| format!("Failed to create temporary file beside '{}'", self.path.display()))?;
+        temporary
+            .as_file()
+            .set_permissions(permissions)
+            .and_then(|()| temporary.write_all(self.text.as_bytes()))
+            .and_then(|()| temporary.as_file().sync_all())
+            .with_context(|| format!("Failed to write temporary file for '{}'", self.path.display()))?;
+        temporary
+            .persist(&self.path)
+            .map_err(|error| error.error)
+            .with_context(|| format!("Failed to replace '{}'", self.path.display()))?;
+        Ok(())
+    }
+
+    fn covered<'o>(object: &'o Object, path: &[String], covered: &mut Vec<(&'o Field, bool)>) {
+        for field in &object.fields {
+            if field.path.starts_with(path) {
+                covered.push((field, field.path.len() == path.len()));
+            } else if let Some(inner) = field.object.as_ref().filter(|_| path.starts_with(&field.path)) {
+                Self::covered(inner, &path[field.path.len()..], covered);
+            }
+        }
+    }
+
+    fn container<'o, 'p>(
+        object: &'o Object,
+        parent: Option<&'o Field>,
+        path: &'p [String],
+    ) -> (&'o Object, Option<&'o Field>, &'p [String]) {
+        for field in object.fields.iter().rev() {
+            if let Some(inner) = &field.object {
+                if field.path.len() < path.len() && path.starts_with(&field.path) {
+                    return Self::container(inner, Some(field), &path[field.path.len()..]);
+                }
+            }
+        }
+        (object, parent, path)
+    }
+
+    fn insert(&mut self, path: &[String], value: &str) -> Result<()> {
+        let root = Self::parse(&self.text)?;
+        let (object, parent, relative) = Self::container(&root, None, path);
+        let text = &self.text;
+        // New keys follow the quoting and separator of their siblings, or of
+        // the enclosing key in an empty object.
+        let style = object.fields.last().or(parent);
+        let key = relative
+            .iter()
+            .map(|segment| Self::render_segment(segment, style.is_some_and(|field| field.quoted)))
+            .collect::<Vec<_>>()
+            .join(".");
+        let separator = style
+            .map(|field| &text[field.key_end..field.value.start])
+            .filter(|separator| separator.contains(['=', ':']) && !separator.contains('\n'))
+            .unwrap_or(" = ");
+        let entry = format!("{}{}{}", key, separator, value);
+
+        let edits = match (object.fields.last(), object.open) {
+            | (None, None) => {
+                let newline = if text.is_empty() || text.ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                vec![(text.len()..text.len(), format!("{}{}\n", newline, entry))]
+            },
+            | (None, Some(open)) => {
+                let indent = Self::line_indent(text, open);
+                if text[open + 1..object.close].trim().is_empty() {
+                    vec![(open + 1..object.close, format!("\n{}  {}\n{}", indent, entry, indent))]
+                } else {
+                    vec![(object.close..object.close, format!("  {}\n{}", entry, indent))]
+                }
+            },
+            | (Some(last), _) => {
+                let previous_end = match object.fields.len() {
+                    | 1 => object.open.map_or(0, |open| open + 1),
+                    | count => object.fields[count - 2].end(),
+                };
+                let inline = object.open.is_some() && !text[previous_end..last.start].contains('\n');
+                if inline {
+                    let separator = if last.comma.is_some() { " " } else { ", " };
+                    vec![(last.end()..last.end(), format!("{}{}", separator, entry))]
+                } else {
+                    let line_start = text[..last.start].rfind('\n').map_or(0, |index| index + 1);
+                    let mut indent = Self::line_indent(text, last.start).to_string();
+                    if !text[line_start..last.start].trim().is_empty() {
+                        indent.push_str("  ");
+                    }
+                    let line_end = text[last.end()..]
+                        .find('\n')
+                        .map_or(text.len(), |index| last.end() + index)
+                        .min(object.close);
+                    let comma_style = object.fields.iter().any(|field| field.comma.is_some())
+                        || text[last.key_end..last.value.start].contains(':');
+                    let mut edits = Vec::new();
+                    if comma_style && last.comma.is_none() {
+                        edits.push((last.value.end..last.value.end, ",".to_string()));
+                    }
+                    edits.push((
+                        line_end..line_end,
+                        format!("\n{}{}{}", indent, entry, if last.comma.is_some() { "," } else { "" }),
+                    ));
+                    edits
+                }
+            },
+        };
+        self.apply(edits);
+        Ok(())
+    }
+
+    fn removal_span(&self, field: &Field) -> Range<usize> {
+        let text = &self.text;
+        let line_start = text[..field.start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = text[field.end()..]
+            .find('\n')
+            .map_or(text.len(), |index| field.end() + index);
+        let trailing = text[field.end()..line_end].trim();
+        if text[line_start..field.start].trim().is_empty()
+            && (trailing.is_empty() || trailing.starts_with('#') || trailing.starts_with("//"))
+        {
+            return line_start..(line_end + 1).min(text.len());
+        }
+        let whitespace = text[field.end()..line_end].len() - text[field.end()..line_end].trim_start().len();
+        field.start..field.end() + whitespace
+    }
+
+    fn apply(&mut self, mut edits: Vec<(Range<usize>, String)>) {
+        edits.sort_by_key(|(span, _)| (span.start, span.end));
+        let mut merged: Vec<(Range<usize>, String)> = Vec::new();
+        for (span, replacement) in edits {
+            match merged.last_mut() {
+                | Some((previous, previous_replacement)) if span.start < previous.end => {
+                    previous.end = previous.end.max(span.end);
+                    previous_replacement.push_str(&replacement);
+                },
+                | _ => merged.push((span, replacement)),
+            }
+        }
+        for (span, replacement) in merged.into_iter().rev() {
+            self.text.replace_range(span, &replacement);
+        }
+    }
+
+    fn line_indent(text: &str, offset: usize) -> &str {
+        let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+        let line = &text[line_start..];
+        &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+    }
+
+    fn render_segment(segment: &str, quoted: bool) -> String {
+        let plain = !segment.is_empty()
+            && segment
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '-');
+        if plain && !quoted {
+            segment.to_string()
+        } else {
+            serde_json::Value::String(segment.to_string()).to_string()
+        }
+    }
+
+    #[cfg(test)]
+    fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(key: &str) -> Vec<String> {
+        ManifestEditor::parse_path(key).unwrap()
+    }
+
+    #[test]
+    fn edits_keep_comments_and_formatting() -> Result<()> {
+        let mut editor = ManifestEditor::new(
+            PathBuf::from("secenv.conf"),
+            r#"# Team secrets
+version = "0.0.0"
+profiles.default {
+  env.vars {
+    # rotated by ops
+    TOKEN.gcs.secret = "projects/demo/secrets/token" // keep me
+    LEGACY.plain.literal = "old"
+  }
+  files { "./a.pem" { aws { secret = "tls" } } }
+}
+profiles.ci = ${profiles.default}
+"#
+            .to_string(),
+        )?;
+
+        editor.set(&path("profiles.default.env.vars.TOKEN.gcs.version"), r#""3""#)?;
+        editor.set(&path("profiles.default.env.vars.NEW"), r#"{ plain.literal = "x" }"#)?;
+        editor.set(&path(r#"profiles.default.files."./a.pem".aws.secret"#), r#""tls-v2""#)?;
+        editor.set(&path("profiles.ci.expires"), r#""2030-01-01""#)?;
+        assert!(editor.remove(&path("profiles.default.env.vars.LEGACY"))?);
+        assert!(!editor.remove(&path("profiles.ci.env.vars.TOKEN"))?);
+        assert_eq!(
+            editor.text(),
+            r#"# Team secrets
+version = "0.0.0"
+profiles.default {
+  env.vars {
+    # rotated by ops
+    TOKEN.gcs.secret = "projects/demo/secrets/token" // keep me
+    TOKEN.gcs.version = "3"
+    NEW = { plain.literal = "x" }
+  }
+  files { "./a.pem" { aws { secret = "tls-v2" } } }
+}
+profiles.ci = ${profiles.default}
+profiles.ci.expires = "2030-01-01"
+"#
+        );
+
+        editor.set(&path("profiles.default.env.vars.TOKEN"), r#"{ plain.literal = "y" }"#)?;
+        assert!(editor.text().contains("    # rotated by ops\n    NEW = {"));
+        assert!(editor.text().contains(
+            "    # rotated by ops\n    NEW = { plain.literal = \"x\" }\n    TOKEN = { plain.literal = \"y\" }\n  }"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn edits_json_manifests_in_their_own_style() -> Result<()> {
+        let mut editor = ManifestEditor::new(
+            PathBuf::from("secenv.conf"),
+            r#"{
+  "version": "0.0.0",
+  "profiles": {
+    "default": {
+      "env": { "vars": {} }
+    }
+  }
+}
+"#
+            .to_string(),
+        )?;
+        editor.set(
+            &path("profiles.default.env.vars.A"),
+            r#"{ "plain": { "literal": "a" } }"#,
+        )?;
+        editor.set(&path("profiles.prod"), "{}")?;
+        assert_eq!(
+            editor.text(),
+            r#"{
+  "version": "0.0.0",
+  "profiles": {
+    "default": {
+      "env": { "vars": {
+        "A": { "plain": { "literal": "a" } }
+      } }
+    },
+    "prod": {}
+  }
+}
+"#
+        );
+        assert!(ManifestEditor::parse_path("a..b").is_err());
+        assert!(ManifestEditor::new(PathBuf::from("secenv.conf"), "a { b = 1".to_string()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::Zeroize,
+};
+
+pub(crate) struct Exec;
+
+impl Exec {
+    /// Run `command` and return what it prints, sharing the terminal.
+    pub(crate) fn capture(&self, command: &str, args: &[String], removed_env_vars: &[String]) -> Result<String> {
+        if command.is_empty() {
+            anyhow::bail!("Secret command must not be empty");
+        }
+        let mut cmd = Command::new(command);
+        cmd.args(args);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let mut output = cmd
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .output()
+            .with_context(|| format!("Failed to execute '{}'", command))
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            output.stdout.zeroize();
+            return Err(Failure::Provider.error(format!("'{}' failed with {}", command, output.status)));
+        }
+        Self::value(std::mem::take(&mut output.stdout)).with_context(|| format!("Invalid output of '{}'", command))
+    }
+
+    fn value(stdout: Vec<u8>) -> Result<String> {
+        let mut value = String::from_utf8(stdout).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("Command output is not valid UTF-8")
+        })?;
+        if value.ends_with('\n') {
+            value.pop();
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_standard_output() -> Result<()> {
+        assert_eq!(Exec::value(b"token\n\n".to_vec())?, "token\n");
+        assert!(Exec::value(vec![0xff]).is_err());
+        assert!(Exec.capture("", &[], &[]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    serde_json::Value,
+    zeroize::Zeroizing,
+};
+
+/// A JSONPath such as `$.hosts[0]`, or a single member name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Member(String),
+    Index(usize),
+}
+
+impl JsonPath {
+    pub(crate) fn parse(path: &str) -> Result<Self> {
+        let Some(mut rest) = path.strip_prefix('$') else {
+            if path.is_empty() {
+                anyhow::bail!("JSON path must not be empty");
+            }
+            return Ok(Self {
+                segments: vec![Segment::Member(path.to_string())],
+            });
+        };
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(member) = rest.strip_prefix('.') {
+                let end = member.find(['.', '[']).unwrap_or(member.len());
+                if end == 0 {
+                    anyhow::bail!("Unsupported JSON path '{}': expected a member name after '.'", path);
+                }
+                segments.push(Segment::Member(member[..end].to_string()));
+                rest = &member[end..];
+            } else if let Some(bracket) = rest.strip_prefix('[') {
+                let end = bracket
+                    .find(']')
+                    .with_context(|| format!("Invalid JSON path '{}': unclosed '['", path))?;
+                let selector = &bracket[..end];
+                let quoted = ['\'', '"'].into_iter().find_map(|quote| {
+                    selector
+                        .strip_prefix(quote)
+                        .and_then(|selector| selector.strip_suffix(quote))
+                });
+                segments.push(match (quoted, selector.parse()) {
+                    | (Some(member), _) => Segment::Member(member.to_string()),
+                    | (None, Ok(index)) => Segment::Index(index),
+                    | (None, Err(_)) => {
+                        anyhow::bail!(
+                            "Unsupported JSON path '{}': '[{}]' is neither an index nor a quoted name",
+                            path,
+                            selector
+                        )
+                    },
+                });
+                rest = &bracket[end + 1..];
+            } else {
+                anyhow::bail!("Invalid JSON path '{}': expected '.' or '[' at '{}'", path, rest);
+            }
+        }
+        Ok(Self { segments })
+    }
+
+    pub(crate) fn value(&self, document: &str) -> Result<Zeroizing<String>> {
+        self.with_selected(document, |selected| {
+            Ok(Zeroizing::new(match selected {
+                | Value::String(value) => value.clone(),
+                | Value::Null => String::new(),
+                | value => serde_json::to_string(value).context("Failed to serialize the extracted value")?,
+            }))
+        })
+    }
+
+    pub(crate) fn variables(&self, document: &str) -> Result<Vec<(String, Zeroizing<String>)>> {
+        self.with_selected(document, |selected| {
+            if !selected.is_object() {
+                anyhow::bail!("JSON path '{}' does not select an object", self);
+            }
+            let object = Zeroizing::new(serde_json::to_string(selected).context("Failed to serialize the object")?);
+            crate::process::json_variables(&object)
+        })
+    }
+
+    fn with_selected<T>(&self, document: &str, use_selected: impl FnOnce(&Value) -> Result<T>) -> Result<T> {
+        let mut parsed: Value = serde_json::from_str(document).map_err(|error| {
+            anyhow::anyhow!("Secret is not JSON (line {}, column {})", error.line(), error.column())
+        })?;
+        let selected = self
+            .segments
+            .iter()
+            .try_fold(&parsed, |node, segment| {
+                match segment {
+                    | Segment::Member(member) => node.get(member),
+                    | Segment::Index(index) => node.get(index),
+                }
+            })
+            .with_context(|| format!("JSON secret has no value at '{}'", self))
+            .and_then(use_selected);
+        crate::sealed::SealedDocument::zeroize_value(&mut parsed);
+        selected
+    }
+}
+
+impl std::fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("$")?;
+        for segment in &self.segments {
+            match segment {
+                | Segment::Member(member) if !member.is_empty() && !member.contains(['.', '[', ']', '\'']) => {
+                    write!(f, ".{}", member)?
+                },
+                | Segment::Member(member) => write!(f, "[\"{}\"]", member)?,
+                | Segment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_fields_of_json_secrets() -> Result<()> {
+        let secret = r#"{
+            "credentials": { "user": "app", "password": "hunter2", "port": 5432, "tls": null },
+            "hosts": ["db-1", "db-2"],
+            "key.with.dots": "dotted",
+            "env": { "DB_HOST": "db", "DEBUG": false }
+        }"#;
+        let value = |path| {
+            JsonPath::parse(path)
+                .and_then(|path| path.value(secret))
+                .map(|value| value.to_string())
+        };
+        assert_eq!(value("$.credentials.password")?, "hunter2");
+        assert_eq!(value("$['credentials'][\"user\"]")?, "app");
+        assert_eq!(value("$.credentials.port")?, "5432");
+        assert_eq!(value("$.credentials.tls")?, "");
+        assert_eq!(value("$.hosts[1]")?, "db-2");
+        assert_eq!(value("$.hosts")?, r#"["db-1","db-2"]"#);
+        assert_eq!(value("key.with.dots")?, "dotted");
+        assert_eq!(value("$['key.with.dots']")?, "dotted");
+
+        let missing = value("$.credentials.token").unwrap_err().to_string();
+        assert_eq!(missing, "JSON secret has no value at '$.credentials.token'");
+        let invalid = JsonPath::parse("$.x")?
+            .value("password=hunter2")
+            .unwrap_err()
+            .to_string();
+        assert!(!invalid.contains("hunter2"), "{}", invalid);
+        for unsupported in ["$..password", "$.hosts[*]", "$[", "$x", ""] {
+            assert!(JsonPath::parse(unsupported).is_err(), "accepted '{}'", unsupported);
+        }
+
+        let variables = JsonPath::parse("$.env")?.variables(secret)?;
+        let variables: Vec<_> = variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(variables, [("DB_HOST", "db"), ("DEBUG", "false")]);
+        assert!(JsonPath::parse("$.hosts")?.variables(secret).is_err());
+        assert_eq!(JsonPath::parse("$['a.b'][0].c")?.to_string(), "$[\"a.b\"][0].c");
+        Ok(())
+    }
+}
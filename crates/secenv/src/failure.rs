@@ -0,0 +1,110 @@
+use {
+    anyhow::Result,
+    std::fmt,
+};
+
+/// What made secenv itself fail, which decides its exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    Decryption,
+    ProfileNotFound,
+    /// A provider could not be reached or refused the request; worth retrying.
+    Provider,
+    FileConflict,
+    Config,
+    CommandNotExecutable,
+    CommandNotFound,
+}
+
+impl Failure {
+    pub(crate) const GENERAL: u8 = 1;
+
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            | Self::Decryption => 65,
+            | Self::ProfileNotFound => 66,
+            | Self::Provider => 69,
+            | Self::FileConflict => 73,
+            | Self::Config => 78,
+            | Self::CommandNotExecutable => 126,
+            | Self::CommandNotFound => 127,
+        }
+    }
+
+    pub(crate) fn wrap(self, error: anyhow::Error) -> anyhow::Error {
+        anyhow::Error::new(Categorized { failure: self, error })
+    }
+
+    pub(crate) fn error(self, message: impl fmt::Display) -> anyhow::Error {
+        self.wrap(anyhow::anyhow!("{}", message))
+    }
+
+    pub(crate) fn of(error: &anyhow::Error) -> Option<Self> {
+        let categorized = error.downcast_ref::<Categorized>()?;
+        Some(Self::of(&categorized.error).unwrap_or(categorized.failure))
+    }
+}
+
+/// Displays and chains exactly like the error it wraps.
+struct Categorized {
+    failure: Failure,
+    error: anyhow::Error,
+}
+
+impl fmt::Debug for Categorized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.error, f)
+    }
+}
+
+impl fmt::Display for Categorized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for Categorized {
+    fn source(&self) -> Option<&(dyn std::error::Error+'static)> {
+        self.error.source()
+    }
+}
+
+pub(crate) trait Categorize<T> {
+    fn categorize(self, failure: Failure) -> Result<T>;
+}
+
+impl<T> Categorize<T> for Result<T> {
+    fn categorize(self, failure: Failure) -> Result<T> {
+        self.map_err(|error| failure.wrap(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anyhow::Context,
+    };
+
+    #[test]
+    fn finds_the_innermost_category_through_context() -> Result<()> {
+        let fetched: Result<()> = Err(anyhow::anyhow!("gcloud failed: timeout")).categorize(Failure::Provider);
+        let error = fetched
+            .context("Failed to load PGP key")
+            .categorize(Failure::Decryption)
+            .context("Failed to resolve environment variable 'TOKEN'")
+            .unwrap_err();
+        assert_eq!(Failure::of(&error), Some(Failure::Provider));
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to resolve environment variable 'TOKEN': Failed to load PGP key: gcloud failed: timeout"
+        );
+        assert_eq!(error.chain().count(), 3);
+
+        let conflict = Failure::FileConflict.error("'out.env' already exists");
+        assert_eq!(conflict.to_string(), "'out.env' already exists");
+        assert_eq!(Failure::of(&conflict.context("Failed")), Some(Failure::FileConflict));
+        assert_eq!(Failure::of(&anyhow::anyhow!("Something else")), None);
+        Ok(())
+    }
+}
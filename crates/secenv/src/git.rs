@@ -0,0 +1,137 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::{
+        path::Path,
+        process::{
+            Command,
+            Stdio,
+        },
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+pub(crate) struct GitRepository;
+
+impl GitRepository {
+    /// Read `path` at `reference` of `repo`, fetching only that commit.
+    pub(crate) fn read_file(
+        &self,
+        repo: &str,
+        reference: &str,
+        path: &str,
+        removed_env_vars: &[String],
+    ) -> Result<Zeroizing<String>> {
+        if repo.is_empty() || repo.starts_with('-') {
+            anyhow::bail!("Invalid git repository: '{}'", repo);
+        }
+        if reference.is_empty() || reference.starts_with('-') || reference.contains(char::is_whitespace) {
+            anyhow::bail!("Invalid git reference: '{}'", reference);
+        }
+        if path.is_empty() || path.starts_with('/') {
+            anyhow::bail!("Invalid path in git repository: '{}'", path);
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.git_file(repo, reference, path);
+        }
+
+        let directory = tempfile::tempdir().context("Failed to create a temporary git repository")?;
+        self.git(directory.path(), &["init", "--quiet", "--bare"], removed_env_vars)?;
+        self.git(
+            directory.path(),
+            &["fetch", "--quiet", "--depth", "1", "--no-tags", "--", repo, reference],
+            removed_env_vars,
+        )
+        .with_context(|| format!("Failed to fetch '{}' of '{}'", reference, repo))?;
+        let mut content = self
+            .git(
+                directory.path(),
+                &["cat-file", "blob", &format!("FETCH_HEAD:{}", path)],
+                removed_env_vars,
+            )
+            .with_context(|| format!("Failed to read '{}' at '{}' of '{}'", path, reference, repo))?;
+        String::from_utf8(std::mem::take(&mut *content))
+            .map(Zeroizing::new)
+            .map_err(|error| {
+                error.into_bytes().zeroize();
+                anyhow::anyhow!("'{}' at '{}' of '{}' is not valid UTF-8", path, reference, repo)
+            })
+    }
+
+    fn git(&self, directory: &Path, args: &[&str], removed_env_vars: &[String]) -> Result<Zeroizing<Vec<u8>>> {
+        let mut cmd = Command::new("git");
+        cmd.arg("-C").arg(directory).args(args);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        // git shares the terminal, so SSH and credential helpers can ask
+        // for a passphrase.
+        let mut output = cmd
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute git")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("git failed: {}", stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        Ok(Zeroizing::new(std::mem::take(&mut output.stdout)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_file_at_a_reference() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let repo = directory.path().to_str().context("path is not valid UTF-8")?;
+        std::fs::create_dir(directory.path().join("env"))?;
+        std::fs::write(directory.path().join("env/prod.env"), "API_KEY=first\n")?;
+        let git = |args: &[&str]| -> Result<()> {
+            let status = Command::new("git")
+                .args([
+                    "-C",
+                    repo,
+                    "-c",
+                    "user.name=secenv",
+                    "-c",
+                    "user.email=secenv@localhost",
+                    "-c",
+                    "commit.gpgsign=false",
+                ])
+                .args(args)
+                .stdout(Stdio::null())
+                .status()?;
+            anyhow::ensure!(status.success(), "git {:?} failed", args);
+            Ok(())
+        };
+        git(&["init", "--quiet", "--initial-branch=main"])?;
+        git(&["add", "."])?;
+        git(&["commit", "--quiet", "--message", "first"])?;
+        git(&["tag", "v1"])?;
+        std::fs::write(directory.path().join("env/prod.env"), "API_KEY=second\n")?;
+        git(&["commit", "--quiet", "--all", "--message", "second"])?;
+
+        let read = |reference: &str, path: &str| GitRepository.read_file(repo, reference, path, &[]);
+        assert_eq!(read("main", "env/prod.env")?.as_str(), "API_KEY=second\n");
+        assert_eq!(read("v1", "env/prod.env")?.as_str(), "API_KEY=first\n");
+        assert!(read("main", "env/dev.env").is_err());
+        assert!(read("--upload-pack=touch", "env/prod.env").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use {
+    crate::{
+        failure::{
+            Categorize,
+            Failure,
+        },
+        http::{
+            percent_encode,
+            HttpClient,
+            HttpMethod,
+        },
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    serde::Deserialize,
+    std::collections::BTreeMap,
+    zeroize::Zeroizing,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct GitlabVariable {
+    key: String,
+    value: Zeroizing<String>,
+    /// `*` when unset.
+    environment_scope: Option<String>,
+    #[serde(default)]
+    protected: bool,
+}
+
+impl GitlabVariable {
+    /// GitLab prefers an exact scope over a wildcard one, and both over `*`.
+    fn scope_rank(&self, environment: Option<&str>) -> Option<u8> {
+        let scope = self.environment_scope.as_deref().unwrap_or("*");
+        if scope == "*" {
+            return Some(0);
+        }
+        let environment = environment?;
+        if scope == environment {
+            return Some(2);
+        }
+        let pattern = format!("^{}$", regex::escape(scope).replace(r"\*", ".*"));
+        regex::Regex::new(&pattern)
+            .is_ok_and(|pattern| pattern.is_match(environment))
+            .then_some(1)
+    }
+}
+
+#[derive(Deserialize)]
+struct Project {
+    namespace: Namespace,
+}
+
+#[derive(Deserialize)]
+struct Namespace {
+    kind: String,
+    full_path: String,
+}
+
+pub(crate) struct GitlabPipeline<'a> {
+    pub(crate) project: &'a str,
+    pub(crate) environment: Option<&'a str>,
+    pub(crate) protected: bool,
+    pub(crate) project_only: bool,
+}
+
+pub(crate) struct GitLab;
+
+impl GitLab {
+    const MAX_PAGES: usize = 100;
+    const PER_PAGE: usize = 100;
+
+    fn ancestors(namespace: &str) -> Vec<String> {
+        let segments: Vec<_> = namespace.split('/').collect();
+        (1..=segments.len()).map(|end| segments[..end].join("/")).collect()
+    }
+
+    /// Groups outermost first, then the project; the nearest scope wins.
+    fn select(
+        owners: Vec<Vec<GitlabVariable>>,
+        environment: Option<&str>,
+        protected: bool,
+    ) -> Vec<(String, Zeroizing<String>)> {
+        let mut selected = BTreeMap::new();
+        for variables in owners {
+            let mut best: BTreeMap<String, (u8, Zeroizing<String>)> = BTreeMap::new();
+            for variable in variables {
+                if variable.protected && !protected {
+                    continue;
+                }
+                let Some(rank) = variable.scope_rank(environment) else {
+                    continue;
+                };
+                if best.get(&variable.key).is_none_or(|(best_rank, _)| rank > *best_rank) {
+                    best.insert(variable.key, (rank, variable.value));
+                }
+            }
+            selected.extend(best.into_iter().map(|(key, (_, value))| (key, value)));
+        }
+        selected.into_iter().collect()
+    }
+
+    fn get(&self, url: &str, token: &Zeroizing<String>) -> Result<Zeroizing<String>> {
+        let headers = [("PRIVATE-TOKEN".to_string(), token.clone())];
+        HttpClient.download(url, HttpMethod::Get, &headers)
+    }
+
+    fn list(&self, api: &str, owner: &str, token: &Zeroizing<String>) -> Result<Vec<GitlabVariable>> {
+        let mut variables = Vec::new();
+        for page in 1..=Self::MAX_PAGES {
+            let url = format!("{}/{}/variables?per_page={}&page={}", api, owner, Self::PER_PAGE, page);
+            let body = self.get(&url, token)?;
+            let listed: Vec<GitlabVariable> = serde_json::from_str(&body)
+                .with_context(|| format!("GitLab response for '{}' is not a list of variables", owner))?;
+            let done = listed.len() < Self::PER_PAGE;
+            variables.extend(listed);
+            if done {
+                return Ok(variables);
+            }
+        }
+        anyhow::bail!(
+            "GitLab '{}' has more than {} pages of variables",
+            owner,
+            Self::MAX_PAGES
+        )
+    }
+
+    pub(crate) fn variables(
+        &self,
+        url: &str,
+        pipeline: &GitlabPipeline<'_>,
+        token: &Zeroizing<String>,
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        let GitlabPipeline {
+            project,
+            environment,
+            protected,
+            project_only,
+        } = *pipeline;
+        if project.is_empty() || project.starts_with('/') || project.ends_with('/') {
+            anyhow::bail!("Invalid GitLab project '{}'", project);
+        }
+        let project_id = format!("projects/{}", percent_encode(project));
+        let mut owners = Vec::new();
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            if !project_only {
+                if let Some((namespace, _)) = project.rsplit_once('/') {
+                    for group in Self::ancestors(namespace) {
+                        owners.push(
+                            fixtures
+                                .gitlab_variables(&format!("groups/{}", group))
+                                .unwrap_or_default(),
+                        );
+                    }
+                }
+            }
+            owners.push(
+                fixtures
+                    .gitlab_variables(&format!("projects/{}", project))
+                    .with_context(|| format!("No mock fixture for GitLab project '{}'", project))
+                    .categorize(Failure::Provider)?,
+            );
+            return Ok(Self::select(owners, environment, protected));
+        }
+
+        let api = format!("{}/api/v4", url.trim_end_matches('/'));
+        if !project_only {
+            let body = self.get(&format!("{}/{}", api, project_id), token)?;
+            let metadata: Project = serde_json::from_str(&body)
+                .with_context(|| format!("GitLab response for project '{}' is not a project", project))?;
+            if metadata.namespace.kind == "group" {
+                for group in Self::ancestors(&metadata.namespace.full_path) {
+                    let owner = format!("groups/{}", percent_encode(&group));
+                    owners.push(
+                        self.list(&api, &owner, token)
+                            .with_context(|| format!("Failed to read the variables of GitLab group '{}'", group))?,
+                    );
+                }
+            }
+        }
+        owners.push(
+            self.list(&api, &project_id, token)
+                .with_context(|| format!("Failed to read the variables of GitLab project '{}'", project))?,
+        );
+        Ok(Self::select(owners, environment, protected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(key: &str, value: &str, environment_scope: &str, protected: bool) -> GitlabVariable {
+        GitlabVariable {
+            key: key.to_string(),
+            value: Zeroizing::new(value.to_string()),
+            environment_scope: Some(environment_scope.to_string()),
+            protected,
+        }
+    }
+
+    #[test]
+    fn selects_variables_like_a_pipeline() {
+        let owners = || {
+            vec![
+                vec![
+                    variable("REGISTRY", "group", "*", false),
+                    variable("DB_HOST", "group", "*", false),
+                ],
+                vec![
+                    variable("DB_HOST", "any", "*", false),
+                    variable("DB_HOST", "review", "review/*", false),
+                    variable("DB_HOST", "production", "production", false),
+                    variable("DEPLOY_KEY", "key", "production", true),
+                ],
+            ]
+        };
+        let values = |environment: Option<&str>, protected: bool| -> Vec<(String, String)> {
+            GitLab::select(owners(), environment, protected)
+                .into_iter()
+                .map(|(key, value)| (key, value.to_string()))
+                .collect()
+        };
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        assert_eq!(values(None, false), pairs(&[("DB_HOST", "any"), ("REGISTRY", "group")]));
+        assert_eq!(
+            values(Some("review/fix-login"), false),
+            pairs(&[("DB_HOST", "review"), ("REGISTRY", "group")])
+        );
+        assert_eq!(
+            values(Some("production"), true),
+            pairs(&[("DB_HOST", "production"), ("DEPLOY_KEY", "key"), ("REGISTRY", "group")])
+        );
+    }
+
+    #[test]
+    fn lists_ancestor_groups() {
+        assert_eq!(GitLab::ancestors("platform/backend"), ["platform", "platform/backend"]);
+    }
+}
@@ -1,19 +1,27 @@
 use {
+    crate::{
+        failure::Failure,
+        pgp::{
+            PgpManager,
+            SignatureStatus,
+        },
+        prompt::PromptMode,
+    },
     anyhow::{
         Context,
         Result,
     },
     std::{
-        io::{
-            Seek,
-            Write,
-        },
+        io::Write,
         process::{
             Command,
             Stdio,
         },
     },
-    zeroize::Zeroize,
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -39,7 +47,7 @@ impl GpgKeySpec {
         })
     }
 
-    fn as_str(&self) -> &str {
+    pub(crate) fn as_str(&self) -> &str {
         &self.fingerprint
     }
 }
@@ -57,7 +65,35 @@ impl GpgManager {
         })
     }
 
+    pub(crate) fn import_private_key(&self, private_key: &str, removed_env_vars: &[String]) -> Result<()> {
+        let mut cmd = Command::new("gpg");
+        cmd.args(["--batch", "--quiet", "--import"]);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to execute gpg to import private key")?;
+        if let Some(mut input) = child.stdin.take() {
+            input
+                .write_all(private_key.as_bytes())
+                .context("Failed to pass the private key to gpg")?;
+        }
+        let output = child.wait_with_output().context("Failed to wait for gpg")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "gpg failed to import private key: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        Ok(())
+    }
+
     pub(crate) fn export_private_key(&self, spec: &GpgKeySpec, removed_env_vars: &[String]) -> Result<String> {
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.gpg_private_key(spec.as_str());
+        }
         let mut cmd = Command::new("gpg");
         cmd.args([
             "--export-secret-keys",
@@ -104,46 +140,192 @@ impl GpgManager {
         Ok(private_key)
     }
 
+    pub(crate) fn export_public_key(&self, spec: &GpgKeySpec, removed_env_vars: &[String]) -> Result<String> {
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            use sequoia_openpgp::{
+                parse::Parse,
+                serialize::SerializeInto,
+            };
+            let private_key = zeroize::Zeroizing::new(fixtures.gpg_private_key(spec.as_str())?);
+            let cert = sequoia_openpgp::Cert::from_bytes(private_key.as_bytes())
+                .context("Failed to parse mock GPG private key")?
+                .strip_secret_key_material();
+            return String::from_utf8(cert.armored().to_vec()?).context("Armored public key is not valid UTF-8");
+        }
+        let mut cmd = Command::new("gpg");
+        cmd.args([
+            "--export",
+            "--armor",
+            "--batch",
+            "--export-options",
+            "export-minimal",
+            spec.as_str(),
+        ]);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute gpg to export public key")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "gpg failed to export public key: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        let public_key = String::from_utf8(output.stdout).context("GPG public key output is not valid UTF-8")?;
+        if public_key.trim().is_empty() {
+            anyhow::bail!(
+                "No public key found for fingerprint: {}. Make sure the key exists in your GPG keyring.",
+                spec.as_str()
+            );
+        }
+        Ok(public_key)
+    }
+
+    fn card_problem(status: &[u8]) -> Option<String> {
+        String::from_utf8_lossy(status).lines().find_map(|line| {
+            let fields: Vec<_> = line.strip_prefix("[GNUPG:] ")?.split_whitespace().collect();
+            match fields.as_slice() {
+                | ["SC_OP_FAILURE", "1", ..] => Some("PIN entry was cancelled".to_string()),
+                | ["SC_OP_FAILURE", "2", ..] => Some("the smartcard PIN is wrong".to_string()),
+                | ["SC_OP_FAILURE", ..] => Some("the smartcard operation failed".to_string()),
+                | ["CARDCTRL", "1", serial, ..] => Some(format!("insert the smartcard with serial number {}", serial)),
+                | ["CARDCTRL", "4", ..] => Some("no smartcard is inserted".to_string()),
+                | ["CARDCTRL", "5", ..] => Some("no smartcard reader is available".to_string()),
+                | _ => None,
+            }
+        })
+    }
+
+    #[cfg(unix)]
+    fn terminal() -> Option<String> {
+        [libc::STDERR_FILENO, libc::STDIN_FILENO].into_iter().find_map(|fd| {
+            let mut name = [0 as libc::c_char; 256];
+            // SAFETY: the buffer outlives the call and its length is passed.
+            if unsafe { libc::ttyname_r(fd, name.as_mut_ptr(), name.len()) } != 0 {
+                return None;
+            }
+            // SAFETY: ttyname_r wrote a terminated string on success.
+            let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+            name.to_str().ok().map(str::to_string)
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn terminal() -> Option<String> {
+        None
+    }
+
+    /// Returns the PIN to write to gpg's stdin when asked for over loopback.
+    fn pinentry(cmd: &mut Command, spec: &GpgKeySpec, pgp_manager: &PgpManager) -> Result<Option<Zeroizing<String>>> {
+        match pgp_manager.prompt().resolve() {
+            | PromptMode::Never => {
+                cmd.arg("--pinentry-mode=error");
+                Ok(None)
+            },
+            | PromptMode::Tty => {
+                if std::env::var_os("GPG_TTY").is_none() {
+                    if let Some(terminal) = Self::terminal() {
+                        cmd.env("GPG_TTY", terminal);
+                    }
+                }
+                Ok(None)
+            },
+            | _ => {
+                cmd.args(["--pinentry-mode=loopback", "--passphrase-fd=0"]);
+                pgp_manager.card_pin(spec.as_str()).map(Some)
+            },
+        }
+    }
+
+    fn signature_status(status: &[u8], pgp_manager: &PgpManager) -> SignatureStatus {
+        let mut signature = SignatureStatus::Unsigned;
+        for line in String::from_utf8_lossy(status).lines() {
+            let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+                continue;
+            };
+            if let Some(fields) = line.strip_prefix("VALIDSIG ") {
+                // The primary key fingerprint is the tenth field when the
+                // signing key is a subkey.
+                let fields: Vec<_> = fields.split_whitespace().collect();
+                if let Some(primary) = fields.get(9).or(fields.first()) {
+                    if pgp_manager.is_trusted_signer(primary) {
+                        return SignatureStatus::Verified(primary.to_ascii_uppercase());
+                    }
+                }
+                signature = SignatureStatus::Unverified;
+            } else if line.starts_with("NEWSIG") {
+                signature = SignatureStatus::Unverified;
+            }
+        }
+        signature
+    }
+
     pub(crate) fn decrypt_data(
         &self,
         spec: &GpgKeySpec,
         encrypted_data: &str,
+        pgp_manager: &PgpManager,
         removed_env_vars: &[String],
     ) -> Result<String> {
-        let mut input = tempfile::tempfile().context("Failed to create temporary GPG input")?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            let private_key = zeroize::Zeroizing::new(fixtures.gpg_private_key(spec.as_str())?);
+            return pgp_manager.decrypt(&private_key, encrypted_data);
+        }
+        // stdin may carry a PIN, so the message is passed as a file.
+        let mut input = tempfile::NamedTempFile::new().context("Failed to create temporary GPG input")?;
         input
             .write_all(encrypted_data.as_bytes())
             .context("Failed to write temporary GPG input")?;
-        input.rewind().context("Failed to rewind temporary GPG input")?;
+        input.flush().context("Failed to write temporary GPG input")?;
 
         let mut cmd = Command::new("gpg");
-        cmd.args([
-            "--batch",
-            "--quiet",
-            "--status-fd=2",
-            "--try-secret-key",
-            spec.as_str(),
-            "--decrypt",
-        ]);
+        cmd.args(["--batch", "--quiet", "--status-fd=2", "--try-secret-key", spec.as_str()]);
+        let pin = Self::pinentry(&mut cmd, spec, pgp_manager)?;
+        cmd.arg("--decrypt").arg(input.path());
         crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
 
-        let mut output = cmd
-            .stdin(Stdio::from(input))
+        let mut child = cmd
+            .stdin(if pin.is_some() { Stdio::piped() } else { Stdio::null() })
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .output()
+            .spawn()
             .context("Failed to execute gpg process for decryption")?;
+        if let (Some(mut stdin), Some(pin)) = (child.stdin.take(), &pin) {
+            let line = Zeroizing::new(format!("{}\n", pin.as_str()));
+            stdin
+                .write_all(line.as_bytes())
+                .context("Failed to pass the PIN to gpg")?;
+        }
+        let mut output = child.wait_with_output().context("Failed to wait for gpg")?;
 
         if !output.status.success() {
+            if pin.is_some() {
+                pgp_manager.forget_card_pin(spec.as_str());
+            }
             let stderr = String::from_utf8_lossy(&output.stderr);
-            let error = anyhow::anyhow!("GPG failed to decrypt data: {}", stderr);
+            let error = match Self::card_problem(&output.stderr) {
+                | Some(problem) => {
+                    Failure::Decryption.error(format!("GPG failed to decrypt data: {}: {}", problem, stderr))
+                },
+                | None => Failure::Decryption.error(format!("GPG failed to decrypt data: {}", stderr)),
+            };
             output.stdout.zeroize();
             return Err(error);
         }
 
         if !Self::status_uses_key(&output.stderr, spec) {
             output.stdout.zeroize();
-            anyhow::bail!("GPG did not decrypt with configured fingerprint {}", spec.as_str());
+            return Err(Failure::Decryption.error(format!(
+                "GPG did not decrypt with configured fingerprint {}",
+                spec.as_str()
+            )));
+        }
+        if let Err(error) = pgp_manager.enforce_signature(&Self::signature_status(&output.stderr, pgp_manager)) {
+            output.stdout.zeroize();
+            return Err(Failure::Decryption.wrap(error));
         }
 
         match String::from_utf8(output.stdout) {
@@ -159,7 +341,13 @@ impl GpgManager {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use {
+        super::*,
+        sequoia_openpgp::{
+            cert::CertBuilder,
+            serialize::SerializeInto,
+        },
+    };
 
     #[test]
     fn verifies_the_decryption_fingerprint() -> Result<()> {
@@ -173,4 +361,52 @@ mod tests {
         ));
         Ok(())
     }
+
+    #[test]
+    fn explains_smartcard_failures() {
+        let problem = |status: &str| GpgManager::card_problem(status.as_bytes());
+        assert_eq!(
+            problem("[GNUPG:] PINENTRY_LAUNCHED 1\n[GNUPG:] SC_OP_FAILURE 2\n").as_deref(),
+            Some("the smartcard PIN is wrong")
+        );
+        assert_eq!(
+            problem("[GNUPG:] CARDCTRL 1 D2760001240103040006012345670000\n").as_deref(),
+            Some("insert the smartcard with serial number D2760001240103040006012345670000")
+        );
+        assert_eq!(problem("[GNUPG:] DECRYPTION_FAILED\n"), None);
+    }
+
+    #[test]
+    fn reads_the_signer_from_the_status() -> Result<()> {
+        let (signer, _) = CertBuilder::new().generate()?;
+        let signer_key = String::from_utf8(signer.armored().to_vec()?)?;
+        let fingerprint = signer.fingerprint().to_hex();
+        let mut manager = PgpManager::default();
+        manager.trust_signers([(fingerprint.as_str(), signer_key.as_str())], false)?;
+
+        let valid = |primary: &str| {
+            format!(
+                "[GNUPG:] NEWSIG\n[GNUPG:] VALIDSIG {} 2024-01-01 1704067200 0 4 0 22 10 00 {}\n",
+                "F".repeat(40),
+                primary
+            )
+        };
+        assert_eq!(
+            GpgManager::signature_status(valid(&fingerprint).as_bytes(), &manager),
+            SignatureStatus::Verified(fingerprint.clone())
+        );
+        assert_eq!(
+            GpgManager::signature_status(valid(&"0".repeat(40)).as_bytes(), &manager),
+            SignatureStatus::Unverified
+        );
+        assert_eq!(
+            GpgManager::signature_status(b"[GNUPG:] NEWSIG\n[GNUPG:] ERRSIG 0123 22 10 00 0 9\n", &manager),
+            SignatureStatus::Unverified
+        );
+        assert_eq!(
+            GpgManager::signature_status(b"[GNUPG:] DECRYPTION_OKAY\n", &manager),
+            SignatureStatus::Unsigned
+        );
+        Ok(())
+    }
 }
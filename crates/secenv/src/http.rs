@@ -0,0 +1,256 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    std::{
+        future::Future,
+        sync::OnceLock,
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Run `future` on the runtime of provider requests, from sync code anywhere.
+pub(crate) fn block_on<F: Future+Send>(future: F) -> F::Output
+where F::Output: Send {
+    let runtime = RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("secenv-http")
+            .enable_all()
+            .build()
+            .expect("Failed to start the HTTP runtime")
+    });
+    // A thread of its own, since this may be called on a runtime's thread.
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| runtime.block_on(future))
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum HttpMethod {
+    #[default]
+    Get,
+    Post,
+}
+
+impl HttpMethod {
+    pub(crate) fn is_get(&self) -> bool {
+        *self == Self::Get
+    }
+
+    fn method(self) -> reqwest::Method {
+        match self {
+            | Self::Get => reqwest::Method::GET,
+            | Self::Post => reqwest::Method::POST,
+        }
+    }
+}
+
+pub(crate) fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| {
+            match byte {
+                | b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+                | byte => format!("%{:02X}", byte),
+            }
+        })
+        .collect()
+}
+
+fn push_option(config: &mut String, option: &str, parts: &[&str]) {
+    config.push_str(option);
+    config.push_str(" = \"");
+    for character in parts.iter().flat_map(|part| part.chars()) {
+        if matches!(character, '\\' | '"') {
+            config.push('\\');
+        }
+        config.push(character);
+    }
+    config.push_str("\"\n");
+}
+
+pub(crate) struct HttpClient;
+
+impl HttpClient {
+    fn validate(url: &str, headers: &[(String, Zeroizing<String>)]) -> Result<()> {
+        if !url.starts_with("https://") || url.len() == "https://".len() || url.chars().any(char::is_whitespace) {
+            anyhow::bail!("Invalid URL '{}': expected https://", url);
+        }
+        for (name, value) in headers {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(character))
+            {
+                anyhow::bail!("Invalid HTTP header name '{}'", name);
+            }
+            if value.contains(['\r', '\n', '\0']) {
+                anyhow::bail!("HTTP header '{}' must not contain line breaks", name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Redirects are not followed.
+    pub(crate) fn download(
+        &self,
+        url: &str,
+        method: HttpMethod,
+        headers: &[(String, Zeroizing<String>)],
+    ) -> Result<Zeroizing<String>> {
+        Self::validate(url, headers)?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.http_body(url);
+        }
+        self.send(url, method, headers, None)
+    }
+
+    /// Tokens go in a curl config so they never appear in the process list.
+    pub(crate) fn config(headers: &[(String, Zeroizing<String>)], body: Option<&str>) -> Zeroizing<String> {
+        let mut config = Zeroizing::new(String::new());
+        for (name, value) in headers {
+            push_option(&mut config, "header", &[name, ": ", value]);
+        }
+        if let Some(body) = body {
+            push_option(&mut config, "data-raw", &[body]);
+        }
+        config
+    }
+
+    pub(crate) fn send(
+        &self,
+        url: &str,
+        method: HttpMethod,
+        headers: &[(String, Zeroizing<String>)],
+        body: Option<&str>,
+    ) -> Result<Zeroizing<String>> {
+        let (status, mut response) = self.request(url, method, headers, body)?;
+        if !status.is_success() {
+            response.zeroize();
+            return Err(Failure::Provider.error(format!("Failed to download '{}': HTTP {}", url, status)));
+        }
+        Self::body(url, response)
+    }
+
+    /// HTTP error responses become the inner error, e.g. an OAuth token error.
+    pub(crate) fn exchange(
+        &self,
+        url: &str,
+        headers: &[(String, Zeroizing<String>)],
+        body: &str,
+    ) -> Result<std::result::Result<Zeroizing<String>, Zeroizing<String>>> {
+        let (status, response) = self.request(url, HttpMethod::Post, headers, Some(body))?;
+        match status {
+            | status if status.is_success() => Self::body(url, response).map(Ok),
+            | status if !response.is_empty() && (status.is_client_error() || status.is_server_error()) => {
+                Self::body(url, response).map(Err)
+            },
+            | status => Err(Failure::Provider.error(format!("Failed to send a request to '{}': HTTP {}", url, status))),
+        }
+    }
+
+    fn body(url: &str, mut response: Zeroizing<Vec<u8>>) -> Result<Zeroizing<String>> {
+        String::from_utf8(std::mem::take(&mut *response))
+            .map(Zeroizing::new)
+            .map_err(|error| {
+                error.into_bytes().zeroize();
+                anyhow::anyhow!("Response of '{}' is not valid UTF-8", url)
+            })
+    }
+
+    fn request(
+        &self,
+        url: &str,
+        method: HttpMethod,
+        headers: &[(String, Zeroizing<String>)],
+        body: Option<&str>,
+    ) -> Result<(reqwest::StatusCode, Zeroizing<Vec<u8>>)> {
+        Self::validate(url, headers)?;
+        let client = CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .https_only(true)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Failed to create the HTTP client")
+        });
+        let mut request = client.request(method.method(), url);
+        for (name, value) in headers {
+            let mut value = reqwest::header::HeaderValue::from_str(value).context("Invalid HTTP header value")?;
+            value.set_sensitive(true);
+            request = request.header(name.as_str(), value);
+        }
+        if let Some(body) = body {
+            request = request.body(body.to_string());
+        }
+        block_on(async {
+            let mut response = request.send().await?;
+            let status = response.status();
+            let mut body = Zeroizing::new(Vec::new());
+            while let Some(chunk) = response.chunk().await? {
+                body.extend_from_slice(&chunk);
+            }
+            Ok::<_, reqwest::Error>((status, body))
+        })
+        .with_context(|| format!("Failed to send a request to '{}'", url))
+        .categorize(Failure::Provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_urls_and_headers() {
+        let header = |name: &str, value: &str| vec![(name.to_string(), Zeroizing::new(value.to_string()))];
+        assert!(HttpClient::validate(
+            "https://config.internal/env/payments",
+            &header("Authorization", "Bearer t")
+        )
+        .is_ok());
+        for url in [
+            "http://config.internal/env",
+            "https://",
+            "--config=/etc/passwd",
+            "https://a b",
+        ] {
+            assert!(HttpClient::validate(url, &[]).is_err(), "{}", url);
+        }
+        let url = "https://config.internal/env";
+        assert!(HttpClient::validate(url, &header("X-Team", "payments\nHost: evil")).is_err());
+        assert!(HttpClient::validate(url, &header("X Team", "payments")).is_err());
+    }
+
+    #[test]
+    fn passes_headers_and_bodies_as_config() {
+        let headers = vec![(
+            "Authorization".to_string(),
+            Zeroizing::new(r#"Basic "a\b""#.to_string()),
+        )];
+        assert_eq!(
+            HttpClient::config(&headers, Some("grant_type=refresh_token")).as_str(),
+            "header = \"Authorization: Basic \\\"a\\\\b\\\"\"\ndata-raw = \"grant_type=refresh_token\"\n"
+        );
+        assert_eq!(percent_encode("platform/payments api"), "platform%2Fpayments%20api");
+    }
+}
@@ -0,0 +1,492 @@
+use {
+    crate::{
+        manifest::{
+            Content,
+            ContentWrapper,
+            EncodedValue,
+            EncodedValueWrapper,
+            ManifestProfile,
+            Secret,
+            SecretAllocation,
+            SecretAllocationWrapper,
+            SecretWrapper,
+        },
+        pgp::PgpManager,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    base64::Engine,
+    sequoia_openpgp::{
+        self as openpgp,
+        parse::Parse,
+    },
+    serde::Deserialize,
+    std::{
+        collections::{
+            BTreeMap,
+            HashMap,
+        },
+        path::{
+            Path,
+            PathBuf,
+        },
+        process::{
+            Command,
+            Stdio,
+        },
+    },
+    zeroize::Zeroizing,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ImportFormat {
+    Sops,
+    Chamber,
+    Teller,
+}
+
+impl std::str::FromStr for ImportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            | "sops" => Ok(Self::Sops),
+            | "chamber" => Ok(Self::Chamber),
+            | "teller" => Ok(Self::Teller),
+            | value => anyhow::bail!("Unknown import format '{}'; expected sops, chamber, or teller", value),
+        }
+    }
+}
+
+pub(crate) struct Encryption {
+    certificate: String,
+    secret: SecretAllocation,
+}
+
+impl Encryption {
+    /// Without `key_source`, the private key is read from the GPG keyring.
+    pub(crate) fn new(certificate: String, key_source: Option<&str>) -> Result<Self> {
+        let cert = openpgp::Cert::from_bytes(certificate.as_bytes()).context("Failed to parse the public key")?;
+        if cert.is_tsk() {
+            anyhow::bail!("The public key must not contain secret key material");
+        }
+        let secret = match key_source {
+            | Some(reference) => SecretAllocation::from_reference(reference)?,
+            | None => {
+                SecretAllocation::Gpg {
+                    fingerprint: cert.fingerprint().to_hex(),
+                }
+            },
+        };
+        Ok(Self { certificate, secret })
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Import {
+    /// Values read in plaintext, encrypted again for the manifest.
+    plaintexts: BTreeMap<String, Zeroizing<String>>,
+    references: BTreeMap<String, Content>,
+}
+
+impl Import {
+    pub(crate) fn read(format: ImportFormat, path: &Path, removed_env_vars: &[String]) -> Result<Self> {
+        match format {
+            | ImportFormat::Sops => Self::from_sops_output(&Self::decrypt_sops(path, removed_env_vars)?),
+            | ImportFormat::Chamber => {
+                let export = Zeroizing::new(
+                    std::fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read chamber export: {}", path.display()))?,
+                );
+                Self::from_chamber_export(&export)
+            },
+            | ImportFormat::Teller => {
+                let config = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read teller configuration: {}", path.display()))?;
+                let directory = path.parent().unwrap_or(Path::new("."));
+                Self::from_teller_config(&config, directory)
+            },
+        }
+    }
+
+    fn decrypt_sops(path: &Path, removed_env_vars: &[String]) -> Result<Zeroizing<String>> {
+        let mut cmd = Command::new("sops");
+        cmd.args(["--decrypt", "--output-type", "json"]).arg(path);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute sops. Is it installed and in PATH?")?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            anyhow::bail!(
+                "sops failed to decrypt '{}': {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        String::from_utf8(stdout.to_vec())
+            .map(Zeroizing::new)
+            .context("sops output is not valid UTF-8")
+    }
+
+    fn add(&mut self, name: String, value: Option<Zeroizing<String>>, reference: Option<Content>) -> Result<()> {
+        if name.is_empty() {
+            anyhow::bail!("Cannot import a variable without a name");
+        }
+        if self.plaintexts.contains_key(&name) || self.references.contains_key(&name) {
+            anyhow::bail!("Variable '{}' is defined more than once", name);
+        }
+        if let Some(value) = value {
+            self.plaintexts.insert(name, value);
+        } else if let Some(reference) = reference {
+            self.references.insert(name, reference);
+        }
+        Ok(())
+    }
+
+    /// Nested keys are joined by `_`.
+    fn from_sops_output(document: &str) -> Result<Self> {
+        fn flatten(import: &mut Import, prefix: Option<&str>, value: &serde_json::Value) -> Result<()> {
+            match value {
+                | serde_json::Value::Object(object) => {
+                    for (key, value) in object {
+                        let name = match prefix {
+                            | Some(prefix) => format!("{}_{}", prefix, key),
+                            | None => key.clone(),
+                        };
+                        flatten(import, Some(&name), value)?;
+                    }
+                },
+                | serde_json::Value::String(value) => {
+                    import.add(
+                        prefix.unwrap_or_default().to_string(),
+                        Some(Zeroizing::new(value.clone())),
+                        None,
+                    )?;
+                },
+                | serde_json::Value::Number(_) | serde_json::Value::Bool(_) => {
+                    import.add(
+                        prefix.unwrap_or_default().to_string(),
+                        Some(Zeroizing::new(value.to_string())),
+                        None,
+                    )?;
+                },
+                | serde_json::Value::Null | serde_json::Value::Array(_) => {
+                    crate::output::warning!(
+                        "Skipping '{}': only strings, numbers, and booleans can be imported",
+                        prefix.unwrap_or_default()
+                    );
+                },
+            }
+            Ok(())
+        }
+
+        let document: serde_json::Value =
+            serde_json::from_str(document).context("Failed to parse decrypted SOPS document")?;
+        if !document.is_object() {
+            anyhow::bail!("Decrypted SOPS document is not an object");
+        }
+        let mut import = Self::default();
+        flatten(&mut import, None, &document)?;
+        Ok(import)
+    }
+
+    /// Keys are named like `chamber exec` names them.
+    fn from_chamber_export(export: &str) -> Result<Self> {
+        let values: BTreeMap<String, Zeroizing<String>> = serde_json::from_str(export)
+            .context("Failed to parse chamber export; expected `chamber export --format json` output")?;
+        let mut import = Self::default();
+        for (key, value) in values {
+            import.add(key.to_uppercase().replace(['-', '.'], "_"), Some(value), None)?;
+        }
+        Ok(import)
+    }
+
+    /// Only GCP and AWS secrets and dotenv files are imported.
+    fn from_teller_config(config: &str, directory: &Path) -> Result<Self> {
+        let config: TellerConfig = serde_yaml::from_str(config).context("Failed to parse teller configuration")?;
+        let mut import = Self::default();
+        for (provider, mappings) in &config.providers {
+            if let Some(kind) = &mappings.kind {
+                anyhow::bail!(
+                    "Provider '{}' uses the teller 2 format (kind: {}); only teller 1 configurations are supported",
+                    provider,
+                    kind
+                );
+            }
+            if let Some(sync) = &mappings.env_sync {
+                let path = config.expand(&sync.path);
+                match provider.as_str() {
+                    | "dotenv" => {
+                        for (name, value) in TellerConfig::read_dotenv(&Self::local_path(&path, directory))? {
+                            import.add(name, Some(value), None)?;
+                        }
+                    },
+                    | provider => {
+                        crate::output::warning!(
+                            "Skipping env_sync of provider '{}' at '{}'; only dotenv is supported",
+                            provider,
+                            path
+                        );
+                    },
+                }
+            }
+            for (name, mapping) in &mappings.env {
+                let path = config.expand(&mapping.path);
+                if mapping.decode.is_some() && provider != "dotenv" {
+                    crate::output::warning!("Skipping '{}': 'decode' is supported only for dotenv", name);
+                    continue;
+                }
+                match provider.as_str() {
+                    | "google_secretmanager" => {
+                        let (secret, version) = match path.split_once("/versions/") {
+                            | Some((secret, version)) => (secret.to_string(), Some(version.to_string())),
+                            | None => (path.clone(), None),
+                        };
+                        import.add(name.clone(), None, Some(Content::Gcs { secret, version }))?;
+                    },
+                    | "aws_secretsmanager" if mapping.field.is_none() => {
+                        import.add(
+                            name.clone(),
+                            None,
+                            Some(Content::Aws {
+                                secret: path,
+                                version: None,
+                                region: None,
+                            }),
+                        )?;
+                    },
+                    | "dotenv" => {
+                        let values = TellerConfig::read_dotenv(&Self::local_path(&path, directory))?;
+                        let field = mapping.field.as_deref().unwrap_or(name);
+                        let value = values
+                            .get(field)
+                            .with_context(|| format!("'{}' has no key '{}' for '{}'", path, field, name))?;
+                        let value = match mapping.decode.as_deref() {
+                            | Some("base64") => {
+                                let decoded = Zeroizing::new(
+                                    base64::engine::general_purpose::STANDARD
+                                        .decode(value.as_bytes())
+                                        .with_context(|| format!("Value of '{}' is not valid base64", name))?,
+                                );
+                                Zeroizing::new(
+                                    String::from_utf8(decoded.to_vec())
+                                        .with_context(|| format!("Decoded value of '{}' is not valid UTF-8", name))?,
+                                )
+                            },
+                            | Some(decode) => anyhow::bail!("Unknown decode '{}' for '{}'", decode, name),
+                            | None => value.clone(),
+                        };
+                        import.add(name.clone(), Some(value), None)?;
+                    },
+                    | provider => {
+                        crate::output::warning!("Skipping '{}': provider '{}' cannot be converted", name, provider);
+                    },
+                }
+            }
+        }
+        Ok(import)
+    }
+
+    fn local_path(path: &str, directory: &Path) -> PathBuf {
+        match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+            | (Some(relative), Some(home)) => PathBuf::from(home).join(relative),
+            | _ => directory.join(path),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.plaintexts.len() + self.references.len()
+    }
+
+    pub(crate) fn encrypted(&self) -> usize {
+        self.plaintexts.len()
+    }
+
+    pub(crate) fn into_profile(
+        self,
+        encryption: Option<&Encryption>,
+        pgp_manager: &PgpManager,
+    ) -> Result<ManifestProfile> {
+        let mut profile = ManifestProfile::default();
+        if !self.plaintexts.is_empty() {
+            let encryption = encryption.context(
+                "Imported values must be encrypted again; pass --public-key or --recipient to choose the key",
+            )?;
+            for (name, plaintext) in &self.plaintexts {
+                let armored = pgp_manager
+                    .encrypt_armored(&encryption.certificate, plaintext)
+                    .with_context(|| format!("Failed to encrypt '{}'", name))?;
+                let secure = Content::Secure {
+                    secret: SecretWrapper {
+                        inner: Secret::Pgp(SecretAllocationWrapper {
+                            inner: encryption.secret.clone(),
+                        }),
+                    },
+                    value: EncodedValueWrapper {
+                        inner: EncodedValue::Base64(base64::engine::general_purpose::STANDARD.encode(armored)),
+                    },
+                };
+                profile.env.vars.insert(name.clone(), ContentWrapper::from(secure));
+            }
+        }
+        for (name, reference) in self.references {
+            profile.env.vars.insert(name, ContentWrapper::from(reference));
+        }
+        Ok(profile)
+    }
+}
+
+#[derive(Deserialize)]
+struct TellerConfig {
+    #[serde(default)]
+    opts: HashMap<String, String>,
+    #[serde(default)]
+    providers: BTreeMap<String, TellerProvider>,
+}
+
+#[derive(Deserialize)]
+struct TellerProvider {
+    kind: Option<String>,
+    env_sync: Option<TellerMapping>,
+    #[serde(default)]
+    env: BTreeMap<String, TellerMapping>,
+}
+
+#[derive(Deserialize)]
+struct TellerMapping {
+    path: String,
+    field: Option<String>,
+    decode: Option<String>,
+}
+
+impl TellerConfig {
+    fn expand(&self, path: &str) -> String {
+        let mut expanded = path.to_string();
+        for (key, value) in &self.opts {
+            let placeholder = regex::Regex::new(&format!(r"\{{\{{\s*{}\s*\}}\}}", regex::escape(key)))
+                .expect("escaped placeholder pattern is valid");
+            expanded = placeholder.replace_all(&expanded, regex::NoExpand(value)).into_owned();
+        }
+        if expanded.contains("{{") {
+            crate::output::warning!("'{}' contains a placeholder that is not an opts key", expanded);
+        }
+        expanded
+    }
+
+    fn read_dotenv(path: &Path) -> Result<BTreeMap<String, Zeroizing<String>>> {
+        let content = Zeroizing::new(
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read dotenv file: {}", path.display()))?,
+        );
+        let entries =
+            crate::dotenv::parse(&content).with_context(|| format!("Malformed dotenv file: {}", path.display()))?;
+        Ok(entries.into_iter().map(|entry| (entry.name, entry.value)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sequoia_openpgp::{
+            cert::CertBuilder,
+            serialize::SerializeInto,
+        },
+    };
+
+    #[test]
+    fn converts_sops_chamber_and_teller_configurations() -> Result<()> {
+        let sops = Import::from_sops_output(r#"{"API_KEY": "s3cret", "db": {"port": 5432, "tags": ["a"]}}"#)?;
+        assert_eq!(sops.plaintexts.keys().collect::<Vec<_>>(), ["API_KEY", "db_port"]);
+        assert_eq!(sops.plaintexts["db_port"].as_str(), "5432");
+
+        let chamber = Import::from_chamber_export(r#"{"api-key": "s3cret", "db.password": "hunter2"}"#)?;
+        assert_eq!(chamber.plaintexts.keys().collect::<Vec<_>>(), [
+            "API_KEY",
+            "DB_PASSWORD"
+        ]);
+        assert!(Import::from_chamber_export(r#"{"a-b": "1", "A_B": "2"}"#).is_err());
+
+        let directory = tempfile::tempdir()?;
+        std::fs::write(
+            directory.path().join("development.env"),
+            "TOKEN=\"abc\"\nENCODED=aGk=\n",
+        )?;
+        let teller = Import::from_teller_config(
+            r#"
+opts:
+  stage: development
+providers:
+  google_secretmanager:
+    env:
+      MG_KEY:
+        path: projects/44882/secrets/MG_KEY/versions/{{ stage }}
+  aws_secretsmanager:
+    env:
+      DB_URL:
+        path: prod/db-url
+      DB_FIELD:
+        path: prod/db
+        field: password
+  dotenv:
+    env_sync:
+      path: "{{stage}}.env"
+    env:
+      DECODED:
+        path: development.env
+        field: ENCODED
+        decode: base64
+"#,
+            directory.path(),
+        )?;
+        assert_eq!(teller.plaintexts.keys().collect::<Vec<_>>(), [
+            "DECODED", "ENCODED", "TOKEN"
+        ]);
+        assert_eq!(teller.plaintexts["TOKEN"].as_str(), "abc");
+        assert_eq!(teller.plaintexts["DECODED"].as_str(), "hi");
+        assert!(matches!(
+            &teller.references["MG_KEY"],
+            Content::Gcs { secret, version: Some(version) }
+                if secret == "projects/44882/secrets/MG_KEY" && version == "development"
+        ));
+        assert!(matches!(&teller.references["DB_URL"], Content::Aws { secret, .. } if secret == "prod/db-url"));
+        assert!(!teller.references.contains_key("DB_FIELD"));
+        assert!(
+            Import::from_teller_config("providers:\n  gsm:\n    kind: google_secretmanager\n", directory.path())
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn encrypts_imported_values_for_the_chosen_key() -> Result<()> {
+        let (cert, _) = CertBuilder::general_purpose(Some("Alice <alice@example.com>")).generate()?;
+        let private_key = String::from_utf8(cert.as_tsk().armored().to_vec()?)?;
+        let public_key = String::from_utf8(cert.armored().to_vec()?)?;
+        let import = Import::from_chamber_export(r#"{"api_key": "s3cret"}"#)?;
+
+        let pgp_manager = PgpManager::default();
+        assert!(Import::from_chamber_export(r#"{"api_key": "s3cret"}"#)?
+            .into_profile(None, &pgp_manager)
+            .is_err());
+        assert!(Encryption::new(private_key.clone(), None).is_err());
+        let encryption = Encryption::new(public_key, Some("file:/keys/alice.asc"))?;
+        let profile = import.into_profile(Some(&encryption), &pgp_manager)?;
+
+        let variable = &profile.env.vars["API_KEY"];
+        let Content::Secure { secret, value } = &variable.inner else {
+            anyhow::bail!("Imported value is not a secure value");
+        };
+        let Secret::Pgp(allocation) = &secret.inner else {
+            anyhow::bail!("Imported value is not encrypted with PGP");
+        };
+        assert_eq!(allocation.inner.reference(), "file:/keys/alice.asc");
+        assert_eq!(pgp_manager.decrypt(&private_key, &value.inner.decode()?)?, "s3cret");
+        Ok(())
+    }
+}
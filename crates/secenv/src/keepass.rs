@@ -0,0 +1,118 @@
+use {
+    crate::{
+        failure::{
+            Categorize,
+            Failure,
+        },
+        prompt::PromptMode,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::{
+        collections::BTreeMap,
+        process::{
+            Command,
+            Stdio,
+        },
+        sync::Mutex,
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+/// Held while prompting, so threads reading one database ask only once.
+static PASSWORDS: Mutex<BTreeMap<String, Zeroizing<String>>> = Mutex::new(BTreeMap::new());
+
+pub(crate) struct KeePass;
+
+impl KeePass {
+    fn validate(database: &str, entry: &str, field: &str) -> Result<()> {
+        for (name, value) in [("database", database), ("entry", entry), ("field", field)] {
+            if value.is_empty() || value.starts_with('-') {
+                anyhow::bail!("Invalid KeePass {}: '{}'", name, value);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read(
+        &self,
+        database: &str,
+        entry: &str,
+        field: &str,
+        prompt: &PromptMode,
+        removed_env_vars: &[String],
+    ) -> Result<String> {
+        Self::validate(database, entry, field)?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.keepass_field(database, entry, field);
+        }
+        let mut passwords = PASSWORDS.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if !passwords.contains_key(database) {
+            let password = prompt.password(&format!("KeePass database {}", database))?;
+            passwords.insert(database.to_string(), password);
+        }
+        let value = Self::show(database, entry, field, &passwords[database], removed_env_vars);
+        if value.is_err() {
+            // The password may be wrong; ask again when the database is next read.
+            passwords.remove(database);
+        }
+        value
+    }
+
+    fn show(database: &str, entry: &str, field: &str, password: &str, removed_env_vars: &[String]) -> Result<String> {
+        let mut cmd = Command::new("keepassxc-cli");
+        cmd.args(["show", "--quiet", "--show-protected", "--attributes", field, "--"])
+            .arg(database)
+            .arg(entry);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to execute keepassxc-cli")
+            .categorize(Failure::Provider)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            let input = Zeroizing::new(format!("{}\n", password));
+            stdin
+                .write_all(input.as_bytes())
+                .context("Failed to pass the database password to keepassxc-cli")?;
+        }
+        let mut output = child.wait_with_output().context("Failed to wait for keepassxc-cli")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("keepassxc-cli failed: {}", stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let mut value = String::from_utf8(std::mem::take(&mut output.stdout)).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("KeePass field is not valid UTF-8")
+        })?;
+        if value.ends_with('\n') {
+            value.pop();
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_entries() {
+        assert!(KeePass::validate("./secrets.kdbx", "Work/Database", "Password").is_ok());
+        assert!(KeePass::validate("--help", "Work/Database", "Password").is_err());
+        assert!(KeePass::validate("./secrets.kdbx", "", "Password").is_err());
+        assert!(KeePass::validate("./secrets.kdbx", "Work/Database", "-a").is_err());
+    }
+}
@@ -0,0 +1,31 @@
+use {
+    crate::failure::Failure,
+    anyhow::Result,
+};
+
+pub(crate) struct Keychain;
+
+impl Keychain {
+    pub(crate) fn read(&self, service: &str, account: &str) -> Result<String> {
+        if service.is_empty() || account.is_empty() {
+            anyhow::bail!("Keychain entries need a non-empty service and account");
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.keyring_entry(service, account);
+        }
+        let entry = keyring::Entry::new(service, account)
+            .map_err(|error| anyhow::anyhow!("Invalid keychain entry '{}/{}': {}", service, account, error))?;
+        entry.get_password().map_err(|error| {
+            match error {
+                | keyring::Error::NoEntry => {
+                    anyhow::anyhow!("No keychain entry for account '{}' of service '{}'", account, service)
+                },
+                | keyring::Error::BadEncoding(mut bytes) => {
+                    zeroize::Zeroize::zeroize(&mut bytes);
+                    anyhow::anyhow!("Keychain entry '{}/{}' is not valid UTF-8", service, account)
+                },
+                | error => Failure::Provider.error(format!("Failed to read the system keychain: {}", error)),
+            }
+        })
+    }
+}
@@ -0,0 +1,153 @@
+use {
+    crate::{
+        gcp::{
+            GcpSecretManager,
+            GcpSecretSpec,
+        },
+        gpg::GpgManager,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    sequoia_openpgp::{
+        self as openpgp,
+        cert::{
+            CertBuilder,
+            CipherSuite,
+        },
+        serialize::SerializeInto,
+    },
+    std::{
+        io::Write,
+        path::PathBuf,
+    },
+    zeroize::Zeroizing,
+};
+
+pub(crate) enum KeyStore {
+    File(PathBuf),
+    Gcp(String),
+    Keyring,
+}
+
+impl KeyStore {
+    pub(crate) fn manifest_source(&self, fingerprint: &str) -> String {
+        match self {
+            | Self::File(path) => format!("secret.pgp.file = \"{}\"", path.display()),
+            | Self::Gcp(secret) => format!("secret.pgp.gcp.secret = \"{}\"", secret),
+            | Self::Keyring => format!("secret.pgp.gpg.fingerprint = \"{}\"", fingerprint),
+        }
+    }
+}
+
+pub(crate) struct GeneratedKey {
+    cert: openpgp::Cert,
+    private_key: Zeroizing<String>,
+}
+
+impl GeneratedKey {
+    pub(crate) fn generate(name: &str, email: &str, password: Option<&str>) -> Result<Self> {
+        if name.trim().is_empty() || !email.contains('@') {
+            anyhow::bail!("A key needs a name and an email address");
+        }
+        let (cert, _) = CertBuilder::general_purpose(Some(format!("{} <{}>", name.trim(), email.trim())))
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .set_password(password.map(openpgp::crypto::Password::from))
+            .generate()
+            .context("Failed to generate PGP key")?;
+        let private_key = Zeroizing::new(
+            String::from_utf8(
+                cert.as_tsk()
+                    .armored()
+                    .to_vec()
+                    .context("Failed to armor the private key")?,
+            )
+            .context("Armored private key is not valid UTF-8")?,
+        );
+        Ok(Self { cert, private_key })
+    }
+
+    pub(crate) fn fingerprint(&self) -> String {
+        self.cert.fingerprint().to_hex()
+    }
+
+    pub(crate) fn public_key(&self) -> Result<String> {
+        String::from_utf8(self.cert.armored().to_vec().context("Failed to armor the public key")?)
+            .context("Armored public key is not valid UTF-8")
+    }
+
+    /// An existing key file is never overwritten.
+    pub(crate) fn store(&self, store: &KeyStore, removed_env_vars: &[String]) -> Result<()> {
+        match store {
+            | KeyStore::File(path) => {
+                let mut options = std::fs::OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+                options
+                    .open(path)
+                    .and_then(|mut file| {
+                        file.write_all(self.private_key.as_bytes())?;
+                        file.sync_all()
+                    })
+                    .with_context(|| format!("Failed to write private key to '{}'", path.display()))
+            },
+            | KeyStore::Gcp(secret) => {
+                let spec = GcpSecretSpec {
+                    secret: secret.clone(),
+                    version: None,
+                };
+                GcpSecretManager
+                    .add_version(&spec, &self.private_key, removed_env_vars)
+                    .with_context(|| format!("Failed to store private key in GCP secret '{}'", secret))
+            },
+            | KeyStore::Keyring => GpgManager.import_private_key(&self.private_key, removed_env_vars),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::pgp::PgpManager,
+        sequoia_openpgp::types::PublicKeyAlgorithm,
+    };
+
+    #[test]
+    fn generates_cv25519_keys_usable_for_sealing() -> Result<()> {
+        let key = GeneratedKey::generate("Alice", "alice@example.com", None)?;
+        assert_eq!(key.cert.primary_key().key().pk_algo(), PublicKeyAlgorithm::EdDSA);
+        let user_id = key.cert.userids().next().context("Missing user ID")?;
+        assert_eq!(user_id.userid().value(), b"Alice <alice@example.com>");
+
+        let manager = PgpManager::default();
+        let ciphertext = manager.encrypt(&key.public_key()?, "value")?;
+        assert_eq!(manager.decrypt_bytes(&key.private_key, &ciphertext)?, "value");
+
+        let protected = GeneratedKey::generate("Alice", "alice@example.com", Some("password"))?;
+        assert!(protected
+            .cert
+            .keys()
+            .secret()
+            .all(|key| key.key().secret().is_encrypted()));
+        assert!(GeneratedKey::generate("", "alice@example.com", None).is_err());
+
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("key.asc");
+        key.store(&KeyStore::File(path.clone()), &[])?;
+        assert_eq!(std::fs::read_to_string(&path)?, key.private_key.as_str());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        }
+        assert!(key.store(&KeyStore::File(path), &[]).is_err());
+        assert_eq!(
+            KeyStore::Keyring.manifest_source(&key.fingerprint()),
+            format!("secret.pgp.gpg.fingerprint = \"{}\"", key.fingerprint())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::{
+        process::{
+            Command,
+            Stdio,
+        },
+        sync::Mutex,
+    },
+    zeroize::Zeroize,
+};
+
+/// Held while checking, so concurrent reads check only once.
+static LOGGED_IN: Mutex<Option<bool>> = Mutex::new(None);
+
+pub(crate) struct LastPass;
+
+impl LastPass {
+    fn validate(item: &str, field: &str) -> Result<()> {
+        for (name, value) in [("item", item), ("field", field)] {
+            if value.is_empty() || value.starts_with('-') || value.contains(['\n', '\0']) {
+                anyhow::bail!("Invalid LastPass {}: '{}'", name, value);
+            }
+        }
+        Ok(())
+    }
+
+    fn field_option(field: &str) -> String {
+        match field {
+            | "password" | "username" | "url" | "notes" => format!("--{}", field),
+            | field => format!("--field={}", field),
+        }
+    }
+
+    fn ensure_logged_in(removed_env_vars: &[String]) -> Result<()> {
+        let mut logged_in = LOGGED_IN.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let logged_in = *logged_in.get_or_insert_with(|| {
+            let mut cmd = Command::new("lpass");
+            cmd.args(["status", "--quiet"]);
+            crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+            cmd.stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        });
+        if !logged_in {
+            return Err(
+                Failure::Provider.error("lpass is not logged in or not installed; run 'lpass login <username>' first")
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn show(&self, item: &str, field: &str, removed_env_vars: &[String]) -> Result<String> {
+        Self::validate(item, field)?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.lastpass_field(item, field);
+        }
+        Self::ensure_logged_in(removed_env_vars)?;
+        let mut cmd = Command::new("lpass");
+        cmd.args(["show", "--color=never"])
+            .arg(Self::field_option(field))
+            .arg("--")
+            .arg(item);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let mut output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute lpass to show item")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("lpass failed: {}", stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let mut value = String::from_utf8(std::mem::take(&mut output.stdout)).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("LastPass field is not valid UTF-8")
+        })?;
+        if value.ends_with('\n') {
+            value.pop();
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_fields() {
+        assert_eq!(LastPass::field_option("password"), "--password");
+        assert_eq!(LastPass::field_option("notes"), "--notes");
+        assert_eq!(LastPass::field_option("API Key"), "--field=API Key");
+        assert!(LastPass::validate("Shared-Infra/postgres", "password").is_ok());
+        assert!(LastPass::validate("--sync=now", "password").is_err());
+        assert!(LastPass::validate("postgres", "").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    std::{
+        fs::File,
+        io::{
+            BufRead,
+            Write,
+        },
+        path::{
+            Path,
+            PathBuf,
+        },
+        sync::Mutex,
+    },
+};
+
+/// A path secenv changed on disk and must clean up after the run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LedgerEntry {
+    Remove { path: PathBuf },
+    Restore { path: PathBuf },
+    RestoreBackup { path: PathBuf, backup: PathBuf },
+}
+
+/// Locked for the lifetime of the run, so `clean` can tell live runs apart.
+pub(crate) struct RunLedger {
+    directory: PathBuf,
+    file: Mutex<Option<(PathBuf, File)>>,
+}
+
+pub(crate) fn state_directory() -> Result<PathBuf> {
+    let state_home = match std::env::var_os("XDG_STATE_HOME").filter(|value| !value.is_empty()) {
+        | Some(state_home) => PathBuf::from(state_home),
+        | None => {
+            let home = std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .filter(|value| !value.is_empty())
+                .context("Cannot locate the state directory: neither XDG_STATE_HOME nor HOME is set")?;
+            PathBuf::from(home).join(".local").join("state")
+        },
+    };
+    Ok(state_home.join("secenv"))
+}
+
+impl RunLedger {
+    pub(crate) fn default_directory() -> Result<PathBuf> {
+        Ok(state_directory()?.join("runs"))
+    }
+
+    pub(crate) fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            file: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn record(&self, entry: &LedgerEntry) -> Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if file.is_none() {
+            *file = Some(self.create()?);
+        }
+        let Some((path, file)) = file.as_mut() else {
+            unreachable!("ledger file was just created");
+        };
+        let mut line = serde_json::to_vec(entry).context("Failed to serialize state ledger entry")?;
+        line.push(b'\n');
+        file.write_all(&line)
+            .and_then(|()| file.sync_data())
+            .with_context(|| format!("Failed to write state ledger '{}'", path.display()))
+    }
+
+    pub(crate) fn complete(&self) -> Result<()> {
+        let Some((path, _file)) = self
+            .file
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .take()
+        else {
+            return Ok(());
+        };
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove state ledger '{}'", path.display()))
+    }
+
+    fn create(&self) -> Result<(PathBuf, File)> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder
+            .create(&self.directory)
+            .with_context(|| format!("Failed to create state directory '{}'", self.directory.display()))?;
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .context("System clock is before the Unix epoch")?
+            .as_nanos();
+        let path = self
+            .directory
+            .join(format!("{}-{}.jsonl", std::process::id(), timestamp));
+        let mut options = std::fs::OpenOptions::new();
+        options.append(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options
+            .open(&path)
+            .with_context(|| format!("Failed to create state ledger '{}'", path.display()))?;
+        file.try_lock()
+            .map_err(std::io::Error::from)
+            .with_context(|| format!("Failed to lock state ledger '{}'", path.display()))?;
+        Ok((path, file))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum CleanOutcome {
+    Removed(PathBuf),
+    Restored { path: PathBuf, backup: PathBuf },
+    Unrecoverable(PathBuf),
+    Missing(PathBuf),
+}
+
+impl std::fmt::Display for CleanOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::Removed(path) => write!(f, "remove '{}'", path.display()),
+            | Self::Restored { path, backup } => {
+                write!(f, "restore '{}' from '{}'", path.display(), backup.display())
+            },
+            | Self::Unrecoverable(path) => {
+                write!(
+                    f,
+                    "leave '{}': its original contents were only held in memory",
+                    path.display()
+                )
+            },
+            | Self::Missing(path) => write!(f, "skip '{}': already gone", path.display()),
+        }
+    }
+}
+
+pub(crate) struct LedgerCleaner {
+    directory: PathBuf,
+}
+
+impl LedgerCleaner {
+    pub(crate) fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    pub(crate) fn clean(&self, dry_run: bool) -> Result<Vec<CleanOutcome>> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            | Ok(entries) => entries,
+            | Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            | Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Failed to read state directory '{}'", self.directory.display()));
+            },
+        };
+        let mut ledgers = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("Failed to read state directory '{}'", self.directory.display()))?;
+        ledgers.retain(|path| path.extension().is_some_and(|extension| extension == "jsonl"));
+        ledgers.sort();
+
+        let mut outcomes = Vec::new();
+        for ledger in ledgers {
+            outcomes.extend(Self::clean_ledger(&ledger, dry_run)?);
+        }
+        Ok(outcomes)
+    }
+
+    fn clean_ledger(ledger: &Path, dry_run: bool) -> Result<Vec<CleanOutcome>> {
+        let file = File::open(ledger).with_context(|| format!("Failed to open state ledger '{}'", ledger.display()))?;
+        match file.try_lock_shared() {
+            | Ok(()) => {},
+            | Err(std::fs::TryLockError::WouldBlock) => return Ok(Vec::new()),
+            | Err(std::fs::TryLockError::Error(error)) => {
+                return Err(error).with_context(|| format!("Failed to lock state ledger '{}'", ledger.display()));
+            },
+        }
+
+        let mut entries = Vec::new();
+        for line in std::io::BufReader::new(&file).lines() {
+            let line = line.with_context(|| format!("Failed to read state ledger '{}'", ledger.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A crash can truncate the final line; everything before it was
+            // recorded before the corresponding path was touched.
+            match serde_json::from_str::<LedgerEntry>(&line) {
+                | Ok(entry) => entries.push(entry),
+                | Err(_) => {
+                    crate::output::warning!("Ignoring a malformed entry in state ledger '{}'", ledger.display())
+                },
+            }
+        }
+
+        let mut outcomes = Vec::new();
+        for entry in entries.into_iter().rev() {
+            outcomes.push(Self::clean_entry(entry, dry_run)?);
+        }
+        if !dry_run {
+            drop(file);
+            std::fs::remove_file(ledger)
+                .with_context(|| format!("Failed to remove state ledger '{}'", ledger.display()))?;
+        }
+        Ok(outcomes)
+    }
+
+    fn clean_entry(entry: LedgerEntry, dry_run: bool) -> Result<CleanOutcome> {
+        match entry {
+            | LedgerEntry::Remove { path } => {
+                if std::fs::symlink_metadata(&path).is_err() {
+                    return Ok(CleanOutcome::Missing(path));
+                }
+                if !dry_run {
+                    std::fs::remove_file(&path).with_context(|| format!("Failed to remove '{}'", path.display()))?;
+                }
+                Ok(CleanOutcome::Removed(path))
+            },
+            | LedgerEntry::RestoreBackup { path, backup } => {
+                if !backup.exists() {
+                    return Ok(CleanOutcome::Missing(backup));
+                }
+                if !dry_run {
+                    std::fs::rename(&backup, &path)
+                        .with_context(|| format!("Failed to restore backup '{}'", backup.display()))?;
+                }
+                Ok(CleanOutcome::Restored { path, backup })
+            },
+            | LedgerEntry::Restore { path } => Ok(CleanOutcome::Unrecoverable(path)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::fs,
+    };
+
+    #[test]
+    fn cleans_abandoned_runs_and_skips_active_ones() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let state = directory.path().join("state");
+        let created = directory.path().join("created.secret");
+        let replaced = directory.path().join("replaced.conf");
+        let backup = directory.path().join("replaced.conf.bak.1");
+        let in_memory = directory.path().join("in-memory.conf");
+        fs::write(&created, "secret")?;
+        fs::write(&replaced, "secret")?;
+        fs::write(&backup, "original")?;
+        fs::write(&in_memory, "secret")?;
+
+        let abandoned = RunLedger::new(state.clone());
+        abandoned.record(&LedgerEntry::Remove { path: created.clone() })?;
+        abandoned.record(&LedgerEntry::RestoreBackup {
+            path: replaced.clone(),
+            backup: backup.clone(),
+        })?;
+        abandoned.record(&LedgerEntry::Restore {
+            path: in_memory.clone(),
+        })?;
+        let abandoned_path = abandoned.file.lock().unwrap().take().map(|(path, _file)| path).unwrap();
+
+        let active = RunLedger::new(state.clone());
+        active.record(&LedgerEntry::Remove {
+            path: directory.path().join("still-in-use"),
+        })?;
+
+        let cleaner = LedgerCleaner::new(state.clone());
+        let planned = cleaner.clean(true)?;
+        assert_eq!(planned.len(), 3);
+        assert!(created.exists());
+        assert!(abandoned_path.exists());
+
+        let cleaned = cleaner.clean(false)?;
+        assert_eq!(cleaned, planned);
+        assert_eq!(cleaned[0], CleanOutcome::Unrecoverable(in_memory));
+        assert!(!created.exists());
+        assert_eq!(fs::read_to_string(&replaced)?, "original");
+        assert!(!backup.exists());
+        assert!(!abandoned_path.exists());
+        assert_eq!(fs::read_dir(&state)?.count(), 1);
+
+        active.complete()?;
+        assert_eq!(fs::read_dir(&state)?.count(), 0);
+        assert!(cleaner.clean(false)?.is_empty());
+        Ok(())
+    }
+}
@@ -1,13 +1,54 @@
 mod args;
+mod audit;
 mod aws;
+mod azure;
+mod condition;
+mod consul;
+mod doppler;
+mod dotenv;
+mod dpapi;
+mod editor;
+mod exec;
+mod extract;
+mod failure;
 mod gcp;
+mod git;
+mod gitlab;
 mod gpg;
+mod http;
+mod import;
+mod keepass;
+mod keychain;
+mod keys;
+mod lastpass;
+mod ledger;
 mod manifest;
+mod mock;
+mod oidc;
+mod onepassword;
+mod output;
+mod pass;
 mod password_cipher;
 mod pgp;
+mod pkcs11;
+mod policy;
 mod process;
+mod prompt;
+mod recipient;
 mod reference;
+mod schema;
 mod sealed;
+mod search;
+mod serve;
+mod snapshot;
+mod sops;
+mod ssh;
+mod systemd;
+mod template;
+mod throttle;
+mod totp;
+mod tpm;
+mod vault;
 
 use {
     anyhow::{
@@ -16,23 +57,39 @@ use {
     },
     args::{
         ManualFormat,
+        OutputFormat,
         SealTarget,
         UnlockAction,
     },
-    manifest::Manifest,
+    manifest::{
+        BackupMode,
+        FileMode,
+        Manifest,
+        SourceReference,
+        ValueType,
+    },
+    serde::Serialize,
     std::{
-        collections::HashMap,
+        collections::BTreeMap,
         io::Write,
+        path::PathBuf,
         process::ExitCode,
     },
-    zeroize::{
-        Zeroize,
-        Zeroizing,
-    },
+    zeroize::Zeroizing,
 };
 
 #[tokio::main]
-async fn main() -> Result<ExitCode> {
+async fn main() -> ExitCode {
+    match run().await {
+        | Ok(exit_code) => exit_code,
+        | Err(error) => {
+            eprintln!("Error: {:?}", error);
+            ExitCode::from(failure::Failure::of(&error).map_or(failure::Failure::GENERAL, failure::Failure::code))
+        },
+    }
+}
+
+async fn run() -> Result<ExitCode> {
     let command = crate::args::ClapArgumentLoader::load()?;
 
     match command {
@@ -46,11 +103,34 @@ async fn main() -> Result<ExitCode> {
             }
             Ok(ExitCode::SUCCESS)
         },
-        | crate::args::Command::Autocomplete { path, shell } => {
-            std::fs::create_dir_all(&path)
-                .with_context(|| format!("Failed to create directory: {}", path.display()))?;
+        | crate::args::Command::Autocomplete { target, shell } => {
             let builder = crate::reference::ReferenceBuilder;
-            builder.build_shell_completion(&path, &shell)?;
+            match target {
+                | args::CompletionTarget::Directory(path) => {
+                    std::fs::create_dir_all(&path)
+                        .with_context(|| format!("Failed to create directory: {}", path.display()))?;
+                    builder.build_shell_completion(&path, &shell)?;
+                },
+                | args::CompletionTarget::Install => {
+                    let path = builder.install_shell_completion(&shell)?;
+                    eprintln!("Installed {} completions to {}", shell, path.display());
+                    if shell == clap_complete::Shell::Zsh {
+                        eprintln!(
+                            "Make sure {} is on your fpath before compinit runs, e.g. in ~/.zshrc:",
+                            path.parent()
+                                .map_or_else(Default::default, |parent| parent.display().to_string())
+                        );
+                        eprintln!("  fpath=(~/.zsh/completions $fpath)");
+                    }
+                    eprintln!("Start a new shell to load them.");
+                },
+                | args::CompletionTarget::Print => {
+                    std::io::stdout()
+                        .lock()
+                        .write_all(&builder.render_shell_completion(&shell))
+                        .context("Failed to write completion script")?;
+                },
+            }
             Ok(ExitCode::SUCCESS)
         },
         | crate::args::Command::Unlock {
@@ -59,49 +139,168 @@ async fn main() -> Result<ExitCode> {
             action,
             force,
             timeout,
+            prompt,
+            overrides,
+            filter,
+            mock_providers,
+            allow_nested,
+            allow_expired,
+            experimental,
+            confirm,
+            strict,
+            snapshot,
+            materialization,
         } => {
+            if !allow_nested && std::env::var_os("SECENV_ACTIVE").is_some_and(|active| active == "1") {
+                anyhow::bail!(
+                    "Already running under secenv unlock (profile '{}'); nesting applies variables and files twice. \
+                     Use --allow-nested to run anyway.",
+                    std::env::var("SECENV_PROFILE").unwrap_or_default()
+                );
+            }
             manifest.warn_if_insecure_permissions();
+            if let Some(fixtures) = mock_providers {
+                fixtures.install()?;
+            }
 
-            let mut pgp_manager = crate::pgp::PgpManager::default();
-
-            let profile = manifest
-                .profiles
-                .get(profile_name.as_str())
-                .with_context(|| format!("Profile '{}' not found in manifest", profile_name))?;
-
-            let mut secret_source_env_vars: Vec<_> =
-                profile.secret_environment_variables().map(str::to_owned).collect();
-            secret_source_env_vars.sort_unstable();
-            secret_source_env_vars.dedup();
+            let mut pgp_manager = crate::pgp::PgpManager::new(prompt);
 
-            let generated_files: Vec<_> = profile.files.keys().cloned().collect();
-            let sealed_file_manager = crate::sealed::SealedFileManager::new(manifest.source_directory()?)?;
-            sealed_file_manager.validate_profile(profile.sealed.as_ref(), &generated_files, force)?;
+            let profile = &manifest
+                .profile(&profile_name)?
+                .select(&condition::ConditionContext::current(&profile_name))?;
+            if let UnlockAction::Run(command) = &action {
+                enforce_exec_policy(profile, &profile_name, command, experimental)?;
+            }
 
-            let mut environment = Environment::load(profile, &secret_source_env_vars)?;
-            environment.remove_secret_sources(&secret_source_env_vars);
+            let mut vars: Vec<_> = profile
+                .env
+                .vars
+                .iter()
+                .filter(|(key, _)| materialization.environment() && filter.matches(key))
+                .collect();
+            vars.sort_by_key(|(key, _)| *key);
+            // Only live values are checked for expiry and signatures.
+            let checks = ProfileChecks {
+                action: "unlocked",
+                allow_expired,
+                confirm: confirm.as_deref(),
+                strict,
+            };
+            let secret_source_env_vars = if snapshot.is_none() {
+                prepare_profile(
+                    &manifest,
+                    &profile_name,
+                    profile,
+                    &vars,
+                    &checks,
+                    Some(&mut pgp_manager),
+                )?
+            } else {
+                prepare_profile(&manifest, &profile_name, profile, &[], &checks, None)?
+            };
 
-            for (key, value) in profile.env.vars.iter() {
-                let value = value
-                    .inner
-                    .resolve(&mut pgp_manager, &secret_source_env_vars)
-                    .with_context(|| format!("Failed to resolve environment variable '{}'", key))?;
-                environment.insert(key.clone(), value)?;
+            let mut sealed_file_manager = crate::sealed::SealedFileManager::new(manifest.source_directory()?)?;
+            match ledger::RunLedger::default_directory() {
+                | Ok(directory) => {
+                    sealed_file_manager = sealed_file_manager.with_ledger(ledger::RunLedger::new(directory))
+                },
+                | Err(error) => crate::output::warning!("{}; leftovers of this run cannot be cleaned up later", error),
             }
 
+            let snapshot = match &snapshot {
+                | Some(path) => {
+                    let (snapshot, created) =
+                        snapshot::Snapshot::read(path, &profile_name, &pgp_manager, &secret_source_env_vars)?;
+                    if profile.sealed.is_some() && materialization.files() {
+                        crate::output::warning!(
+                            "Sealed documents of profile '{}' are not part of snapshots and stay sealed",
+                            profile_name
+                        );
+                    }
+                    Some((snapshot, Origin::Snapshot {
+                        path: path.clone(),
+                        created,
+                    }))
+                },
+                | None => {
+                    if materialization.files() {
+                        sealed_file_manager.validate_profile(profile.sealed.as_ref(), &profile.files, force)?;
+                    }
+                    None
+                },
+            };
+
             // Resolve remote and interactive sources before materializing any
             // plaintext files. Signals retain their default behavior here.
-            let mut generated_content = Vec::with_capacity(profile.files.len());
-            for (file_path, content) in profile.files.iter() {
-                generated_content.push((
-                    file_path.clone(),
-                    Zeroizing::new(
-                        content
-                            .inner
-                            .resolve_temporary_file(&mut pgp_manager, &secret_source_env_vars)
-                            .with_context(|| format!("Failed to resolve temporary file '{}'", file_path))?,
-                    ),
-                ));
+            let (mut environment, mut generated_content) = match &snapshot {
+                | Some((snapshot, origin)) => {
+                    let mut environment = Environment::default();
+                    for (name, value) in snapshot.variables().filter(|_| materialization.environment()) {
+                        environment.insert(name.clone(), value.to_string(), origin.clone())?;
+                    }
+                    let mut files = snapshot.files()?;
+                    files.retain(|_| materialization.files());
+                    for (_, _, backup, _) in &mut files {
+                        *backup = backup.or(force.then_some(BackupMode::Memory));
+                    }
+                    (environment, files)
+                },
+                | None => {
+                    let mut environment = if materialization.environment() {
+                        Environment::load(profile, &pgp_manager, &secret_source_env_vars)?
+                    } else {
+                        Environment::default()
+                    };
+                    environment.remove_secret_sources(&secret_source_env_vars);
+                    let generated_content = resolve_alongside(
+                        || {
+                            if !materialization.files() {
+                                return Ok(Vec::new());
+                            }
+                            resolve_generated_files(
+                                profile,
+                                force,
+                                &sealed_file_manager,
+                                &pgp_manager,
+                                &secret_source_env_vars,
+                            )
+                        },
+                        || environment.resolve_vars(vars, &pgp_manager, &secret_source_env_vars),
+                    )?;
+                    (environment, generated_content)
+                },
+            };
+            for variable in overrides {
+                match variable {
+                    | args::VariableOverride::Value { name, value } => {
+                        environment
+                            .insert(name.clone(), value.to_string(), Origin::Var)
+                            .with_context(|| format!("Invalid --var '{}'", name))?;
+                    },
+                    | args::VariableOverride::File(path) => {
+                        let contents = Zeroizing::new(
+                            std::fs::read_to_string(&path)
+                                .with_context(|| format!("Failed to read --var-file '{}'", path.display()))?,
+                        );
+                        environment
+                            .extend_from(&contents, &Origin::VarFile { path: path.clone() })
+                            .with_context(|| format!("Invalid --var-file '{}'", path.display()))?;
+                    },
+                }
+            }
+            environment.retain(|name| filter.matches(name));
+            // Snapshots hold files as they were rendered.
+            if snapshot.is_none() {
+                for (file_path, content, ..) in &mut generated_content {
+                    if let Some(file) = profile.files.get(file_path) {
+                        render_template(file_path, file, content, &environment.values)?;
+                    }
+                }
+            }
+            let mut leases = environment.take_leases();
+            if !matches!(action, UnlockAction::Run(_)) {
+                // Printed credentials are used after secenv exits.
+                std::mem::take(&mut leases).release();
             }
 
             let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel(1);
@@ -126,23 +325,30 @@ async fn main() -> Result<ExitCode> {
                 };
 
                 (|| {
-                    if let Some(sealed) = &profile.sealed {
+                    if let Some(sealed) = profile
+                        .sealed
+                        .as_ref()
+                        .filter(|_| snapshot.is_none() && materialization.files())
+                    {
                         sealed_file_manager.unseal(
                             sealed,
-                            &generated_files,
+                            &profile.files,
                             &secret_source_env_vars,
-                            &mut pgp_manager,
+                            &pgp_manager,
                             force,
                             &mut poll_shutdown,
                         )?;
                     }
 
-                    for (file_path, value) in generated_content.iter_mut() {
+                    for (file_path, content, backup, permissions) in std::mem::take(&mut generated_content) {
                         if poll_shutdown() {
                             anyhow::bail!("Interrupted before plaintext files were written");
                         }
-                        sealed_file_manager.write_generated(file_path, value.as_str(), force)?;
-                        value.zeroize();
+                        if profile.files.get(&file_path).is_some_and(|file| file.persist) {
+                            sealed_file_manager.persist(&file_path, &content, permissions)?;
+                        } else {
+                            sealed_file_manager.materialize(&file_path, &content, backup, permissions)?;
+                        }
                     }
                     Ok(())
                 })()
@@ -158,11 +364,19 @@ async fn main() -> Result<ExitCode> {
                 }
                 match action {
                     | UnlockAction::Run(command) => {
+                        let keep_patterns = profile.env.keep_patterns()?;
                         let executor =
-                            CommandExecutor::new(&command, &environment, &profile.env.keep, &secret_source_env_vars);
-                        executor.execute(timeout, &mut shutdown_rx).await
+                            CommandExecutor::new(&command, &environment, &keep_patterns, &secret_source_env_vars)
+                                .with_metadata(&profile_name, manifest.source_path());
+                        let renewal = leases.renew_in_background();
+                        let outcome = executor.execute(timeout, &mut shutdown_rx).await;
+                        renewal.abort();
+                        outcome
                     },
-                    | UnlockAction::Print => {
+                    | UnlockAction::Print {
+                        format: OutputFormat::Shell,
+                        ..
+                    } => {
                         let stdout = std::io::stdout();
                         let mut stdout = stdout.lock();
                         for (key, value) in environment.iter() {
@@ -179,6 +393,14 @@ async fn main() -> Result<ExitCode> {
                         }
                         Ok(ExecutionOutcome::Printed)
                     },
+                    | UnlockAction::Print { format, reveal } => {
+                        let rendered = environment.render(format, &profile_name, reveal, &secret_source_env_vars)?;
+                        std::io::stdout()
+                            .lock()
+                            .write_all(rendered.as_bytes())
+                            .context("Failed to write environment report")?;
+                        Ok(ExecutionOutcome::Printed)
+                    },
                 }
             }
             .await;
@@ -198,22 +420,336 @@ async fn main() -> Result<ExitCode> {
             };
             Ok(outcome.exit_code())
         },
+        | crate::args::Command::Snapshot {
+            manifest,
+            profile_name,
+            out,
+            recipient,
+            force,
+            prompt,
+            mock_providers,
+            allow_expired,
+            confirm,
+            strict,
+        } => {
+            manifest.warn_if_insecure_permissions();
+            if let Some(fixtures) = mock_providers {
+                fixtures.install()?;
+            }
+            if out.exists() && !force {
+                return Err(failure::Failure::FileConflict.error(format!(
+                    "Snapshot '{}' already exists. Use --force to overwrite.",
+                    out.display()
+                )));
+            }
+
+            let mut pgp_manager = crate::pgp::PgpManager::new(prompt);
+
+            let profile = &manifest
+                .profile(&profile_name)?
+                .select(&condition::ConditionContext::current(&profile_name))?;
+
+            let mut vars: Vec<_> = profile.env.vars.iter().collect();
+            vars.sort_by_key(|(key, _)| *key);
+            let checks = ProfileChecks {
+                action: "captured",
+                allow_expired,
+                confirm: confirm.as_deref(),
+                strict,
+            };
+            let secret_source_env_vars = prepare_profile(
+                &manifest,
+                &profile_name,
+                profile,
+                &vars,
+                &checks,
+                Some(&mut pgp_manager),
+            )?;
+            let recipient = gpg::GpgKeySpec::new(recipient)?;
+            let certificate = gpg::GpgManager.export_public_key(&recipient, &secret_source_env_vars)?;
+            if profile.sealed.is_some() {
+                crate::output::warning!(
+                    "Sealed documents of profile '{}' are not part of snapshots",
+                    profile_name
+                );
+            }
+
+            let mut environment = Environment::load(profile, &pgp_manager, &secret_source_env_vars)?;
+            environment.remove_secret_sources(&secret_source_env_vars);
+            let sealed_file_manager = crate::sealed::SealedFileManager::new(manifest.source_directory()?)?;
+            let resolved = resolve_alongside(
+                || {
+                    resolve_generated_files(
+                        profile,
+                        false,
+                        &sealed_file_manager,
+                        &pgp_manager,
+                        &secret_source_env_vars,
+                    )
+                },
+                || environment.resolve_vars(vars, &pgp_manager, &secret_source_env_vars),
+            );
+            pgp_manager.clear_cache();
+            let mut files = resolved?;
+            for (file_path, content, ..) in &mut files {
+                if let Some(file) = profile.files.get(file_path) {
+                    render_template(file_path, file, content, &environment.values)?;
+                }
+            }
+
+            let leases = environment.take_leases();
+            let values = environment.into_values();
+            let variables = values.len();
+            let mut captured = snapshot::Snapshot::new(values);
+            for (path, file, backup, permissions) in &files {
+                captured.add_file(path.clone(), file, *backup, *permissions);
+            }
+            let sealed = captured.seal(&profile_name, &recipient, &certificate, &pgp_manager)?;
+            snapshot::Snapshot::write(&out, &sealed, force)?;
+            leases.release();
+            eprintln!(
+                "Captured {} variable(s) and {} file(s) of profile '{}' in {}",
+                variables,
+                files.len(),
+                profile_name,
+                out.display()
+            );
+            Ok(ExitCode::SUCCESS)
+        },
+        | crate::args::Command::ResolveAll {
+            manifest,
+            profiles,
+            output_dir,
+            format,
+            force,
+            prompt,
+            mock_providers,
+            allow_expired,
+            confirm,
+            strict,
+        } => {
+            manifest.warn_if_insecure_permissions();
+            if let Some(fixtures) = mock_providers {
+                fixtures.install()?;
+            }
+
+            // Check every profile before calling any provider.
+            let checks = ProfileChecks {
+                action: "resolved",
+                allow_expired,
+                confirm: confirm.as_deref(),
+                strict,
+            };
+            let mut selected = Vec::new();
+            for profile_name in manifest.profile_names(&profiles)? {
+                let profile = manifest
+                    .profile(profile_name)?
+                    .select(&condition::ConditionContext::current(profile_name))?;
+                let vars: Vec<_> = profile.env.vars.iter().collect();
+                let secret_source_env_vars = prepare_profile(&manifest, profile_name, &profile, &vars, &checks, None)?;
+                let out = output_dir.join(format!("{}.{}", profile_name, format.extension()));
+                if out.exists() && !force {
+                    return Err(failure::Failure::FileConflict
+                        .error(format!("'{}' already exists. Use --force to overwrite.", out.display())));
+                }
+                selected.push((profile_name, profile, secret_source_env_vars, out));
+            }
+
+            let mut pgp_manager = crate::pgp::PgpManager::new(prompt);
+            let mut secret_source_env_vars: Vec<_> = selected
+                .iter()
+                .flat_map(|(_, _, secret_source_env_vars, _)| secret_source_env_vars.iter().cloned())
+                .collect();
+            secret_source_env_vars.sort_unstable();
+            secret_source_env_vars.dedup();
+            trust_signers(&manifest, &mut pgp_manager, &secret_source_env_vars, strict)?;
+
+            let resolve = |profile_name: &str,
+                           profile: &manifest::ManifestProfile,
+                           secret_source_env_vars: &[String]|
+             -> Result<Zeroizing<String>> {
+                let mut vars: Vec<_> = profile.env.vars.iter().collect();
+                vars.sort_by_key(|(key, _)| *key);
+                let mut environment = Environment::load(profile, &pgp_manager, secret_source_env_vars)?;
+                environment.remove_secret_sources(secret_source_env_vars);
+                environment.resolve_vars(vars, &pgp_manager, secret_source_env_vars)?;
+                let rendered = environment.render(format, profile_name, true, secret_source_env_vars)?;
+                environment.take_leases().release();
+                Ok(rendered)
+            };
+            let resolved: Result<Vec<_>> = selected
+                .iter()
+                .map(|(profile_name, profile, secret_source_env_vars, out)| {
+                    resolve(profile_name, profile, secret_source_env_vars)
+                        .map(|rendered| (out, rendered))
+                        .with_context(|| format!("Failed to resolve profile '{}'", profile_name))
+                })
+                .collect();
+            pgp_manager.clear_cache();
+
+            let rendered = resolved?;
+            for (out, contents) in &rendered {
+                write_private_file(out, contents, force)?;
+            }
+            eprintln!("Resolved {} profile(s) into {}", rendered.len(), output_dir.display());
+            Ok(ExitCode::SUCCESS)
+        },
+        | crate::args::Command::Serve {
+            manifest,
+            profile_name,
+            listen,
+            token_file,
+            command,
+            prompt,
+            mock_providers,
+            allow_expired,
+            confirm,
+            metrics_listen,
+            strict,
+        } => {
+            manifest.warn_if_insecure_permissions();
+            if let Some(fixtures) = mock_providers {
+                fixtures.install()?;
+            }
+            // Checked again when the token is written; this avoids resolving in vain.
+            if token_file.exists() {
+                return Err(failure::Failure::FileConflict
+                    .error(format!("Token file '{}' already exists", token_file.display())));
+            }
+
+            let mut pgp_manager = crate::pgp::PgpManager::new(prompt);
+
+            let profile = &manifest
+                .profile(&profile_name)?
+                .select(&condition::ConditionContext::current(&profile_name))?;
+            if let Some(command) = &command {
+                enforce_exec_policy(profile, &profile_name, command, false)?;
+            }
+
+            let mut vars: Vec<_> = profile.env.vars.iter().collect();
+            vars.sort_by_key(|(key, _)| *key);
+            let checks = ProfileChecks {
+                action: "served",
+                allow_expired,
+                confirm: confirm.as_deref(),
+                strict,
+            };
+            let secret_source_env_vars = prepare_profile(
+                &manifest,
+                &profile_name,
+                profile,
+                &vars,
+                &checks,
+                Some(&mut pgp_manager),
+            )?;
+
+            let mut environment = Environment::load(profile, &pgp_manager, &secret_source_env_vars)?;
+            environment.remove_secret_sources(&secret_source_env_vars);
+            let resolve_result = environment.resolve_vars(vars, &pgp_manager, &secret_source_env_vars);
+            pgp_manager.clear_cache();
+            let timings = resolve_result?;
+            let leases = environment.take_leases();
+
+            let mut server = serve::SecretServer::bind(listen).await?;
+            let metrics_server = match metrics_listen {
+                | Some(metrics_listen) => {
+                    let metrics = std::sync::Arc::new(serve::Metrics::new(&timings, &pgp_manager));
+                    server = server.with_metrics(metrics.clone());
+                    Some(serve::MetricsServer::bind(metrics_listen, metrics).await?)
+                },
+                | None => None,
+            };
+            let address = server.local_addr()?;
+            // The token file is this run's from here on, so it is removed.
+            server.write_token(&token_file)?;
+            eprintln!("Serving profile '{}' on http://{}", profile_name, address);
+            if let Some(metrics_server) = &metrics_server {
+                eprintln!("Serving metrics on http://{}/metrics", metrics_server.local_addr()?);
+            }
+
+            let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel(1);
+            let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+            let shutdown_handle = tokio::spawn(async move {
+                let exit_code = shutdown_signal(ready_tx).await;
+                let _ = shutdown_tx.send(exit_code).await;
+            });
+            let values = environment.into_values();
+            let metrics_handle = metrics_server.map(|metrics_server| tokio::spawn(metrics_server.run(values.len())));
+            let server_handle = tokio::spawn(server.run(values));
+            let renewal = leases.renew_in_background();
+
+            let execution_result: Result<ExecutionOutcome> = async {
+                ready_rx.await.context("Failed to initialize signal handling")??;
+                match &command {
+                    | Some(command) => {
+                        // The values are only available over the API.
+                        let keep_patterns = profile.env.keep_patterns()?;
+                        let no_values = Environment::default();
+                        let executor =
+                            CommandExecutor::new(command, &no_values, &keep_patterns, &secret_source_env_vars)
+                                .with_metadata(&profile_name, manifest.source_path())
+                                .with_server(address, &token_file);
+                        executor.execute(None, &mut shutdown_rx).await
+                    },
+                    | None => {
+                        let exit_code = shutdown_rx
+                            .recv()
+                            .await
+                            .context("Shutdown monitor stopped unexpectedly")?;
+                        Ok(ExecutionOutcome::Interrupted(exit_code))
+                    },
+                }
+            }
+            .await;
+
+            renewal.abort();
+            server_handle.abort();
+            let _ = server_handle.await;
+            if let Some(metrics_handle) = metrics_handle {
+                metrics_handle.abort();
+                let _ = metrics_handle.await;
+            }
+            shutdown_handle.abort();
+            let _ = shutdown_handle.await;
+            if let Err(error) = std::fs::remove_file(&token_file) {
+                crate::output::warning!("Failed to remove token file '{}': {}", token_file.display(), error);
+            }
+            Ok(execution_result?.exit_code())
+        },
         | crate::args::Command::Seal {
             manifest,
             profile_name,
             target,
             input,
+            recipient,
+            prompt,
+            mock_providers,
         } => {
             manifest.warn_if_insecure_permissions();
-            let profile = manifest
-                .profiles
-                .get(&profile_name)
-                .with_context(|| format!("Profile '{}' not found in manifest", profile_name))?;
-            let pgp_manager = crate::pgp::PgpManager::default();
+            if let Some(fixtures) = mock_providers {
+                fixtures.install()?;
+            }
+            let profile = &manifest.profile(&profile_name)?;
+            if let Some(policy) = &profile.policy {
+                policy
+                    .enforce(&policy::PolicyContext::current())
+                    .with_context(|| format!("Profile '{}' cannot be used here", profile_name))?;
+            }
+            let pgp_manager = crate::pgp::PgpManager::new(prompt);
             let mut secret_source_env_vars: Vec<_> =
                 profile.secret_environment_variables().map(str::to_owned).collect();
             secret_source_env_vars.sort_unstable();
             secret_source_env_vars.dedup();
+            let recipient = match recipient {
+                | Some(query) => {
+                    let recipient =
+                        recipient::Recipient::locate(&query.email, &query.keyserver, &secret_source_env_vars)?;
+                    recipient.confirm(query.fingerprint.as_deref())?;
+                    Some(recipient)
+                },
+                | None => None,
+            };
+            let certificate = recipient.as_ref().map(recipient::Recipient::certificate);
 
             let seal_value = |plaintext: &str| -> Result<String> {
                 match &target {
@@ -226,6 +762,7 @@ async fn main() -> Result<ExitCode> {
                             sealed,
                             configured_file,
                             plaintext,
+                            certificate,
                             &pgp_manager,
                             &secret_source_env_vars,
                         )
@@ -242,7 +779,7 @@ async fn main() -> Result<ExitCode> {
                                 )
                             })?
                             .inner
-                            .seal(plaintext, &pgp_manager, &secret_source_env_vars)
+                            .seal(plaintext, certificate, &pgp_manager, &secret_source_env_vars)
                             .with_context(|| format!("Failed to seal environment variable '{}'", name))
                     },
                 }
@@ -258,7 +795,14 @@ async fn main() -> Result<ExitCode> {
                         .as_ref()
                         .with_context(|| format!("Profile '{}' has no sealed files", profile_name))?;
                     let manager = crate::sealed::SealedFileManager::new(manifest.source_directory()?)?;
-                    manager.seal_path(sealed, configured_file, &pointer, &pgp_manager, &secret_source_env_vars)?
+                    manager.seal_path(
+                        sealed,
+                        configured_file,
+                        &pointer,
+                        certificate,
+                        &pgp_manager,
+                        &secret_source_env_vars,
+                    )?
                 },
                 | crate::args::SealInput::Direct(plaintext) => seal_value(plaintext.as_str())?,
                 | crate::args::SealInput::Stdin => {
@@ -280,20 +824,280 @@ async fn main() -> Result<ExitCode> {
             writeln!(std::io::stdout().lock(), "{}", marker).context("Failed to write sealed marker")?;
             Ok(ExitCode::SUCCESS)
         },
+        | args::Command::Audit {
+            manifest,
+            profiles,
+            strict,
+        } => {
+            let findings = audit::audit(&manifest, &profiles, chrono::Utc::now())?;
+            let mut table = output::Table::new(["PROFILE", "VARIABLE", "ISSUE"]);
+            for finding in &findings {
+                let style = match finding.issue {
+                    | audit::AuditIssue::Expired { .. } => output::Style::Failure,
+                    | audit::AuditIssue::RotationOverdue { .. } => output::Style::Warning,
+                };
+                table.push([
+                    (finding.profile.clone(), None),
+                    (finding.variable.clone().unwrap_or_default(), None),
+                    (finding.issue.to_string(), Some(style)),
+                ]);
+            }
+            let stdout = std::io::stdout();
+            let mut stdout = stdout.lock();
+            if table.is_empty() {
+                writeln!(stdout, "No findings.")?;
+            } else {
+                write!(stdout, "{}", table.render(output::Stream::Stdout))?;
+            }
+            Ok(if strict && !findings.is_empty() {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            })
+        },
+        | args::Command::VerifyFiles {
+            manifest,
+            profile_name,
+            prompt,
+            mock_providers,
+            allow_expired,
+            confirm,
+            strict,
+        } => {
+            manifest.warn_if_insecure_permissions();
+            if let Some(fixtures) = mock_providers {
+                fixtures.install()?;
+            }
+
+            let profile = &manifest
+                .profile(&profile_name)?
+                .select(&condition::ConditionContext::current(&profile_name))?;
+            let mut persisted: Vec<_> = profile.files.iter().filter(|(_, file)| file.persist).collect();
+            persisted.sort_by_key(|(path, _)| *path);
+            if persisted.is_empty() {
+                anyhow::bail!("Profile '{}' has no persisted files", profile_name);
+            }
+
+            let mut pgp_manager = crate::pgp::PgpManager::new(prompt);
+            let checks = ProfileChecks {
+                action: "verified",
+                allow_expired,
+                confirm: confirm.as_deref(),
+                strict,
+            };
+            // Variables are only resolved when a template needs them.
+            let templated = persisted.iter().any(|(_, file)| file.template);
+            let mut vars: Vec<_> = profile.env.vars.iter().filter(|_| templated).collect();
+            vars.sort_by_key(|(key, _)| *key);
+            let secret_source_env_vars = prepare_profile(
+                &manifest,
+                &profile_name,
+                profile,
+                &vars,
+                &checks,
+                Some(&mut pgp_manager),
+            )?;
+            let sealed_file_manager = crate::sealed::SealedFileManager::new(manifest.source_directory()?)?;
+            let states: Result<Vec<_>> = (|| {
+                let mut environment = Environment::default();
+                if templated {
+                    environment = Environment::load(profile, &pgp_manager, &secret_source_env_vars)?;
+                    environment.remove_secret_sources(&secret_source_env_vars);
+                    environment.resolve_vars(vars, &pgp_manager, &secret_source_env_vars)?;
+                }
+                persisted
+                    .iter()
+                    .map(|(file_path, file)| {
+                        let mut content =
+                            resolve_generated_file(file, &sealed_file_manager, &pgp_manager, &secret_source_env_vars)
+                                .with_context(|| format!("Failed to resolve persisted file '{}'", file_path))?;
+                        render_template(file_path, file, &mut content, &environment.values)?;
+                        Ok((file_path, sealed_file_manager.persisted_state(file_path, &content)?))
+                    })
+                    .collect()
+            })();
+            pgp_manager.clear_cache();
+
+            let states = states?;
+            let mut table = output::Table::new(["PATH", "STATE"]);
+            for (file_path, state) in &states {
+                let style = match state {
+                    | sealed::PersistedState::InSync => output::Style::Success,
+                    | sealed::PersistedState::Drifted => output::Style::Failure,
+                    | sealed::PersistedState::Missing => output::Style::Warning,
+                };
+                table.push([(file_path.to_string(), None), (state.to_string(), Some(style))]);
+            }
+            write!(std::io::stdout().lock(), "{}", table.render(output::Stream::Stdout))?;
+            Ok(
+                if states.iter().all(|(_, state)| *state == sealed::PersistedState::InSync) {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                },
+            )
+        },
+        | args::Command::Explain {
+            manifest,
+            profile_name,
+            variable,
+        } => {
+            let profile = &manifest.profile(&profile_name)?;
+            write!(
+                std::io::stdout().lock(),
+                "{}",
+                profile.explain(&profile_name, &variable)
+            )
+            .context("Failed to write explanation")?;
+            Ok(ExitCode::SUCCESS)
+        },
+        | args::Command::Search { manifest, pattern } => {
+            let hits = search::search(&manifest, &pattern);
+            let mut table = output::Table::new(["PROFILE", "LOCATION", "SOURCE"]);
+            for hit in &hits {
+                table.push([
+                    (hit.profile.clone(), None),
+                    (hit.location.clone(), Some(output::Style::Bold)),
+                    (hit.reference.to_string(), None),
+                ]);
+            }
+            if !table.is_empty() {
+                write!(std::io::stdout().lock(), "{}", table.render(output::Stream::Stdout))?;
+            }
+            Ok(if hits.is_empty() {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            })
+        },
+        | args::Command::CompleteKeys { target } => {
+            let Some((manifest, profile_name)) = target else {
+                return Ok(ExitCode::FAILURE);
+            };
+            let mut stdout = std::io::stdout().lock();
+            for key in crate::reference::ReferenceBuilder.variable_keys(&manifest, &profile_name) {
+                writeln!(stdout, "{}", key)?;
+            }
+            Ok(ExitCode::SUCCESS)
+        },
+        | args::Command::Set {
+            manifest,
+            profile_name,
+            key,
+            value,
+        } => {
+            let mut editor = editor::ManifestEditor::load(manifest.source_path())?;
+            let path = [vec!["profiles".to_string(), profile_name], key].concat();
+            editor.set(&path, &value)?;
+            editor.save()?;
+            Ok(ExitCode::SUCCESS)
+        },
+        | args::Command::Remove {
+            manifest,
+            profile_name,
+            key,
+        } => {
+            let mut editor = editor::ManifestEditor::load(manifest.source_path())?;
+            let path = [vec!["profiles".to_string(), profile_name.clone()], key.clone()].concat();
+            if !editor.remove(&path)? {
+                anyhow::bail!(
+                    "'{}' is not set in profile '{}' (values inherited through substitutions cannot be removed)",
+                    key.join("."),
+                    profile_name
+                );
+            }
+            editor.save()?;
+            Ok(ExitCode::SUCCESS)
+        },
+        | args::Command::Import {
+            format,
+            source,
+            path,
+            force,
+            profile_name,
+            public_key,
+            recipient,
+            key_source,
+        } => {
+            if path.exists() && !force {
+                return Err(failure::Failure::FileConflict.error(format!(
+                    "Config file '{}' already exists. Use --force to overwrite.",
+                    path.display()
+                )));
+            }
+            let import = import::Import::read(format, &source, &[])?;
+            let certificate = match (public_key, recipient) {
+                | (Some(public_key), _) => {
+                    Some(
+                        std::fs::read_to_string(&public_key)
+                            .with_context(|| format!("Failed to read public key: {}", public_key.display()))?,
+                    )
+                },
+                | (None, Some(query)) => {
+                    let recipient = recipient::Recipient::locate(&query.email, &query.keyserver, &[])?;
+                    recipient.confirm(query.fingerprint.as_deref())?;
+                    Some(recipient.certificate().to_string())
+                },
+                | (None, None) => None,
+            };
+            let encryption = certificate
+                .map(|certificate| import::Encryption::new(certificate, key_source.as_deref()))
+                .transpose()?;
+            let (imported, encrypted) = (import.len(), import.encrypted());
+            let profile = import.into_profile(encryption.as_ref(), &crate::pgp::PgpManager::default())?;
+            let manifest = Manifest::with_profile(path.clone(), &profile_name, profile);
+            std::fs::write(&path, manifest.render()?)
+                .with_context(|| format!("Failed to write config file: {}", path.display()))?;
+            eprintln!(
+                "Imported {} variable(s) into profile '{}' of {}; {} encrypted again",
+                imported,
+                profile_name,
+                path.display(),
+                encrypted
+            );
+            Ok(ExitCode::SUCCESS)
+        },
+        | args::Command::GenerateKey {
+            name,
+            email,
+            store,
+            protect,
+            prompt,
+        } => {
+            let password = if protect {
+                let description = format!("the new key of {}", email);
+                let password = prompt.password(&description)?;
+                if password.is_empty() {
+                    anyhow::bail!("The key password must not be empty");
+                }
+                if prompt.password(&format!("{} (again)", description))? != password {
+                    anyhow::bail!("The passwords do not match");
+                }
+                Some(password)
+            } else {
+                None
+            };
+            let key = keys::GeneratedKey::generate(&name, &email, password.as_deref().map(String::as_str))?;
+            key.store(&store, &[])?;
+            print!("{}", key.public_key()?);
+            eprintln!(
+                "Generated key {}",
+                output::Style::Bold.paint(output::Stream::Stderr, key.fingerprint())
+            );
+            eprintln!("Private key source: {}", store.manifest_source(&key.fingerprint()));
+            Ok(ExitCode::SUCCESS)
+        },
         | args::Command::Init { path, force } => {
             if path.exists() && !force {
-                return Err(anyhow::anyhow!(
+                return Err(failure::Failure::FileConflict.error(format!(
                     "Config file '{}' already exists. Use --force to overwrite.",
                     path.display()
-                ));
+                )));
             }
 
-            let manifest = Manifest::example(path.clone());
+            let config = Manifest::example(path.clone()).render()?;
 
-            let json_config =
-                serde_json::to_string_pretty(&manifest).context("Failed to serialize example config to JSON")?;
-
-            std::fs::write(&path, json_config)
+            std::fs::write(&path, config)
                 .with_context(|| format!("Failed to write config file: {}", path.display()))?;
 
             let stdout = std::io::stdout();
@@ -306,9 +1110,272 @@ async fn main() -> Result<ExitCode> {
             )?;
             Ok(ExitCode::SUCCESS)
         },
+        | args::Command::Clean { dry_run } => {
+            let cleaner = ledger::LedgerCleaner::new(ledger::RunLedger::default_directory()?);
+            let outcomes = cleaner.clean(dry_run)?;
+            let stdout = std::io::stdout();
+            let mut stdout = stdout.lock();
+            if outcomes.is_empty() {
+                writeln!(stdout, "Nothing to clean up.")?;
+            }
+            for outcome in outcomes {
+                let prefix = if dry_run { "Would" } else { "Cleanup:" };
+                match outcome {
+                    | ledger::CleanOutcome::Unrecoverable(_) => crate::output::warning!("Cannot {}", outcome),
+                    | _ => writeln!(stdout, "{} {}", prefix, outcome)?,
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        },
+        | args::Command::Schema => {
+            writeln!(
+                std::io::stdout(),
+                "{}",
+                serde_json::to_string_pretty(&schema::manifest())?
+            )?;
+            Ok(ExitCode::SUCCESS)
+        },
     }
 }
 
+/// An existing file is replaced only with `force`.
+fn write_private_file(path: &std::path::Path, contents: &str, force: bool) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder
+            .create(parent)
+            .with_context(|| format!("Failed to create directory '{}'", parent.display()))?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// A variable error is reported before a file error.
+fn resolve_alongside<F: Send, V>(
+    resolve_files: impl FnOnce() -> Result<F>+Send,
+    resolve_vars: impl FnOnce() -> Result<V>,
+) -> Result<F> {
+    std::thread::scope(|scope| {
+        let files = scope.spawn(resolve_files);
+        let vars = resolve_vars();
+        let files = files.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        vars?;
+        files
+    })
+}
+
+/// Entries take turns by backend, so no provider holds up the rest.
+#[allow(clippy::type_complexity)]
+fn resolve_generated_files(
+    profile: &manifest::ManifestProfile,
+    force: bool,
+    sealed_file_manager: &sealed::SealedFileManager,
+    pgp_manager: &pgp::PgpManager,
+    secret_source_env_vars: &[String],
+) -> Result<Vec<(String, sealed::GeneratedFile, Option<BackupMode>, Option<u32>)>> {
+    let mut files: Vec<_> = profile.files.iter().collect();
+    files.sort_by_key(|(path, _)| *path);
+    let order = interleave_by_backend(&files);
+    let resolve = |(file_path, file): (&String, &manifest::ManifestFile)| {
+        let content = resolve_generated_file(file, sealed_file_manager, pgp_manager, secret_source_env_vars)
+            .with_context(|| format!("Failed to resolve temporary file '{}'", file_path))?;
+        let backup = file.backup.or(force.then_some(BackupMode::Memory));
+        Ok((file_path.clone(), content, backup, file.permissions()?))
+    };
+
+    let workers = files.len().min(throttle::max_concurrency());
+    if workers <= 1 {
+        return files.into_iter().map(resolve).collect();
+    }
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let mut resolved: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut resolved = Vec::new();
+                    loop {
+                        let turn = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        let Some(&index) = order.get(turn) else {
+                            return resolved;
+                        };
+                        let file = &files[index];
+                        resolved.push((index, resolve(*file)));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+    resolved.sort_by_key(|(index, _)| *index);
+    resolved.into_iter().map(|(_, file)| file).collect()
+}
+
+fn interleave_by_backend(files: &[(&String, &manifest::ManifestFile)]) -> Vec<usize> {
+    let mut queues: Vec<(&str, std::collections::VecDeque<usize>)> = Vec::new();
+    for (index, (_, file)) in files.iter().enumerate() {
+        let backend = file.inner.reference().backend;
+        match queues.iter_mut().find(|(queued, _)| *queued == backend) {
+            | Some((_, queue)) => queue.push_back(index),
+            | None => queues.push((backend, std::collections::VecDeque::from([index]))),
+        }
+    }
+    let mut order = Vec::with_capacity(files.len());
+    while order.len() < files.len() {
+        for (_, queue) in &mut queues {
+            order.extend(queue.pop_front());
+        }
+    }
+    order
+}
+
+fn resolve_generated_file(
+    file: &manifest::ManifestFile,
+    sealed_file_manager: &sealed::SealedFileManager,
+    pgp_manager: &pgp::PgpManager,
+    secret_source_env_vars: &[String],
+) -> Result<sealed::GeneratedFile> {
+    let resolve_text = |pgp_manager: &pgp::PgpManager| -> Result<Zeroizing<String>> {
+        let content = Zeroizing::new(file.inner.resolve_temporary_file(pgp_manager, secret_source_env_vars)?);
+        file.verify_digest(content.as_bytes())
+            .context("Integrity check failed")?;
+        Ok(content)
+    };
+    match file.mode {
+        | FileMode::Write => Ok(sealed::GeneratedFile::Write(resolve_text(pgp_manager)?)),
+        | FileMode::Append => Ok(sealed::GeneratedFile::Append(resolve_text(pgp_manager)?)),
+        | FileMode::Copy => {
+            let copied = sealed_file_manager.read_copy_source(file.linked_path()?)?;
+            if let sealed::GeneratedFile::Copy(contents) = &copied {
+                file.verify_digest(contents).context("Integrity check failed")?;
+            }
+            Ok(copied)
+        },
+        | FileMode::Symlink => Ok(sealed_file_manager.symlink_target(file.linked_path()?)),
+    }
+}
+
+fn render_template(
+    file_path: &str,
+    file: &manifest::ManifestFile,
+    content: &mut sealed::GeneratedFile,
+    variables: &BTreeMap<String, Zeroizing<String>>,
+) -> Result<()> {
+    if let (true, sealed::GeneratedFile::Write(text) | sealed::GeneratedFile::Append(text)) = (file.template, content) {
+        *text =
+            template::render(text, variables).with_context(|| format!("Failed to render template '{}'", file_path))?;
+    }
+    Ok(())
+}
+
+struct ProfileChecks<'a> {
+    /// Completes "Profile '...' cannot be {action} here".
+    action: &'a str,
+    allow_expired: bool,
+    confirm: Option<&'a str>,
+    strict: bool,
+}
+
+/// Returns the sorted variables holding provider credentials.
+fn prepare_profile(
+    manifest: &manifest::Manifest,
+    profile_name: &str,
+    profile: &manifest::ManifestProfile,
+    vars: &[(&String, &manifest::ContentWrapper)],
+    checks: &ProfileChecks,
+    pgp_manager: Option<&mut crate::pgp::PgpManager>,
+) -> Result<Vec<String>> {
+    if let Some(policy) = &profile.policy {
+        policy
+            .enforce(&policy::PolicyContext::current())
+            .with_context(|| format!("Profile '{}' cannot be {} here", profile_name, checks.action))?;
+    }
+    let now = chrono::Utc::now();
+    for issue in audit::AuditIssue::check_profile(profile, now)? {
+        issue.enforce(&format!("Profile '{}'", profile_name), checks.allow_expired)?;
+    }
+    for (key, value) in vars {
+        for issue in audit::AuditIssue::check(&value.metadata, now)? {
+            issue.enforce(
+                &format!("Environment variable '{}' of profile '{}'", key, profile_name),
+                checks.allow_expired,
+            )?;
+        }
+    }
+    if let Some(confirmation) = &profile.confirm {
+        confirmation.confirm(profile_name, checks.confirm)?;
+    }
+
+    let mut secret_source_env_vars: Vec<_> = profile.secret_environment_variables().map(str::to_owned).collect();
+    secret_source_env_vars.sort_unstable();
+    secret_source_env_vars.dedup();
+    if let Some(pgp_manager) = pgp_manager {
+        trust_signers(manifest, pgp_manager, &secret_source_env_vars, checks.strict)?;
+    }
+    Ok(secret_source_env_vars)
+}
+
+fn trust_signers(
+    manifest: &manifest::Manifest,
+    pgp_manager: &mut crate::pgp::PgpManager,
+    secret_source_env_vars: &[String],
+    strict: bool,
+) -> Result<()> {
+    let signer_keys = manifest.signer_keys(secret_source_env_vars)?;
+    pgp_manager.trust_signers(
+        signer_keys
+            .iter()
+            .map(|(fingerprint, key)| (*fingerprint, key.as_str())),
+        strict,
+    )
+}
+
+fn enforce_exec_policy(
+    profile: &manifest::ManifestProfile,
+    profile_name: &str,
+    command: &args::ChildCommand,
+    experimental: bool,
+) -> Result<()> {
+    let Some(exec) = &profile.exec else {
+        return Ok(());
+    };
+    if !exec.allows(command.program(), command.arguments())? {
+        if !experimental {
+            anyhow::bail!(
+                "Profile '{}' does not allow running '{}'; its exec.allow list permits: {}",
+                profile_name,
+                command.program(),
+                exec.allow.join(", ")
+            );
+        }
+        crate::output::warning!(
+            "Running '{}' outside the exec.allow list of profile '{}' (--experimental)",
+            command.program(),
+            profile_name
+        );
+    }
+    Ok(())
+}
+
 enum ExecutionOutcome {
     Exited(std::process::ExitStatus),
     Printed,
@@ -372,21 +1439,133 @@ async fn shutdown_signal(ready: tokio::sync::oneshot::Sender<Result<()>>) -> i32
     130
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+enum Origin {
+    Manifest {
+        #[serde(flatten)]
+        reference: SourceReference,
+        #[serde(rename = "type", skip_serializing_if = "ValueType::is_string")]
+        value_type: ValueType,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        join: Option<String>,
+        cached: bool,
+    },
+    From {
+        index: usize,
+        #[serde(flatten)]
+        reference: SourceReference,
+    },
+    Extended {
+        entries: Vec<String>,
+    },
+    Var,
+    VarFile {
+        path: PathBuf,
+    },
+    Snapshot {
+        path: PathBuf,
+        created: String,
+    },
+}
+
+#[derive(Serialize)]
+struct EnvironmentReport<'a> {
+    profile: &'a str,
+    variables: BTreeMap<&'a str, VariableReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct VariableReport<'a> {
+    #[serde(flatten)]
+    origin: &'a Origin,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<TypedValue<'a>>,
+}
+
+struct TypedValue<'a> {
+    value: &'a str,
+    value_type: ValueType,
+    join: Option<&'a str>,
+}
+
+impl Serialize for TypedValue<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let Some(join) = self.join else {
+            return self.value_type.serialize(self.value, serializer);
+        };
+        let elements = (!self.value.is_empty())
+            .then(|| self.value.split(join))
+            .into_iter()
+            .flatten()
+            .map(|value| {
+                TypedValue {
+                    value,
+                    value_type: self.value_type,
+                    join: None,
+                }
+            });
+        serializer.collect_seq(elements)
+    }
+}
+
+/// Kept sorted so exports and child environments are identical between runs.
 #[derive(Default)]
 struct Environment {
-    values: HashMap<String, Zeroizing<String>>,
+    values: BTreeMap<String, Zeroizing<String>>,
+    origins: BTreeMap<String, Origin>,
+    leases: vault::Leases,
 }
 
 impl Environment {
-    fn load(profile: &manifest::ManifestProfile, removed_env_vars: &[String]) -> Result<Self> {
-        let mut environment = Self::default();
-        for source in &profile.env.from {
-            let value = Zeroizing::new(source.inner.resolve(removed_env_vars)?);
-            environment.extend_from(&value)?;
+    const GITLAB_DOTENV_LIMIT: usize = 5 * 1024;
+
+    fn load(
+        profile: &manifest::ManifestProfile,
+        pgp_manager: &pgp::PgpManager,
+        removed_env_vars: &[String],
+    ) -> Result<Self> {
+        let mut environment = Self {
+            leases: vault::Leases::new(removed_env_vars),
+            ..Self::default()
+        };
+        for (index, source) in profile.env.from.iter().enumerate() {
+            let origin = Origin::From {
+                index,
+                reference: source.inner.reference(),
+            };
+            let mut imported = std::collections::BTreeSet::new();
+            let mut name = |name: &str| {
+                imported.insert(name.to_string());
+                source.name(name)
+            };
+            match source.resolve(pgp_manager, removed_env_vars)? {
+                | manifest::SourcePayload::Document(document) => {
+                    environment.extend_from_renamed(&document, &origin, name)?
+                },
+                | manifest::SourcePayload::Entries(entries) => {
+                    for (imported, value) in entries {
+                        environment.insert(name(&imported), value.to_string(), origin.clone())?;
+                    }
+                },
+                | manifest::SourcePayload::Leased(entries, lease) => {
+                    environment.leases.push(lease);
+                    for (imported, value) in entries {
+                        environment.insert(name(&imported), value.to_string(), origin.clone())?;
+                    }
+                },
+            }
+            for renamed in source.rename.keys().filter(|renamed| !imported.contains(*renamed)) {
+                crate::output::warning!("env.from[{}] renames '{}', which it does not import", index, renamed);
+            }
         }
         Ok(environment)
     }
 
+    fn take_leases(&mut self) -> vault::Leases {
+        std::mem::take(&mut self.leases)
+    }
+
     fn remove_secret_sources(&mut self, names: &[String]) {
         #[cfg(windows)]
         self.values
@@ -395,37 +1574,162 @@ impl Environment {
         for name in names {
             self.values.remove(name);
         }
+        self.origins.retain(|name, _| self.values.contains_key(name));
     }
 
-    fn insert(&mut self, name: String, value: String) -> Result<()> {
+    fn resolve_vars(
+        &mut self,
+        vars: Vec<(&String, &manifest::ContentWrapper)>,
+        pgp_manager: &pgp::PgpManager,
+        secret_source_env_vars: &[String],
+    ) -> Result<Vec<(&'static str, std::time::Duration)>> {
+        let mut timings = Vec::with_capacity(vars.len());
+        let mut values = Vec::with_capacity(vars.len());
+        // Required variables go first, so their failures end the run early.
+        let (required, others): (Vec<_>, Vec<_>) = vars.into_iter().partition(|(_, value)| value.required);
+        for batch in [required, others] {
+            let mut failures = Vec::new();
+            for (key, value) in batch {
+                let cache_hits = pgp::PgpManager::thread_cache_hits();
+                let started = std::time::Instant::now();
+                let resolved = value.resolve(pgp_manager, secret_source_env_vars);
+                timings.push((value.inner.reference().backend, started.elapsed()));
+                match resolved {
+                    | Ok(resolved) => {
+                        let cached = pgp::PgpManager::thread_cache_hits() > cache_hits;
+                        values.push((key, value, resolved, cached));
+                    },
+                    | Err(error)
+                        if value.optional && failure::Failure::of(&error) != Some(failure::Failure::Decryption) => {},
+                    | Err(error) => failures.push((key, error)),
+                }
+            }
+            Self::unresolved(failures)?;
+        }
+
+        // Extensions apply last, so they extend the profile's own value.
+        let (extensions, values): (Vec<_>, Vec<_>) = values
+            .into_iter()
+            .partition(|(_, value, ..)| value.extension().is_some());
+        for (key, value, resolved, cached) in values.into_iter().chain(extensions) {
+            let origin = Origin::Manifest {
+                reference: value.inner.reference(),
+                value_type: value.value_type,
+                join: value.list_delimiter().map(str::to_string),
+                cached,
+            };
+            match value.extension() {
+                | Some(extension) => {
+                    let host_value = std::env::var(extension.target)
+                        .ok()
+                        .filter(|_| !secret_source_env_vars.iter().any(|name| name == extension.target));
+                    self.extend(key, &extension, resolved, host_value)
+                        .with_context(|| format!("Failed to extend '{}' with '{}'", extension.target, key))?;
+                },
+                | None => self.insert(key.clone(), resolved, origin)?,
+            }
+        }
+        Ok(timings)
+    }
+
+    fn unresolved(failures: Vec<(&String, anyhow::Error)>) -> Result<()> {
+        let mut failures = failures.into_iter();
+        let Some((key, first)) = failures.next() else {
+            return Ok(());
+        };
+        let rest: Vec<_> = failures.collect();
+        if rest.is_empty() {
+            return Err(first.context(format!("Failed to resolve environment variable '{}'", key)));
+        }
+        let mut message = format!("Failed to resolve {} environment variables:", rest.len() + 1);
+        for (key, error) in std::iter::once((key, &first)).chain(rest.iter().map(|(key, error)| (*key, error))) {
+            message.push_str(&format!("\n  {}: {:#}", key, error));
+        }
+        let error = anyhow::anyhow!(message);
+        Err(match failure::Failure::of(&first) {
+            | Some(category) => category.wrap(error),
+            | None => error,
+        })
+    }
+
+    fn insert(&mut self, name: String, value: String, origin: Origin) -> Result<()> {
         let value = Zeroizing::new(value);
         if !Self::is_valid_name(&name) {
             anyhow::bail!("Invalid environment variable name '{}'", name);
         }
+        self.origins.insert(name.clone(), origin);
         self.values.insert(name, value);
         Ok(())
     }
 
-    fn extend_from(&mut self, value: &str) -> Result<()> {
-        for (line_number, line) in value.lines().enumerate() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            let line = line.strip_prefix("export ").unwrap_or(line);
-            let (name, value) = line.split_once('=').with_context(|| {
-                format!(
-                    "Malformed line {} in env source (missing '='): '{}'",
-                    line_number + 1,
-                    line
-                )
-            })?;
-            self.insert(name.trim().to_string(), value.trim().to_string())
-                .with_context(|| format!("Invalid environment variable at line {}", line_number + 1))?;
+    fn extend(
+        &mut self,
+        entry: &str,
+        extension: &manifest::Extension<'_>,
+        value: String,
+        host_value: Option<String>,
+    ) -> Result<()> {
+        let value = Zeroizing::new(value);
+        let host_value = host_value.map(Zeroizing::new);
+        let current = self.values.get(extension.target).or(host_value.as_ref());
+        let extended = extension.apply(current.map(|current| current.as_str()), &value);
+        let mut entries = match self.origins.get(extension.target) {
+            | Some(Origin::Extended { entries }) => entries.clone(),
+            | _ => Vec::new(),
+        };
+        entries.push(entry.to_string());
+        self.insert(extension.target.to_string(), extended, Origin::Extended { entries })
+    }
+
+    fn extend_from(&mut self, value: &str, origin: &Origin) -> Result<()> {
+        self.extend_from_renamed(value, origin, str::to_string)
+    }
+
+    fn extend_from_renamed(
+        &mut self,
+        value: &str,
+        origin: &Origin,
+        mut rename: impl FnMut(&str) -> String,
+    ) -> Result<()> {
+        for entry in dotenv::parse(value).context("Malformed env source")? {
+            self.insert(rename(&entry.name), entry.value.to_string(), origin.clone())
+                .with_context(|| format!("Invalid environment variable at line {}", entry.line))?;
         }
         Ok(())
     }
 
+    fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.values.retain(|name, _| keep(name));
+        self.origins.retain(|name, _| self.values.contains_key(name));
+    }
+
+    fn report<'a>(&'a self, profile: &'a str, reveal: bool) -> EnvironmentReport<'a> {
+        let variables = self
+            .values
+            .iter()
+            .filter_map(|(name, value)| {
+                let origin = self.origins.get(name)?;
+                let (value_type, join) = match origin {
+                    | Origin::Manifest { value_type, join, .. } => (*value_type, join.as_deref()),
+                    | _ => (ValueType::String, None),
+                };
+                Some((name.as_str(), VariableReport {
+                    origin,
+                    value: reveal.then_some(TypedValue {
+                        value: value.as_str(),
+                        value_type,
+                        join,
+                    }),
+                }))
+            })
+            .collect();
+        EnvironmentReport { profile, variables }
+    }
+
+    fn into_values(self) -> BTreeMap<String, Zeroizing<String>> {
+        self.values
+    }
+
     fn iter(&self) -> impl Iterator<Item=(&String, &Zeroizing<String>)> {
         self.values.iter()
     }
@@ -436,6 +1740,94 @@ impl Environment {
             && characters.all(|character| character.is_ascii_alphanumeric() || character == '_')
     }
 
+    fn render(
+        &self,
+        format: OutputFormat,
+        profile: &str,
+        reveal: bool,
+        removed_env_vars: &[String],
+    ) -> Result<Zeroizing<String>> {
+        match format {
+            | OutputFormat::Shell => {
+                let mut exports = Zeroizing::new(String::new());
+                for (key, value) in self.iter() {
+                    exports.push_str(&format!(
+                        "export {}={}\n",
+                        Self::shell_escape(key),
+                        Self::shell_escape(value)
+                    ));
+                }
+                Ok(exports)
+            },
+            | OutputFormat::Json => {
+                let mut report = Zeroizing::new(
+                    serde_json::to_string_pretty(&self.report(profile, reveal))
+                        .context("Failed to serialize environment report")?,
+                );
+                report.push('\n');
+                Ok(report)
+            },
+            | OutputFormat::Dotenv => self.dotenv(),
+            | OutputFormat::GitlabDotenv => self.gitlab_dotenv(),
+            | OutputFormat::SystemdCreds => {
+                let mut dropin = Zeroizing::new("[Service]\n".to_string());
+                for (name, value) in self.iter() {
+                    dropin.push_str(&systemd::SystemdCredentials.encrypt(name, value, removed_env_vars)?);
+                }
+                Ok(dropin)
+            },
+        }
+    }
+
+    /// Names and values dotenv parsers read verbatim.
+    fn dotenv(&self) -> Result<Zeroizing<String>> {
+        let mut report = Zeroizing::new(String::new());
+        for (name, value) in &self.values {
+            if !name
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric() || character == '_')
+            {
+                anyhow::bail!("'{}' is not a valid dotenv variable name", name);
+            }
+            if value.contains(['\n', '\r', '\0']) {
+                anyhow::bail!(
+                    "Value of '{}' spans multiple lines, which dotenv files do not support",
+                    name
+                );
+            }
+            if value.trim() != value.as_str() {
+                anyhow::bail!(
+                    "Value of '{}' has leading or trailing whitespace, which dotenv parsers strip",
+                    name
+                );
+            }
+            let quoted = value.len() >= 2
+                && ['"', '\'']
+                    .iter()
+                    .any(|quote| value.starts_with(*quote) && value.ends_with(*quote));
+            if quoted {
+                anyhow::bail!("Value of '{}' is enclosed in quotes, which dotenv parsers strip", name);
+            }
+            report.push_str(name);
+            report.push('=');
+            report.push_str(value);
+            report.push('\n');
+        }
+        Ok(report)
+    }
+
+    fn gitlab_dotenv(&self) -> Result<Zeroizing<String>> {
+        let report = self.dotenv()?;
+        if report.len() > Self::GITLAB_DOTENV_LIMIT {
+            anyhow::bail!(
+                "GitLab dotenv report is {} bytes, more than the {} bytes GitLab accepts",
+                report.len(),
+                Self::GITLAB_DOTENV_LIMIT
+            );
+        }
+        Ok(report)
+    }
+
     fn shell_escape(value: &str) -> String {
         format!("'{}'", value.replace('\'', "'\\''"))
     }
@@ -445,15 +1837,16 @@ impl Environment {
 struct CommandExecutor<'a> {
     command: &'a args::ChildCommand,
     environment: &'a Environment,
-    keep_env_vars: &'a Option<Vec<String>>,
+    keep_env_vars: &'a Option<Vec<regex::Regex>>,
     sealed_secret_env_vars: &'a [String],
+    metadata: Vec<(&'static str, String)>,
 }
 
 impl<'a> CommandExecutor<'a> {
     fn new(
         command: &'a args::ChildCommand,
         environment: &'a Environment,
-        keep_env_vars: &'a Option<Vec<String>>,
+        keep_env_vars: &'a Option<Vec<regex::Regex>>,
         sealed_secret_env_vars: &'a [String],
     ) -> Self {
         Self {
@@ -461,18 +1854,93 @@ impl<'a> CommandExecutor<'a> {
             environment,
             keep_env_vars,
             sealed_secret_env_vars,
+            metadata: Vec::new(),
         }
     }
 
+    fn with_metadata(mut self, profile: &str, config: &std::path::Path) -> Self {
+        self.metadata = vec![
+            ("SECENV_ACTIVE", "1".to_string()),
+            ("SECENV_PROFILE", profile.to_string()),
+            ("SECENV_CONFIG", config.display().to_string()),
+        ];
+        self
+    }
+
+    fn with_server(mut self, address: std::net::SocketAddr, token_file: &std::path::Path) -> Self {
+        self.metadata.extend([
+            ("SECENV_SERVE_ADDR", address.to_string()),
+            ("SECENV_SERVE_TOKEN_FILE", token_file.display().to_string()),
+        ]);
+        self
+    }
+
     fn spawn(&self) -> Result<tokio::process::Child> {
         let mut command = tokio::process::Command::new(self.command.program());
         command.args(self.command.arguments());
 
         self.configure_env(&mut command)?;
+        self.check_limits()?;
         command.kill_on_drop(true);
-        command
-            .spawn()
-            .with_context(|| format!("Failed to execute command: {}", self.command.program()))
+        command.spawn().map_err(|error| {
+            let kind = error.kind();
+            #[cfg(unix)]
+            let error = match error.raw_os_error() {
+                | Some(libc::E2BIG) => {
+                    anyhow::Error::new(error).context(
+                        "The arguments and environment exceed the system limit; provide large values through the \
+                         profile's `files` instead",
+                    )
+                },
+                | _ => anyhow::Error::new(error),
+            };
+            #[cfg(not(unix))]
+            let error = anyhow::Error::new(error);
+            let error = error.context(format!("Failed to execute command: {}", self.command.program()));
+            match kind {
+                | std::io::ErrorKind::NotFound => failure::Failure::CommandNotFound.wrap(error),
+                | std::io::ErrorKind::PermissionDenied => failure::Failure::CommandNotExecutable.wrap(error),
+                | _ => error,
+            }
+        })
+    }
+
+    fn check_limits(&self) -> Result<()> {
+        let arguments: Vec<_> = std::iter::once(self.command.program())
+            .chain(self.command.arguments().iter().map(String::as_str))
+            .map(std::ffi::OsStr::new)
+            .collect();
+        // Mirrors `configure_env`, counting `NAME=value` sizes only.
+        let mut sizes = BTreeMap::new();
+        for (key, value) in std::env::vars_os() {
+            let kept = match self.keep_env_vars {
+                | Some(patterns) => {
+                    key.to_str()
+                        .is_some_and(|key| patterns.iter().any(|pattern| pattern.is_match(key)))
+                },
+                | None => true,
+            };
+            if kept {
+                sizes.insert(key.to_string_lossy().into_owned(), (key.len() + 1 + value.len(), false));
+            }
+        }
+        for variable in self.sealed_secret_env_vars {
+            sizes.remove(variable);
+        }
+        for (key, value) in self.environment.iter() {
+            sizes.insert(key.clone(), (key.len() + 1 + value.len(), true));
+        }
+        for (key, value) in &self.metadata {
+            sizes.insert(key.to_string(), (key.len() + 1 + value.len(), false));
+        }
+        let sizes: Vec<_> = sizes
+            .into_iter()
+            .map(|(name, (size, managed))| (name, size, managed))
+            .collect();
+        for warning in process::ExecLimits::current().check(&arguments, &sizes)? {
+            crate::output::warning!("{}", warning);
+        }
+        Ok(())
     }
 
     async fn execute(
@@ -530,18 +1998,8 @@ impl<'a> CommandExecutor<'a> {
         if let Some(patterns) = self.keep_env_vars {
             command.env_clear();
 
-            let compiled_patterns: Result<Vec<regex::Regex>, _> = patterns
-                .iter()
-                .map(|pattern| {
-                    regex::Regex::new(pattern)
-                        .with_context(|| format!("Invalid regex pattern in env.keep: '{}'", pattern))
-                })
-                .collect();
-
-            let compiled_patterns = compiled_patterns?;
-
             for (env_key, env_value) in std::env::vars_os() {
-                for pattern in &compiled_patterns {
+                for pattern in patterns {
                     if env_key.to_str().is_some_and(|key| pattern.is_match(key)) {
                         command.env(&env_key, &env_value);
                         break;
@@ -556,6 +2014,9 @@ impl<'a> CommandExecutor<'a> {
         for (key, value) in self.environment.iter() {
             command.env(key, value.as_str());
         }
+        for (key, value) in &self.metadata {
+            command.env(key, value);
+        }
         Ok(())
     }
 }
@@ -568,9 +2029,272 @@ mod tests {
     fn environment_rejects_invalid_names() {
         let mut environment = Environment::default();
         assert!(environment
-            .insert("VALID_NAME".to_string(), "value".to_string())
+            .insert("VALID_NAME".to_string(), "value".to_string(), Origin::Var)
             .is_ok());
-        assert!(environment.insert("1INVALID".to_string(), "value".to_string()).is_err());
+        assert!(environment
+            .insert("1INVALID".to_string(), "value".to_string(), Origin::Var)
+            .is_err());
+    }
+
+    #[test]
+    fn environment_iterates_in_name_order() -> Result<()> {
+        let mut environment = Environment::default();
+        environment.extend_from("ZETA=1\nALPHA=2\nMIDDLE=3\n", &Origin::Var)?;
+        environment.insert("BETA".to_string(), "4".to_string(), Origin::Var)?;
+        let names: Vec<_> = environment.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["ALPHA", "BETA", "MIDDLE", "ZETA"]);
+        Ok(())
+    }
+
+    #[test]
+    fn environment_renames_imported_variables() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let source = directory.path().join("source.env");
+        std::fs::write(&source, "db-host=db\nlog_level=debug\nOLD_TOKEN=secret\n")?;
+        let manifest = manifest::Manifest::parse(
+            &format!(
+                r#"version = "0.0.0"
+profiles.default.env.from = [
+  {{ file = "{source}", case = upper, prefix = "APP_", rename {{ "db-host" = DATABASE_HOST, OLD_TOKEN = TOKEN }} }}
+  {{ file = "{source}", case = lower, rename {{ "db-host" = db_host }} }}
+]"#,
+                source = source.display()
+            ),
+            manifest::ManifestFormat::Hocon,
+        )?;
+        let environment = Environment::load(&manifest.profiles["default"], &pgp::PgpManager::default(), &[])?;
+        let values: BTreeMap<_, _> = environment
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            BTreeMap::from([
+                ("APP_LOG_LEVEL", "debug"),
+                ("DATABASE_HOST", "db"),
+                ("TOKEN", "secret"),
+                ("db_host", "db"),
+                ("log_level", "debug"),
+                ("old_token", "secret"),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn environment_renders_gitlab_dotenv_reports() -> Result<()> {
+        let mut environment = Environment::default();
+        environment.insert("TOKEN".to_string(), "a=b c".to_string(), Origin::Var)?;
+        environment.insert("EMPTY".to_string(), String::new(), Origin::Var)?;
+        assert_eq!(environment.gitlab_dotenv()?.as_str(), "EMPTY=\nTOKEN=a=b c\n");
+
+        for value in ["line\nbreak", " padded", "\"quoted\"", "'quoted'"] {
+            let mut environment = Environment::default();
+            environment.insert("VALUE".to_string(), value.to_string(), Origin::Var)?;
+            let error = environment.gitlab_dotenv().unwrap_err().to_string();
+            assert!(!error.contains(value.trim()), "{}", error);
+        }
+
+        let mut environment = Environment::default();
+        environment.insert(
+            "LARGE".to_string(),
+            "x".repeat(Environment::GITLAB_DOTENV_LIMIT),
+            Origin::Var,
+        )?;
+        assert!(environment.gitlab_dotenv().is_err());
+        assert_eq!(
+            environment.render(OutputFormat::Dotenv, "default", false, &[])?.len(),
+            "LARGE=\n".len() + Environment::GITLAB_DOTENV_LIMIT
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn environment_report_records_origins_without_values() -> Result<()> {
+        let mut environment = Environment::default();
+        let from = Origin::From {
+            index: 0,
+            reference: SourceReference {
+                backend: "gcs",
+                reference: Some("shared-env@3".to_string()),
+            },
+        };
+        environment.extend_from("SHARED=from-source\nREPLACED=from-source\n", &from)?;
+        environment.insert("REPLACED".to_string(), "override".to_string(), Origin::Var)?;
+        environment.insert("TOKEN".to_string(), "secret".to_string(), Origin::Manifest {
+            reference: SourceReference {
+                backend: "pgp",
+                reference: Some("gpg:0123".to_string()),
+            },
+            value_type: ValueType::String,
+            join: None,
+            cached: true,
+        })?;
+        environment.insert("PORT".to_string(), "8080".to_string(), Origin::Manifest {
+            reference: SourceReference {
+                backend: "plain",
+                reference: None,
+            },
+            value_type: ValueType::Int,
+            join: None,
+            cached: false,
+        })?;
+        environment.insert(
+            "HOSTS".to_string(),
+            "a.example;b.example".to_string(),
+            Origin::Manifest {
+                reference: SourceReference {
+                    backend: "list",
+                    reference: None,
+                },
+                value_type: ValueType::String,
+                join: Some(";".to_string()),
+                cached: false,
+            },
+        )?;
+        environment.retain(|name| name != "SHARED");
+
+        let report = serde_json::to_value(environment.report("dev", false))?;
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "profile": "dev",
+                "variables": {
+                    "HOSTS": { "source": "manifest", "backend": "list", "join": ";", "cached": false },
+                    "PORT": { "source": "manifest", "backend": "plain", "type": "int", "cached": false },
+                    "REPLACED": { "source": "var" },
+                    "TOKEN": { "source": "manifest", "backend": "pgp", "reference": "gpg:0123", "cached": true },
+                },
+            })
+        );
+        let revealed = serde_json::to_value(environment.report("dev", true))?;
+        assert_eq!(revealed["variables"]["TOKEN"]["value"], "secret");
+        assert_eq!(revealed["variables"]["PORT"]["value"], 8080);
+        assert_eq!(
+            revealed["variables"]["HOSTS"]["value"],
+            serde_json::json!(["a.example", "b.example"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn environment_extends_profile_and_host_values() -> Result<()> {
+        let mut environment = Environment::default();
+        let prepend = manifest::Extension {
+            target: "PATH",
+            prepend: true,
+            separator: ":",
+        };
+        environment.extend(
+            "TOOLS",
+            &prepend,
+            "/opt/tools/bin".to_string(),
+            Some("/usr/bin".to_string()),
+        )?;
+        environment.extend(
+            "LOCAL",
+            &prepend,
+            "/home/me/bin".to_string(),
+            Some("/usr/bin".to_string()),
+        )?;
+        let append = manifest::Extension {
+            target: "PYTHONPATH",
+            prepend: false,
+            separator: ":",
+        };
+        environment.insert("PYTHONPATH".to_string(), "/app".to_string(), Origin::Var)?;
+        environment.extend("VENDOR", &append, "/vendor".to_string(), Some("/host".to_string()))?;
+        environment.extend("EXTRA", &append, "/extra".to_string(), None)?;
+
+        let values: BTreeMap<_, _> = environment
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(values["PATH"], "/home/me/bin:/opt/tools/bin:/usr/bin");
+        assert_eq!(values["PYTHONPATH"], "/app:/vendor:/extra");
+        assert_eq!(
+            serde_json::to_value(environment.report("dev", false))?["variables"]["PATH"],
+            serde_json::json!({ "source": "extended", "entries": ["TOOLS", "LOCAL"] })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn environment_reports_every_unresolved_variable() -> Result<()> {
+        use {
+            manifest::{
+                Content,
+                ContentWrapper,
+                EncodedValue,
+                EncodedValueWrapper,
+                Secret,
+                SecretAllocation,
+                SecretAllocationWrapper,
+                SecretWrapper,
+            },
+            sequoia_openpgp::{
+                cert::CertBuilder,
+                serialize::SerializeInto,
+            },
+        };
+
+        let directory = tempfile::tempdir()?;
+        let present = directory.path().join("present");
+        std::fs::write(&present, "value")?;
+        let file = |name: &str, required: bool, optional: bool| {
+            ContentWrapper {
+                required,
+                optional,
+                ..ContentWrapper::from(Content::File(directory.path().join(name).display().to_string()))
+            }
+        };
+        let pgp_manager = pgp::PgpManager::default();
+        let resolve = |vars: &BTreeMap<String, ContentWrapper>| {
+            let mut environment = Environment::default();
+            environment
+                .resolve_vars(vars.iter().collect(), &pgp_manager, &[])
+                .map(|_| environment.into_values())
+        };
+
+        let mut vars = BTreeMap::new();
+        vars.insert("PRESENT".to_string(), file("present", false, false));
+        vars.insert("SKIPPED".to_string(), file("skipped", false, true));
+        let values = resolve(&vars)?;
+        assert_eq!(values.keys().collect::<Vec<_>>(), ["PRESENT"]);
+
+        vars.insert("FIRST".to_string(), file("first", false, false));
+        vars.insert("SECOND".to_string(), file("second", false, false));
+        let message = format!("{:#}", resolve(&vars).unwrap_err());
+        assert!(message.starts_with("Failed to resolve 2 environment variables:\n  FIRST: "));
+        assert!(message.contains("\n  SECOND: "));
+        assert!(!message.contains("SKIPPED"));
+
+        vars.insert("REQUIRED".to_string(), file("required", true, false));
+        let message = format!("{:#}", resolve(&vars).unwrap_err());
+        assert!(message.starts_with("Failed to resolve environment variable 'REQUIRED'"));
+
+        // A value that fails to decrypt is not a missing one.
+        let (cert, _) = CertBuilder::general_purpose(Some("Keys <keys@example.com>")).generate()?;
+        let private_key = String::from_utf8(cert.as_tsk().armored().to_vec()?)?;
+        vars.clear();
+        vars.insert("TAMPERED".to_string(), ContentWrapper {
+            optional: true,
+            ..ContentWrapper::from(Content::Secure {
+                secret: SecretWrapper {
+                    inner: Secret::Pgp(SecretAllocationWrapper {
+                        inner: SecretAllocation::Literal(EncodedValue::Literal(private_key)),
+                    }),
+                },
+                value: EncodedValueWrapper {
+                    inner: EncodedValue::Literal(
+                        "-----BEGIN PGP MESSAGE-----\n\nAAAA\n-----END PGP MESSAGE-----".to_string(),
+                    ),
+                },
+            })
+        });
+        let error = resolve(&vars).unwrap_err();
+        assert_eq!(failure::Failure::of(&error), Some(failure::Failure::Decryption));
+        Ok(())
     }
 
     #[tokio::test]
@@ -620,13 +2344,117 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn command_executor_sets_secenv_metadata() -> Result<()> {
+        let command = args::ChildCommand::new("sh".to_string(), vec![])?;
+        let mut environment = Environment::default();
+        environment.insert("SECENV_PROFILE".to_string(), "spoofed".to_string(), Origin::Var)?;
+        let keep_env_vars = Some(vec![regex::Regex::new("^PATH$")?]);
+        let sealed_secret_env_vars = Vec::new();
+        let executor = CommandExecutor::new(&command, &environment, &keep_env_vars, &sealed_secret_env_vars)
+            .with_metadata("staging", std::path::Path::new("/srv/app/secenv.conf"));
+        let mut child = tokio::process::Command::new("sh");
+        child.args([
+            "-c",
+            "[ \"$SECENV_ACTIVE\" = 1 ] && [ \"$SECENV_PROFILE\" = staging ] && [ \"$SECENV_CONFIG\" = \
+             /srv/app/secenv.conf ]",
+        ]);
+
+        executor.configure_env(&mut child)?;
+        assert!(child.spawn()?.wait().await?.success());
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_files_concurrently_in_path_order() -> Result<()> {
+        use {
+            manifest::{
+                Content,
+                EncodedValue,
+                EncodedValueWrapper,
+                ManifestFile,
+                Secret,
+                SecretAllocation,
+                SecretAllocationWrapper,
+                SecretWrapper,
+            },
+            sequoia_openpgp::{
+                cert::CertBuilder,
+                serialize::SerializeInto,
+            },
+        };
+
+        let (cert, _) = CertBuilder::general_purpose(Some("Files <files@example.com>")).generate()?;
+        let private_key = String::from_utf8(cert.as_tsk().armored().to_vec()?)?;
+        let public_key = String::from_utf8(cert.armored().to_vec()?)?;
+        let pgp_manager = pgp::PgpManager::default();
+        let file = |inner| {
+            ManifestFile {
+                inner,
+                mode: FileMode::Write,
+                sha256: None,
+                backup: None,
+                permissions: None,
+                when: None,
+                persist: false,
+                template: false,
+            }
+        };
+        let mut profile = manifest::ManifestProfile::default();
+        for index in 0..3 * throttle::DEFAULT_MAX_CONCURRENCY {
+            let value = pgp_manager.encrypt_armored(&public_key, &format!("content-{}", index % 4))?;
+            profile.files.insert(
+                format!("./{:02}.txt", index),
+                file(Content::Secure {
+                    secret: SecretWrapper {
+                        inner: Secret::Pgp(SecretAllocationWrapper {
+                            inner: SecretAllocation::Literal(EncodedValue::Literal(private_key.clone())),
+                        }),
+                    },
+                    value: EncodedValueWrapper {
+                        inner: EncodedValue::Literal(value),
+                    },
+                }),
+            );
+        }
+        let directory = tempfile::tempdir()?;
+        let sealed_file_manager = sealed::SealedFileManager::new(directory.path().to_path_buf())?;
+
+        let files = resolve_generated_files(&profile, true, &sealed_file_manager, &pgp_manager, &[])?;
+        assert_eq!(files.len(), 3 * throttle::DEFAULT_MAX_CONCURRENCY);
+        for (index, (path, content, backup, _)) in files.iter().enumerate() {
+            assert_eq!(path, &format!("./{:02}.txt", index));
+            assert!(matches!(content, sealed::GeneratedFile::Write(content)
+                if content.as_str() == format!("content-{}", index % 4)));
+            assert_eq!(*backup, Some(BackupMode::Memory));
+        }
+        assert_eq!(pgp_manager.decryptions(), 3 * throttle::DEFAULT_MAX_CONCURRENCY);
+
+        profile.files.insert(
+            "./missing.txt".to_string(),
+            file(Content::File(directory.path().join("missing").display().to_string())),
+        );
+        let mut files: Vec<_> = profile.files.iter().collect();
+        files.sort_by_key(|(path, _)| *path);
+        assert_eq!(interleave_by_backend(&files)[..3], [0, 24, 1]);
+        let Err(error) = resolve_generated_files(&profile, false, &sealed_file_manager, &pgp_manager, &[]) else {
+            anyhow::bail!("Resolved a missing file");
+        };
+        assert!(error.to_string().contains("'./missing.txt'"));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn only_explicit_profile_values_can_reintroduce_secret_sources() -> Result<()> {
         let command = args::ChildCommand::new("sh".to_string(), vec![])?;
         let keep_env_vars = None;
         let secret_source_env_vars = vec!["SECENV_TEST_SEALED_SECRET".to_string()];
         let mut environment = Environment::default();
-        environment.insert("SECENV_TEST_SEALED_SECRET".to_string(), "from-env-source".to_string())?;
+        environment.insert(
+            "SECENV_TEST_SEALED_SECRET".to_string(),
+            "from-env-source".to_string(),
+            Origin::Var,
+        )?;
         environment.remove_secret_sources(&secret_source_env_vars);
 
         let executor = CommandExecutor::new(&command, &environment, &keep_env_vars, &secret_source_env_vars);
@@ -637,7 +2465,11 @@ mod tests {
         executor.configure_env(&mut child)?;
         assert!(child.spawn()?.wait().await?.success());
 
-        environment.insert("SECENV_TEST_SEALED_SECRET".to_string(), "explicit".to_string())?;
+        environment.insert(
+            "SECENV_TEST_SEALED_SECRET".to_string(),
+            "explicit".to_string(),
+            Origin::Var,
+        )?;
         let executor = CommandExecutor::new(&command, &environment, &keep_env_vars, &secret_source_env_vars);
         let mut child = tokio::process::Command::new("sh");
         child.args(["-c", "[ \"$SECENV_TEST_SEALED_SECRET\" = explicit ]"]);
@@ -0,0 +1,403 @@
+use {
+    crate::{
+        failure::Failure,
+        http::{
+            percent_encode,
+            HttpClient,
+            HttpMethod,
+        },
+        prompt::PromptMode,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    base64::Engine,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    sha2::{
+        Digest,
+        Sha256,
+    },
+    std::{
+        collections::BTreeMap,
+        path::PathBuf,
+        sync::Mutex,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+    zeroize::Zeroizing,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OidcFlow {
+    #[default]
+    ClientCredentials,
+    DeviceCode,
+}
+
+impl OidcFlow {
+    pub(crate) fn is_client_credentials(&self) -> bool {
+        *self == Self::ClientCredentials
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OidcTokenKind {
+    #[default]
+    Access,
+    Id,
+}
+
+impl OidcTokenKind {
+    pub(crate) fn is_access(&self) -> bool {
+        *self == Self::Access
+    }
+}
+
+/// Tokens of this run by client, so variables sharing a client sign in once.
+static TOKENS: Mutex<BTreeMap<String, Zeroizing<String>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Deserialize)]
+struct Discovery {
+    token_endpoint: String,
+    device_authorization_endpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeviceAuthorization {
+    device_code: Zeroizing<String>,
+    user_code: String,
+    /// Some providers, such as Google, name it `verification_url`.
+    #[serde(alias = "verification_url")]
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Zeroizing<String>,
+    id_token: Option<Zeroizing<String>>,
+    refresh_token: Option<Zeroizing<String>>,
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedRefreshToken {
+    refresh_token: Zeroizing<String>,
+}
+
+pub(crate) struct OidcClient<'a> {
+    pub(crate) issuer: &'a str,
+    pub(crate) client_id: &'a str,
+    pub(crate) scope: Option<&'a str>,
+    pub(crate) audience: Option<&'a str>,
+}
+
+impl OidcClient<'_> {
+    /// Refresh tokens are cached on disk per issuer, client, and scope.
+    pub(crate) fn token(
+        &self,
+        flow: OidcFlow,
+        kind: OidcTokenKind,
+        client_secret: Option<&str>,
+        prompt: &PromptMode,
+    ) -> Result<String> {
+        if self.client_id.is_empty() {
+            anyhow::bail!("OIDC client_id must not be empty");
+        }
+        if flow == OidcFlow::ClientCredentials && client_secret.is_none() {
+            anyhow::bail!("The OIDC client-credentials flow needs a client_secret");
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.oidc_token(self.issuer, self.client_id);
+        }
+        let mut tokens = TOKENS.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let key = format!("{}\n{:?}\n{:?}", self.cache_key(), flow, kind);
+        if let Some(token) = tokens.get(&key) {
+            return Ok(token.to_string());
+        }
+
+        let discovery = HttpClient.send(
+            &format!("{}/.well-known/openid-configuration", self.issuer.trim_end_matches('/')),
+            HttpMethod::Get,
+            &[],
+            None,
+        )?;
+        let discovery: Discovery = serde_json::from_str(&discovery)
+            .with_context(|| format!("Invalid OpenID configuration of '{}'", self.issuer))?;
+        let response = match flow {
+            | OidcFlow::ClientCredentials => {
+                let mut form = vec![("grant_type", "client_credentials")];
+                form.extend(self.scope.map(|scope| ("scope", scope)));
+                form.extend(self.audience.map(|audience| ("audience", audience)));
+                self.request(&discovery.token_endpoint, &form, client_secret)?
+                    .map_err(|error| Self::failure(&error))?
+            },
+            | OidcFlow::DeviceCode => {
+                match self.refresh(&discovery.token_endpoint, client_secret)? {
+                    | Some(response) => response,
+                    | None => self.device_code(&discovery, client_secret, prompt)?,
+                }
+            },
+        };
+        if let Some(refresh_token) = &response.refresh_token {
+            self.store_refresh_token(refresh_token)?;
+        }
+        let token = match kind {
+            | OidcTokenKind::Access => response.access_token,
+            | OidcTokenKind::Id => {
+                response
+                    .id_token
+                    .context("The token response has no ID token; add the 'openid' scope")?
+            },
+        };
+        tokens.insert(key, token.clone());
+        Ok(token.to_string())
+    }
+
+    fn refresh(&self, token_endpoint: &str, client_secret: Option<&str>) -> Result<Option<TokenResponse>> {
+        let path = self.cache_path()?;
+        let cached = match std::fs::read(&path) {
+            | Ok(cached) => Zeroizing::new(cached),
+            | Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            | Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read OIDC token cache '{}'", path.display()));
+            },
+        };
+        let Ok(cached) = serde_json::from_slice::<CachedRefreshToken>(&cached) else {
+            return Ok(None);
+        };
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", cached.refresh_token.as_str()),
+        ];
+        form.extend(self.scope.map(|scope| ("scope", scope)));
+        match self.request(token_endpoint, &form, client_secret)? {
+            | Ok(response) => Ok(Some(response)),
+            | Err(_) => {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove OIDC token cache '{}'", path.display()))?;
+                Ok(None)
+            },
+        }
+    }
+
+    fn device_code(
+        &self,
+        discovery: &Discovery,
+        client_secret: Option<&str>,
+        prompt: &PromptMode,
+    ) -> Result<TokenResponse> {
+        let endpoint = discovery
+            .device_authorization_endpoint
+            .as_deref()
+            .with_context(|| format!("OIDC issuer '{}' does not support the device-code flow", self.issuer))?;
+        if *prompt.resolve() == PromptMode::Never {
+            anyhow::bail!(
+                "Signing in to '{}' with a device code needs a terminal, but prompting is disabled (no terminal, CI, \
+                 or --prompt never)",
+                self.issuer
+            );
+        }
+        let mut form = Vec::new();
+        form.extend(self.scope.map(|scope| ("scope", scope)));
+        form.extend(self.audience.map(|audience| ("audience", audience)));
+        let authorization = HttpClient
+            .exchange(endpoint, &self.headers(client_secret), &self.form(&form, client_secret))?
+            .map_err(|error| {
+                serde_json::from_str::<TokenError>(&error).map_or_else(
+                    |_| Failure::Provider.error(format!("Device authorization at '{}' failed", endpoint)),
+                    |error| Self::failure(&error),
+                )
+            })?;
+        let authorization: DeviceAuthorization =
+            serde_json::from_str(&authorization).context("Invalid device authorization response")?;
+
+        match &authorization.verification_uri_complete {
+            | Some(uri) => eprintln!("To sign in to {}, open {}", self.issuer, uri),
+            | None => {
+                eprintln!(
+                    "To sign in to {}, open {} and enter the code {}",
+                    self.issuer, authorization.verification_uri, authorization.user_code
+                )
+            },
+        }
+        let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+        let mut interval = authorization.interval.unwrap_or(5).max(1);
+        let form = [
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("device_code", authorization.device_code.as_str()),
+        ];
+        loop {
+            std::thread::sleep(Duration::from_secs(interval));
+            if Instant::now() >= deadline {
+                anyhow::bail!("The device code of '{}' expired before signing in", self.issuer);
+            }
+            match self.request(&discovery.token_endpoint, &form, client_secret)? {
+                | Ok(response) => return Ok(response),
+                | Err(error) if error.error == "authorization_pending" => {},
+                | Err(error) if error.error == "slow_down" => interval += 5,
+                | Err(error) => return Err(Self::failure(&error)),
+            }
+        }
+    }
+
+    fn request(
+        &self,
+        token_endpoint: &str,
+        form: &[(&str, &str)],
+        client_secret: Option<&str>,
+    ) -> Result<std::result::Result<TokenResponse, TokenError>> {
+        let response = HttpClient.exchange(
+            token_endpoint,
+            &self.headers(client_secret),
+            &self.form(form, client_secret),
+        )?;
+        match response {
+            | Ok(body) => {
+                serde_json::from_str(&body)
+                    .map(Ok)
+                    .context("Invalid OIDC token response")
+            },
+            | Err(body) => {
+                serde_json::from_str(&body)
+                    .map(Err)
+                    .map_err(|_| Failure::Provider.error(format!("Token request to '{}' failed", token_endpoint)))
+            },
+        }
+    }
+
+    /// Confidential clients use HTTP Basic, which every provider must support.
+    fn headers(&self, client_secret: Option<&str>) -> Vec<(String, Zeroizing<String>)> {
+        let Some(client_secret) = client_secret else {
+            return Vec::new();
+        };
+        let credentials = Zeroizing::new(format!(
+            "{}:{}",
+            percent_encode(self.client_id),
+            percent_encode(client_secret)
+        ));
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials.as_bytes());
+        vec![(
+            "Authorization".to_string(),
+            Zeroizing::new(format!("Basic {}", encoded)),
+        )]
+    }
+
+    fn form(&self, form: &[(&str, &str)], client_secret: Option<&str>) -> Zeroizing<String> {
+        let client = client_secret.is_none().then_some(("client_id", self.client_id));
+        let pairs: Vec<String> = form
+            .iter()
+            .copied()
+            .chain(client)
+            .map(|(name, value)| format!("{}={}", name, percent_encode(value)))
+            .collect();
+        Zeroizing::new(pairs.join("&"))
+    }
+
+    fn failure(error: &TokenError) -> anyhow::Error {
+        let description = error
+            .error_description
+            .as_deref()
+            .map(|description| format!(": {}", description))
+            .unwrap_or_default();
+        Failure::Provider.error(format!(
+            "OIDC token request failed with '{}'{}",
+            error.error, description
+        ))
+    }
+
+    fn cache_key(&self) -> String {
+        format!(
+            "{}\n{}\n{}",
+            self.issuer,
+            self.client_id,
+            self.scope.unwrap_or_default()
+        )
+    }
+
+    fn cache_path(&self) -> Result<PathBuf> {
+        let digest: String = Sha256::digest(self.cache_key())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Ok(crate::ledger::state_directory()?
+            .join("oidc")
+            .join(format!("{}.json", digest)))
+    }
+
+    fn store_refresh_token(&self, refresh_token: &Zeroizing<String>) -> Result<()> {
+        let path = self.cache_path()?;
+        let directory = path.parent().context("OIDC token cache has no directory")?;
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder
+            .create(directory)
+            .with_context(|| format!("Failed to create OIDC token cache '{}'", directory.display()))?;
+
+        let cached = Zeroizing::new(
+            serde_json::to_vec(&CachedRefreshToken {
+                refresh_token: refresh_token.clone(),
+            })
+            .context("Failed to serialize OIDC token cache")?,
+        );
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&path)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, &cached))
+            .with_context(|| format!("Failed to write OIDC token cache '{}'", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn authenticates_confidential_clients_with_basic() {
+        let client = OidcClient {
+            issuer: "https://login.example.com",
+            client_id: "ci runner",
+            scope: Some("api:read"),
+            audience: None,
+        };
+        let form = [("grant_type", "client_credentials"), ("scope", "api:read")];
+        assert_eq!(
+            client.form(&form, Some("s3cret")).as_str(),
+            "grant_type=client_credentials&scope=api%3Aread"
+        );
+        assert_eq!(
+            client.form(&form, None).as_str(),
+            "grant_type=client_credentials&scope=api%3Aread&client_id=ci%20runner"
+        );
+        assert!(client.headers(None).is_empty());
+        assert_eq!(
+            client.headers(Some("s3cret"))[0].1.as_str(),
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode("ci%20runner:s3cret")
+            )
+        );
+        let other_scope = OidcClient { scope: None, ..client };
+        assert_ne!(client.cache_key(), other_scope.cache_key());
+    }
+}
@@ -0,0 +1,89 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::Zeroize,
+};
+
+pub(crate) struct OnePassword;
+
+impl OnePassword {
+    fn validate(reference: &str) -> Result<()> {
+        let path = reference
+            .strip_prefix("op://")
+            .with_context(|| format!("Invalid 1Password secret reference '{}': expected op://", reference))?;
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        let segments: Vec<_> = path.split('/').collect();
+        if !(3..=4).contains(&segments.len()) || segments.iter().any(|segment| segment.is_empty()) {
+            anyhow::bail!(
+                "Invalid 1Password secret reference '{}': expected op://<vault>/<item>/[<section>/]<field>",
+                reference
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read(&self, reference: &str, removed_env_vars: &[String]) -> Result<String> {
+        Self::validate(reference)?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.op_secret(reference);
+        }
+        let mut cmd = Command::new("op");
+        cmd.args(["read", "--no-newline", "--"]).arg(reference);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = crate::throttle::Provider::OnePassword
+            .request(|| {
+                cmd.stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .output()
+            })
+            .context("Failed to execute op CLI to read secret")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("op CLI failed: {}", stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        String::from_utf8(std::mem::take(&mut output.stdout)).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("1Password secret is not valid UTF-8")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_secret_references() {
+        for valid in [
+            "op://dev/github/token",
+            "op://dev/database/admin/password",
+            "op://dev/github/one-time password?attribute=otp",
+        ] {
+            assert!(OnePassword::validate(valid).is_ok(), "{}", valid);
+        }
+        for invalid in [
+            "dev/github/token",
+            "op://dev/github",
+            "op://dev//token",
+            "op://dev/a/b/c/d",
+        ] {
+            assert!(OnePassword::validate(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
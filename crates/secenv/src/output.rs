@@ -0,0 +1,181 @@
+use std::{
+    fmt::Display,
+    io::IsTerminal,
+    sync::OnceLock,
+};
+
+static PLAIN: OnceLock<bool> = OnceLock::new();
+
+pub(crate) fn set_plain(plain: bool) {
+    let _ = PLAIN.set(plain);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    /// A terminal, without `--plain`.
+    pub(crate) fn is_rich(self) -> bool {
+        if PLAIN.get().copied().unwrap_or(false) {
+            return false;
+        }
+        match self {
+            | Self::Stdout => std::io::stdout().is_terminal(),
+            | Self::Stderr => std::io::stderr().is_terminal(),
+        }
+    }
+
+    pub(crate) fn has_colors(self) -> bool {
+        self.is_rich() && Self::colors_allowed(std::env::var_os("NO_COLOR"), std::env::var_os("TERM"))
+    }
+
+    fn colors_allowed(no_color: Option<std::ffi::OsString>, term: Option<std::ffi::OsString>) -> bool {
+        no_color.is_none_or(|no_color| no_color.is_empty()) && term.is_none_or(|term| term != "dumb")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Style {
+    Bold,
+    Success,
+    Warning,
+    Failure,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            | Self::Bold => "1",
+            | Self::Success => "32",
+            | Self::Warning => "1;33",
+            | Self::Failure => "1;31",
+        }
+    }
+
+    pub(crate) fn paint(self, stream: Stream, text: impl Display) -> String {
+        self.paint_if(stream.has_colors(), text)
+    }
+
+    fn paint_if(self, colors: bool, text: impl Display) -> String {
+        if colors {
+            format!("\x1b[{}m{}\x1b[0m", self.code(), text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+pub(crate) fn print_warning(message: std::fmt::Arguments<'_>) {
+    eprintln!("{} {}", Style::Warning.paint(Stream::Stderr, "WARNING:"), message);
+}
+
+/// Print a `WARNING:` line to stderr, highlighted on a terminal.
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::output::print_warning(format_args!($($arg)*))
+    };
+}
+pub(crate) use warning;
+
+/// Aligned columns for a person, tab-separated values for scripts.
+pub(crate) struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<(String, Option<Style>)>>,
+}
+
+impl Table {
+    pub(crate) fn new(header: impl IntoIterator<Item=&'static str>) -> Self {
+        Self {
+            header: header.into_iter().collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, row: impl IntoIterator<Item=(String, Option<Style>)>) {
+        self.rows.push(row.into_iter().collect());
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub(crate) fn render(&self, stream: Stream) -> String {
+        self.render_as(stream.is_rich(), stream.has_colors())
+    }
+
+    fn render_as(&self, rich: bool, colors: bool) -> String {
+        let mut rendered = String::new();
+        if !rich {
+            for row in &self.rows {
+                let cells: Vec<_> = row.iter().map(|(text, _)| text.as_str()).collect();
+                rendered.push_str(&cells.join("\t"));
+                rendered.push('\n');
+            }
+            return rendered;
+        }
+
+        let mut widths: Vec<_> = self.header.iter().map(|title| title.chars().count()).collect();
+        for row in &self.rows {
+            for (index, (text, _)) in row.iter().enumerate() {
+                if let Some(width) = widths.get_mut(index) {
+                    *width = (*width).max(text.chars().count());
+                }
+            }
+        }
+        let mut line = |cells: Vec<(&str, Option<Style>)>| {
+            let last = cells.len().saturating_sub(1);
+            for (index, (text, style)) in cells.into_iter().enumerate() {
+                let painted = match style {
+                    | Some(style) => style.paint_if(colors, text),
+                    | None => text.to_string(),
+                };
+                rendered.push_str(&painted);
+                if index < last {
+                    let padding = widths.get(index).copied().unwrap_or(0) - text.chars().count();
+                    rendered.push_str(&" ".repeat(padding + 2));
+                }
+            }
+            rendered.push('\n');
+        };
+        line(self.header.iter().map(|title| (*title, Some(Style::Bold))).collect());
+        for row in &self.rows {
+            line(row.iter().map(|(text, style)| (text.as_str(), *style)).collect());
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_align_for_people_and_tabulate_for_scripts() {
+        let mut table = Table::new(["PATH", "STATE"]);
+        table.push([
+            ("./credentials.json".to_string(), None),
+            ("drifted".to_string(), Some(Style::Failure)),
+        ]);
+        table.push([("./a".to_string(), None), ("in sync".to_string(), Some(Style::Success))]);
+
+        assert_eq!(
+            table.render_as(false, false),
+            "./credentials.json\tdrifted\n./a\tin sync\n"
+        );
+        assert_eq!(
+            table.render_as(true, false),
+            "PATH                STATE\n./credentials.json  drifted\n./a                 in sync\n"
+        );
+        assert!(table
+            .render_as(true, true)
+            .contains("./credentials.json  \x1b[1;31mdrifted\x1b[0m"));
+
+        assert!(Stream::colors_allowed(None, Some("xterm-256color".into())));
+        assert!(Stream::colors_allowed(Some("".into()), None));
+        assert!(!Stream::colors_allowed(Some("1".into()), None));
+        assert!(!Stream::colors_allowed(None, Some("dumb".into())));
+    }
+}
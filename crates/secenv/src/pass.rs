@@ -0,0 +1,108 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::Zeroize,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PassTool {
+    #[default]
+    Pass,
+    Gopass,
+}
+
+impl PassTool {
+    pub(crate) fn is_pass(&self) -> bool {
+        *self == Self::Pass
+    }
+
+    pub(crate) fn program(self) -> &'static str {
+        match self {
+            | Self::Pass => "pass",
+            | Self::Gopass => "gopass",
+        }
+    }
+}
+
+pub(crate) struct PasswordStore;
+
+impl PasswordStore {
+    fn validate(entry: &str) -> Result<()> {
+        if entry.is_empty()
+            || entry.starts_with('-')
+            || entry.starts_with('/')
+            || entry.split('/').any(|segment| segment.is_empty() || segment == "..")
+        {
+            anyhow::bail!("Invalid password-store entry: '{}'", entry);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn show(&self, entry: &str, tool: PassTool, removed_env_vars: &[String]) -> Result<String> {
+        Self::validate(entry)?;
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.pass_entry(entry);
+        }
+        let name = tool.program();
+        let mut cmd = Command::new(name);
+        cmd.arg("show");
+        if tool == PassTool::Gopass {
+            // Print the entry as stored rather than as parsed key-values.
+            cmd.arg("--noparsing");
+        }
+        cmd.arg("--").arg(entry);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let mut output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .with_context(|| format!("Failed to execute {} to show entry", name))
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("{} failed: {}", name, stderr));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let mut value = String::from_utf8(std::mem::take(&mut output.stdout)).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("Password-store entry is not valid UTF-8")
+        })?;
+        if value.ends_with('\n') {
+            value.pop();
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_entries() {
+        for valid in ["github", "work/db/password", "work/.hidden"] {
+            assert!(PasswordStore::validate(valid).is_ok(), "{}", valid);
+        }
+        for invalid in ["", "--help", "/etc/passwd", "work//db", "work/../db", "work/"] {
+            assert!(PasswordStore::validate(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use {
+    crate::failure::Failure,
     anyhow::Result,
     argon2::{
         Algorithm,
@@ -74,10 +75,10 @@ impl PasswordCipher {
         Self::validate_passphrase(passphrase)?;
         let minimum_len = 1 + SALT_LEN + NONCE_LEN + TAG_LEN;
         if payload.len() < minimum_len {
-            anyhow::bail!("Argon2id/XChaCha20-Poly1305 payload is truncated");
+            return Err(Failure::Decryption.error("Argon2id/XChaCha20-Poly1305 payload is truncated"));
         }
         if payload[0] != FORMAT_VERSION {
-            anyhow::bail!("Unsupported Argon2id/XChaCha20-Poly1305 payload version");
+            return Err(Failure::Decryption.error("Unsupported Argon2id/XChaCha20-Poly1305 payload version"));
         }
 
         let salt_start = 1;
@@ -94,7 +95,7 @@ impl PasswordCipher {
                 msg: ciphertext,
                 aad: AAD,
             })
-            .map_err(|_| anyhow::anyhow!("Failed to decrypt Argon2id/XChaCha20-Poly1305 value"));
+            .map_err(|_| Failure::Decryption.error("Failed to decrypt Argon2id/XChaCha20-Poly1305 value"));
         let plaintext = plaintext?;
 
         match String::from_utf8(plaintext) {
@@ -1,10 +1,27 @@
 use {
+    crate::{
+        failure::{
+            Categorize,
+            Failure,
+        },
+        prompt::PromptMode,
+    },
     anyhow::{
         Context,
         Result,
     },
     openpgp::{
+        crypto::{
+            Decryptor,
+            KeyPair,
+            S2K,
+        },
         packet::{
+            key::{
+                self,
+                SecretKeyMaterial,
+            },
+            Key,
             PKESK,
             SKESK,
         },
@@ -12,6 +29,8 @@ use {
             stream::{
                 DecryptionHelper,
                 DecryptorBuilder,
+                GoodChecksum,
+                MessageLayer,
                 MessageStructure,
                 VerificationHelper,
             },
@@ -32,12 +51,25 @@ use {
     sequoia_openpgp::{
         self as openpgp,
     },
+    sha2::{
+        Digest,
+        Sha256,
+    },
     std::{
+        cell::Cell,
         collections::HashMap,
         io::{
             Read,
             Write,
         },
+        sync::{
+            atomic::{
+                AtomicUsize,
+                Ordering,
+            },
+            Mutex,
+            MutexGuard,
+        },
     },
     zeroize::{
         Zeroize,
@@ -45,56 +77,210 @@ use {
     },
 };
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SignatureStatus {
+    Verified(String),
+    Unverified,
+    Unsigned,
+}
+
+/// Secret keys of one certificate, unlocked once per run.
 #[derive(Clone)]
-struct KeyCredentials {
+struct UnlockedKeys {
     cert: openpgp::Cert,
-    password: Option<Zeroizing<String>>,
+    keypairs: Vec<KeyPair>,
 }
 
+/// Decrypted payloads by key fingerprint and payload digest.
+type Plaintexts = HashMap<(String, [u8; 32]), Zeroizing<String>>;
+
+thread_local! {
+    /// So concurrent decryptions on other threads do not count as hits of this one.
+    static THREAD_CACHE_HITS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Decrypts PGP values with keys unlocked once per run, shared across threads.
 #[derive(Default)]
 pub(crate) struct PgpManager {
-    cache: HashMap<String, KeyCredentials>,
+    prompt: PromptMode,
+    cache: Mutex<HashMap<String, UnlockedKeys>>,
+    plaintexts: Mutex<Plaintexts>,
+    cache_hits: AtomicUsize,
+    decryptions: AtomicUsize,
+    password_prompts: AtomicUsize,
+    pins: Mutex<HashMap<String, Zeroizing<String>>>,
+    /// Empty when signatures are not checked.
+    signers: Vec<openpgp::Cert>,
+    strict: bool,
 }
 
 impl PgpManager {
+    pub(crate) fn new(prompt: PromptMode) -> Self {
+        Self {
+            prompt,
+            cache: Mutex::new(HashMap::new()),
+            plaintexts: Mutex::new(HashMap::new()),
+            cache_hits: AtomicUsize::new(0),
+            decryptions: AtomicUsize::new(0),
+            password_prompts: AtomicUsize::new(0),
+            pins: Mutex::new(HashMap::new()),
+            signers: Vec::new(),
+            strict: false,
+        }
+    }
+
+    pub(crate) fn trust_signers<'a>(
+        &mut self,
+        signers: impl IntoIterator<Item=(&'a str, &'a str)>,
+        strict: bool,
+    ) -> Result<()> {
+        for (fingerprint, key) in signers {
+            let cert = openpgp::Cert::from_bytes(key.as_bytes())
+                .with_context(|| format!("Failed to parse the key of signer {}", fingerprint))?;
+            if !cert.fingerprint().to_hex().eq_ignore_ascii_case(fingerprint) {
+                anyhow::bail!(
+                    "The key configured for signer {} has fingerprint {}",
+                    fingerprint,
+                    cert.fingerprint().to_hex()
+                );
+            }
+            self.signers.push(cert.strip_secret_key_material());
+        }
+        if strict && self.signers.is_empty() {
+            anyhow::bail!("--strict requires trusted signers in the manifest's 'signers'");
+        }
+        self.strict = strict;
+        Ok(())
+    }
+
+    pub(crate) fn is_trusted_signer(&self, fingerprint: &str) -> bool {
+        self.signers
+            .iter()
+            .any(|cert| cert.fingerprint().to_hex().eq_ignore_ascii_case(fingerprint))
+    }
+
+    pub(crate) fn enforce_signature(&self, status: &SignatureStatus) -> Result<()> {
+        let problem = match status {
+            | _ if self.signers.is_empty() => return Ok(()),
+            | SignatureStatus::Verified(_) => return Ok(()),
+            | SignatureStatus::Unverified => "is not signed by a trusted signer",
+            | SignatureStatus::Unsigned => "is not signed",
+        };
+        if self.strict {
+            anyhow::bail!("The decrypted value {} (--strict)", problem);
+        }
+        crate::output::warning!("A decrypted value {}", problem);
+        Ok(())
+    }
+
     fn policy() -> Box<dyn Policy+Send+Sync> {
         Box::new(StandardPolicy::new())
     }
 
-    fn key_credentials(&self, private_key_asc: &str) -> Result<(String, KeyCredentials)> {
-        let cert = openpgp::Cert::from_bytes(private_key_asc.as_bytes()).context("Failed to parse PGP private key")?;
-        let fingerprint = cert.fingerprint().to_hex();
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// A key whose secret is held by gpg-agent, e.g. on a smartcard.
+    fn is_stub<P: key::KeyParts, R: key::KeyRole>(key: &Key<P, R>) -> bool {
+        key.optional_secret().is_some_and(|secret| {
+            match secret {
+                | SecretKeyMaterial::Encrypted(encrypted) => {
+                    // GnuPG's private S2K extension.
+                    matches!(encrypted.s2k(), S2K::Private { tag: 101, .. })
+                },
+                | SecretKeyMaterial::Unencrypted(_) => false,
+            }
+        })
+    }
+
+    pub(crate) fn card_pin(&self, fingerprint: &str) -> Result<Zeroizing<String>> {
+        self.pin(fingerprint, || {
+            format!("PGP key {} (smartcard PIN or passphrase)", &fingerprint[..16])
+        })
+    }
 
-        if let Some(cached_key) = self.cache.get(&fingerprint) {
-            return Ok((fingerprint, cached_key.clone()));
+    pub(crate) fn token_pin(&self, token: &str) -> Result<Zeroizing<String>> {
+        self.pin(token, || format!("PKCS#11 token {} (user PIN)", token))
+    }
+
+    fn pin(&self, id: &str, description: impl FnOnce() -> String) -> Result<Zeroizing<String>> {
+        let mut pins = Self::lock(&self.pins);
+        if let Some(pin) = pins.get(id) {
+            return Ok(pin.clone());
         }
+        self.password_prompts.fetch_add(1, Ordering::Relaxed);
+        let pin = self.prompt.password(&description())?;
+        pins.insert(id.to_string(), pin.clone());
+        Ok(pin)
+    }
 
-        let policy = Self::policy();
-        let unlocked_cert = cert.clone();
-        let mut needs_password = false;
+    pub(crate) fn forget_card_pin(&self, fingerprint: &str) {
+        Self::lock(&self.pins).remove(fingerprint);
+    }
 
-        for key in cert.keys().secret().with_policy(&*policy, None) {
-            if key.key().secret().is_encrypted() {
-                needs_password = true;
-                break;
-            }
+    /// The cache stays locked while a password is asked for.
+    fn unlocked_keys(&self, cert: openpgp::Cert, fingerprint: &str) -> Result<UnlockedKeys> {
+        let mut cache = Self::lock(&self.cache);
+        if let Some(cached_keys) = cache.get(fingerprint) {
+            return Ok(cached_keys.clone());
+        }
+
+        let policy = Self::policy();
+        let secret_keys: Vec<_> = cert
+            .keys()
+            .secret()
+            .with_policy(&*policy, None)
+            .alive()
+            .revoked(false)
+            .map(|key| key.key().clone())
+            .collect();
+        if !secret_keys.is_empty() && secret_keys.iter().all(Self::is_stub) {
+            anyhow::bail!(
+                "The secret keys of PGP key {} are on a smartcard or held by gpg-agent and cannot be exported; \
+                 decrypt with secret.pgp.gpg.fingerprint = \"{}\" instead",
+                &fingerprint[..16],
+                fingerprint.to_ascii_uppercase()
+            );
         }
+        let secret_keys: Vec<_> = secret_keys.into_iter().filter(|key| !Self::is_stub(key)).collect();
 
-        let password = if needs_password {
-            let pwd = rpassword::prompt_password(format!("Enter password for PGP key {}: ", &fingerprint[..16]))
-                .context("Failed to read password")?;
-            Some(Zeroizing::new(pwd))
+        let password = if secret_keys.iter().any(|key| key.secret().is_encrypted()) {
+            self.password_prompts.fetch_add(1, Ordering::Relaxed);
+            Some(self.prompt.password(&format!("PGP key {}", &fingerprint[..16]))?)
         } else {
             None
         };
 
-        Ok((fingerprint, KeyCredentials {
-            cert: unlocked_cert,
-            password,
-        }))
+        let mut keypairs = Vec::with_capacity(secret_keys.len());
+        for key in secret_keys {
+            let keypair = if key.secret().is_encrypted() {
+                let Some(password) = &password else {
+                    continue;
+                };
+                key.decrypt_secret(&openpgp::crypto::Password::from(password.as_str()))
+                    .and_then(|key| key.into_keypair())
+            } else {
+                key.into_keypair()
+            };
+            if let Ok(keypair) = keypair {
+                keypairs.push(keypair);
+            }
+        }
+        if keypairs.is_empty() {
+            anyhow::bail!("Failed to unlock any secret key of PGP key {}", &fingerprint[..16]);
+        }
+
+        let unlocked_keys = UnlockedKeys { cert, keypairs };
+        cache.insert(fingerprint.to_string(), unlocked_keys.clone());
+        Ok(unlocked_keys)
     }
 
-    pub(crate) fn decrypt(&mut self, private_key_asc: &str, encrypted_data: &str) -> Result<String> {
+    fn payload_digest(encrypted_data: &[u8]) -> [u8; 32] {
+        Sha256::digest(encrypted_data).into()
+    }
+
+    pub(crate) fn decrypt(&self, private_key_asc: &str, encrypted_data: &str) -> Result<String> {
         self.decrypt_bytes(private_key_asc, encrypted_data.as_bytes())
     }
 
@@ -137,13 +323,75 @@ impl PgpManager {
         Ok(ciphertext)
     }
 
-    pub(crate) fn decrypt_bytes(&mut self, private_key_asc: &str, encrypted_data: &[u8]) -> Result<String> {
-        let (fingerprint, credentials) = self.key_credentials(private_key_asc)?;
+    pub(crate) fn encrypt_armored(&self, certificate: &str, plaintext: &str) -> Result<String> {
+        let ciphertext = self.encrypt(certificate, plaintext)?;
+        let mut armored = Vec::new();
+        let mut writer = openpgp::armor::Writer::new(&mut armored, openpgp::armor::Kind::Message)
+            .context("Failed to initialize PGP armor writer")?;
+        writer.write_all(&ciphertext).context("Failed to armor PGP message")?;
+        writer.finalize().context("Failed to armor PGP message")?;
+        String::from_utf8(armored).context("Armored PGP message is not valid UTF-8")
+    }
+
+    pub(crate) fn decrypt_bytes(&self, private_key_asc: &str, encrypted_data: &[u8]) -> Result<String> {
+        self.decrypt_payload(private_key_asc, encrypted_data)
+            .categorize(Failure::Decryption)
+    }
+
+    fn decrypt_payload(&self, private_key_asc: &str, encrypted_data: &[u8]) -> Result<String> {
+        let cert = openpgp::Cert::from_bytes(private_key_asc.as_bytes()).context("Failed to parse PGP private key")?;
+        let fingerprint = cert.fingerprint().to_hex();
+        let payload_key = (fingerprint.clone(), Self::payload_digest(encrypted_data));
+        if let Some(plaintext) = self.cached_plaintext(&payload_key) {
+            return Ok(plaintext);
+        }
+
+        let unlocked_keys = self.unlocked_keys(cert, &fingerprint)?;
+        self.decrypt_message(
+            payload_key,
+            Some(unlocked_keys.cert),
+            unlocked_keys.keypairs,
+            encrypted_data,
+        )
+    }
+
+    /// Cached by the decryptor's `id`, which is only created on a miss.
+    pub(crate) fn decrypt_with<D: Decryptor>(
+        &self,
+        id: &str,
+        decryptor: impl FnOnce() -> Result<D>,
+        encrypted_data: &[u8],
+    ) -> Result<String> {
+        let payload_key = (id.to_string(), Self::payload_digest(encrypted_data));
+        if let Some(plaintext) = self.cached_plaintext(&payload_key) {
+            return Ok(plaintext);
+        }
+        self.decrypt_message(payload_key, None, vec![decryptor()?], encrypted_data)
+            .categorize(Failure::Decryption)
+    }
+
+    fn cached_plaintext(&self, payload_key: &(String, [u8; 32])) -> Option<String> {
+        self.decryptions.fetch_add(1, Ordering::Relaxed);
+        let plaintexts = Self::lock(&self.plaintexts);
+        let plaintext = plaintexts.get(payload_key)?;
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        THREAD_CACHE_HITS.with(|hits| hits.set(hits.get() + 1));
+        Some(plaintext.to_string())
+    }
 
+    fn decrypt_message<D: Decryptor>(
+        &self,
+        payload_key: (String, [u8; 32]),
+        cert: Option<openpgp::Cert>,
+        decryptors: Vec<D>,
+        encrypted_data: &[u8],
+    ) -> Result<String> {
         let policy = Self::policy();
-        let helper = KeyCredentialsHelper {
-            cert: credentials.cert.clone(),
-            password: credentials.password.clone(),
+        let helper = CachedKeyHelper {
+            cert,
+            decryptors,
+            signers: self.signers.clone(),
+            signature: SignatureStatus::Unsigned,
         };
 
         let mut decryptor = DecryptorBuilder::from_bytes(encrypted_data)
@@ -157,6 +405,11 @@ impl PgpManager {
             return Err(error).context("Failed reading decrypted plaintext");
         }
 
+        if let Err(error) = self.enforce_signature(&decryptor.helper_ref().signature) {
+            plaintext.zeroize();
+            return Err(error);
+        }
+
         let plaintext = match String::from_utf8(plaintext) {
             | Ok(decrypted_data) => decrypted_data,
             | Err(error) => {
@@ -165,13 +418,35 @@ impl PgpManager {
                 anyhow::bail!("Decrypted data is not valid UTF-8");
             },
         };
-        self.cache.insert(fingerprint, credentials);
+        Self::lock(&self.plaintexts).insert(payload_key, Zeroizing::new(plaintext.clone()));
         Ok(plaintext)
     }
 
-    /// Clear the PGP key cache, zeroizing cached passwords
-    pub(crate) fn clear_cache(&mut self) {
-        self.cache.clear();
+    pub(crate) fn cache_hits(&self) -> usize {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn thread_cache_hits() -> usize {
+        THREAD_CACHE_HITS.with(Cell::get)
+    }
+
+    pub(crate) fn decryptions(&self) -> usize {
+        self.decryptions.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn prompt(&self) -> &PromptMode {
+        &self.prompt
+    }
+
+    pub(crate) fn password_prompts(&self) -> usize {
+        self.password_prompts.load(Ordering::Relaxed)
+    }
+
+    /// Clear the key, PIN, and payload caches, zeroizing their contents
+    pub(crate) fn clear_cache(&self) {
+        Self::lock(&self.cache).clear();
+        Self::lock(&self.pins).clear();
+        Self::lock(&self.plaintexts).clear();
     }
 }
 
@@ -181,35 +456,51 @@ impl Drop for PgpManager {
     }
 }
 
-struct KeyCredentialsHelper {
-    cert: openpgp::Cert,
-    password: Option<Zeroizing<String>>,
+struct CachedKeyHelper<D> {
+    cert: Option<openpgp::Cert>,
+    decryptors: Vec<D>,
+    signers: Vec<openpgp::Cert>,
+    signature: SignatureStatus,
 }
 
-impl VerificationHelper for KeyCredentialsHelper {
+impl<D> VerificationHelper for CachedKeyHelper<D> {
     fn get_certs(&mut self, _ids: &[KeyHandle]) -> openpgp::Result<Vec<openpgp::Cert>> {
-        Ok(Vec::new())
+        Ok(self.signers.clone())
     }
 
     fn check(&mut self, structure: MessageStructure) -> openpgp::Result<()> {
-        // Note: secenv provides confidentiality (encryption) but does not verify
-        // message signatures. If signature verification is needed, provide signing
-        // certificates via get_certs() and validate the MessageStructure here.
-        //
-        // We still check that the message was successfully decrypted by verifying
-        // the structure contains at least one encryption layer.
+        // Whether signatures are required is decided by the manager; here
+        // the message must be encrypted, and the signature status is
+        // recorded. Only signer certificates are offered, so any good
+        // checksum is made by a trusted signer.
+        let mut encrypted = false;
         for layer in structure {
             match layer {
-                | openpgp::parse::stream::MessageLayer::Encryption { .. } => return Ok(()),
-                | openpgp::parse::stream::MessageLayer::Compression { .. } => continue,
-                | _ => continue,
+                | MessageLayer::Encryption { .. } => encrypted = true,
+                | MessageLayer::SignatureGroup { results } => {
+                    for result in results {
+                        match result {
+                            | Ok(GoodChecksum { ka, .. }) => {
+                                self.signature = SignatureStatus::Verified(ka.cert().fingerprint().to_hex());
+                            },
+                            | Err(_) if self.signature == SignatureStatus::Unsigned => {
+                                self.signature = SignatureStatus::Unverified;
+                            },
+                            | Err(_) => {},
+                        }
+                    }
+                },
+                | MessageLayer::Compression { .. } => {},
             }
         }
-        Err(anyhow::anyhow!("Message was not encrypted"))
+        if !encrypted {
+            return Err(anyhow::anyhow!("Message was not encrypted"));
+        }
+        Ok(())
     }
 }
 
-impl DecryptionHelper for KeyCredentialsHelper {
+impl<D: Decryptor> DecryptionHelper for CachedKeyHelper<D> {
     fn decrypt(
         &mut self,
         pkesks: &[PKESK],
@@ -217,41 +508,11 @@ impl DecryptionHelper for KeyCredentialsHelper {
         sym_algo: Option<SymmetricAlgorithm>,
         decrypt: &mut dyn for<'a> FnMut(Option<SymmetricAlgorithm>, &'a openpgp::crypto::SessionKey) -> bool,
     ) -> openpgp::Result<Option<openpgp::Cert>> {
-        let policy = PgpManager::policy();
-        for secret in self
-            .cert
-            .keys()
-            .secret()
-            .with_policy(&*policy, None)
-            .alive()
-            .revoked(false)
-        {
-            let keypair_result = if secret.key().secret().is_encrypted() {
-                if let Some(ref password) = self.password {
-                    let result = secret.key().clone().parts_into_secret().and_then(|secret_key| {
-                        let decrypted_key =
-                            secret_key.decrypt_secret(&openpgp::crypto::Password::from(password.as_str()))?;
-                        decrypted_key.into_keypair()
-                    });
-
-                    result
-                } else {
-                    continue;
-                }
-            } else {
-                secret
-                    .key()
-                    .clone()
-                    .parts_into_secret()
-                    .and_then(|secret_key| secret_key.into_keypair())
-            };
-
-            if let Ok(mut keypair) = keypair_result {
-                for pkesk in pkesks {
-                    if let Some((algo, session_key)) = pkesk.decrypt(&mut keypair, sym_algo) {
-                        if decrypt(algo, &session_key) {
-                            return Ok(Some(self.cert.clone()));
-                        }
+        for decryptor in &mut self.decryptors {
+            for pkesk in pkesks {
+                if let Some((algo, session_key)) = pkesk.decrypt(decryptor, sym_algo) {
+                    if decrypt(algo, &session_key) {
+                        return Ok(self.cert.clone());
                     }
                 }
             }
@@ -279,10 +540,133 @@ mod tests {
             .generate()?;
         let public_cert = String::from_utf8(cert.armored().to_vec()?)?;
         let private_key = String::from_utf8(cert.as_tsk().armored().to_vec()?)?;
-        let mut manager = PgpManager::default();
+        let manager = PgpManager::default();
 
         let ciphertext = manager.encrypt(&public_cert, "sealed value")?;
         assert_eq!(manager.decrypt_bytes(&private_key, &ciphertext)?, "sealed value");
         Ok(())
     }
+
+    #[test]
+    fn reuses_unlocked_keys_and_decrypted_payloads() -> Result<()> {
+        let (cert, _) = CertBuilder::new()
+            .add_userid("secenv cache test")
+            .add_storage_encryption_subkey()
+            .generate()?;
+        let public_cert = String::from_utf8(cert.armored().to_vec()?)?;
+        let private_key = String::from_utf8(cert.as_tsk().armored().to_vec()?)?;
+        let manager = PgpManager::default();
+
+        let first = manager.encrypt(&public_cert, "first value")?;
+        let second = manager.encrypt(&public_cert, "second value")?;
+        assert_eq!(manager.decrypt_bytes(&private_key, &first)?, "first value");
+        assert_eq!(manager.decrypt_bytes(&private_key, &second)?, "second value");
+        assert_eq!(manager.decrypt_bytes(&private_key, &first)?, "first value");
+        assert_eq!(PgpManager::lock(&manager.cache).len(), 1);
+        assert_eq!(PgpManager::lock(&manager.plaintexts).len(), 2);
+        assert_eq!(manager.cache_hits(), 1);
+        assert_eq!((manager.decryptions(), manager.password_prompts()), (3, 0));
+
+        manager.clear_cache();
+        assert!(PgpManager::lock(&manager.cache).is_empty() && PgpManager::lock(&manager.plaintexts).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn decrypts_with_keys_that_stay_in_their_decryptor() -> Result<()> {
+        let (cert, _) = CertBuilder::new().add_storage_encryption_subkey().generate()?;
+        let public_cert = String::from_utf8(cert.armored().to_vec()?)?;
+        let keypair = || -> Result<KeyPair> {
+            let policy = PgpManager::policy();
+            let key = cert
+                .keys()
+                .with_policy(&*policy, None)
+                .secret()
+                .for_storage_encryption()
+                .next();
+            key.context("no encryption key")?.key().clone().into_keypair()
+        };
+        let manager = PgpManager::default();
+
+        let ciphertext = manager.encrypt(&public_cert, "token value")?;
+        assert_eq!(
+            manager.decrypt_with("token#0/key", keypair, &ciphertext)?,
+            "token value"
+        );
+        let unused = || -> Result<KeyPair> { anyhow::bail!("the plaintext is cached") };
+        assert_eq!(manager.decrypt_with("token#0/key", unused, &ciphertext)?, "token value");
+        assert!(manager.decrypt_with("token#1/key", unused, &ciphertext).is_err());
+        assert_eq!(manager.cache_hits(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn refers_smartcard_keys_to_gpg_agent() -> Result<()> {
+        let (cert, _) = CertBuilder::new().add_storage_encryption_subkey().generate()?;
+        let public_cert = String::from_utf8(cert.armored().to_vec()?)?;
+        let stubs = cert.as_tsk().set_filter(|_| false).emit_secret_key_stubs(true);
+        let stubs = String::from_utf8(stubs.armored().to_vec()?)?;
+        let manager = PgpManager::default();
+
+        let ciphertext = manager.encrypt(&public_cert, "value")?;
+        let error = format!("{:#}", manager.decrypt_bytes(&stubs, &ciphertext).unwrap_err());
+        assert!(error.contains("secret.pgp.gpg.fingerprint"), "{}", error);
+        assert_eq!(manager.password_prompts(), 0);
+        Ok(())
+    }
+
+    fn sign_and_encrypt(recipient: &openpgp::Cert, signer: &openpgp::Cert, plaintext: &str) -> Result<Vec<u8>> {
+        use openpgp::serialize::stream::Signer;
+
+        let policy = PgpManager::policy();
+        let recipients = recipient.keys().with_policy(&*policy, None).for_storage_encryption();
+        let keypair = signer
+            .keys()
+            .unencrypted_secret()
+            .with_policy(&*policy, None)
+            .for_signing()
+            .next()
+            .context("Signer has no signing key")?
+            .key()
+            .clone()
+            .into_keypair()?;
+        let mut ciphertext = Vec::new();
+        let message = Encryptor::for_recipients(Message::new(&mut ciphertext), recipients).build()?;
+        let message = Signer::new(message, keypair)?.build()?;
+        let mut message = LiteralWriter::new(message).build()?;
+        message.write_all(plaintext.as_bytes())?;
+        message.finalize()?;
+        Ok(ciphertext)
+    }
+
+    #[test]
+    fn verifies_signatures_of_trusted_signers() -> Result<()> {
+        let (recipient, _) = CertBuilder::new().add_storage_encryption_subkey().generate()?;
+        let (signer, _) = CertBuilder::new().add_signing_subkey().generate()?;
+        let (stranger, _) = CertBuilder::new().add_signing_subkey().generate()?;
+        let private_key = String::from_utf8(recipient.as_tsk().armored().to_vec()?)?;
+        let public_cert = String::from_utf8(recipient.armored().to_vec()?)?;
+        let signer_key = String::from_utf8(signer.armored().to_vec()?)?;
+        let fingerprint = signer.fingerprint().to_hex();
+
+        let signed = sign_and_encrypt(&recipient, &signer, "signed value")?;
+        let forged = sign_and_encrypt(&recipient, &stranger, "forged value")?;
+        let unsigned = PgpManager::default().encrypt(&public_cert, "unsigned value")?;
+
+        let mut lenient = PgpManager::default();
+        assert!(lenient
+            .trust_signers([(fingerprint.as_str(), private_key.as_str())], false)
+            .is_err());
+        lenient.trust_signers([(fingerprint.as_str(), signer_key.as_str())], false)?;
+        assert_eq!(lenient.decrypt_bytes(&private_key, &forged)?, "forged value");
+        assert_eq!(lenient.decrypt_bytes(&private_key, &unsigned)?, "unsigned value");
+
+        let mut strict = PgpManager::default();
+        assert!(strict.trust_signers([], true).is_err());
+        strict.trust_signers([(fingerprint.as_str(), signer_key.as_str())], true)?;
+        assert_eq!(strict.decrypt_bytes(&private_key, &signed)?, "signed value");
+        assert!(strict.decrypt_bytes(&private_key, &forged).is_err());
+        assert!(strict.decrypt_bytes(&private_key, &unsigned).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,224 @@
+use {
+    crate::{
+        failure::{
+            Categorize,
+            Failure,
+        },
+        pgp::PgpManager,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    sequoia_openpgp::{
+        self as openpgp,
+        crypto::{
+            mpi,
+            Decryptor,
+            SessionKey,
+        },
+        packet::{
+            key::{
+                Key4,
+                PublicParts,
+                UnspecifiedRole,
+            },
+            Key,
+        },
+    },
+    std::{
+        process::{
+            Command,
+            Stdio,
+        },
+        sync::Mutex,
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+/// Passes the PIN to pkcs11-tool outside its command line.
+const PIN_VARIABLE: &str = "SECENV_PKCS11_PIN";
+
+/// An RSA key on a PKCS#11 token, which decrypts PGP session keys itself.
+pub(crate) struct Pkcs11Key<'a> {
+    module: &'a str,
+    slot: u64,
+    label: &'a str,
+    removed_env_vars: &'a [String],
+    /// Why the token failed, which the PGP decryptor does not report.
+    failure: Mutex<Option<anyhow::Error>>,
+}
+
+impl<'a> Pkcs11Key<'a> {
+    pub(crate) fn new(module: &'a str, slot: u64, label: &'a str, removed_env_vars: &'a [String]) -> Result<Self> {
+        if module.is_empty() || module.starts_with('-') {
+            anyhow::bail!("Invalid PKCS#11 module: '{}'", module);
+        }
+        if label.is_empty() || label.starts_with('-') {
+            anyhow::bail!("Invalid PKCS#11 key label: '{}'", label);
+        }
+        Ok(Self {
+            module,
+            slot,
+            label,
+            removed_env_vars,
+            failure: Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn id(&self) -> String {
+        format!("{}#{}/{}", self.module, self.slot, self.label)
+    }
+
+    pub(crate) fn decrypt(&self, encrypted_data: &str, pgp_manager: &PgpManager) -> Result<String> {
+        let decryptor = || {
+            Ok(TokenDecryptor {
+                key: self,
+                public: self.public_key()?,
+                pgp_manager,
+            })
+        };
+        let result = pgp_manager.decrypt_with(&self.id(), decryptor, encrypted_data.as_bytes());
+        let failure = self
+            .failure
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        match (result, failure) {
+            | (Err(error), Some(failure)) => Err(failure.context(error.to_string())),
+            | (result, _) => result,
+        }
+    }
+
+    fn public_key(&self) -> Result<Key<PublicParts, UnspecifiedRole>> {
+        let der = self
+            .tool(&["--read-object", "--type", "pubkey"], None)
+            .with_context(|| format!("Failed to read the public key of PKCS#11 key {}", self.id()))?;
+        let rsa = openssl::pkey::PKey::public_key_from_der(&der)
+            .and_then(|key| key.rsa())
+            .or_else(|_| openssl::rsa::Rsa::public_key_from_der_pkcs1(&der))
+            .with_context(|| {
+                format!(
+                    "PKCS#11 key {} is not an RSA key; only RSA keys are supported",
+                    self.id()
+                )
+            })?;
+        // Only the algorithm of the key is used, so its creation time does
+        // not matter.
+        let key = Key4::import_public_rsa(&rsa.e().to_vec(), &rsa.n().to_vec(), std::time::UNIX_EPOCH)
+            .context("Failed to import the public key of the PKCS#11 key")?;
+        Ok(key.into())
+    }
+
+    fn decrypt_block(&self, ciphertext: &[u8], pin: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let mut input = tempfile::NamedTempFile::new().context("Failed to create a temporary file")?;
+        std::io::Write::write_all(&mut input, ciphertext).context("Failed to write the encrypted session key")?;
+        let path = input.path().to_str().context("Temporary path is not valid UTF-8")?;
+        self.tool(
+            &[
+                "--login",
+                "--pin",
+                &format!("env:{}", PIN_VARIABLE),
+                "--decrypt",
+                "--mechanism",
+                "RSA-PKCS",
+                "--input-file",
+                path,
+            ],
+            Some(pin),
+        )
+    }
+
+    fn tool(&self, args: &[&str], pin: Option<&str>) -> Result<Zeroizing<Vec<u8>>> {
+        let mut cmd = Command::new("pkcs11-tool");
+        cmd.arg("--module")
+            .arg(self.module)
+            .arg("--slot")
+            .arg(self.slot.to_string())
+            .arg("--label")
+            .arg(self.label)
+            .args(args);
+        crate::process::remove_environment_variables(&mut cmd, self.removed_env_vars);
+        if let Some(pin) = pin {
+            cmd.env(PIN_VARIABLE, pin);
+        }
+
+        let mut output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute pkcs11-tool")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Decryption.error(format!("pkcs11-tool failed: {}", stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        Ok(Zeroizing::new(std::mem::take(&mut output.stdout)))
+    }
+}
+
+struct TokenDecryptor<'a> {
+    key: &'a Pkcs11Key<'a>,
+    public: Key<PublicParts, UnspecifiedRole>,
+    pgp_manager: &'a PgpManager,
+}
+
+impl Decryptor for TokenDecryptor<'_> {
+    fn public(&self) -> &Key<PublicParts, UnspecifiedRole> {
+        &self.public
+    }
+
+    fn decrypt(&mut self, ciphertext: &mpi::Ciphertext, _plaintext_len: Option<usize>) -> openpgp::Result<SessionKey> {
+        let mpi::Ciphertext::RSA { c } = ciphertext else {
+            return Err(anyhow::anyhow!("Not an RSA encrypted session key"));
+        };
+        let modulus = match self.public.mpis() {
+            | mpi::PublicKey::RSA { n, .. } => n.value().len(),
+            | _ => return Err(anyhow::anyhow!("Not an RSA key")),
+        };
+        // The token expects a block as long as the modulus.
+        let value = c.value();
+        let mut block = vec![0; modulus.saturating_sub(value.len())];
+        block.extend_from_slice(value);
+
+        let id = self.key.id();
+        let decrypted = self
+            .pgp_manager
+            .token_pin(&id)
+            .and_then(|pin| self.key.decrypt_block(&block, &pin));
+        match decrypted {
+            | Ok(plaintext) => Ok(plaintext.as_slice().into()),
+            | Err(error) => {
+                if format!("{:#}", error).contains("CKR_PIN_INCORRECT") {
+                    self.pgp_manager.forget_card_pin(&id);
+                }
+                let error = error.context(format!("PKCS#11 key {} failed to decrypt the session key", id));
+                *self.key.failure.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(error);
+                Err(anyhow::anyhow!("PKCS#11 decryption failed"))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert!(Pkcs11Key::new("--list-slots", 0, "pgp", &[]).is_err());
+        assert!(Pkcs11Key::new("/usr/lib/softhsm/libsofthsm2.so", 0, "", &[]).is_err());
+        let key = Pkcs11Key::new("/usr/lib/softhsm/libsofthsm2.so", 3, "pgp-decrypt", &[]);
+        assert_eq!(
+            key.map(|key| key.id()).ok().as_deref(),
+            Some("/usr/lib/softhsm/libsofthsm2.so#3/pgp-decrypt")
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use {
+    crate::manifest::glob,
+    anyhow::{
+        Context,
+        Result,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+};
+
+/// Checked before any secret of the profile is fetched.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct ProfilePolicy {
+    /// Matched case-insensitively.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hosts: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) users: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) require_tty: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Confirmation {
+    Flag(bool),
+    Phrase(String),
+}
+
+impl Confirmation {
+    fn question(&self, profile: &str) -> Option<String> {
+        match self {
+            | Self::Flag(false) => None,
+            | Self::Flag(true) => Some(format!("Unlock profile '{}'? [y/N] ", profile)),
+            | Self::Phrase(phrase) => Some(format!("Type '{}' to unlock profile '{}': ", phrase, profile)),
+        }
+    }
+
+    fn accepts(&self, answer: &str) -> bool {
+        match self {
+            | Self::Flag(required) => !required || matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+            | Self::Phrase(phrase) => answer.trim_end_matches(['\r', '\n']) == phrase,
+        }
+    }
+
+    pub(crate) fn confirm(&self, profile: &str, answer: Option<&str>) -> Result<()> {
+        use std::io::{
+            IsTerminal,
+            Write,
+        };
+
+        let Some(question) = self.question(profile) else {
+            return Ok(());
+        };
+        let answer = match answer {
+            | Some(answer) => answer.to_string(),
+            | None => {
+                if !std::io::stdin().is_terminal() {
+                    anyhow::bail!(
+                        "Profile '{}' requires confirmation, but standard input is not a terminal. Pass --confirm \
+                         with the answer to confirm non-interactively.",
+                        profile
+                    );
+                }
+                eprint!("{}", question);
+                std::io::stderr().flush().context("Failed to ask for confirmation")?;
+                let mut answer = String::new();
+                std::io::stdin()
+                    .read_line(&mut answer)
+                    .context("Failed to read the confirmation")?;
+                answer
+            },
+        };
+        if !self.accepts(&answer) {
+            anyhow::bail!("Unlocking profile '{}' was not confirmed", profile);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct PolicyContext {
+    hostname: String,
+    user: Option<String>,
+    interactive: bool,
+}
+
+impl PolicyContext {
+    pub(crate) fn current() -> Self {
+        use std::io::IsTerminal;
+        Self {
+            hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+            user: whoami::username().ok(),
+            interactive: std::io::stdin().is_terminal(),
+        }
+    }
+}
+
+impl ProfilePolicy {
+    pub(crate) fn validate(&self) -> Result<()> {
+        for pattern in self.hosts.iter().chain(&self.users).flatten() {
+            glob(pattern).with_context(|| format!("Invalid policy pattern '{}'", pattern))?;
+        }
+        Ok(())
+    }
+
+    fn matches(patterns: &[String], value: &str) -> Result<bool> {
+        for pattern in patterns {
+            if glob(pattern)?.is_match(value) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub(crate) fn enforce(&self, context: &PolicyContext) -> Result<()> {
+        if let Some(hosts) = &self.hosts {
+            let patterns: Vec<_> = hosts.iter().map(|host| host.to_lowercase()).collect();
+            if !Self::matches(&patterns, &context.hostname.to_lowercase())? {
+                anyhow::bail!(
+                    "Host '{}' is not allowed; policy.hosts permits: {}",
+                    context.hostname,
+                    hosts.join(", ")
+                );
+            }
+        }
+        if let Some(users) = &self.users {
+            let user = context
+                .user
+                .as_deref()
+                .context("Cannot determine the current user, which policy.users requires")?;
+            if !Self::matches(users, user)? {
+                anyhow::bail!(
+                    "User '{}' is not allowed; policy.users permits: {}",
+                    user,
+                    users.join(", ")
+                );
+            }
+        }
+        if self.require_tty && !context.interactive {
+            anyhow::bail!("policy.require_tty refuses non-interactive sessions (standard input is not a terminal)");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforces_hosts_users_and_tty() -> Result<()> {
+        let policy = ProfilePolicy {
+            hosts: Some(vec!["bastion-*.prod.example".to_string()]),
+            users: Some(vec!["deploy".to_string(), "ops-*".to_string()]),
+            require_tty: true,
+        };
+        let context = |hostname: &str, user: Option<&str>, interactive: bool| {
+            PolicyContext {
+                hostname: hostname.to_string(),
+                user: user.map(str::to_string),
+                interactive,
+            }
+        };
+
+        policy.enforce(&context("Bastion-01.prod.example", Some("ops-alice"), true))?;
+        for denied in [
+            context("laptop.local", Some("deploy"), true),
+            context("bastion-01.prod.example", Some("alice"), true),
+            context("bastion-01.prod.example", None, true),
+            context("bastion-01.prod.example", Some("deploy"), false),
+        ] {
+            assert!(policy.enforce(&denied).is_err());
+        }
+        ProfilePolicy::default().enforce(&context("anywhere", None, false))?;
+        Ok(())
+    }
+
+    #[test]
+    fn confirms_with_yes_or_the_exact_phrase() -> Result<()> {
+        let flag = Confirmation::Flag(true);
+        assert!(flag.accepts("Y\n") && flag.accepts("yes") && !flag.accepts("") && !flag.accepts("no"));
+        assert!(Confirmation::Flag(false).accepts(""));
+        let phrase = Confirmation::Phrase("prod".to_string());
+        assert!(phrase.accepts("prod\n") && !phrase.accepts("preprod") && !phrase.accepts("PROD"));
+
+        phrase.confirm("prod", Some("prod"))?;
+        assert!(phrase.confirm("prod", Some("yes")).is_err());
+        Confirmation::Flag(false).confirm("dev", None)?;
+        Ok(())
+    }
+}
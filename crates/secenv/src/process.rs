@@ -1,4 +1,11 @@
-use std::process::Command;
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::process::Command,
+    zeroize::Zeroizing,
+};
 
 pub(crate) fn remove_environment_variables(command: &mut Command, variables: &[String]) {
     for variable in variables {
@@ -6,6 +13,138 @@ pub(crate) fn remove_environment_variables(command: &mut Command, variables: &[S
     }
 }
 
+/// Upper-cased, with invalid characters replaced by `_`.
+pub(crate) fn environment_variable_name(name: &str) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() || name.starts_with(|character: char| character.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Nested values are rejected.
+pub(crate) fn json_variables(document: &str) -> Result<Vec<(String, Zeroizing<String>)>> {
+    let document = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(document)
+        .context("Document is not a JSON object")?;
+    // Move every value into a zeroizing buffer before anything can fail.
+    let mut variables = Vec::with_capacity(document.len());
+    let mut nested = None;
+    for (key, value) in document {
+        let value = match value {
+            | serde_json::Value::String(value) => value,
+            | serde_json::Value::Bool(_) | serde_json::Value::Number(_) => value.to_string(),
+            | serde_json::Value::Null => String::new(),
+            | mut value => {
+                crate::sealed::SealedDocument::zeroize_value(&mut value);
+                nested.get_or_insert(key.clone());
+                String::new()
+            },
+        };
+        variables.push((key, Zeroizing::new(value)));
+    }
+    if let Some(key) = nested {
+        anyhow::bail!("Key '{}' does not hold a scalar value", key);
+    }
+    Ok(variables)
+}
+
+pub(crate) const LARGE_VALUE_BYTES: usize = 32 * 1024;
+
+/// Exceeding these fails `execve` with `E2BIG`.
+pub(crate) struct ExecLimits {
+    /// `ARG_MAX`: all argument and `NAME=value` strings with their pointers.
+    total: Option<usize>,
+    /// Linux's `MAX_ARG_STRLEN`: a single argument or `NAME=value` string.
+    single: Option<usize>,
+}
+
+impl ExecLimits {
+    /// Kept free of `ARG_MAX`, as `xargs` does.
+    const HEADROOM: usize = 2048;
+
+    pub(crate) fn current() -> Self {
+        // SAFETY: `sysconf` only reads a configuration value.
+        #[cfg(unix)]
+        let total = usize::try_from(unsafe { libc::sysconf(libc::_SC_ARG_MAX) })
+            .ok()
+            .filter(|&limit| limit > 0);
+        #[cfg(not(unix))]
+        let total = None;
+        Self {
+            total,
+            single: cfg!(target_os = "linux").then_some(32 * 4096),
+        }
+    }
+
+    /// Returns warnings for large profile values.
+    pub(crate) fn check(
+        &self,
+        arguments: &[&std::ffi::OsStr],
+        environment: &[(String, usize, bool)],
+    ) -> Result<Vec<String>> {
+        let pointer = std::mem::size_of::<usize>();
+        let mut warnings = Vec::new();
+        for (name, size, managed) in environment {
+            if let Some(single) = self.single.filter(|&single| *size + 1 > single) {
+                anyhow::bail!(
+                    "Environment variable '{}' is {} bytes, over the limit of {} bytes for a single variable (E2BIG); \
+                     provide it through the profile's `files` instead",
+                    name,
+                    size,
+                    single
+                );
+            }
+            if *managed && *size > LARGE_VALUE_BYTES {
+                warnings.push(format!(
+                    "Environment variable '{}' is {} bytes; consider providing large values through the profile's \
+                     `files` instead",
+                    name, size
+                ));
+            }
+        }
+
+        let Some(limit) = self.total else {
+            return Ok(warnings);
+        };
+        let total: usize = arguments
+            .iter()
+            .map(|argument| argument.len() + 1 + pointer)
+            .sum::<usize>()
+            + environment.iter().map(|(_, size, _)| size + 1 + pointer).sum::<usize>()
+            + 2 * pointer;
+        if total + Self::HEADROOM > limit {
+            let mut largest: Vec<_> = environment.iter().collect();
+            largest.sort_by(|left, right| right.1.cmp(&left.1).then_with(|| left.0.cmp(&right.0)));
+            let largest: Vec<_> = largest
+                .iter()
+                .take(3)
+                .map(|(name, size, _)| format!("{} ({} bytes)", name, size))
+                .collect();
+            anyhow::bail!(
+                "The arguments and environment of '{}' take {} bytes, over the system limit of {} bytes (ARG_MAX, \
+                 E2BIG); the largest variables are {}. Provide large values through the profile's `files` instead",
+                arguments
+                    .first()
+                    .map(|program| program.to_string_lossy())
+                    .unwrap_or_default(),
+                total,
+                limit - Self::HEADROOM,
+                largest.join(", ")
+            );
+        }
+        Ok(warnings)
+    }
+}
+
 #[cfg(all(test, unix))]
 mod tests {
     use super::*;
@@ -21,4 +160,37 @@ mod tests {
         remove_environment_variables(&mut command, &variables);
         assert!(command.status().expect("failed to run test command").success());
     }
+
+    #[test]
+    fn checks_environment_sizes_against_exec_limits() -> Result<()> {
+        let limits = ExecLimits {
+            total: Some(4096 + ExecLimits::HEADROOM),
+            single: Some(3000),
+        };
+        let arguments = [std::ffi::OsStr::new("sh")];
+        let environment = [
+            ("HOME".to_string(), 10, false),
+            ("CERTIFICATE".to_string(), LARGE_VALUE_BYTES + 1, true),
+        ];
+        let unlimited = ExecLimits {
+            total: None,
+            single: None,
+        };
+        let warnings = unlimited.check(&arguments, &environment)?;
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("'CERTIFICATE'"));
+
+        let error = limits.check(&arguments, &environment).unwrap_err().to_string();
+        assert!(error.contains("'CERTIFICATE'") && error.contains("single variable"));
+
+        let environment = [
+            ("FIRST".to_string(), 2000, true),
+            ("SECOND".to_string(), 2500, true),
+            ("HOME".to_string(), 10, false),
+        ];
+        let error = limits.check(&arguments, &environment).unwrap_err().to_string();
+        assert!(error.contains("SECOND (2500 bytes), FIRST (2000 bytes), HOME (10 bytes)"));
+        assert!(limits.check(&arguments, &environment[1..])?.is_empty());
+        Ok(())
+    }
 }
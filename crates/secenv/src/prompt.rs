@@ -0,0 +1,280 @@
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::{
+        collections::BTreeMap,
+        io::{
+            BufRead,
+            BufReader,
+            IsTerminal,
+            Read,
+            Seek,
+            SeekFrom,
+            Write,
+        },
+        path::PathBuf,
+        str::FromStr,
+        sync::Mutex,
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) enum PromptMode {
+    /// Fails in CI and when neither stdin nor stderr is a terminal.
+    #[default]
+    Auto,
+    Tty,
+    Never,
+    Pinentry(PathBuf),
+    Fd(u32),
+}
+
+impl FromStr for PromptMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            | "auto" => Ok(Self::Auto),
+            | "tty" => Ok(Self::Tty),
+            | "never" => Ok(Self::Never),
+            | "pinentry" => Ok(Self::Pinentry(PathBuf::from("pinentry"))),
+            | _ => {
+                if let Some(program) = value.strip_prefix("pinentry:").filter(|program| !program.is_empty()) {
+                    return Ok(Self::Pinentry(PathBuf::from(program)));
+                }
+                if let Some(fd) = value.strip_prefix("fd:") {
+                    return fd
+                        .parse()
+                        .map(Self::Fd)
+                        .with_context(|| format!("Invalid file descriptor '{}'", fd));
+                }
+                anyhow::bail!(
+                    "Invalid prompt mode '{}'. Expected auto, tty, never, pinentry[:<program>], or fd:<number>.",
+                    value
+                )
+            },
+        }
+    }
+}
+
+impl PromptMode {
+    fn in_ci() -> bool {
+        std::env::var("CI").is_ok_and(|value| !value.is_empty() && value != "false" && value != "0")
+    }
+
+    pub(crate) fn resolve(&self) -> &Self {
+        match self {
+            | Self::Auto if Self::in_ci() => &Self::Never,
+            | Self::Auto if std::io::stdin().is_terminal() || std::io::stderr().is_terminal() => &Self::Tty,
+            | Self::Auto => &Self::Never,
+            | mode => mode,
+        }
+    }
+
+    pub(crate) fn password(&self, description: &str) -> Result<Zeroizing<String>> {
+        match self.resolve() {
+            | Self::Tty => {
+                rpassword::prompt_password(format!("Enter password for {}: ", description))
+                    .map(Zeroizing::new)
+                    .context("Failed to read password from the terminal")
+            },
+            | Self::Never => {
+                anyhow::bail!(
+                    "A password for {} is required, but prompting is disabled (no terminal, CI, or --prompt never). \
+                     Use --prompt pinentry or --prompt fd:<number>, or provide an unencrypted key.",
+                    description
+                )
+            },
+            | Self::Pinentry(program) => Self::pinentry(program, description),
+            | Self::Fd(fd) => Self::read_fd(*fd),
+            | Self::Auto => unreachable!("auto prompt mode is resolved above"),
+        }
+    }
+
+    /// Does not buffer past the newline, so prompts consume successive lines.
+    fn read_fd(fd: u32) -> Result<Zeroizing<String>> {
+        static FILE_OFFSETS: Mutex<BTreeMap<u32, u64>> = Mutex::new(BTreeMap::new());
+
+        let path = PathBuf::from(format!("/dev/fd/{}", fd));
+        let mut file =
+            std::fs::File::open(&path).with_context(|| format!("Failed to open file descriptor {} for reading", fd))?;
+        let mut offsets = FILE_OFFSETS.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        let is_file = file.metadata().is_ok_and(|metadata| metadata.is_file());
+        if is_file {
+            file.seek(SeekFrom::Start(offsets.get(&fd).copied().unwrap_or(0)))
+                .with_context(|| format!("Failed to read file descriptor {}", fd))?;
+        }
+        let mut consumed = 0u64;
+        let mut line = Zeroizing::new(Vec::new());
+        let mut byte = [0u8; 1];
+        loop {
+            match file.read(&mut byte) {
+                | Ok(0) if line.is_empty() => anyhow::bail!("File descriptor {} is closed; no password left", fd),
+                | Ok(0) => break,
+                | Ok(_) => {
+                    consumed += 1;
+                    if byte[0] == b'\n' {
+                        break;
+                    }
+                    line.push(byte[0]);
+                },
+                | Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {},
+                | Err(error) => return Err(error).with_context(|| format!("Failed to read file descriptor {}", fd)),
+            }
+        }
+        if is_file {
+            *offsets.entry(fd).or_default() += consumed;
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        let password = String::from_utf8(std::mem::take(&mut *line)).map_err(|error| {
+            let mut bytes = error.into_bytes();
+            bytes.zeroize();
+            anyhow::anyhow!("Password read from file descriptor {} is not valid UTF-8", fd)
+        })?;
+        Ok(Zeroizing::new(password))
+    }
+
+    fn pinentry(program: &PathBuf, description: &str) -> Result<Zeroizing<String>> {
+        let mut child = std::process::Command::new(program)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start pinentry program '{}'", program.display()))?;
+        let result = (|| {
+            let mut input = child.stdin.take().context("Failed to open pinentry stdin")?;
+            let mut output = BufReader::new(child.stdout.take().context("Failed to open pinentry stdout")?);
+            Self::assuan_expect_ok(&mut output)?;
+            for command in [
+                format!("SETDESC Enter the password for {}", Self::assuan_escape(description)),
+                "SETPROMPT Password:".to_string(),
+            ] {
+                writeln!(input, "{}", command).context("Failed to write to pinentry")?;
+                Self::assuan_expect_ok(&mut output)?;
+            }
+            writeln!(input, "GETPIN").context("Failed to write to pinentry")?;
+            let password = Self::assuan_expect_ok(&mut output)?;
+            let _ = writeln!(input, "BYE");
+            Ok(password)
+        })();
+        let _ = child.wait();
+        result
+    }
+
+    fn assuan_expect_ok(output: &mut impl BufRead) -> Result<Zeroizing<String>> {
+        let mut data = Zeroizing::new(Vec::new());
+        loop {
+            let mut line = Zeroizing::new(String::new());
+            if output.read_line(&mut line).context("Failed to read from pinentry")? == 0 {
+                anyhow::bail!("pinentry exited unexpectedly");
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line == "OK" || line.starts_with("OK ") {
+                break;
+            } else if let Some(payload) = line.strip_prefix("D ") {
+                Self::assuan_unescape(payload, &mut data)?;
+            } else if let Some(error) = line.strip_prefix("ERR ") {
+                anyhow::bail!("pinentry failed: {}", error);
+            }
+        }
+        let password = String::from_utf8(std::mem::take(&mut *data)).map_err(|error| {
+            let mut bytes = error.into_bytes();
+            bytes.zeroize();
+            anyhow::anyhow!("pinentry returned a password that is not valid UTF-8")
+        })?;
+        Ok(Zeroizing::new(password))
+    }
+
+    fn assuan_escape(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for character in value.chars() {
+            match character {
+                | '%' | '\r' | '\n' => escaped.push_str(&format!("%{:02X}", character as u32)),
+                | character => escaped.push(character),
+            }
+        }
+        escaped
+    }
+
+    fn assuan_unescape(payload: &str, data: &mut Vec<u8>) -> Result<()> {
+        let bytes = payload.as_bytes();
+        let mut index = 0;
+        while index < bytes.len() {
+            if bytes[index] == b'%' {
+                let hex = payload
+                    .get(index + 1..index + 3)
+                    .context("Truncated escape sequence in pinentry response")?;
+                data.push(u8::from_str_radix(hex, 16).context("Invalid escape sequence in pinentry response")?);
+                index += 3;
+            } else {
+                data.push(bytes[index]);
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_prompt_modes() -> Result<()> {
+        assert_eq!("auto".parse::<PromptMode>()?, PromptMode::Auto);
+        assert_eq!("never".parse::<PromptMode>()?, PromptMode::Never);
+        assert_eq!(
+            "pinentry".parse::<PromptMode>()?,
+            PromptMode::Pinentry(PathBuf::from("pinentry"))
+        );
+        assert_eq!(
+            "pinentry:/usr/bin/pinentry-tty".parse::<PromptMode>()?,
+            PromptMode::Pinentry(PathBuf::from("/usr/bin/pinentry-tty"))
+        );
+        assert_eq!("fd:3".parse::<PromptMode>()?, PromptMode::Fd(3));
+        assert!("fd:three".parse::<PromptMode>().is_err());
+        assert!("pinentry:".parse::<PromptMode>().is_err());
+        assert!("ask".parse::<PromptMode>().is_err());
+
+        let error = PromptMode::Never.password("PGP key 0123").unwrap_err();
+        assert!(error.to_string().contains("prompting is disabled"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_successive_lines_from_a_file_descriptor() -> Result<()> {
+        use std::os::fd::AsRawFd;
+
+        let mut passwords = tempfile::tempfile()?;
+        passwords.write_all(b"first\r\nsecond\n")?;
+        let fd = u32::try_from(passwords.as_raw_fd())?;
+        assert_eq!(PromptMode::Fd(fd).password("key one")?.as_str(), "first");
+        assert_eq!(PromptMode::Fd(fd).password("key two")?.as_str(), "second");
+        assert!(PromptMode::Fd(fd).password("key three").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn reads_pinentry_responses() -> Result<()> {
+        let mut output = "OK Pleased to meet you\nD pass%25word%0Awith newline\nOK\n".as_bytes();
+        assert!(PromptMode::assuan_expect_ok(&mut output)?.is_empty());
+        assert_eq!(
+            PromptMode::assuan_expect_ok(&mut output)?.as_str(),
+            "pass%word\nwith newline"
+        );
+
+        let mut cancelled = "ERR 83886179 Operation cancelled\n".as_bytes();
+        assert!(PromptMode::assuan_expect_ok(&mut cancelled).is_err());
+        assert_eq!(PromptMode::assuan_escape("50% done\n"), "50%25 done%0A");
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use {
+    crate::failure::Failure,
+    anyhow::{
+        Context,
+        Result,
+    },
+    sequoia_openpgp::{
+        self as openpgp,
+        parse::Parse,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+};
+
+pub(crate) const DEFAULT_KEYSERVER: &str = "hkps://keys.openpgp.org";
+
+/// A public key located by email address instead of the configured one.
+pub(crate) struct Recipient {
+    certificate: String,
+    fingerprint: String,
+    user_ids: Vec<String>,
+}
+
+impl Recipient {
+    /// The local GPG keyring is neither used nor changed.
+    pub(crate) fn locate(email: &str, keyserver: &str, removed_env_vars: &[String]) -> Result<Self> {
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return Self::from_certificate(email, &fixtures.public_key(email)?);
+        }
+        let home = tempfile::tempdir().context("Failed to create a temporary GPG home")?;
+        let gpg = |args: &[&str]| {
+            let mut cmd = Command::new("gpg");
+            cmd.arg("--homedir")
+                .arg(home.path())
+                .args(["--batch", "--quiet"])
+                .args(args);
+            crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+            cmd.stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()
+                .context("Failed to execute gpg to locate the recipient key")
+        };
+
+        let located = gpg(&[
+            "--keyserver",
+            keyserver,
+            "--auto-key-locate",
+            "clear,wkd,keyserver",
+            "--locate-external-keys",
+            email,
+        ]);
+        let exported = gpg(&["--armor", "--export-options", "export-minimal", "--export", email]);
+        let _ = Command::new("gpgconf")
+            .arg("--homedir")
+            .arg(home.path())
+            .args(["--kill", "all"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        let located = located?;
+        if !located.status.success() {
+            return Err(Failure::Provider.error(format!(
+                "No key found for '{}' via WKD or {}: {}",
+                email,
+                keyserver,
+                String::from_utf8_lossy(&located.stderr).trim()
+            )));
+        }
+        let certificate = String::from_utf8(exported?.stdout).context("Located key is not valid UTF-8")?;
+        Self::from_certificate(email, &certificate)
+    }
+
+    fn from_certificate(email: &str, certificate: &str) -> Result<Self> {
+        let cert = openpgp::Cert::from_bytes(certificate.as_bytes())
+            .with_context(|| format!("Failed to parse the key located for '{}'", email))?;
+        if cert.is_tsk() {
+            anyhow::bail!("The key located for '{}' contains secret key material", email);
+        }
+        let user_ids: Vec<_> = cert
+            .userids()
+            .map(|user_id| String::from_utf8_lossy(user_id.userid().value()).into_owned())
+            .collect();
+        let matches_email = cert.userids().any(|user_id| {
+            user_id
+                .userid()
+                .email()
+                .ok()
+                .flatten()
+                .is_some_and(|address| address.eq_ignore_ascii_case(email))
+        });
+        if !matches_email {
+            anyhow::bail!("The key located for '{}' has no user ID with that address", email);
+        }
+        Ok(Self {
+            certificate: certificate.to_string(),
+            fingerprint: cert.fingerprint().to_hex(),
+            user_ids,
+        })
+    }
+
+    fn matches_pin(&self, pin: &str) -> bool {
+        let pin: String = pin.chars().filter(|character| !character.is_whitespace()).collect();
+        pin.eq_ignore_ascii_case(&self.fingerprint)
+    }
+
+    pub(crate) fn confirm(&self, pin: Option<&str>) -> Result<()> {
+        use std::io::{
+            IsTerminal,
+            Write,
+        };
+
+        if let Some(pin) = pin {
+            if !self.matches_pin(pin) {
+                anyhow::bail!(
+                    "The located key {} does not match the pinned fingerprint {}",
+                    self.fingerprint,
+                    pin
+                );
+            }
+            return Ok(());
+        }
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!(
+                "Cannot confirm the located key {} without a terminal; pass --fingerprint to pin it",
+                self.fingerprint
+            );
+        }
+        eprintln!("Located key {}", self.fingerprint);
+        for user_id in &self.user_ids {
+            eprintln!("  {}", user_id);
+        }
+        eprint!("Seal to this key? [y/N] ");
+        std::io::stderr().flush().context("Failed to ask for confirmation")?;
+        let mut answer = String::new();
+        std::io::stdin()
+            .read_line(&mut answer)
+            .context("Failed to read the confirmation")?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            anyhow::bail!("The located key {} was not trusted", self.fingerprint);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn certificate(&self) -> &str {
+        &self.certificate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sequoia_openpgp::{
+            cert::CertBuilder,
+            serialize::SerializeInto,
+        },
+    };
+
+    #[test]
+    fn accepts_keys_of_the_address_matching_the_pin() -> Result<()> {
+        let (cert, _) = CertBuilder::general_purpose(Some("Alice <alice@example.com>")).generate()?;
+        let certificate = String::from_utf8(cert.armored().to_vec()?)?;
+        let fingerprint = cert.fingerprint().to_hex();
+
+        let recipient = Recipient::from_certificate("Alice@Example.com", &certificate)?;
+        assert_eq!(recipient.user_ids, ["Alice <alice@example.com>"]);
+        recipient.confirm(Some(&fingerprint.to_lowercase()))?;
+        recipient.confirm(Some(&cert.fingerprint().to_spaced_hex()))?;
+        assert!(recipient.confirm(Some(&"0".repeat(40))).is_err());
+
+        assert!(Recipient::from_certificate("bob@example.com", &certificate).is_err());
+        let private_key = String::from_utf8(cert.as_tsk().armored().to_vec()?)?;
+        assert!(Recipient::from_certificate("alice@example.com", &private_key).is_err());
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
 use {
-    crate::args::ClapArgumentLoader,
+    crate::{
+        args::ClapArgumentLoader,
+        manifest::Manifest,
+    },
     anyhow::{
         Context,
         Result,
@@ -9,7 +12,10 @@ use {
     std::{
         fs::File,
         io::Write,
-        path::Path,
+        path::{
+            Path,
+            PathBuf,
+        },
     },
 };
 
@@ -19,12 +25,119 @@ pub(crate) struct ReferenceBuilder;
 
 impl ReferenceBuilder {
     pub(crate) fn build_shell_completion(&self, outdir: &Path, shell: &Shell) -> Result<()> {
-        let mut app = ClapArgumentLoader::root_command();
-        clap_complete::generate_to(*shell, &mut app, env!("CARGO_PKG_NAME"), outdir)
+        let path = outdir.join(clap_complete::Generator::file_name(shell, env!("CARGO_PKG_NAME")));
+        std::fs::write(&path, self.render_shell_completion(shell))
             .with_context(|| format!("Failed to generate completion in '{}'", outdir.display()))?;
         Ok(())
     }
 
+    /// Extended so the key of `set` and `rm` completes to profile variables.
+    pub(crate) fn render_shell_completion(&self, shell: &Shell) -> Vec<u8> {
+        let mut script = Vec::new();
+        clap_complete::generate(
+            *shell,
+            &mut ClapArgumentLoader::root_command(),
+            env!("CARGO_PKG_NAME"),
+            &mut script,
+        );
+        let script = String::from_utf8_lossy(&script);
+        let name = env!("CARGO_PKG_NAME");
+        match shell {
+            // Bash and zsh dispatch to `_secenv`, so clap's function is
+            // renamed and wrapped by one that asks for the keys first.
+            | Shell::Bash | Shell::Zsh => {
+                let hook = if *shell == Shell::Bash {
+                    BASH_KEY_COMPLETION
+                } else {
+                    ZSH_KEY_COMPLETION
+                };
+                let script = script.replacen(&format!("_{name}() {{"), &format!("_{name}_clap() {{"), 1);
+                match script.rfind("\nif [") {
+                    | Some(dispatch) => {
+                        format!("{}\n{}{}", &script[..dispatch], hook, &script[dispatch..]).into_bytes()
+                    },
+                    | None => script.into_bytes(),
+                }
+            },
+            | Shell::Fish => format!("{}{}", script, FISH_KEY_COMPLETION).into_bytes(),
+            | _ => script.into_owned().into_bytes(),
+        }
+    }
+
+    pub(crate) fn variable_keys(&self, manifest: &Manifest, profile_name: &str) -> Vec<String> {
+        let Some(profile) = manifest.profiles.get(profile_name) else {
+            return Vec::new();
+        };
+        let mut keys: Vec<_> = profile
+            .env
+            .vars
+            .keys()
+            .map(|name| {
+                if name.is_empty()
+                    || name.contains(|character: char| character.is_whitespace() || "\".=:{}[],#$+".contains(character))
+                {
+                    format!("env.vars.{}", serde_json::Value::from(name.as_str()))
+                } else {
+                    format!("env.vars.{}", name)
+                }
+            })
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    pub(crate) fn install_shell_completion(&self, shell: &Shell) -> Result<PathBuf> {
+        let path = Self::completion_path(shell, |name| {
+            std::env::var_os(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        })?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        std::fs::write(&path, self.render_shell_completion(shell))
+            .with_context(|| format!("Failed to write completion script '{}'", path.display()))?;
+        Ok(path)
+    }
+
+    fn completion_path(shell: &Shell, var: impl Fn(&str) -> Option<PathBuf>) -> Result<PathBuf> {
+        let home = || var("HOME").context("Cannot locate the home directory: HOME is not set");
+        let name = env!("CARGO_PKG_NAME");
+        match shell {
+            | Shell::Bash => {
+                let directory = match var("BASH_COMPLETION_USER_DIR") {
+                    | Some(directory) => directory,
+                    | None => {
+                        match var("XDG_DATA_HOME") {
+                            | Some(data_home) => data_home,
+                            | None => home()?.join(".local").join("share"),
+                        }
+                        .join("bash-completion")
+                    },
+                };
+                Ok(directory.join("completions").join(name))
+            },
+            | Shell::Zsh => Ok(home()?.join(".zsh").join("completions").join(format!("_{}", name))),
+            | Shell::Fish => {
+                let config_home = match var("XDG_CONFIG_HOME") {
+                    | Some(config_home) => config_home,
+                    | None => home()?.join(".config"),
+                };
+                Ok(config_home
+                    .join("fish")
+                    .join("completions")
+                    .join(format!("{}.fish", name)))
+            },
+            | _ => {
+                anyhow::bail!(
+                    "{} has no standard completion directory; use --print or --out instead",
+                    shell
+                )
+            },
+        }
+    }
+
     pub(crate) fn build_markdown(&self, outdir: &Path) -> Result<()> {
         for (name, command) in self.collect_commands() {
             let path = outdir.join(format!("{}.md", name));
@@ -53,7 +166,11 @@ impl ReferenceBuilder {
         let mut pending = vec![(root.get_name().to_string(), root)];
         let mut commands = Vec::new();
         while let Some((name, command)) = pending.pop() {
-            let subcommands: Vec<_> = command.get_subcommands().cloned().collect();
+            let subcommands: Vec<_> = command
+                .get_subcommands()
+                .filter(|subcommand| !subcommand.is_hide_set())
+                .cloned()
+                .collect();
             for subcommand in subcommands.into_iter().rev() {
                 pending.push((format!("{}-{}", name, subcommand.get_name()), subcommand.clone()));
             }
@@ -62,3 +179,107 @@ impl ReferenceBuilder {
         commands
     }
 }
+
+const BASH_KEY_COMPLETION: &str = r#"_secenv() {
+    local keys
+    if [[ "${COMP_WORDS[COMP_CWORD]}" != -* ]] \
+        && keys="$(secenv complete-keys -- "${COMP_WORDS[@]:0:COMP_CWORD}" 2>/dev/null)"; then
+        COMPREPLY=( $(compgen -W "${keys}" -- "${COMP_WORDS[COMP_CWORD]}") )
+        return 0
+    fi
+    _secenv_clap "$@"
+}
+"#;
+
+const ZSH_KEY_COMPLETION: &str = r#"_secenv() {
+    local -a keys
+    if [[ "$PREFIX" != -* ]] \
+        && keys=(${(f)"$(secenv complete-keys -- "${(@)words[1,CURRENT-1]}" 2>/dev/null)"}); then
+        compadd -a keys
+    else
+        _secenv_clap "$@"
+    fi
+}
+"#;
+
+const FISH_KEY_COMPLETION: &str = r#"complete -c secenv \
+    -n "__fish_secenv_using_subcommand set; or __fish_secenv_using_subcommand rm" \
+    -a "(secenv complete-keys -- (commandline -opc) 2>/dev/null)"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_per_user_completion_directories() -> Result<()> {
+        let environment = |variables: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                variables
+                    .iter()
+                    .find(|(variable, _)| *variable == name)
+                    .map(|(_, value)| PathBuf::from(value))
+            }
+        };
+        let home = environment(&[("HOME", "/home/alice")]);
+        assert_eq!(
+            ReferenceBuilder::completion_path(&Shell::Bash, home)?,
+            Path::new("/home/alice/.local/share/bash-completion/completions/secenv")
+        );
+        assert_eq!(
+            ReferenceBuilder::completion_path(&Shell::Zsh, home)?,
+            Path::new("/home/alice/.zsh/completions/_secenv")
+        );
+        assert_eq!(
+            ReferenceBuilder::completion_path(&Shell::Fish, home)?,
+            Path::new("/home/alice/.config/fish/completions/secenv.fish")
+        );
+        assert!(ReferenceBuilder::completion_path(&Shell::PowerShell, home).is_err());
+
+        let xdg = environment(&[("XDG_DATA_HOME", "/data"), ("XDG_CONFIG_HOME", "/config")]);
+        assert_eq!(
+            ReferenceBuilder::completion_path(&Shell::Bash, xdg)?,
+            Path::new("/data/bash-completion/completions/secenv")
+        );
+        assert_eq!(
+            ReferenceBuilder::completion_path(&Shell::Fish, xdg)?,
+            Path::new("/config/fish/completions/secenv.fish")
+        );
+        assert!(ReferenceBuilder::completion_path(&Shell::Zsh, xdg).is_err());
+        assert_eq!(
+            ReferenceBuilder::completion_path(&Shell::Bash, environment(&[("BASH_COMPLETION_USER_DIR", "/bc")]))?,
+            Path::new("/bc/completions/secenv")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn completes_the_variable_keys_of_a_profile() -> Result<()> {
+        let manifest = Manifest::parse(
+            r#"{
+  "version": "0.0.0",
+  "profiles": {
+    "default": {
+      "env": { "vars": { "TOKEN": { "plain": { "literal": "a" } }, "my.key": { "plain": { "literal": "b" } } } }
+    }
+  }
+}"#,
+            crate::manifest::ManifestFormat::Hocon,
+        )?;
+        assert_eq!(ReferenceBuilder.variable_keys(&manifest, "default"), [
+            r#"env.vars."my.key""#,
+            "env.vars.TOKEN"
+        ]);
+        assert!(ReferenceBuilder.variable_keys(&manifest, "missing").is_empty());
+
+        for shell in [Shell::Bash, Shell::Zsh] {
+            let script = String::from_utf8(ReferenceBuilder.render_shell_completion(&shell))?;
+            assert!(script.contains("_secenv_clap() {"));
+            let hook = script.find("secenv complete-keys").context("missing key completion")?;
+            assert!(hook < script.rfind("\nif [").context("missing dispatch")?);
+        }
+        let fish = String::from_utf8(ReferenceBuilder.render_shell_completion(&Shell::Fish))?;
+        assert!(fish.contains("(secenv complete-keys -- (commandline -opc) 2>/dev/null)"));
+        Ok(())
+    }
+}
@@ -0,0 +1,911 @@
+use serde_json::{
+    json,
+    Map,
+    Value,
+};
+
+/// Each object holding a flattened source is one closed alternative per source.
+pub(crate) fn manifest() -> Value {
+    let definitions = json!({
+        "encoded_value": tagged(&[
+            ("literal", string("The value as written.")),
+            ("base64", string("The value, base64-encoded.")),
+            ("gzip", string("The value, gzip-compressed and base64-encoded.")),
+            ("zstd", string("The value, zstd-compressed and base64-encoded.")),
+        ], &[]),
+        "secret_allocation": tagged(&[
+            ("literal", reference("encoded_value")),
+            ("file", string("A local file.")),
+            ("env", string("An environment variable of secenv's own environment.")),
+            ("gpg", object(&[("fingerprint", string("The key's fingerprint in the GPG keyring."))], &["fingerprint"])),
+            ("gcp", object(&[
+                ("secret", string("`projects/<project>/secrets/<name>`.")),
+                ("version", optional(string("Defaults to the latest version."))),
+            ], &["secret"])),
+            ("aws", object(&[
+                ("secret", string("The secret's name or ARN.")),
+                ("version", optional(string("Defaults to the current version."))),
+                ("region", optional(string("Defaults to the configured region."))),
+            ], &["secret"])),
+            ("azure", object(&[
+                ("vault", string("The Key Vault's name.")),
+                ("secret", string("The secret's name.")),
+                ("version", optional(string("Defaults to the current version."))),
+            ], &["vault", "secret"])),
+            ("op", string("A 1Password secret reference, `op://<vault>/<item>/<field>`.")),
+            ("pass", pass()),
+            ("keyring", keyring()),
+            ("lastpass", lastpass()),
+            ("exec", exec()),
+            ("systemd_credential", string("A credential systemd passes to the service by name.")),
+            ("ssh", ssh()),
+            ("dpapi", string("A file holding a base64 blob protected with Windows DPAPI for the current user.")),
+            ("pkcs11", object(&[
+                ("module", string("The PKCS#11 module of the token.")),
+                ("slot", json!({ "type": "integer", "minimum": 0 })),
+                ("label", string("The label of the RSA key.")),
+            ], &["module", "slot", "label"])),
+        ], &[]),
+        "secret": tagged(&[
+            ("pgp", reference("secret_allocation")),
+            ("kms", object(&[
+                ("key_id", string("The AWS KMS key.")),
+                ("region", optional(string("Defaults to the configured region."))),
+            ], &["key_id"])),
+            ("gcp_kms", object(&[
+                ("key_ring", string("The Cloud KMS key ring.")),
+                ("key", string("The key's name.")),
+                ("location", string("The key ring's location, e.g. `global`.")),
+                ("project", optional(string("Defaults to the gcloud configuration."))),
+            ], &["key_ring", "key", "location"])),
+            ("tpm", object(&[
+                ("parent", optional(string("A persistent key handle, e.g. `0x81000001`."))),
+                ("pcrs", optional(string("The PCRs of the object's policy, e.g. `sha256:0,7`."))),
+            ], &[])),
+            ("vault_transit", object(&[
+                ("key", string("The Transit key.")),
+                ("mount", optional(string("Defaults to `transit`."))),
+                ("address", optional(string("The Vault server; `VAULT_ADDR` when unset."))),
+                ("auth", optional(reference("vault_auth"))),
+            ], &["key"])),
+            ("ref", string("The name of a key in the top-level `secrets`.")),
+        ], &[]),
+        "sealed_secret": tagged(&[
+            ("pgp", reference("secret_allocation")),
+            ("argon2id_xchacha20_poly1305", reference("secret_allocation")),
+        ], &[]),
+        "sealed_secret_reference": {
+            "oneOf": [
+                reference("sealed_secret"),
+                object(&[("ref", string("The name of a key in the top-level `secrets`."))], &["ref"]),
+            ],
+        },
+        "vault_auth": tagged(&[
+            ("token", reference("secret_allocation")),
+            ("approle", object(&[
+                ("role_id", string("The AppRole's role ID.")),
+                ("secret_id", reference("secret_allocation")),
+                ("mount", optional(string("Defaults to `approle`."))),
+            ], &["role_id", "secret_id"])),
+            ("kubernetes", object(&[
+                ("role", string("The Vault role.")),
+                ("mount", optional(string("Defaults to `kubernetes`."))),
+                ("jwt", optional(reference("secret_allocation"))),
+            ], &["role"])),
+            ("oidc", object(&[
+                ("role", optional(string("Defaults to the mount's default role."))),
+                ("mount", optional(string("Defaults to `oidc`."))),
+            ], &[])),
+        ], &[]),
+        "http_auth": tagged(&[
+            ("bearer", reference("secret_allocation")),
+            ("basic", object(&[
+                ("username", string("The user name.")),
+                ("password", reference("secret_allocation")),
+            ], &["username", "password"])),
+        ], &[]),
+        "content": tagged(&content(), &[]),
+        "value": tagged(&content(), &[
+            ("type", enumeration(&["string", "bool", "int", "float"], "The type the value must have.")),
+            ("join", string("Delimiter joining the elements of a list value. Defaults to `,`.")),
+            ("prepend_to", string("Prepend the value to this variable instead of setting one.")),
+            ("append_to", string("Append the value to this variable instead of setting one.")),
+            ("separator", string("Separator between the value and the one it extends.")),
+            ("when", string("Condition under which the variable is set.")),
+            ("required", boolean("Resolve the variable before all others, failing before any other source is read.")),
+            ("optional", boolean("Leave the variable unset when its source cannot be resolved.")),
+            ("extract", string("JSONPath of the field of a JSON secret to use, e.g. `$.credentials.password`.")),
+            ("default", reference("content")),
+            ("rotate_after", string("How long a value may be used after `last_rotated`, e.g. `90d`.")),
+            ("last_rotated", string("When the value was last rotated: an RFC 3339 timestamp or a date.")),
+            ("expires", string("After this instant the value is refused without `--allow-expired`.")),
+        ]),
+        "file": tagged(&content(), &[
+            ("mode", enumeration(&["write", "append", "symlink", "copy"], "How the content is placed at the path.")),
+            ("sha256", json!({ "type": "string", "pattern": "^[0-9a-fA-F]{64}$" })),
+            ("backup", enumeration(&["memory", "file"], "Preserve an existing file and restore it afterwards.")),
+            ("permissions", json!({ "type": "string", "pattern": "^0*[0-7]{1,3}$" })),
+            ("when", string("Condition under which the file is created.")),
+            ("persist", boolean("Leave the file in place after the run.")),
+            ("template", boolean("Replace `{{ NAME }}` placeholders in the content with the profile's variables.")),
+        ]),
+        "from": tagged(&from(), &[
+            ("when", string("Condition under which this source is read.")),
+            ("default", reference("from_location")),
+            ("rename", map(json!({ "type": "string" }), "New names of imported variables by the name the source gives them.")),
+            ("case", enumeration(&["upper", "lower"], "The case imported names are changed to.")),
+            ("prefix", string("Prepended to imported names, after `case`.")),
+            ("extract", string("JSONPath of the object of the source's JSON document whose entries are imported.")),
+        ]),
+        "from_location": tagged(&from(), &[]),
+        "env": object(&[
+            ("keep", json!({
+                "description": "Host variables a command inherits: all, none, or names and regexes.",
+                "anyOf": [{ "type": "boolean" }, strings("Variable names and regexes.")],
+            })),
+            ("vars", map(reference("value"), "Variables by name.")),
+            ("from", json!({ "type": "array", "items": reference("from") })),
+        ], &[]),
+        "profile": object(&[
+            ("expires", string("After this instant the profile is refused without `--allow-expired`.")),
+            ("sealed", object(&[
+                ("files", map(
+                    object(&[("secret", reference("sealed_secret_reference"))], &["secret"]),
+                    "HOCON or JSON files with sealed values, decrypted in place for the command.",
+                )),
+                ("templates", map(
+                    object(&[
+                        ("source", string("The encrypted template.")),
+                        ("secret", reference("sealed_secret_reference")),
+                    ], &["source", "secret"]),
+                    "Encrypted templates by their temporary output path.",
+                )),
+            ], &[])),
+            ("files", map(reference("file"), "Temporary files by path.")),
+            ("env", reference("env")),
+            ("exec", object(&[("allow", strings("Glob patterns of the command lines allowed."))], &[])),
+            ("policy", object(&[
+                ("hosts", strings("Glob patterns of allowed hostnames.")),
+                ("users", strings("Glob patterns of allowed user names.")),
+                ("require_tty", boolean("Refuse sessions whose standard input is not a terminal.")),
+            ], &[])),
+            ("confirm", json!({
+                "description": "Ask before unlocking: `true` for a yes/no question, or a phrase to type.",
+                "anyOf": [{ "type": "boolean" }, { "type": "string" }],
+            })),
+        ], &[]),
+        "provider_limits": object(&[("rate_limit", rate_limit())], &[]),
+    });
+
+    let mut schema = object(
+        &[
+            ("$schema", string("The schema editors validate this file with.")),
+            (
+                "version",
+                string("The semver version of secenv the manifest is written for."),
+            ),
+            (
+                "substitutions",
+                strings("Environment variables HOCON substitutions may read."),
+            ),
+            (
+                "signers",
+                map(
+                    reference("secret_allocation"),
+                    "Public keys of trusted signers by fingerprint.",
+                ),
+            ),
+            (
+                "secrets",
+                map(reference("sealed_secret"), "Keys referenced by name from `secret.ref`."),
+            ),
+            (
+                "providers",
+                object(
+                    &[
+                        (
+                            "gcp",
+                            object(
+                                &[
+                                    ("rate_limit", rate_limit()),
+                                    (
+                                        "client",
+                                        enumeration(&["auto", "rest", "gcloud"], "How Secret Manager is read."),
+                                    ),
+                                ],
+                                &[],
+                            ),
+                        ),
+                        (
+                            "aws",
+                            object(
+                                &[
+                                    ("rate_limit", rate_limit()),
+                                    (
+                                        "client",
+                                        enumeration(&["auto", "rest", "cli"], "How Secrets Manager is read."),
+                                    ),
+                                ],
+                                &[],
+                            ),
+                        ),
+                        ("azure", reference("provider_limits")),
+                        ("op", reference("provider_limits")),
+                        ("doppler", reference("provider_limits")),
+                    ],
+                    &[],
+                ),
+            ),
+            ("env", reference("env")),
+            ("profiles", map(reference("profile"), "Profiles by name.")),
+        ],
+        &["version"],
+    );
+    let root = schema.as_object_mut().expect("the manifest schema is an object");
+    root.insert("$schema".to_string(), json!("http://json-schema.org/draft-07/schema#"));
+    root.insert("title".to_string(), json!("secenv manifest"));
+    root.insert("definitions".to_string(), definitions);
+    schema
+}
+
+fn content() -> Vec<(&'static str, Value)> {
+    vec![
+        ("plain", reference("encoded_value")),
+        (
+            "list",
+            strings("Plain elements joined into one value; environment variables only."),
+        ),
+        (
+            "secure",
+            object(
+                &[("secret", reference("secret")), ("value", reference("encoded_value"))],
+                &["secret", "value"],
+            ),
+        ),
+        (
+            "sealed",
+            object(
+                &[
+                    ("secret", reference("sealed_secret_reference")),
+                    ("value", string("An `ENC[...]` marker.")),
+                ],
+                &["secret", "value"],
+            ),
+        ),
+        ("file", string("A local file.")),
+        (
+            "gcs",
+            object(
+                &[
+                    ("secret", string("`projects/<project>/secrets/<name>`.")),
+                    ("version", optional(string("Defaults to the latest version."))),
+                ],
+                &["secret"],
+            ),
+        ),
+        (
+            "aws",
+            object(
+                &[
+                    ("secret", string("The secret's name or ARN.")),
+                    ("version", optional(string("Defaults to the current version."))),
+                    ("region", optional(string("Defaults to the configured region."))),
+                ],
+                &["secret"],
+            ),
+        ),
+        (
+            "azure",
+            object(
+                &[
+                    ("vault", string("The Key Vault's name.")),
+                    ("secret", string("The secret's name.")),
+                    ("version", optional(string("Defaults to the current version."))),
+                ],
+                &["vault", "secret"],
+            ),
+        ),
+        (
+            "op",
+            string("A 1Password secret reference, `op://<vault>/<item>/<field>`."),
+        ),
+        ("pass", pass()),
+        ("keyring", keyring()),
+        ("lastpass", lastpass()),
+        (
+            "keepass",
+            object(
+                &[
+                    ("database", string("The `.kdbx` file.")),
+                    ("entry", string("The entry's path, e.g. `Work/Database`.")),
+                    ("field", optional(string("Defaults to `Password`."))),
+                ],
+                &["database", "entry"],
+            ),
+        ),
+        ("exec", exec()),
+        (
+            "systemd_credential",
+            string("A credential systemd passes to the service by name."),
+        ),
+        (
+            "gcp_token",
+            object(
+                &[
+                    ("service_account", string("The service account to impersonate.")),
+                    (
+                        "audience",
+                        optional(string("Mint an ID token for this audience instead of an access token.")),
+                    ),
+                ],
+                &["service_account"],
+            ),
+        ),
+        (
+            "azure_token",
+            object(
+                &[
+                    (
+                        "scope",
+                        optional(string("A scope such as `https://vault.azure.net/.default`.")),
+                    ),
+                    (
+                        "resource",
+                        optional(string("A resource such as `https://vault.azure.net`.")),
+                    ),
+                    (
+                        "tenant",
+                        optional(string("Defaults to the signed-in account's tenant.")),
+                    ),
+                ],
+                &[],
+            ),
+        ),
+        (
+            "oidc_token",
+            object(
+                &[
+                    ("issuer", string("The issuer URL its configuration is discovered from.")),
+                    ("client_id", string("The OAuth client.")),
+                    ("client_secret", optional(reference("content"))),
+                    (
+                        "flow",
+                        enumeration(&["client_credentials", "device_code"], "How the token is obtained."),
+                    ),
+                    ("scope", optional(string("Space-separated scopes."))),
+                    ("audience", optional(string("The API the token is for."))),
+                    ("token", enumeration(&["access", "id"], "Which token to inject.")),
+                ],
+                &["issuer", "client_id"],
+            ),
+        ),
+        (
+            "vault_pki",
+            object(
+                &[
+                    ("role", string("The PKI role.")),
+                    ("common_name", string("The certificate's common name.")),
+                    ("ttl", optional(string("Defaults to the role's TTL."))),
+                    ("alt_names", strings("Subject alternative names.")),
+                    (
+                        "part",
+                        enumeration(
+                            &["certificate", "private_key", "issuing_ca", "ca_chain"],
+                            "Which PEM to inject.",
+                        ),
+                    ),
+                    ("mount", optional(string("Defaults to `pki`."))),
+                    (
+                        "address",
+                        optional(string("The Vault server; `VAULT_ADDR` when unset.")),
+                    ),
+                    ("auth", optional(reference("vault_auth"))),
+                ],
+                &["role", "common_name"],
+            ),
+        ),
+        (
+            "totp",
+            object(
+                &[
+                    ("seed", reference("content")),
+                    (
+                        "digits",
+                        optional(json!({ "type": "integer", "minimum": 6, "maximum": 10 })),
+                    ),
+                    ("period", optional(json!({ "type": "integer", "minimum": 1 }))),
+                    (
+                        "algorithm",
+                        enumeration(&["sha1", "sha256", "sha512"], "The HMAC hash function."),
+                    ),
+                ],
+                &["seed"],
+            ),
+        ),
+        (
+            "composite",
+            object(
+                &[
+                    (
+                        "template",
+                        string("`{name}` is replaced with the part `name`, `{name:url}` with it percent-encoded."),
+                    ),
+                    (
+                        "parts",
+                        map(reference("content"), "The values filled into the template by name."),
+                    ),
+                ],
+                &["template", "parts"],
+            ),
+        ),
+    ]
+}
+
+fn from() -> Vec<(&'static str, Value)> {
+    let vault_server = || {
+        [
+            (
+                "address",
+                optional(string("The Vault server; `VAULT_ADDR` when unset.")),
+            ),
+            ("auth", optional(reference("vault_auth"))),
+        ]
+    };
+    vec![
+        ("file", string("A local `KEY=VALUE` file.")),
+        (
+            "gcs",
+            object(
+                &[
+                    ("secret", string("`projects/<project>/secrets/<name>`.")),
+                    ("version", optional(string("Defaults to the latest version."))),
+                ],
+                &["secret"],
+            ),
+        ),
+        (
+            "aws",
+            object(
+                &[
+                    ("secret", string("The secret's name or ARN.")),
+                    ("version", optional(string("Defaults to the current version."))),
+                    ("region", optional(string("Defaults to the configured region."))),
+                ],
+                &["secret"],
+            ),
+        ),
+        (
+            "azure",
+            object(
+                &[
+                    ("vault", string("The Key Vault's name.")),
+                    ("secret", string("The secret's name.")),
+                    ("version", optional(string("Defaults to the current version."))),
+                ],
+                &["vault", "secret"],
+            ),
+        ),
+        (
+            "gcs_all",
+            object(
+                &[
+                    ("project", string("The GCP project.")),
+                    ("filter", optional(string("A Secret Manager list filter."))),
+                    ("prefix_strip", optional(string("Removed from the start of each name."))),
+                ],
+                &["project"],
+            ),
+        ),
+        (
+            "aws_all",
+            object(
+                &[
+                    (
+                        "filter_tags",
+                        map(json!({ "type": "string" }), "Tags every imported secret carries."),
+                    ),
+                    ("region", optional(string("Defaults to the configured region."))),
+                    ("expand_json", boolean("Import the keys of JSON secrets as variables.")),
+                ],
+                &["filter_tags"],
+            ),
+        ),
+        (
+            "ssm_path",
+            object(
+                &[
+                    ("path", string("The parameter path.")),
+                    ("recursive", boolean("Include the parameters of nested paths.")),
+                    ("decrypt", boolean("Decrypt `SecureString` parameters.")),
+                    ("region", optional(string("Defaults to the configured region."))),
+                ],
+                &["path"],
+            ),
+        ),
+        (
+            "doppler",
+            object(
+                &[
+                    ("project", string("The Doppler project.")),
+                    ("config", string("The config, e.g. `prd`.")),
+                    ("only", strings("Glob patterns of the names to keep.")),
+                    ("exclude", strings("Glob patterns of the names to drop.")),
+                ],
+                &["project", "config"],
+            ),
+        ),
+        (
+            "sops",
+            object(
+                &[
+                    ("path", string("The sops-encrypted file.")),
+                    (
+                        "format",
+                        optional(enumeration(
+                            &["yaml", "json", "dotenv"],
+                            "Inferred from the extension when unset.",
+                        )),
+                    ),
+                ],
+                &["path"],
+            ),
+        ),
+        (
+            "vault",
+            object(
+                &[
+                    [
+                        ("path", string("The secret, e.g. `database/creds/my-role`.")),
+                        (
+                            "vars",
+                            map(json!({ "type": "string" }), "Variable names by data field."),
+                        ),
+                    ]
+                    .as_slice(),
+                    &vault_server(),
+                ]
+                .concat(),
+                &["path"],
+            ),
+        ),
+        (
+            "vault_tree",
+            object(
+                &[
+                    [("path", string("The KV path to import beneath."))].as_slice(),
+                    &vault_server(),
+                ]
+                .concat(),
+                &["path"],
+            ),
+        ),
+        (
+            "gcs_object",
+            object(
+                &[
+                    ("bucket", string("The Cloud Storage bucket.")),
+                    ("object", string("The object's name.")),
+                ],
+                &["bucket", "object"],
+            ),
+        ),
+        (
+            "s3",
+            object(
+                &[
+                    ("bucket", string("The S3 bucket.")),
+                    ("key", string("The object's key.")),
+                    ("region", optional(string("Defaults to the configured region."))),
+                    ("secret", optional(reference("secret"))),
+                ],
+                &["bucket", "key"],
+            ),
+        ),
+        (
+            "consul",
+            object(
+                &[
+                    ("prefix", string("The KV prefix.")),
+                    ("datacenter", optional(string("Defaults to the agent's datacenter."))),
+                ],
+                &["prefix"],
+            ),
+        ),
+        ("ssh", ssh()),
+        (
+            "git",
+            object(
+                &[
+                    ("repo", string("Anything `git fetch` accepts.")),
+                    ("ref", string("A branch, tag, or commit.")),
+                    ("path", string("The file's path in the repository.")),
+                ],
+                &["repo", "ref", "path"],
+            ),
+        ),
+        ("exec", exec()),
+        (
+            "http",
+            object(
+                &[
+                    ("url", string("The HTTPS URL.")),
+                    ("method", enumeration(&["GET", "POST"], "The request method.")),
+                    (
+                        "headers",
+                        map(json!({ "type": "string" }), "Plain request headers by name."),
+                    ),
+                    ("auth", optional(reference("http_auth"))),
+                ],
+                &["url"],
+            ),
+        ),
+        (
+            "gitlab",
+            object(
+                &[
+                    ("project", string("A numeric ID or a `<group>/<project>` path.")),
+                    ("environment", optional(string("The environment of the job."))),
+                    ("protected", boolean("Include protected variables.")),
+                    ("project_only", boolean("Skip the variables of the project's groups.")),
+                    ("url", optional(string("Defaults to `https://gitlab.com`."))),
+                    ("token", reference("secret_allocation")),
+                ],
+                &["project", "token"],
+            ),
+        ),
+    ]
+}
+
+fn pass() -> Value {
+    object(
+        &[
+            ("entry", string("The entry's name.")),
+            (
+                "tool",
+                enumeration(&["pass", "gopass"], "The password-store implementation."),
+            ),
+        ],
+        &["entry"],
+    )
+}
+
+fn keyring() -> Value {
+    object(
+        &[
+            ("service", string("The keychain service.")),
+            ("account", string("The keychain account.")),
+        ],
+        &["service", "account"],
+    )
+}
+
+fn lastpass() -> Value {
+    object(
+        &[
+            ("item", string("The item's name.")),
+            ("field", optional(string("Defaults to `password`."))),
+        ],
+        &["item"],
+    )
+}
+
+fn exec() -> Value {
+    object(
+        &[
+            ("command", string("The program, run without a shell.")),
+            ("args", strings("Its arguments.")),
+        ],
+        &["command"],
+    )
+}
+
+fn ssh() -> Value {
+    object(
+        &[
+            ("host", string("Anything `ssh` accepts as a destination.")),
+            ("path", string("The file's path on the host.")),
+        ],
+        &["host", "path"],
+    )
+}
+
+fn rate_limit() -> Value {
+    optional(json!({
+        "type": "number",
+        "exclusiveMinimum": 0,
+        "description": "Requests per second; unlimited when unset.",
+    }))
+}
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn strings(description: &str) -> Value {
+    json!({ "type": "array", "items": { "type": "string" }, "description": description })
+}
+
+fn boolean(description: &str) -> Value {
+    json!({ "type": "boolean", "description": description })
+}
+
+fn enumeration(values: &[&str], description: &str) -> Value {
+    json!({ "enum": values, "description": description })
+}
+
+fn reference(definition: &str) -> Value {
+    json!({ "$ref": format!("#/definitions/{}", definition) })
+}
+
+fn map(values: Value, description: &str) -> Value {
+    json!({ "type": "object", "additionalProperties": values, "description": description })
+}
+
+fn optional(mut schema: Value) -> Value {
+    let description = schema.as_object_mut().and_then(|schema| schema.remove("description"));
+    let mut optional = json!({ "anyOf": [schema, { "type": "null" }] });
+    if let Some(description) = description {
+        optional["description"] = description;
+    }
+    optional
+}
+
+fn object(properties: &[(&str, Value)], required: &[&str]) -> Value {
+    let properties: Map<String, Value> = properties
+        .iter()
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+    let mut object = json!({ "type": "object", "properties": properties, "additionalProperties": false });
+    if !required.is_empty() {
+        object["required"] = json!(required);
+    }
+    object
+}
+
+fn tagged(variants: &[(&str, Value)], common: &[(&str, Value)]) -> Value {
+    let alternatives: Vec<Value> = variants
+        .iter()
+        .map(|(name, schema)| {
+            let mut properties = vec![(*name, schema.clone())];
+            properties.extend(common.iter().cloned());
+            object(&properties, &[name])
+        })
+        .collect();
+    json!({ "oneOf": alternatives })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::manifest::{
+            Manifest,
+            ManifestFormat,
+        },
+        anyhow::Result,
+    };
+
+    fn is_valid(root: &Value, schema: &Value, instance: &Value) -> bool {
+        if let Some(target) = schema["$ref"].as_str() {
+            let name = target.trim_start_matches("#/definitions/");
+            return is_valid(root, &root["definitions"][name], instance);
+        }
+        if let Some(alternatives) = schema["oneOf"].as_array() {
+            let valid = alternatives
+                .iter()
+                .filter(|alternative| is_valid(root, alternative, instance))
+                .count();
+            if valid != 1 {
+                return false;
+            }
+        }
+        if let Some(alternatives) = schema["anyOf"].as_array() {
+            if !alternatives
+                .iter()
+                .any(|alternative| is_valid(root, alternative, instance))
+            {
+                return false;
+            }
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(instance) {
+                return false;
+            }
+        }
+        let type_matches = match schema["type"].as_str() {
+            | None => true,
+            | Some("object") => instance.is_object(),
+            | Some("array") => instance.is_array(),
+            | Some("string") => instance.is_string(),
+            | Some("boolean") => instance.is_boolean(),
+            | Some("integer") => instance.is_u64() || instance.is_i64(),
+            | Some("number") => instance.is_number(),
+            | Some("null") => instance.is_null(),
+            | Some(other) => panic!("unsupported type {}", other),
+        };
+        if !type_matches {
+            return false;
+        }
+        if let (Some(items), Some(elements)) = (schema.get("items"), instance.as_array()) {
+            if !elements.iter().all(|element| is_valid(root, items, element)) {
+                return false;
+            }
+        }
+        let Some(object) = instance.as_object() else {
+            return true;
+        };
+        let required = schema["required"].as_array().into_iter().flatten();
+        if !required.filter_map(Value::as_str).all(|name| object.contains_key(name)) {
+            return false;
+        }
+        object.iter().all(|(name, value)| {
+            match (schema["properties"].get(name), schema.get("additionalProperties")) {
+                | (Some(property), _) => is_valid(root, property, value),
+                | (None, Some(Value::Bool(false))) => false,
+                | (None, Some(Value::Bool(true)) | None) => true,
+                | (None, Some(additional)) => is_valid(root, additional, value),
+            }
+        })
+    }
+
+    #[test]
+    fn describes_the_manifests_secenv_accepts() -> Result<()> {
+        let schema = manifest();
+        let example = serde_json::to_value(Manifest::example("secenv.conf".into()))?;
+        assert!(is_valid(&schema, &schema, &example));
+
+        let document = json!({
+            "version": "1.0.0",
+            "secrets": { "deploy": { "pgp": { "env": "DEPLOY_KEY" } } },
+            "providers": { "gcp": { "rate_limit": 10.0, "client": "rest" }, "op": { "rate_limit": 2 } },
+            "env": { "vars": { "REGION": { "plain": { "literal": "eu" }, "type": "string" } } },
+            "profiles": {
+                "prod": {
+                    "confirm": "prod",
+                    "policy": { "hosts": ["ci-*"], "require_tty": false },
+                    "env": {
+                        "keep": ["PATH"],
+                        "vars": {
+                            "TOKEN": { "secure": { "secret": { "ref": "deploy" }, "value": { "base64": "eA==" } } },
+                            "TRANSIT": { "secure": { "secret": { "vault_transit": { "key": "app" } }, "value": { "literal": "vault:v1:x" } } },
+                            "CODE": { "totp": { "seed": { "keyring": { "service": "mfa", "account": "aws" } }, "digits": 6 } },
+                            "URL": {
+                                "composite": {
+                                    "template": "postgres://{user}@db",
+                                    "parts": { "user": { "op": "op://dev/db/user" } }
+                                },
+                                "when": "env.CI"
+                            },
+                            "HOSTS": { "list": ["a", "b"], "join": ";" },
+                            "PORT": { "gcs": { "secret": "projects/p/secrets/port" }, "default": { "plain": { "literal": "8080" } } }
+                        },
+                        "from": [
+                            { "vault": { "path": "database/creds/app", "auth": { "approle": { "role_id": "ci", "secret_id": { "env": "SECRET_ID" } } } } },
+                            { "http": { "url": "https://config.internal", "auth": { "bearer": { "file": "/run/token" } } }, "when": "!env.CI", "default": { "file": "./local.env" } }
+                        ]
+                    },
+                    "files": {
+                        "./tls.key": { "vault_pki": { "role": "web", "common_name": "app", "part": "private_key" }, "permissions": "0600" },
+                        "./ca.pem": { "file": "/etc/ca.pem", "mode": "symlink" }
+                    },
+                    "sealed": { "files": { "./app.conf": { "secret": { "ref": "deploy" } } } }
+                }
+            }
+        });
+        Manifest::parse(&document.to_string(), ManifestFormat::Hocon)?;
+        assert!(is_valid(&schema, &schema, &document));
+
+        for typo in [
+            json!({ "profiles": {} }),
+            json!({ "version": "1.0.0", "profile": {} }),
+            json!({ "version": "1.0.0", "env": { "vars": { "X": { "plain": { "literal": "x" }, "wen": "env.CI" } } } }),
+            json!({ "version": "1.0.0", "env": { "vars": { "X": { "plain": { "literal": "x" }, "file": "./x" } } } }),
+            json!({ "version": "1.0.0", "env": { "vars": { "X": { "gcs": { "secrets": "projects/p/secrets/x" } } } } }),
+            json!({ "version": "1.0.0", "env": { "from": [{ "gcs_all": {} }] } }),
+            json!({ "version": "1.0.0", "profiles": { "dev": { "files": { "x": { "plain": { "literal": "x" }, "mode": "move" } } } } }),
+        ] {
+            assert!(!is_valid(&schema, &schema, &typo), "{} is valid", typo);
+        }
+        Ok(())
+    }
+}
@@ -5,7 +5,15 @@ use std::os::unix::fs::{
 };
 use {
     crate::{
+        failure::Failure,
+        ledger::{
+            LedgerEntry,
+            RunLedger,
+        },
         manifest::{
+            BackupMode,
+            FileMode,
+            ManifestFile,
             SealedFile,
             SealedFiles,
             SealedSecret,
@@ -26,6 +34,10 @@ use {
     },
     path_clean::PathClean,
     serde_json::Value,
+    sha2::{
+        Digest,
+        Sha256,
+    },
     std::{
         collections::{
             HashMap,
@@ -78,16 +90,36 @@ enum CleanupAction {
         path: PathBuf,
         contents: Vec<u8>,
         permissions: Permissions,
+        written: Option<[u8; 32]>,
+    },
+    RestoreBackup {
+        path: PathBuf,
+        backup: PathBuf,
+        written: Option<[u8; 32]>,
     },
     Remove {
         path: PathBuf,
+        written: Option<[u8; 32]>,
     },
 }
 
 impl CleanupAction {
     fn path(&self) -> &Path {
         match self {
-            | Self::Restore { path, .. } | Self::Remove { path } => path,
+            | Self::Restore { path, .. } | Self::RestoreBackup { path, .. } | Self::Remove { path, .. } => path,
+        }
+    }
+
+    fn ledger_entry(&self) -> LedgerEntry {
+        match self {
+            | Self::Restore { path, .. } => LedgerEntry::Restore { path: path.clone() },
+            | Self::RestoreBackup { path, backup, .. } => {
+                LedgerEntry::RestoreBackup {
+                    path: path.clone(),
+                    backup: backup.clone(),
+                }
+            },
+            | Self::Remove { path, .. } => LedgerEntry::Remove { path: path.clone() },
         }
     }
 
@@ -97,8 +129,18 @@ impl CleanupAction {
                 path,
                 contents,
                 permissions,
-            } => FileStorage::write_atomic(path, contents, Some(permissions.clone()), ReplaceMode::Overwrite),
-            | Self::Remove { path } => {
+                written,
+            } => {
+                FileStorage::warn_if_modified(path, written);
+                FileStorage::write_atomic(path, contents, Some(permissions.clone()), ReplaceMode::Overwrite)
+            },
+            | Self::RestoreBackup { path, backup, written } => {
+                FileStorage::warn_if_modified(path, written);
+                std::fs::rename(&backup, &path)
+                    .with_context(|| format!("Failed to restore backup '{}'", backup.display()))
+            },
+            | Self::Remove { path, written } => {
+                FileStorage::warn_if_modified(path, written);
                 match std::fs::remove_file(&path) {
                     | Ok(()) => Ok(()),
                     | Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
@@ -124,6 +166,7 @@ impl Drop for CleanupAction {
 pub(crate) struct SealedFileRestorer {
     actions: Arc<Mutex<Vec<CleanupAction>>>,
     lifecycle: Arc<Mutex<LifecycleState>>,
+    ledger: Option<Arc<RunLedger>>,
 }
 
 enum LifecycleState {
@@ -132,18 +175,23 @@ enum LifecycleState {
 }
 
 impl SealedFileRestorer {
-    fn push(&self, action: CleanupAction) {
+    /// Recorded in the state ledger first, so `secenv clean` can undo it.
+    fn push(&self, action: CleanupAction) -> Result<()> {
+        if let Some(ledger) = &self.ledger {
+            ledger.record(&action.ledger_entry())?;
+        }
         self.actions
             .lock()
             .unwrap_or_else(std::sync::PoisonError::into_inner)
             .push(action);
+        Ok(())
     }
 
     fn discard_remove(&self, path: &Path) {
         self.actions
             .lock()
             .unwrap_or_else(std::sync::PoisonError::into_inner)
-            .retain(|action| !matches!(action, CleanupAction::Remove { path: action_path } if action_path == path));
+            .retain(|action| !matches!(action, CleanupAction::Remove { path: action_path, .. } if action_path == path));
     }
 
     fn while_active<T>(&self, operation: impl FnOnce() -> Result<T>) -> Result<T> {
@@ -179,6 +227,9 @@ impl SealedFileRestorer {
             anyhow::bail!("Failed to restore sealed file(s): {}", failures.join("; "));
         }
 
+        if let Some(ledger) = &self.ledger {
+            ledger.complete()?;
+        }
         Ok(())
     }
 }
@@ -205,6 +256,30 @@ struct SelectedFile<'a> {
     secret: &'a SealedSecretWrapper,
 }
 
+pub(crate) enum GeneratedFile {
+    Write(Zeroizing<String>),
+    Append(Zeroizing<String>),
+    Copy(Zeroizing<Vec<u8>>),
+    Symlink(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PersistedState {
+    InSync,
+    Drifted,
+    Missing,
+}
+
+impl std::fmt::Display for PersistedState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            | Self::InSync => "in sync",
+            | Self::Drifted => "drifted",
+            | Self::Missing => "missing",
+        })
+    }
+}
+
 pub(crate) struct ResolvedSealedSecret {
     algorithm: SealedAlgorithm,
     value: Zeroizing<String>,
@@ -228,6 +303,26 @@ impl ResolvedSealedSecret {
         })
     }
 
+    /// `recipient` is a public key to use instead of a configured key.
+    pub(crate) fn for_sealing(
+        secret: &SealedSecretWrapper,
+        recipient: Option<&str>,
+        removed_env_vars: &[String],
+    ) -> Result<Self> {
+        match (recipient, &secret.inner) {
+            | (None, _) => Self::load(secret, removed_env_vars),
+            | (Some(certificate), SealedSecret::Pgp(_)) => {
+                Ok(Self {
+                    algorithm: SealedAlgorithm::Pgp,
+                    value: Zeroizing::new(certificate.to_string()),
+                })
+            },
+            | (Some(_), SealedSecret::Argon2idXchacha20Poly1305(_)) => {
+                anyhow::bail!("A recipient can only be used with PGP sealed secrets")
+            },
+        }
+    }
+
     pub(crate) fn seal_marker(&self, plaintext: &str, pgp_manager: &PgpManager) -> Result<String> {
         let ciphertext = match self.algorithm {
             | SealedAlgorithm::Pgp => {
@@ -244,7 +339,7 @@ impl ResolvedSealedSecret {
         ))
     }
 
-    pub(crate) fn open_marker(&self, marker: &str, pgp_manager: &mut PgpManager) -> Result<String> {
+    pub(crate) fn open_marker(&self, marker: &str, pgp_manager: &PgpManager) -> Result<String> {
         let ciphertext = SealedDocument::decode_marker_for(marker, self.algorithm)
             .context("Invalid sealed value")?
             .context("Value must be a complete ENC[<algorithm>,<base64>] marker")?;
@@ -252,7 +347,7 @@ impl ResolvedSealedSecret {
             .context("Failed to decrypt sealed value")
     }
 
-    fn decrypt_payload(&self, ciphertext: &[u8], pgp_manager: &mut PgpManager) -> Result<String> {
+    fn decrypt_payload(&self, ciphertext: &[u8], pgp_manager: &PgpManager) -> Result<String> {
         match self.algorithm {
             | SealedAlgorithm::Pgp => pgp_manager.decrypt_bytes(self.value.as_str(), ciphertext),
             | SealedAlgorithm::Argon2idXchacha20Poly1305 => PasswordCipher::decrypt(self.value.as_str(), ciphertext),
@@ -285,10 +380,16 @@ impl SealedFileManager {
             restorer: SealedFileRestorer {
                 actions: Arc::new(Mutex::new(Vec::new())),
                 lifecycle: Arc::new(Mutex::new(LifecycleState::Active)),
+                ledger: None,
             },
         })
     }
 
+    pub(crate) fn with_ledger(mut self, ledger: RunLedger) -> Self {
+        self.restorer.ledger = Some(Arc::new(ledger));
+        self
+    }
+
     #[cfg(test)]
     fn restorer(&self) -> SealedFileRestorer {
         self.restorer.clone()
@@ -297,16 +398,17 @@ impl SealedFileManager {
     pub(crate) fn unseal<C>(
         &self,
         config: &SealedFiles,
-        generated_files: &[String],
+        generated_files: &HashMap<String, ManifestFile>,
         removed_env_vars: &[String],
-        pgp_manager: &mut PgpManager,
+        pgp_manager: &PgpManager,
         force: bool,
         mut cancelled: C,
     ) -> Result<()>
     where
         C: FnMut() -> bool,
     {
-        let prepared = self.prepare(&config.files, &config.templates, generated_files, force)?;
+        let generated_paths: Vec<_> = generated_files.keys().cloned().collect();
+        let prepared = self.prepare(&config.files, &config.templates, &generated_paths, force)?;
         if prepared.is_empty() {
             return Ok(());
         }
@@ -344,48 +446,138 @@ impl SealedFileManager {
     pub(crate) fn validate_profile(
         &self,
         sealed: Option<&SealedFiles>,
-        generated_files: &[String],
+        generated_files: &HashMap<String, ManifestFile>,
         force: bool,
     ) -> Result<()> {
         let mut outputs = HashSet::new();
-        for configured_path in generated_files {
+        let mut generated_entries: Vec<_> = generated_files.iter().collect();
+        generated_entries.sort_by_key(|(path, _)| *path);
+        for (configured_path, file) in generated_entries {
             let (path, exists) = self.output_file(configured_path)?;
-            if exists && !force {
-                anyhow::bail!(
+            if exists && !force && file.backup.is_none() && file.mode != FileMode::Append && !file.persist {
+                return Err(Failure::FileConflict.error(format!(
                     "File '{}' already exists. Use --force to overwrite it temporarily.",
                     path.display()
-                );
+                )));
             }
             if !outputs.insert(path) {
                 anyhow::bail!("Profile file '{}' is configured more than once", configured_path);
             }
         }
         if let Some(sealed) = sealed {
-            self.prepare(&sealed.files, &sealed.templates, generated_files, force)?;
+            let generated_paths: Vec<_> = generated_files.keys().cloned().collect();
+            self.prepare(&sealed.files, &sealed.templates, &generated_paths, force)?;
         }
         Ok(())
     }
 
-    pub(crate) fn write_generated(&self, configured_path: &str, contents: &str, force: bool) -> Result<()> {
+    pub(crate) fn read_copy_source(&self, configured_source: &str) -> Result<GeneratedFile> {
+        let source = self.resolve(configured_source);
+        let metadata = std::fs::metadata(&source)
+            .with_context(|| format!("Failed to inspect copy source '{}'", source.display()))?;
+        if !metadata.is_file() {
+            anyhow::bail!("Copy source '{}' is not a regular file", source.display());
+        }
+        let contents = Zeroizing::new(
+            std::fs::read(&source).with_context(|| format!("Failed to read copy source '{}'", source.display()))?,
+        );
+        Ok(GeneratedFile::Copy(contents))
+    }
+
+    pub(crate) fn symlink_target(&self, configured_target: &str) -> GeneratedFile {
+        GeneratedFile::Symlink(self.resolve(configured_target))
+    }
+
+    /// New files get `permissions` (0600 by default) regardless of the umask.
+    pub(crate) fn materialize(
+        &self,
+        configured_path: &str,
+        file: &GeneratedFile,
+        backup: Option<BackupMode>,
+        permissions: Option<u32>,
+    ) -> Result<()> {
+        let permissions = permissions.and_then(FileStorage::permissions_from_mode);
+        match file {
+            | GeneratedFile::Write(contents) => {
+                self.write_output(configured_path, contents.as_bytes(), permissions, backup)
+            },
+            | GeneratedFile::Append(contents) => {
+                self.append_generated(
+                    configured_path,
+                    contents,
+                    backup.unwrap_or(BackupMode::Memory),
+                    permissions,
+                )
+            },
+            | GeneratedFile::Copy(contents) => self.write_output(configured_path, contents, permissions, backup),
+            | GeneratedFile::Symlink(target) => self.link_generated(configured_path, target, backup),
+        }
+    }
+
+    /// It stays after the run, so it is replaced without a backup.
+    pub(crate) fn persist(&self, configured_path: &str, file: &GeneratedFile, permissions: Option<u32>) -> Result<()> {
+        let permissions = permissions.and_then(FileStorage::permissions_from_mode);
+        let (contents, permissions) = match file {
+            | GeneratedFile::Write(contents) => (contents.as_bytes(), permissions),
+            | GeneratedFile::Copy(contents) => (contents.as_slice(), permissions),
+            | GeneratedFile::Append(_) | GeneratedFile::Symlink(_) => {
+                anyhow::bail!("Only write and copy entries can be persisted")
+            },
+        };
         self.restorer.while_active(|| {
             let (path, exists) = self.output_file(configured_path)?;
-            if exists && !force {
-                anyhow::bail!(
-                    "File '{}' already exists. Use --force to overwrite it temporarily.",
-                    path.display()
-                );
-            }
-            self.prepare_output_cleanup(&path, exists)?;
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)
-                    .with_context(|| format!("Failed to create directory for temporary file '{}'", path.display()))?;
-            }
+            Self::create_parent(&path)?;
             let replace_mode = if exists {
                 ReplaceMode::Overwrite
             } else {
                 ReplaceMode::Create
             };
-            let write_result = FileStorage::write_atomic(&path, contents.as_bytes(), None, replace_mode)
+            FileStorage::write_atomic(&path, contents, permissions, replace_mode)
+                .with_context(|| format!("Failed to write persisted file '{}'", path.display()))
+        })
+    }
+
+    /// Compares SHA-256 digests, so neither content is revealed.
+    pub(crate) fn persisted_state(&self, configured_path: &str, file: &GeneratedFile) -> Result<PersistedState> {
+        let expected = match file {
+            | GeneratedFile::Write(contents) => FileStorage::digest(contents.as_bytes()),
+            | GeneratedFile::Copy(contents) => FileStorage::digest(contents),
+            | GeneratedFile::Append(_) | GeneratedFile::Symlink(_) => {
+                anyhow::bail!("Only write and copy entries can be persisted")
+            },
+        };
+        let (path, exists) = self.output_file(configured_path)?;
+        if !exists {
+            return Ok(PersistedState::Missing);
+        }
+        let contents = Zeroizing::new(
+            std::fs::read(&path).with_context(|| format!("Failed to read persisted file '{}'", path.display()))?,
+        );
+        Ok(if FileStorage::digest(&contents) == expected {
+            PersistedState::InSync
+        } else {
+            PersistedState::Drifted
+        })
+    }
+
+    fn write_output(
+        &self,
+        configured_path: &str,
+        contents: &[u8],
+        permissions: Option<Permissions>,
+        backup: Option<BackupMode>,
+    ) -> Result<()> {
+        self.restorer.while_active(|| {
+            let (path, exists) = self.output_file(configured_path)?;
+            let backup = Self::existing_backup(&path, exists, backup)?;
+            self.prepare_output_cleanup(&path, exists, Some(FileStorage::digest(contents)), backup)?;
+            Self::create_parent(&path)?;
+            let replace_mode = if exists {
+                ReplaceMode::Overwrite
+            } else {
+                ReplaceMode::Create
+            };
+            let write_result = FileStorage::write_atomic(&path, contents, permissions, replace_mode)
                 .with_context(|| format!("Failed to write temporary file '{}'", path.display()));
             if write_result.is_err() && !exists {
                 self.restorer.discard_remove(&path);
@@ -394,6 +586,100 @@ impl SealedFileManager {
         })
     }
 
+    fn append_generated(
+        &self,
+        configured_path: &str,
+        contents: &str,
+        backup: BackupMode,
+        permissions: Option<Permissions>,
+    ) -> Result<()> {
+        let (path, exists) = self.output_file(configured_path)?;
+        if !exists {
+            return self.write_output(configured_path, contents.as_bytes(), permissions, None);
+        }
+        self.restorer.while_active(|| {
+            let (original, original_permissions) = FileStorage::read_original(&path)?;
+            let mut appended = Zeroizing::new(Vec::with_capacity(original.len() + contents.len() + 1));
+            appended.extend_from_slice(&original);
+            if !original.is_empty() && !original.ends_with(b"\n") {
+                appended.push(b'\n');
+            }
+            appended.extend_from_slice(contents.as_bytes());
+            let written = Some(FileStorage::digest(&appended));
+            match backup {
+                | BackupMode::Memory => {
+                    self.restorer.push(CleanupAction::Restore {
+                        path: path.clone(),
+                        contents: original,
+                        permissions: original_permissions.clone(),
+                        written,
+                    })?
+                },
+                | BackupMode::File => self.back_up_to_file(&path, written)?,
+            }
+            let permissions = permissions.clone().unwrap_or(original_permissions);
+            FileStorage::write_atomic(&path, &appended, Some(permissions), ReplaceMode::Overwrite)
+                .with_context(|| format!("Failed to append to file '{}'", path.display()))
+        })
+    }
+
+    fn link_generated(&self, configured_path: &str, target: &Path, backup: Option<BackupMode>) -> Result<()> {
+        self.restorer.while_active(|| {
+            let (path, exists) = self.output_file(configured_path)?;
+            let backup = Self::existing_backup(&path, exists, backup)?;
+            self.prepare_output_cleanup(&path, exists, None, backup)?;
+            Self::create_parent(&path)?;
+            if exists && path.exists() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to replace '{}' with a symbolic link", path.display()))?;
+            }
+            let link_result = FileStorage::symlink(target, &path)
+                .with_context(|| format!("Failed to link '{}' to '{}'", path.display(), target.display()));
+            if link_result.is_err() && !exists {
+                self.restorer.discard_remove(&path);
+            }
+            link_result
+        })
+    }
+
+    fn existing_backup(path: &Path, exists: bool, backup: Option<BackupMode>) -> Result<BackupMode> {
+        match backup {
+            | Some(backup) => Ok(backup),
+            | None if exists => {
+                Err(Failure::FileConflict.error(format!(
+                    "File '{}' already exists. Use --force or a backup mode to replace it temporarily.",
+                    path.display()
+                )))
+            },
+            | None => Ok(BackupMode::Memory),
+        }
+    }
+
+    fn back_up_to_file(&self, path: &Path, written: Option<[u8; 32]>) -> Result<()> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .context("System clock is before the Unix epoch")?
+            .as_secs();
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(format!(".bak.{}", timestamp));
+        let backup = PathBuf::from(backup);
+        FileStorage::copy_new(path, &backup)?;
+        self.restorer.push(CleanupAction::RestoreBackup {
+            path: path.to_path_buf(),
+            backup,
+            written,
+        })?;
+        Ok(())
+    }
+
+    fn create_parent(path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            FileStorage::create_private_dir_all(parent)
+                .with_context(|| format!("Failed to create directory for temporary file '{}'", path.display()))?;
+        }
+        Ok(())
+    }
+
     #[cfg(test)]
     fn while_active<T>(&self, operation: impl FnOnce() -> Result<T>) -> Result<T> {
         self.restorer.while_active(operation)
@@ -404,13 +690,15 @@ impl SealedFileManager {
         config: &SealedFiles,
         configured_file: &str,
         plaintext: &str,
+        recipient: Option<&str>,
         pgp_manager: &PgpManager,
         removed_env_vars: &[String],
     ) -> Result<String> {
         self.prepare(&config.files, &config.templates, &[], true)
             .context("Invalid sealed file configuration")?;
         let selected = Self::select_file(config, configured_file)?;
-        ResolvedSealedSecret::load(selected.secret, removed_env_vars)?.seal_marker(plaintext, pgp_manager)
+        ResolvedSealedSecret::for_sealing(selected.secret, recipient, removed_env_vars)?
+            .seal_marker(plaintext, pgp_manager)
     }
 
     pub(crate) fn seal_path(
@@ -418,6 +706,7 @@ impl SealedFileManager {
         config: &SealedFiles,
         configured_file: &str,
         json_pointer: &str,
+        recipient: Option<&str>,
         pgp_manager: &PgpManager,
         removed_env_vars: &[String],
     ) -> Result<String> {
@@ -429,7 +718,7 @@ impl SealedFileManager {
         let contents = Zeroizing::new(contents);
         let document = std::str::from_utf8(&contents)
             .with_context(|| format!("Sealed source '{}' is not valid UTF-8", source_path.display()))?;
-        let secret = ResolvedSealedSecret::load(selected.secret, removed_env_vars)?;
+        let secret = ResolvedSealedSecret::for_sealing(selected.secret, recipient, removed_env_vars)?;
         let (rendered, marker) = SealedDocument::seal_path_with(document, json_pointer, |plaintext| {
             secret.seal_marker(plaintext, pgp_manager)
         })?;
@@ -520,10 +809,10 @@ impl SealedFileManager {
             let (destination, destination_exists) = self.output_file(destination)?;
 
             if destination_exists && !force {
-                anyhow::bail!(
+                return Err(Failure::FileConflict.error(format!(
                     "Sealed template output '{}' already exists. Use --force to overwrite it temporarily.",
                     destination.display()
-                );
+                )));
             }
             if !targets.insert(destination.clone()) {
                 anyhow::bail!("Sealed target '{}' is configured more than once", destination.display());
@@ -616,7 +905,8 @@ impl SealedFileManager {
                     path: file.path.clone(),
                     contents,
                     permissions,
-                });
+                    written: Some(FileStorage::digest(decrypted.as_bytes())),
+                })?;
                 let write_result =
                     FileStorage::write_atomic(&file.path, decrypted.as_bytes(), None, ReplaceMode::Overwrite)
                         .with_context(|| format!("Failed to replace sealed file '{}'", file.path.display()));
@@ -624,9 +914,14 @@ impl SealedFileManager {
             }
 
             for (template, decrypted) in materialized_templates {
-                self.prepare_output_cleanup(&template.destination, template.destination_exists)?;
+                self.prepare_output_cleanup(
+                    &template.destination,
+                    template.destination_exists,
+                    Some(FileStorage::digest(decrypted.as_bytes())),
+                    BackupMode::Memory,
+                )?;
                 if let Some(parent) = template.destination.parent() {
-                    std::fs::create_dir_all(parent).with_context(|| {
+                    FileStorage::create_private_dir_all(parent).with_context(|| {
                         format!(
                             "Failed to create sealed template output directory '{}'",
                             parent.display()
@@ -656,24 +951,33 @@ impl SealedFileManager {
         })
     }
 
-    fn prepare_output_cleanup(&self, path: &Path, expected_exists: bool) -> Result<()> {
+    fn prepare_output_cleanup(
+        &self,
+        path: &Path,
+        expected_exists: bool,
+        written: Option<[u8; 32]>,
+        backup: BackupMode,
+    ) -> Result<()> {
         match (expected_exists, path.try_exists()) {
             | (_, Err(error)) => {
                 Err(error).with_context(|| format!("Failed to inspect temporary output '{}'", path.display()))
             },
+            | (true, Ok(true)) if backup == BackupMode::File => self.back_up_to_file(path, written),
             | (true, Ok(true)) => {
                 let (contents, permissions) = FileStorage::read_original(path)?;
                 self.restorer.push(CleanupAction::Restore {
                     path: path.to_path_buf(),
                     contents,
                     permissions,
-                });
+                    written,
+                })?;
                 Ok(())
             },
             | (false, Ok(false)) => {
                 self.restorer.push(CleanupAction::Remove {
                     path: path.to_path_buf(),
-                });
+                    written,
+                })?;
                 Ok(())
             },
             | _ => {
@@ -788,12 +1092,12 @@ impl SealedFileManager {
 impl Drop for SealedFileManager {
     fn drop(&mut self) {
         if let Err(error) = self.restorer.restore_all() {
-            eprintln!("WARNING: {}", error);
+            crate::output::warning!("{}", error);
         }
     }
 }
 
-struct SealedDocument;
+pub(crate) struct SealedDocument;
 
 struct SensitiveValue(Value);
 
@@ -838,7 +1142,7 @@ impl Drop for SensitiveValue {
 }
 
 impl SealedDocument {
-    fn decrypt(document: &str, secret: &ResolvedSealedSecret, pgp_manager: &mut PgpManager) -> Result<String> {
+    fn decrypt(document: &str, secret: &ResolvedSealedSecret, pgp_manager: &PgpManager) -> Result<String> {
         Self::decrypt_with(document, secret.algorithm, |_marker_algorithm, ciphertext| {
             secret.decrypt_payload(ciphertext, pgp_manager)
         })
@@ -1026,7 +1330,7 @@ impl SealedDocument {
         Ok(Some(ciphertext))
     }
 
-    fn zeroize_value(value: &mut Value) {
+    pub(crate) fn zeroize_value(value: &mut Value) {
         match value {
             | Value::String(value) => value.zeroize(),
             | Value::Array(values) => {
@@ -1065,6 +1369,82 @@ impl FileStorage {
         Ok((contents, metadata.permissions()))
     }
 
+    fn digest(contents: &[u8]) -> [u8; 32] {
+        Sha256::digest(contents).into()
+    }
+
+    fn modified_since_written(path: &Path, written: &[u8; 32]) -> bool {
+        match std::fs::read(path) {
+            | Ok(mut contents) => {
+                let modified = Self::digest(&contents) != *written;
+                contents.zeroize();
+                modified
+            },
+            | Err(_) => false,
+        }
+    }
+
+    fn warn_if_modified(path: &Path, written: &Option<[u8; 32]>) {
+        if written.is_some_and(|written| Self::modified_since_written(path, &written)) {
+            crate::output::warning!(
+                "'{}' was modified after secenv wrote it; cleaning it up anyway.",
+                path.display()
+            );
+        }
+    }
+
+    /// Existing directories keep their permissions.
+    fn create_private_dir_all(directory: &Path) -> std::io::Result<()> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(directory)
+    }
+
+    #[cfg(unix)]
+    fn permissions_from_mode(mode: u32) -> Option<Permissions> {
+        Some(Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn permissions_from_mode(_mode: u32) -> Option<Permissions> {
+        None
+    }
+
+    fn copy_new(source: &Path, destination: &Path) -> Result<()> {
+        let mut input =
+            std::fs::File::open(source).with_context(|| format!("Failed to open '{}' for backup", source.display()))?;
+        let permissions = input.metadata()?.permissions();
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // Readable by the owner only until the copy is complete, so the
+        // backup never exposes the contents more widely than the original.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, permissions.mode() & 0o700);
+        let mut output = options
+            .open(destination)
+            .with_context(|| format!("Failed to create backup '{}'", destination.display()))?;
+        std::io::copy(&mut input, &mut output)
+            .with_context(|| format!("Failed to write backup '{}'", destination.display()))?;
+        output
+            .set_permissions(permissions)
+            .with_context(|| format!("Failed to set permissions for backup '{}'", destination.display()))?;
+        output
+            .sync_all()
+            .with_context(|| format!("Failed to sync backup '{}'", destination.display()))
+    }
+
+    #[cfg(unix)]
+    fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    #[cfg(windows)]
+    fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+
     #[cfg(unix)]
     fn ensure_single_link(metadata: &std::fs::Metadata, path: &Path) -> Result<()> {
         if metadata.nlink() > 1 {
@@ -1105,9 +1485,8 @@ impl FileStorage {
         let mut temporary = tempfile::NamedTempFile::new_in(parent)
             .with_context(|| format!("Failed to create temporary file beside '{}'", path.display()))?;
 
-        temporary
-            .write_all(contents)
-            .with_context(|| format!("Failed to write temporary file for '{}'", path.display()))?;
+        // Apply the final permissions before any content is written so the
+        // plaintext is never readable with broader permissions.
         if let Some(permissions) = restore_permissions {
             temporary
                 .as_file()
@@ -1120,6 +1499,9 @@ impl FileStorage {
                 .set_permissions(Permissions::from_mode(0o600))
                 .with_context(|| format!("Failed to secure temporary file for '{}'", path.display()))?;
         }
+        temporary
+            .write_all(contents)
+            .with_context(|| format!("Failed to write temporary file for '{}'", path.display()))?;
         temporary
             .as_file_mut()
             .sync_all()
@@ -1131,7 +1513,18 @@ impl FileStorage {
         persist_result
             .map_err(|error| error.error)
             .with_context(|| format!("Failed to atomically replace '{}'", path.display()))?;
+        Self::sync_directory(parent)
+    }
 
+    #[cfg(unix)]
+    fn sync_directory(directory: &Path) -> Result<()> {
+        std::fs::File::open(directory)
+            .and_then(|directory| directory.sync_all())
+            .with_context(|| format!("Failed to sync directory '{}'", directory.display()))
+    }
+
+    #[cfg(not(unix))]
+    fn sync_directory(_directory: &Path) -> Result<()> {
         Ok(())
     }
 }
@@ -1275,13 +1668,13 @@ mod tests {
             "token": password_marker("password-encrypted", passphrase)?,
         })
         .to_string();
-        let mut pgp_manager = PgpManager::default();
+        let pgp_manager = PgpManager::default();
         let secret = ResolvedSealedSecret {
             algorithm: SealedAlgorithm::Argon2idXchacha20Poly1305,
             value: Zeroizing::new(passphrase.to_string()),
         };
 
-        let rendered = SealedDocument::decrypt(&document, &secret, &mut pgp_manager)?;
+        let rendered = SealedDocument::decrypt(&document, &secret, &pgp_manager)?;
         let value: Value = serde_json::from_str(&rendered)?;
         assert_eq!(value["token"], "password-encrypted");
 
@@ -1289,7 +1682,7 @@ mod tests {
             algorithm: SealedAlgorithm::Pgp,
             value: Zeroizing::new("not-a-pgp-key".to_string()),
         };
-        let mismatch = SealedDocument::decrypt(&document, &pgp_secret, &mut pgp_manager);
+        let mismatch = SealedDocument::decrypt(&document, &pgp_secret, &pgp_manager);
         assert!(format!("{:#}", mismatch.unwrap_err()).contains("configured for PGP"));
         Ok(())
     }
@@ -1310,13 +1703,10 @@ mod tests {
             algorithm: SealedAlgorithm::Pgp,
             value: Zeroizing::new(private_key),
         };
-        let mut pgp_manager = PgpManager::default();
+        let pgp_manager = PgpManager::default();
 
         let marker = encrypting_secret.seal_marker("profile-value", &pgp_manager)?;
-        assert_eq!(
-            decrypting_secret.open_marker(&marker, &mut pgp_manager)?,
-            "profile-value"
-        );
+        assert_eq!(decrypting_secret.open_marker(&marker, &pgp_manager)?, "profile-value");
         Ok(())
     }
 
@@ -1462,8 +1852,18 @@ mod tests {
         fs::write(&existing, "original")?;
 
         let manager = SealedFileManager::new(directory.path().to_path_buf())?;
-        manager.write_generated(&created.display().to_string(), "created plaintext", false)?;
-        manager.write_generated(&existing.display().to_string(), "replacement plaintext", true)?;
+        manager.materialize(
+            &created.display().to_string(),
+            &GeneratedFile::Write(Zeroizing::new("created plaintext".to_owned())),
+            None,
+            None,
+        )?;
+        manager.materialize(
+            &existing.display().to_string(),
+            &GeneratedFile::Write(Zeroizing::new("replacement plaintext".to_owned())),
+            Some(BackupMode::Memory),
+            None,
+        )?;
         assert_eq!(fs::read_to_string(&created)?, "created plaintext");
         assert_eq!(fs::read_to_string(&existing)?, "replacement plaintext");
 
@@ -1482,7 +1882,12 @@ mod tests {
         let manager = SealedFileManager::new(manifest.source_directory()?)?;
 
         let output = project.join("generated.secret");
-        manager.write_generated("./generated.secret", "plaintext", false)?;
+        manager.materialize(
+            "./generated.secret",
+            &GeneratedFile::Write(Zeroizing::new("plaintext".to_owned())),
+            None,
+            None,
+        )?;
         assert_eq!(fs::read_to_string(&output)?, "plaintext");
         manager.restore_all()?;
         assert!(!output.exists());
@@ -1542,7 +1947,14 @@ mod tests {
         let manager = SealedFileManager::new(directory.path().to_path_buf())?;
         let pgp_manager = PgpManager::default();
         let error = manager
-            .seal_value(&config, &source.display().to_string(), "plaintext", &pgp_manager, &[])
+            .seal_value(
+                &config,
+                &source.display().to_string(),
+                "plaintext",
+                None,
+                &pgp_manager,
+                &[],
+            )
             .unwrap_err();
         assert!(format!("{:#}", error).contains("also configured as an output"));
         Ok(())
@@ -1747,4 +2159,202 @@ mod tests {
         assert!(error.to_string().contains("hard-link aliases"));
         Ok(())
     }
+
+    #[test]
+    fn detects_files_modified_after_materialization() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("credentials.txt");
+        fs::write(&path, "written by secenv")?;
+        let written = FileStorage::digest(b"written by secenv");
+
+        assert!(!FileStorage::modified_since_written(&path, &written));
+        fs::write(&path, "edited by the command")?;
+        assert!(FileStorage::modified_since_written(&path, &written));
+        Ok(())
+    }
+
+    #[test]
+    fn atomic_writes_leave_no_partial_files() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let target = directory.path().join("credentials");
+        fs::write(&target, "previous")?;
+
+        FileStorage::write_atomic(&target, b"replacement", None, ReplaceMode::Overwrite)?;
+        assert_eq!(fs::read_to_string(&target)?, "replacement");
+        #[cfg(unix)]
+        assert_eq!(fs::metadata(&target)?.permissions().mode() & 0o777, 0o600);
+
+        let error = FileStorage::write_atomic(&target, b"clobbered", None, ReplaceMode::Create).unwrap_err();
+        assert!(error.to_string().contains("Failed to atomically replace"));
+        assert_eq!(fs::read_to_string(&target)?, "replacement");
+        assert_eq!(fs::read_dir(directory.path())?.count(), 1);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn backups_keep_the_original_mode() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        for mode in [0o600, 0o640] {
+            let original = directory.path().join(format!("original-{:o}", mode));
+            let backup = directory.path().join(format!("backup-{:o}", mode));
+            fs::write(&original, "secret")?;
+            fs::set_permissions(&original, Permissions::from_mode(mode))?;
+            FileStorage::copy_new(&original, &backup)?;
+            assert_eq!(fs::read_to_string(&backup)?, "secret");
+            assert_eq!(fs::metadata(&backup)?.permissions().mode() & 0o777, mode);
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn materialized_files_ignore_the_umask() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let default = directory.path().join("nested/deeper/default.secret");
+        let configured = directory.path().join("configured.secret");
+
+        let manager = SealedFileManager::new(directory.path().to_path_buf())?;
+        let secret = GeneratedFile::Write(Zeroizing::new("secret".to_string()));
+        manager.materialize(&default.display().to_string(), &secret, None, None)?;
+        manager.materialize(&configured.display().to_string(), &secret, None, Some(0o640))?;
+
+        assert_eq!(fs::metadata(&default)?.permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(&configured)?.permissions().mode() & 0o777, 0o640);
+        for created in [directory.path().join("nested"), directory.path().join("nested/deeper")] {
+            assert_eq!(fs::metadata(&created)?.permissions().mode() & 0o777, 0o700);
+        }
+        manager.restore_all()?;
+        Ok(())
+    }
+
+    #[test]
+    fn existing_targets_are_backed_up_and_restored() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let in_memory = directory.path().join("memory.conf");
+        let on_disk = directory.path().join("disk.conf");
+        fs::write(&in_memory, "original memory")?;
+        fs::write(&on_disk, "original disk")?;
+
+        let manager = SealedFileManager::new(directory.path().to_path_buf())?;
+        let secret = GeneratedFile::Write(Zeroizing::new("secret".to_string()));
+        let error = manager
+            .materialize(&in_memory.display().to_string(), &secret, None, None)
+            .unwrap_err();
+        assert!(error.to_string().contains("already exists"));
+
+        manager.materialize(
+            &in_memory.display().to_string(),
+            &secret,
+            Some(BackupMode::Memory),
+            None,
+        )?;
+        manager.materialize(&on_disk.display().to_string(), &secret, Some(BackupMode::File), None)?;
+        assert_eq!(fs::read_to_string(&in_memory)?, "secret");
+        assert_eq!(fs::read_to_string(&on_disk)?, "secret");
+        let backups: Vec<_> = fs::read_dir(directory.path())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("disk.conf.bak."))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(backups[0].path())?, "original disk");
+
+        manager.restore_all()?;
+        assert_eq!(fs::read_to_string(&in_memory)?, "original memory");
+        assert_eq!(fs::read_to_string(&on_disk)?, "original disk");
+        assert!(!backups[0].path().exists());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn appended_and_linked_files_are_restored() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let netrc = directory.path().join("netrc");
+        let artifact = directory.path().join("artifact.bin");
+        let link = directory.path().join("current.bin");
+        fs::write(&netrc, "machine existing")?;
+        fs::write(&artifact, "cached")?;
+
+        let manager = SealedFileManager::new(directory.path().to_path_buf())?;
+        manager.materialize(
+            &netrc.display().to_string(),
+            &GeneratedFile::Append(Zeroizing::new("machine secret".to_string())),
+            None,
+            None,
+        )?;
+        manager.materialize(
+            &link.display().to_string(),
+            &manager.symlink_target("./artifact.bin"),
+            None,
+            None,
+        )?;
+        assert_eq!(fs::read_to_string(&netrc)?, "machine existing\nmachine secret");
+        assert_eq!(fs::read_link(&link)?, artifact.canonicalize()?);
+
+        manager.restore_all()?;
+        assert_eq!(fs::read_to_string(&netrc)?, "machine existing");
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(fs::read_to_string(&artifact)?, "cached");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copied_and_appended_files_get_the_configured_mode() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let source = directory.path().join("defaults.json");
+        let copy = directory.path().join("settings.json");
+        let netrc = directory.path().join("netrc");
+        fs::write(&source, "{}")?;
+        fs::set_permissions(&source, Permissions::from_mode(0o644))?;
+        fs::write(&netrc, "machine existing")?;
+        fs::set_permissions(&netrc, Permissions::from_mode(0o644))?;
+
+        let manager = SealedFileManager::new(directory.path().to_path_buf())?;
+        manager.materialize(
+            &copy.display().to_string(),
+            &manager.read_copy_source("./defaults.json")?,
+            None,
+            None,
+        )?;
+        manager.materialize(
+            &netrc.display().to_string(),
+            &GeneratedFile::Append(Zeroizing::new("machine secret".to_string())),
+            None,
+            Some(0o600),
+        )?;
+        assert_eq!(fs::metadata(&copy)?.permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(&netrc)?.permissions().mode() & 0o777, 0o600);
+
+        manager.restore_all()?;
+        assert_eq!(fs::read_to_string(&netrc)?, "machine existing");
+        assert_eq!(fs::metadata(&netrc)?.permissions().mode() & 0o777, 0o644);
+        Ok(())
+    }
+
+    #[test]
+    fn persisted_files_stay_and_report_drift() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let credentials = directory.path().join("credentials.json");
+        let configured = credentials.display().to_string();
+        let content = GeneratedFile::Write(Zeroizing::new("{\"token\": \"s3cret\"}".to_string()));
+
+        let manager = SealedFileManager::new(directory.path().to_path_buf())?;
+        assert_eq!(manager.persisted_state(&configured, &content)?, PersistedState::Missing);
+        manager.persist(&configured, &content, None)?;
+        manager.restore_all()?;
+        assert_eq!(fs::read_to_string(&credentials)?, "{\"token\": \"s3cret\"}");
+
+        let manager = SealedFileManager::new(directory.path().to_path_buf())?;
+        assert_eq!(manager.persisted_state(&configured, &content)?, PersistedState::InSync);
+        fs::write(&credentials, "{\"token\": \"edited\"}")?;
+        assert_eq!(manager.persisted_state(&configured, &content)?, PersistedState::Drifted);
+        manager.persist(&configured, &content, None)?;
+        assert_eq!(manager.persisted_state(&configured, &content)?, PersistedState::InSync);
+
+        let appended = GeneratedFile::Append(Zeroizing::new("line".to_string()));
+        assert!(manager.persist(&configured, &appended, None).is_err());
+        Ok(())
+    }
 }
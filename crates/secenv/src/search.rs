@@ -0,0 +1,125 @@
+use {
+    crate::manifest::{
+        Manifest,
+        SourceReference,
+    },
+    regex::Regex,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SearchHit {
+    pub(crate) profile: String,
+    /// E.g. `env.vars.TOKEN`.
+    pub(crate) location: String,
+    pub(crate) reference: SourceReference,
+}
+
+impl std::fmt::Display for SearchHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} ({})", self.profile, self.location, self.reference)
+    }
+}
+
+/// Only names, paths, and references are searched; values never are.
+pub(crate) fn search(manifest: &Manifest, pattern: &Regex) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    let profiles = manifest
+        .profiles
+        .iter()
+        .map(|(name, profile)| (name.as_str(), &profile.env, Some(profile)));
+    for (profile_name, env, profile) in std::iter::once(("*", &manifest.env, None)).chain(profiles) {
+        let mut candidates = Vec::new();
+        for (name, value) in &env.vars {
+            candidates.push((
+                format!("env.vars.{}", name),
+                vec![name.as_str()],
+                value.inner.reference(),
+            ));
+        }
+        for (index, from) in env.from.iter().enumerate() {
+            candidates.push((format!("env.from[{}]", index), Vec::new(), from.inner.reference()));
+        }
+        for (path, file) in profile.iter().flat_map(|profile| &profile.files) {
+            candidates.push((format!("files.{}", path), vec![path.as_str()], file.inner.reference()));
+        }
+        if let Some(sealed) = profile.and_then(|profile| profile.sealed.as_ref()) {
+            for (path, file) in &sealed.files {
+                candidates.push((
+                    format!("sealed.files.{}", path),
+                    vec![path.as_str()],
+                    file.secret.reference(),
+                ));
+            }
+            for (path, template) in &sealed.templates {
+                candidates.push((
+                    format!("sealed.templates.{}", path),
+                    vec![path.as_str(), template.source.as_str()],
+                    template.secret.reference(),
+                ));
+            }
+        }
+
+        for (location, names, reference) in candidates {
+            let reference_matches = reference
+                .reference
+                .as_deref()
+                .into_iter()
+                .chain([reference.backend])
+                .any(|haystack| pattern.is_match(haystack));
+            if reference_matches || names.iter().any(|name| pattern.is_match(name)) {
+                hits.push(SearchHit {
+                    profile: profile_name.to_string(),
+                    location,
+                    reference,
+                });
+            }
+        }
+    }
+    hits.sort_by(|left, right| (&left.profile, &left.location).cmp(&(&right.profile, &right.location)));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::manifest::ManifestFormat,
+        anyhow::Result,
+    };
+
+    #[test]
+    fn matches_names_paths_and_references_but_not_values() -> Result<()> {
+        let manifest = Manifest::parse(
+            r#"
+version = "0.0.0"
+profiles.default.env.vars {
+  DB_URL.gcs.secret = "projects/legacy/secrets/db-url"
+  API_TOKEN.gcs { secret = "projects/main/secrets/token", version = "3" }
+  GREETING.plain.literal = "legacy hello"
+}
+profiles.default.env.from = [{ aws_all { filter_tags { team = "legacy" } } }]
+env.vars.REGION.gcs.secret = "projects/legacy/secrets/region"
+profiles.default.files { "legacy.pem" { aws { secret = "prod/tls" } } }
+profiles.staging = ${profiles.default}
+profiles.staging.env.vars.DB_URL.gcs.secret = "projects/main/secrets/db-url"
+"#,
+            ManifestFormat::Hocon,
+        )?;
+
+        let hits = search(&manifest, &Regex::new("legacy")?);
+        assert_eq!(hits.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+            "*: env.vars.REGION (gcs projects/legacy/secrets/region)",
+            "default: env.from[0] (aws_all team=legacy)",
+            "default: env.vars.DB_URL (gcs projects/legacy/secrets/db-url)",
+            "default: files.legacy.pem (aws prod/tls)",
+            "staging: env.from[0] (aws_all team=legacy)",
+            "staging: files.legacy.pem (aws prod/tls)",
+        ]);
+
+        let hits = search(&manifest, &Regex::new("^API_")?);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].reference.to_string(), "gcs projects/main/secrets/token@3");
+        assert!(search(&manifest, &Regex::new("hello")?).is_empty());
+        Ok(())
+    }
+}
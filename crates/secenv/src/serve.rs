@@ -0,0 +1,574 @@
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    rand::{
+        rngs::OsRng,
+        RngCore,
+    },
+    std::{
+        collections::BTreeMap,
+        fmt::Write as _,
+        io::Write,
+        net::SocketAddr,
+        path::Path,
+        sync::{
+            Arc,
+            Mutex,
+            PoisonError,
+        },
+        time::Duration,
+    },
+    tokio::{
+        io::{
+            AsyncReadExt,
+            AsyncWriteExt,
+        },
+        net::{
+            TcpListener,
+            TcpStream,
+        },
+    },
+    zeroize::Zeroizing,
+};
+
+/// Requests have no body.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves resolved values on loopback to clients presenting the bearer token.
+pub(crate) struct SecretServer {
+    listener: TcpListener,
+    token: Zeroizing<String>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+struct State {
+    token: Zeroizing<String>,
+    values: BTreeMap<String, Zeroizing<String>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+/// Answers only `/metrics`, so scrapers need no token that can read values.
+pub(crate) struct MetricsServer {
+    listener: TcpListener,
+    metrics: Arc<Metrics>,
+}
+
+/// Only variable names and counts are reported, never values.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    resolve: BTreeMap<&'static str, (Duration, usize)>,
+    pgp_decryptions: usize,
+    pgp_cache_hits: usize,
+    password_prompts: usize,
+    requests: Mutex<BTreeMap<u16, u64>>,
+    reads: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Metrics {
+    pub(crate) fn new(timings: &[(&'static str, Duration)], pgp_manager: &crate::pgp::PgpManager) -> Self {
+        let mut resolve = BTreeMap::<_, (Duration, usize)>::new();
+        for (backend, duration) in timings {
+            let entry = resolve.entry(*backend).or_default();
+            entry.0 += *duration;
+            entry.1 += 1;
+        }
+        Self {
+            resolve,
+            pgp_decryptions: pgp_manager.decryptions(),
+            pgp_cache_hits: pgp_manager.cache_hits(),
+            password_prompts: pgp_manager.password_prompts(),
+            ..Self::default()
+        }
+    }
+
+    fn record_request(&self, status: u16) {
+        *self
+            .requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(status)
+            .or_default() += 1;
+    }
+
+    fn record_read(&self, name: &str) {
+        *self
+            .reads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(name.to_string())
+            .or_default() += 1;
+    }
+
+    fn render(&self, variables: usize) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(text, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(text, "{}{} {}", name, labels, value);
+            }
+        };
+        let unlabeled = |value: usize| vec![(String::new(), value.to_string())];
+
+        metric(
+            "secenv_variables",
+            "gauge",
+            "Variables being served.",
+            &unlabeled(variables),
+        );
+        let requests = self.requests.lock().unwrap_or_else(PoisonError::into_inner);
+        metric(
+            "secenv_requests_total",
+            "counter",
+            "Answered requests by status code.",
+            &requests
+                .iter()
+                .map(|(status, count)| (format!("{{code=\"{}\"}}", status), count.to_string()))
+                .collect::<Vec<_>>(),
+        );
+        let reads = self.reads.lock().unwrap_or_else(PoisonError::into_inner);
+        metric(
+            "secenv_reads_total",
+            "counter",
+            "Values read by variable.",
+            &reads
+                .iter()
+                .map(|(name, count)| (format!("{{variable=\"{}\"}}", name), count.to_string()))
+                .collect::<Vec<_>>(),
+        );
+        metric(
+            "secenv_provider_resolve_seconds",
+            "summary",
+            "Time spent resolving values before serving, by backend.",
+            &self
+                .resolve
+                .iter()
+                .flat_map(|(backend, (duration, count))| {
+                    [
+                        (
+                            format!("_sum{{backend=\"{}\"}}", backend),
+                            duration.as_secs_f64().to_string(),
+                        ),
+                        (format!("_count{{backend=\"{}\"}}", backend), count.to_string()),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        );
+        metric(
+            "secenv_pgp_decryptions_total",
+            "counter",
+            "PGP decryptions, including those answered from the cache.",
+            &unlabeled(self.pgp_decryptions),
+        );
+        metric(
+            "secenv_pgp_cache_hits_total",
+            "counter",
+            "PGP decryptions answered from the plaintext cache.",
+            &unlabeled(self.pgp_cache_hits),
+        );
+        metric(
+            "secenv_password_prompts_total",
+            "counter",
+            "Key passwords asked for.",
+            &unlabeled(self.password_prompts),
+        );
+        text
+    }
+}
+
+struct Response {
+    status: u16,
+    reason: &'static str,
+    content_type: &'static str,
+    body: Zeroizing<String>,
+}
+
+impl Response {
+    fn new(status: u16, reason: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            reason,
+            content_type: "text/plain; charset=utf-8",
+            body: Zeroizing::new(body.into()),
+        }
+    }
+
+    fn to_bytes(&self) -> Zeroizing<String> {
+        let authenticate = if self.status == 401 {
+            "WWW-Authenticate: Bearer\r\n"
+        } else {
+            ""
+        };
+        Zeroizing::new(format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\n{}Connection: \
+             close\r\n\r\n{}",
+            self.status,
+            self.reason,
+            self.content_type,
+            self.body.len(),
+            authenticate,
+            self.body.as_str()
+        ))
+    }
+}
+
+impl SecretServer {
+    pub(crate) async fn bind(address: SocketAddr) -> Result<Self> {
+        if !address.ip().is_loopback() {
+            anyhow::bail!(
+                "Refusing to listen on '{}'; serve only listens on loopback addresses",
+                address
+            );
+        }
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to listen on '{}'", address))?;
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(bytes.as_mut());
+        let token = Zeroizing::new(bytes.iter().map(|byte| format!("{:02x}", byte)).collect());
+        Ok(Self {
+            listener,
+            token,
+            metrics: None,
+        })
+    }
+
+    pub(crate) fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub(crate) fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .context("Failed to read the listening address")
+    }
+
+    /// Fails rather than replace an existing file.
+    pub(crate) fn write_token(&self, path: &Path) -> Result<()> {
+        let parent = path
+            .parent()
+            .with_context(|| format!("Token file '{}' has no parent directory", path.display()))?;
+        let mut temporary = tempfile::NamedTempFile::new_in(parent)
+            .with_context(|| format!("Failed to create temporary file beside '{}'", path.display()))?;
+        temporary
+            .write_all(self.token.as_bytes())
+            .and_then(|()| temporary.as_file().sync_all())
+            .with_context(|| format!("Failed to write token file '{}'", path.display()))?;
+        match temporary.persist_noclobber(path) {
+            | Ok(_) => Ok(()),
+            | Err(error) if error.error.kind() == std::io::ErrorKind::AlreadyExists => {
+                Err(crate::failure::Failure::FileConflict
+                    .error(format!("Token file '{}' already exists", path.display())))
+            },
+            | Err(error) => {
+                Err(error.error).with_context(|| format!("Failed to write token file '{}'", path.display()))
+            },
+        }
+    }
+
+    pub(crate) async fn run(self, values: BTreeMap<String, Zeroizing<String>>) {
+        let Self {
+            listener,
+            token,
+            metrics,
+        } = self;
+        let state = Arc::new(State { token, values, metrics });
+        accept(listener, move |head| {
+            let response = head.map_or_else(too_large, |head| Self::respond(head, &state));
+            if let Some(metrics) = &state.metrics {
+                metrics.record_request(response.status);
+            }
+            response
+        })
+        .await
+    }
+
+    fn respond(head: &[u8], state: &State) -> Response {
+        let Some((method, target, mut lines)) = request_line(head) else {
+            return Response::new(400, "Bad Request", "Malformed request\n");
+        };
+        let authorized = lines
+            .by_ref()
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+            .and_then(|(_, value)| value.trim().strip_prefix("Bearer "))
+            .is_some_and(|presented| Self::constant_time_eq(presented.trim().as_bytes(), state.token.as_bytes()));
+        if !authorized {
+            return Response::new(401, "Unauthorized", "Missing or invalid bearer token\n");
+        }
+        if method != "GET" {
+            return Response::new(405, "Method Not Allowed", "Only GET is supported\n");
+        }
+
+        let path = target.split('?').next().unwrap_or_default();
+        match path.strip_prefix("/v1/secrets") {
+            | Some("" | "/") => {
+                let names: Vec<_> = state.values.keys().collect();
+                Response {
+                    content_type: "application/json",
+                    ..Response::new(200, "OK", serde_json::to_string(&names).unwrap_or_default())
+                }
+            },
+            | Some(name) if name.starts_with('/') => {
+                match state.values.get(&name[1..]) {
+                    | Some(value) => {
+                        if let Some(metrics) = &state.metrics {
+                            metrics.record_read(&name[1..]);
+                        }
+                        Response::new(200, "OK", value.as_str())
+                    },
+                    | None => Response::new(404, "Not Found", "No such variable\n"),
+                }
+            },
+            | _ => Response::new(404, "Not Found", "Not found\n"),
+        }
+    }
+
+    fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+        left.len() == right.len()
+            && left
+                .iter()
+                .zip(right)
+                .fold(0, |difference, (l, r)| difference | (l ^ r))
+                == 0
+    }
+}
+
+impl MetricsServer {
+    pub(crate) async fn bind(address: SocketAddr, metrics: Arc<Metrics>) -> Result<Self> {
+        if !address.ip().is_loopback() {
+            anyhow::bail!(
+                "Refusing to serve metrics on '{}'; serve only listens on loopback addresses",
+                address
+            );
+        }
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to listen on '{}'", address))?;
+        Ok(Self { listener, metrics })
+    }
+
+    pub(crate) fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .context("Failed to read the listening address")
+    }
+
+    pub(crate) async fn run(self, variables: usize) {
+        let Self { listener, metrics } = self;
+        accept(listener, move |head| {
+            head.map_or_else(too_large, |head| Self::respond(head, &metrics, variables))
+        })
+        .await
+    }
+
+    fn respond(head: &[u8], metrics: &Metrics, variables: usize) -> Response {
+        match request_line(head) {
+            | None => Response::new(400, "Bad Request", "Malformed request\n"),
+            | Some(("GET", target, _)) if target.split('?').next() == Some("/metrics") => {
+                Response {
+                    content_type: "text/plain; version=0.0.4",
+                    ..Response::new(200, "OK", metrics.render(variables))
+                }
+            },
+            | Some(("GET", ..)) => Response::new(404, "Not Found", "Not found\n"),
+            | Some(_) => Response::new(405, "Method Not Allowed", "Only GET is supported\n"),
+        }
+    }
+}
+
+/// `None` stands for a head that is too large.
+async fn accept(listener: TcpListener, respond: impl Fn(Option<&[u8]>) -> Response+Send+Sync+'static) {
+    let respond = Arc::new(respond);
+    loop {
+        let stream = match listener.accept().await {
+            | Ok((stream, _)) => stream,
+            | Err(error) => {
+                crate::output::warning!("Failed to accept a connection: {}", error);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            },
+        };
+        let respond = respond.clone();
+        tokio::spawn(async move {
+            let _ = handle(stream, &*respond).await;
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, respond: &(dyn Fn(Option<&[u8]>) -> Response+Send+Sync)) -> Result<()> {
+    let head = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream))
+        .await
+        .context("Timed out reading the request")??;
+    let response = respond(head.as_deref().map(|head| head.as_slice()));
+    stream.write_all(response.to_bytes().as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn read_head(stream: &mut TcpStream) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    let mut head = Zeroizing::new(Vec::new());
+    let mut buffer = Zeroizing::new([0u8; 1024]);
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+        let read = stream.read(buffer.as_mut()).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    Ok(Some(head))
+}
+
+fn too_large() -> Response {
+    Response::new(431, "Request Header Fields Too Large", "Request too large\n")
+}
+
+fn request_line(head: &[u8]) -> Option<(&str, &str, std::str::Split<'_, &str>)> {
+    let mut lines = std::str::from_utf8(head).ok()?.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(target), Some(version), None) = (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) else {
+        return None;
+    };
+    version.starts_with("HTTP/1.").then_some((method, target, lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> BTreeMap<String, Zeroizing<String>> {
+        BTreeMap::from([
+            ("API_KEY".to_string(), Zeroizing::new("s3cret".to_string())),
+            ("DB_URL".to_string(), Zeroizing::new("postgres://".to_string())),
+        ])
+    }
+
+    fn state(metrics: Option<Arc<Metrics>>) -> State {
+        State {
+            token: Zeroizing::new("token".to_string()),
+            values: values(),
+            metrics,
+        }
+    }
+
+    #[test]
+    fn responds_only_to_authorized_gets() {
+        let state = state(None);
+        let respond = |request: &str| SecretServer::respond(request.as_bytes(), &state);
+
+        let found = respond("GET /v1/secrets/API_KEY HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer token\r\n\r\n");
+        assert_eq!((found.status, found.body.as_str()), (200, "s3cret"));
+        let list = respond("GET /v1/secrets HTTP/1.1\r\nauthorization:  Bearer token \r\n\r\n");
+        assert_eq!((list.status, list.body.as_str()), (200, r#"["API_KEY","DB_URL"]"#));
+        assert_eq!(list.content_type, "application/json");
+
+        for (request, status) in [
+            ("GET /v1/secrets/API_KEY HTTP/1.1\r\n\r\n", 401),
+            (
+                "GET /v1/secrets/API_KEY HTTP/1.1\r\nAuthorization: Bearer tokem\r\n\r\n",
+                401,
+            ),
+            (
+                "GET /v1/secrets/API_KEY HTTP/1.1\r\nAuthorization: Basic token\r\n\r\n",
+                401,
+            ),
+            (
+                "POST /v1/secrets/API_KEY HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n",
+                405,
+            ),
+            (
+                "GET /v1/secrets/MISSING HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n",
+                404,
+            ),
+            ("GET /v1/other HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n", 404),
+            ("GET /metrics HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n", 404),
+            ("GET /v1/secrets\r\n\r\n", 400),
+        ] {
+            assert_eq!(respond(request).status, status, "{}", request);
+        }
+    }
+
+    #[test]
+    fn reports_metrics_without_values() {
+        let metrics = Arc::new(Metrics {
+            resolve: BTreeMap::from([("pgp", (Duration::from_millis(1500), 2))]),
+            pgp_decryptions: 2,
+            pgp_cache_hits: 1,
+            ..Metrics::default()
+        });
+        let state = state(Some(metrics.clone()));
+        let respond = |request: &str| SecretServer::respond(request.as_bytes(), &state);
+        let scrape = |request: &str| MetricsServer::respond(request.as_bytes(), &metrics, 2);
+
+        respond("GET /v1/secrets/API_KEY HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n");
+        // Metrics are only on their own listener, which needs no token.
+        let authorized = "GET /metrics HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n";
+        assert_eq!(respond(authorized).status, 404);
+        assert_eq!(scrape("GET /v1/secrets/API_KEY HTTP/1.1\r\n\r\n").status, 404);
+        assert_eq!(scrape("POST /metrics HTTP/1.1\r\n\r\n").status, 405);
+        let response = scrape("GET /metrics HTTP/1.1\r\n\r\n");
+        assert_eq!(response.status, 200);
+        for sample in [
+            "secenv_variables 2\n",
+            "secenv_reads_total{variable=\"API_KEY\"} 1\n",
+            "secenv_provider_resolve_seconds_sum{backend=\"pgp\"} 1.5\n",
+            "secenv_provider_resolve_seconds_count{backend=\"pgp\"} 2\n",
+            "secenv_pgp_cache_hits_total 1\n",
+            "# TYPE secenv_password_prompts_total counter\nsecenv_password_prompts_total 0\n",
+        ] {
+            assert!(response.body.contains(sample), "{}", response.body.as_str());
+        }
+        assert!(!response.body.contains("s3cret"));
+    }
+
+    #[tokio::test]
+    async fn serves_values_on_loopback_only() -> Result<()> {
+        assert!(SecretServer::bind("0.0.0.0:0".parse()?).await.is_err());
+
+        let server = SecretServer::bind("127.0.0.1:0".parse()?).await?;
+        let address = server.local_addr()?;
+        let directory = tempfile::tempdir()?;
+        let token_file = directory.path().join("token");
+        server.write_token(&token_file)?;
+        let token = std::fs::read_to_string(&token_file)?;
+        assert_eq!(token.len(), 64);
+        assert!(server.write_token(&token_file).is_err());
+        assert_eq!(std::fs::read_to_string(&token_file)?, token);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&token_file)?.permissions().mode() & 0o777, 0o600);
+        }
+
+        let task = tokio::spawn(server.run(values()));
+        let mut stream = TcpStream::connect(address).await?;
+        stream
+            .write_all(
+                format!(
+                    "GET /v1/secrets/DB_URL HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n",
+                    token
+                )
+                .as_bytes(),
+            )
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        task.abort();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\npostgres://"), "{}", response);
+        Ok(())
+    }
+}
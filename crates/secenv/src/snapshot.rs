@@ -0,0 +1,274 @@
+use {
+    crate::{
+        gpg::{
+            GpgKeySpec,
+            GpgManager,
+        },
+        manifest::BackupMode,
+        pgp::PgpManager,
+        sealed::GeneratedFile,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    base64::Engine,
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    std::{
+        collections::BTreeMap,
+        io::Write,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+    zeroize::Zeroizing,
+};
+
+const FORMAT: &str = "secenv-snapshot/1";
+
+/// Values and files are only in the encrypted `message`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+struct Envelope {
+    format: String,
+    profile: String,
+    created: String,
+    recipient: String,
+    message: String,
+}
+
+/// Replayed by `unlock --from-snapshot` without reading any provider.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct Snapshot {
+    #[serde(default)]
+    variables: BTreeMap<String, Zeroizing<String>>,
+    #[serde(default)]
+    files: BTreeMap<String, SnapshotFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct SnapshotFile {
+    #[serde(flatten)]
+    content: SnapshotContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backup: Option<BackupMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    permissions: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+enum SnapshotContent {
+    Write { content: Zeroizing<String> },
+    Append { content: Zeroizing<String> },
+    Copy { base64: Zeroizing<String> },
+    Symlink { target: PathBuf },
+}
+
+impl Snapshot {
+    pub(crate) fn new(variables: BTreeMap<String, Zeroizing<String>>) -> Self {
+        Self {
+            variables,
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn add_file(
+        &mut self,
+        path: String,
+        file: &GeneratedFile,
+        backup: Option<BackupMode>,
+        permissions: Option<u32>,
+    ) {
+        let content = match file {
+            | GeneratedFile::Write(content) => {
+                SnapshotContent::Write {
+                    content: content.clone(),
+                }
+            },
+            | GeneratedFile::Append(content) => {
+                SnapshotContent::Append {
+                    content: content.clone(),
+                }
+            },
+            | GeneratedFile::Copy(contents) => {
+                SnapshotContent::Copy {
+                    base64: Zeroizing::new(base64::engine::general_purpose::STANDARD.encode(contents.as_slice())),
+                }
+            },
+            | GeneratedFile::Symlink(target) => SnapshotContent::Symlink { target: target.clone() },
+        };
+        self.files.insert(path, SnapshotFile {
+            content,
+            backup,
+            permissions,
+        });
+    }
+
+    pub(crate) fn variables(&self) -> impl Iterator<Item=(&String, &Zeroizing<String>)> {
+        self.variables.iter()
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn files(&self) -> Result<Vec<(String, GeneratedFile, Option<BackupMode>, Option<u32>)>> {
+        self.files
+            .iter()
+            .map(|(path, file)| {
+                let generated = match &file.content {
+                    | SnapshotContent::Write { content } => GeneratedFile::Write(content.clone()),
+                    | SnapshotContent::Append { content } => GeneratedFile::Append(content.clone()),
+                    | SnapshotContent::Copy { base64 } => {
+                        GeneratedFile::Copy(Zeroizing::new(
+                            base64::engine::general_purpose::STANDARD
+                                .decode(base64.as_bytes())
+                                .with_context(|| format!("Snapshot file '{}' is not valid base64", path))?,
+                        ))
+                    },
+                    | SnapshotContent::Symlink { target } => GeneratedFile::Symlink(target.clone()),
+                };
+                Ok((path.clone(), generated, file.backup, file.permissions))
+            })
+            .collect()
+    }
+
+    pub(crate) fn seal(
+        &self,
+        profile: &str,
+        recipient: &GpgKeySpec,
+        certificate: &str,
+        pgp_manager: &PgpManager,
+    ) -> Result<String> {
+        let plaintext = Zeroizing::new(serde_json::to_string(self).context("Failed to serialize snapshot")?);
+        let envelope = Envelope {
+            format: FORMAT.to_string(),
+            profile: profile.to_string(),
+            created: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            recipient: recipient.as_str().to_string(),
+            message: pgp_manager
+                .encrypt_armored(certificate, &plaintext)
+                .context("Failed to encrypt snapshot")?,
+        };
+        serde_json::to_string_pretty(&envelope).context("Failed to serialize snapshot")
+    }
+
+    pub(crate) fn write(path: &Path, sealed: &str, force: bool) -> Result<()> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true);
+        if force {
+            options.create(true).truncate(true);
+        } else {
+            options.create_new(true);
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(path)
+            .and_then(|mut file| {
+                file.write_all(sealed.as_bytes())?;
+                file.write_all(b"\n")?;
+                file.sync_all()
+            })
+            .with_context(|| format!("Failed to write snapshot '{}'", path.display()))
+    }
+
+    /// Returns the snapshot and when it was created.
+    pub(crate) fn read(
+        path: &Path,
+        profile: &str,
+        pgp_manager: &PgpManager,
+        removed_env_vars: &[String],
+    ) -> Result<(Self, String)> {
+        let envelope: Envelope = serde_json::from_str(
+            &std::fs::read_to_string(path).with_context(|| format!("Failed to read snapshot '{}'", path.display()))?,
+        )
+        .with_context(|| format!("'{}' is not a secenv snapshot", path.display()))?;
+        Self::open(envelope, profile, |recipient, message| {
+            GpgManager.decrypt_data(recipient, message, pgp_manager, removed_env_vars)
+        })
+        .with_context(|| format!("Failed to open snapshot '{}'", path.display()))
+    }
+
+    fn open(
+        envelope: Envelope,
+        profile: &str,
+        decrypt: impl FnOnce(&GpgKeySpec, &str) -> Result<String>,
+    ) -> Result<(Self, String)> {
+        if envelope.format != FORMAT {
+            anyhow::bail!(
+                "Unsupported snapshot format '{}'; expected '{}'",
+                envelope.format,
+                FORMAT
+            );
+        }
+        if envelope.profile != profile {
+            anyhow::bail!("Snapshot is of profile '{}', not '{}'", envelope.profile, profile);
+        }
+        let recipient = GpgKeySpec::new(envelope.recipient)?;
+        let plaintext = Zeroizing::new(decrypt(&recipient, &envelope.message)?);
+        let snapshot = serde_json::from_str(&plaintext).context("Decrypted snapshot is malformed")?;
+        Ok((snapshot, envelope.created))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        sequoia_openpgp::{
+            cert::CertBuilder,
+            serialize::SerializeInto,
+        },
+    };
+
+    #[test]
+    fn snapshots_round_trip_only_for_their_profile() -> Result<()> {
+        let (cert, _) = CertBuilder::general_purpose(Some("Incident <incident@example.com>")).generate()?;
+        let private_key = String::from_utf8(cert.as_tsk().armored().to_vec()?)?;
+        let public_key = String::from_utf8(cert.armored().to_vec()?)?;
+        let recipient = GpgKeySpec::new(cert.fingerprint().to_hex())?;
+
+        let mut snapshot = Snapshot::new(BTreeMap::from([(
+            "API_KEY".to_string(),
+            Zeroizing::new("s3cret".to_string()),
+        )]));
+        snapshot.add_file(
+            "./config.json".to_string(),
+            &GeneratedFile::Write(Zeroizing::new("{}".to_string())),
+            Some(BackupMode::Memory),
+            Some(0o640),
+        );
+        snapshot.add_file(
+            "./link".to_string(),
+            &GeneratedFile::Symlink(PathBuf::from("/etc/hosts")),
+            None,
+            None,
+        );
+        let sealed = snapshot.seal("production", &recipient, &public_key, &PgpManager::default())?;
+        assert!(!sealed.contains("s3cret"));
+
+        let pgp_manager = PgpManager::default();
+        let decrypt = |_: &GpgKeySpec, message: &str| pgp_manager.decrypt(&private_key, message);
+        let (opened, _) = Snapshot::open(serde_json::from_str(&sealed)?, "production", decrypt)?;
+        let variables: Vec<_> = opened
+            .variables()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(variables, [("API_KEY", "s3cret")]);
+        let files = opened.files()?;
+        assert!(matches!(
+            &files[0],
+            (path, GeneratedFile::Write(content), Some(BackupMode::Memory), Some(0o640))
+                if path == "./config.json" && content.as_str() == "{}"
+        ));
+        assert!(matches!(&files[1].1, GeneratedFile::Symlink(target) if target == Path::new("/etc/hosts")));
+
+        assert!(Snapshot::open(serde_json::from_str(&sealed)?, "staging", decrypt).is_err());
+        Ok(())
+    }
+}
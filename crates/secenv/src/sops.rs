@@ -0,0 +1,151 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SopsFormat {
+    Yaml,
+    Json,
+    Dotenv,
+}
+
+impl SopsFormat {
+    fn of(path: &str) -> Result<Self> {
+        let lowercase = path.to_ascii_lowercase();
+        if lowercase.ends_with(".yaml") || lowercase.ends_with(".yml") {
+            Ok(Self::Yaml)
+        } else if lowercase.ends_with(".json") {
+            Ok(Self::Json)
+        } else if lowercase.ends_with(".env") {
+            Ok(Self::Dotenv)
+        } else {
+            anyhow::bail!("Cannot tell the format of sops file '{}'; set 'format'", path)
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            | Self::Yaml => "yaml",
+            | Self::Json => "json",
+            | Self::Dotenv => "dotenv",
+        }
+    }
+}
+
+pub(crate) struct SopsManager;
+
+impl SopsManager {
+    /// YAML and JSON keys are upper-cased, other characters as `_`.
+    pub(crate) fn decrypt(
+        &self,
+        path: &str,
+        format: Option<SopsFormat>,
+        removed_env_vars: &[String],
+    ) -> Result<Vec<(String, Zeroizing<String>)>> {
+        let format = match format {
+            | Some(format) => format,
+            | None => SopsFormat::of(path)?,
+        };
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            let fields = fixtures.sops_file(path)?;
+            return Ok(fields
+                .into_iter()
+                .map(|(key, value)| (Self::variable_name(key, format), value))
+                .collect());
+        }
+        let mut cmd = Command::new("sops");
+        cmd.args([
+            "--decrypt",
+            "--input-type",
+            format.name(),
+            "--output-type",
+            "json",
+            "--",
+        ])
+        .arg(path);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+        let mut output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute sops")
+            .categorize(Failure::Provider)?;
+        if !output.status.success() {
+            let error = Failure::Decryption.error(format!(
+                "sops failed to decrypt '{}': {}",
+                path,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        let document = Zeroizing::new(String::from_utf8(std::mem::take(&mut output.stdout)).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("sops output is not valid UTF-8")
+        })?);
+        Self::variables(&document, format).with_context(|| format!("Failed to import sops file '{}'", path))
+    }
+
+    fn variables(document: &str, format: SopsFormat) -> Result<Vec<(String, Zeroizing<String>)>> {
+        Ok(crate::process::json_variables(document)?
+            .into_iter()
+            .map(|(key, value)| (Self::variable_name(key, format), value))
+            .collect())
+    }
+
+    fn variable_name(key: String, format: SopsFormat) -> String {
+        match format {
+            | SopsFormat::Dotenv => key,
+            | SopsFormat::Yaml | SopsFormat::Json => crate::process::environment_variable_name(&key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_top_level_keys() -> Result<()> {
+        assert_eq!(SopsFormat::of("secrets.enc.yaml")?, SopsFormat::Yaml);
+        assert_eq!(SopsFormat::of("./.env")?, SopsFormat::Dotenv);
+        assert!(SopsFormat::of("secrets").is_err());
+
+        let document = r#"{"db-password": "p4ss", "port": 5432, "debug_mode": false}"#;
+        let variables = SopsManager::variables(document, SopsFormat::Yaml)?;
+        let variables: Vec<_> = variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(variables, [
+            ("DB_PASSWORD", "p4ss"),
+            ("DEBUG_MODE", "false"),
+            ("PORT", "5432")
+        ]);
+        let variables = SopsManager::variables(r#"{"api_key": "k"}"#, SopsFormat::Dotenv)?;
+        assert_eq!(variables[0].0, "api_key");
+        assert!(SopsManager::variables(r#"{"db": {"password": "p4ss"}}"#, SopsFormat::Json).is_err());
+        Ok(())
+    }
+}
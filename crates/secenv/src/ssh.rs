@@ -0,0 +1,75 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::Zeroize,
+};
+
+pub(crate) struct SshHost;
+
+impl SshHost {
+    /// Uses `ssh <host> cat`, so the file is never copied to the local disk.
+    pub(crate) fn read_file(&self, host: &str, path: &str, removed_env_vars: &[String]) -> Result<String> {
+        if host.is_empty() || host.starts_with('-') || host.contains(char::is_whitespace) {
+            anyhow::bail!("Invalid SSH host: '{}'", host);
+        }
+        if path.is_empty() || path.contains(['\n', '\0']) {
+            anyhow::bail!("Invalid path on SSH host '{}': '{}'", host, path);
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.ssh_file(host, path);
+        }
+
+        let mut cmd = Command::new("ssh");
+        cmd.args(["-T", "-o", "ClearAllForwardings=yes", "--", host])
+            .arg(format!("cat -- {}", Self::quote(path)));
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        // ssh asks for passphrases and unknown host keys on the terminal
+        // itself.
+        let mut output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("Failed to execute ssh")
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Provider.error(format!("Failed to read '{}' on '{}': {}", path, host, stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        String::from_utf8(std::mem::take(&mut output.stdout)).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("'{}' on '{}' is not valid UTF-8", path, host)
+        })
+    }
+
+    fn quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_remote_paths() {
+        assert_eq!(SshHost::quote("/etc/app/key.pem"), "'/etc/app/key.pem'");
+        assert_eq!(SshHost::quote("it's $HOME; rm"), r"'it'\''s $HOME; rm'");
+        assert!(SshHost.read_file("-oProxyCommand=touch", "/etc/key", &[]).is_err());
+        assert!(SshHost.read_file("bastion", "", &[]).is_err());
+    }
+}
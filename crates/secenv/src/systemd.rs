@@ -0,0 +1,95 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    std::process::{
+        Command,
+        Stdio,
+    },
+    zeroize::Zeroize,
+};
+
+pub(crate) struct SystemdCredentials;
+
+impl SystemdCredentials {
+    fn validate(name: &str) -> Result<()> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) || name.len() > 255 {
+            anyhow::bail!("Invalid systemd credential name '{}'", name);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read(&self, name: &str) -> Result<String> {
+        Self::validate(name)?;
+        let directory = std::env::var_os("CREDENTIALS_DIRECTORY").context(
+            "CREDENTIALS_DIRECTORY is not set; systemd credentials are only passed to services with LoadCredential= \
+             or SetCredentialEncrypted=",
+        )?;
+        let path = std::path::Path::new(&directory).join(name);
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read systemd credential '{}'", name))
+    }
+
+    /// Returns the `SetCredentialEncrypted=` setting of a unit file.
+    pub(crate) fn encrypt(&self, name: &str, value: &str, removed_env_vars: &[String]) -> Result<String> {
+        Self::validate(name)?;
+        let mut cmd = Command::new("systemd-creds");
+        cmd.args(["encrypt", "--pretty"])
+            .arg(format!("--name={}", name))
+            .args(["-", "-"]);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Failed to execute systemd-creds")
+            .categorize(Failure::Provider)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            stdin
+                .write_all(value.as_bytes())
+                .context("Failed to pass the value to systemd-creds")?;
+        }
+        let mut output = child.wait_with_output().context("Failed to wait for systemd-creds")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error =
+                Failure::Provider.error(format!("systemd-creds failed to encrypt '{}': {}", name, stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        String::from_utf8(output.stdout).context("systemd-creds output is not valid UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_credentials_from_the_credentials_directory() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        std::fs::write(directory.path().join("db-password"), "p4ss\n")?;
+        std::env::set_var("CREDENTIALS_DIRECTORY", directory.path());
+        assert_eq!(SystemdCredentials.read("db-password")?, "p4ss\n");
+        assert!(SystemdCredentials.read("api-key").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn validates_credential_names() {
+        for valid in ["db-password", "DATABASE_URL", "tls.key"] {
+            assert!(SystemdCredentials::validate(valid).is_ok(), "{}", valid);
+        }
+        for invalid in ["", ".", "..", "../etc/shadow", "keys/tls"] {
+            assert!(SystemdCredentials::validate(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    base64::Engine,
+    std::collections::BTreeMap,
+    zeroize::Zeroizing,
+};
+
+/// Render `{{ NAME }}` placeholders with optional filters.
+pub(crate) fn render(template: &str, variables: &BTreeMap<String, Zeroizing<String>>) -> Result<Zeroizing<String>> {
+    let mut rendered = Zeroizing::new(String::with_capacity(template.len()));
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let line = template[..template.len() - rest.len() + start].matches('\n').count() + 1;
+        let placeholder = &rest[start + 2..];
+        let end = placeholder
+            .find("}}")
+            .with_context(|| format!("Unclosed '{{{{' at line {}", line))?;
+        let value = Placeholder::parse(&placeholder[..end])
+            .and_then(|placeholder| placeholder.render(variables))
+            .with_context(|| format!("Invalid placeholder at line {}", line))?;
+        rendered.push_str(&value);
+        rest = &placeholder[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+struct Placeholder<'a> {
+    source: Source<'a>,
+    filters: Vec<Filter>,
+}
+
+enum Source<'a> {
+    Variable(&'a str),
+    Literal(String),
+}
+
+enum Filter {
+    Default(String),
+    Json,
+    Base64,
+}
+
+impl<'a> Placeholder<'a> {
+    fn parse(placeholder: &'a str) -> Result<Self> {
+        let mut rest = placeholder.trim_start();
+        let source = if rest.starts_with('"') {
+            let (literal, after) = Self::literal(rest)?;
+            rest = after;
+            Source::Literal(literal)
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || c == '|').unwrap_or(rest.len());
+            if end == 0 {
+                anyhow::bail!("Expected a variable name or a quoted string");
+            }
+            let name = &rest[..end];
+            rest = &rest[end..];
+            Source::Variable(name)
+        };
+        let mut filters = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            rest = rest
+                .strip_prefix('|')
+                .context("Expected '|' before a filter")?
+                .trim_start();
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let (filter, after) = rest.split_at(end);
+            rest = after;
+            filters.push(match filter {
+                | "json" => Filter::Json,
+                | "base64" => Filter::Base64,
+                | "default" => {
+                    let argument = rest
+                        .trim_start()
+                        .strip_prefix('(')
+                        .context("Expected '(' after 'default'")?
+                        .trim_start();
+                    let (value, after) = Self::literal(argument)?;
+                    rest = after
+                        .trim_start()
+                        .strip_prefix(')')
+                        .context("Expected ')' after the default value")?;
+                    Filter::Default(value)
+                },
+                | _ => anyhow::bail!("Unknown filter '{}'; expected default, json, or base64", filter),
+            });
+        }
+        Ok(Self { source, filters })
+    }
+
+    fn literal(rest: &str) -> Result<(String, &str)> {
+        let mut literal = String::new();
+        let mut chars = rest.char_indices().skip(1);
+        while let Some((index, c)) = chars.next() {
+            match c {
+                | '"' => return Ok((literal, &rest[index + 1..])),
+                | '\\' => literal.extend(chars.next().map(|(_, escaped)| escaped)),
+                | c => literal.push(c),
+            }
+        }
+        anyhow::bail!("Unterminated string")
+    }
+
+    fn render(self, variables: &BTreeMap<String, Zeroizing<String>>) -> Result<Zeroizing<String>> {
+        // Literals always have a value, so only a variable can be missing.
+        let (mut value, name) = match self.source {
+            | Source::Variable(name) => (variables.get(name).cloned(), name),
+            | Source::Literal(literal) => (Some(Zeroizing::new(literal)), ""),
+        };
+        for filter in self.filters {
+            value = match filter {
+                | Filter::Default(default) => value.or(Some(Zeroizing::new(default))),
+                | Filter::Json => {
+                    value
+                        .map(|value| serde_json::to_string(value.as_str()).map(Zeroizing::new))
+                        .transpose()?
+                },
+                | Filter::Base64 => {
+                    value
+                        .map(|value| Zeroizing::new(base64::engine::general_purpose::STANDARD.encode(value.as_bytes())))
+                },
+            };
+        }
+        value.with_context(|| format!("Variable '{}' is not set", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_templates_with_variables() -> Result<()> {
+        let variables = BTreeMap::from([
+            ("DB_PASSWORD".to_string(), Zeroizing::new("p\"ss".to_string())),
+            ("DB_USER".to_string(), Zeroizing::new("app".to_string())),
+        ]);
+        let template =
+            "spring:\n  datasource:\n    username: {{DB_USER}}\n    password: {{ DB_PASSWORD | json }}\n    token: {{ \
+             TOKEN | default(\"none\") }}\n    basic: {{ DB_USER|base64 }}\n    literal: {{ \"{{\" }} not a \
+             placeholder }}\n";
+        assert_eq!(
+            render(template, &variables)?.as_str(),
+            "spring:\n  datasource:\n    username: app\n    password: \"p\\\"ss\"\n    token: none\n    basic: \
+             YXBw\n    literal: {{ not a placeholder }}\n"
+        );
+
+        for (template, error) in [
+            (
+                "a\n{{ MISSING }}",
+                "Invalid placeholder at line 2: Variable 'MISSING' is not set",
+            ),
+            ("{{ DB_USER", "Unclosed '{{' at line 1"),
+            (
+                "{{ DB_USER | upper }}",
+                "Invalid placeholder at line 1: Unknown filter 'upper'; expected default, json, or base64",
+            ),
+            (
+                "{{ DB_USER DB_PASSWORD }}",
+                "Invalid placeholder at line 1: Expected '|' before a filter",
+            ),
+            (
+                "{{ }}",
+                "Invalid placeholder at line 1: Expected a variable name or a quoted string",
+            ),
+        ] {
+            let rendered = render(template, &variables).map_err(|error| format!("{:#}", error));
+            assert_eq!(rendered.err().as_deref(), Some(error));
+        }
+        Ok(())
+    }
+}
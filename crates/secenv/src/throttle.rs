@@ -0,0 +1,291 @@
+use {
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    std::{
+        sync::{
+            Condvar,
+            Mutex,
+            OnceLock,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+pub(crate) const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+static MAX_CONCURRENCY: OnceLock<usize> = OnceLock::new();
+static SLOTS: Slots = Slots::new();
+static GCP: RateLimiter = RateLimiter::new();
+static AWS: RateLimiter = RateLimiter::new();
+static AZURE: RateLimiter = RateLimiter::new();
+static OP: RateLimiter = RateLimiter::new();
+static DOPPLER: RateLimiter = RateLimiter::new();
+
+pub(crate) fn set_max_concurrency(max: usize) {
+    let _ = MAX_CONCURRENCY.set(max.max(1));
+}
+
+pub(crate) fn max_concurrency() -> usize {
+    MAX_CONCURRENCY.get().copied().unwrap_or(DEFAULT_MAX_CONCURRENCY)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct ProviderSettings {
+    #[serde(default, skip_serializing_if = "GcpProviderSettings::is_default")]
+    pub(crate) gcp: GcpProviderSettings,
+    #[serde(default, skip_serializing_if = "AwsProviderSettings::is_default")]
+    pub(crate) aws: AwsProviderSettings,
+    #[serde(default, skip_serializing_if = "ProviderLimits::is_unlimited")]
+    pub(crate) azure: ProviderLimits,
+    #[serde(default, skip_serializing_if = "ProviderLimits::is_unlimited")]
+    pub(crate) op: ProviderLimits,
+    #[serde(default, skip_serializing_if = "ProviderLimits::is_unlimited")]
+    pub(crate) doppler: ProviderLimits,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct ProviderLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rate_limit: Option<f64>,
+}
+
+impl ProviderLimits {
+    fn is_unlimited(&self) -> bool {
+        self.rate_limit.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct GcpProviderSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rate_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) client: Option<crate::gcp::GcpClient>,
+}
+
+impl GcpProviderSettings {
+    fn is_default(&self) -> bool {
+        self.rate_limit.is_none() && self.client.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct AwsProviderSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rate_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) client: Option<crate::aws::AwsClient>,
+}
+
+impl AwsProviderSettings {
+    fn is_default(&self) -> bool {
+        self.rate_limit.is_none() && self.client.is_none()
+    }
+}
+
+impl ProviderSettings {
+    pub(crate) fn is_unlimited(&self) -> bool {
+        self.gcp.is_default()
+            && self.aws.is_default()
+            && self.azure.is_unlimited()
+            && self.op.is_unlimited()
+            && self.doppler.is_unlimited()
+    }
+
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        for (name, rate_limit) in [
+            ("gcp", self.gcp.rate_limit),
+            ("aws", self.aws.rate_limit),
+            ("azure", self.azure.rate_limit),
+            ("op", self.op.rate_limit),
+            ("doppler", self.doppler.rate_limit),
+        ] {
+            if let Some(rate_limit) = rate_limit {
+                if !rate_limit.is_finite() || rate_limit <= 0.0 {
+                    anyhow::bail!(
+                        "'providers.{}.rate_limit' must be a positive number of requests per second",
+                        name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply(&self) {
+        crate::gcp::GcpClient::configure(self.gcp.client);
+        crate::aws::AwsClient::configure(self.aws.client);
+        GCP.set_rate(self.gcp.rate_limit);
+        AWS.set_rate(self.aws.rate_limit);
+        AZURE.set_rate(self.azure.rate_limit);
+        OP.set_rate(self.op.rate_limit);
+        DOPPLER.set_rate(self.doppler.rate_limit);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Provider {
+    Gcp,
+    Aws,
+    Azure,
+    OnePassword,
+    Doppler,
+}
+
+impl Provider {
+    /// Waiting for the rate limit holds no slot, so others are not held up.
+    pub(crate) fn request<T>(self, send: impl FnOnce() -> T) -> T {
+        let limiter = match self {
+            | Self::Gcp => &GCP,
+            | Self::Aws => &AWS,
+            | Self::Azure => &AZURE,
+            | Self::OnePassword => &OP,
+            | Self::Doppler => &DOPPLER,
+        };
+        limiter.wait();
+        let _slot = SLOTS.acquire(max_concurrency());
+        send()
+    }
+}
+
+struct RateLimiter {
+    state: Mutex<RateState>,
+}
+
+struct RateState {
+    interval: Option<Duration>,
+    next: Option<Instant>,
+}
+
+impl RateLimiter {
+    const fn new() -> Self {
+        Self {
+            state: Mutex::new(RateState {
+                interval: None,
+                next: None,
+            }),
+        }
+    }
+
+    fn set_rate(&self, per_second: Option<f64>) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.interval = per_second.map(|per_second| Duration::from_secs_f64(1.0 / per_second));
+    }
+
+    fn wait(&self) {
+        let at = self.reserve(Instant::now());
+        if let Some(delay) = at.checked_duration_since(Instant::now()) {
+            std::thread::sleep(delay);
+        }
+    }
+
+    fn reserve(&self, now: Instant) -> Instant {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(interval) = state.interval else {
+            return now;
+        };
+        let at = state.next.map_or(now, |next| next.max(now));
+        state.next = Some(at + interval);
+        at
+    }
+}
+
+/// Admits waiters first come, first served.
+struct Slots {
+    tickets: Mutex<Tickets>,
+    released: Condvar,
+}
+
+struct Tickets {
+    issued: u64,
+    returned: u64,
+}
+
+struct Slot<'a>(&'a Slots);
+
+impl Slots {
+    const fn new() -> Self {
+        Self {
+            tickets: Mutex::new(Tickets { issued: 0, returned: 0 }),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self, max: usize) -> Slot<'_> {
+        let mut tickets = self.tickets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let ticket = tickets.issued;
+        tickets.issued += 1;
+        while ticket >= tickets.returned + max as u64 {
+            tickets = self
+                .released
+                .wait(tickets)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        Slot(self)
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        let mut tickets = self.0.tickets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        tickets.returned += 1;
+        self.0.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_requests_and_bounds_those_in_flight() {
+        let limiter = RateLimiter::new();
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), now);
+        assert_eq!(limiter.reserve(now), now);
+
+        limiter.set_rate(Some(10.0));
+        let reserved: Vec<_> = (0..3).map(|_| limiter.reserve(now) - now).collect();
+        assert_eq!(reserved, [
+            Duration::ZERO,
+            Duration::from_millis(100),
+            Duration::from_millis(200)
+        ]);
+        let later = now + Duration::from_secs(5);
+        assert_eq!(limiter.reserve(later), later);
+
+        let slots = Slots::new();
+        let in_flight = std::sync::atomic::AtomicUsize::new(0);
+        let peak = std::sync::atomic::AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let _slot = slots.acquire(2);
+                    let current = in_flight.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                    peak.fetch_max(current, std::sync::atomic::Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    in_flight.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(peak.into_inner() <= 2);
+
+        let settings = ProviderSettings {
+            gcp: GcpProviderSettings {
+                rate_limit: Some(0.0),
+                client: None,
+            },
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+}
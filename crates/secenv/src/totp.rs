@@ -0,0 +1,177 @@
+use {
+    anyhow::{
+        Context,
+        Result,
+    },
+    openssl::{
+        hash::MessageDigest,
+        pkey::PKey,
+        sign::Signer,
+    },
+    serde::{
+        Deserialize,
+        Serialize,
+    },
+    std::time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+    zeroize::Zeroizing,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TotpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl TotpAlgorithm {
+    pub(crate) fn is_sha1(&self) -> bool {
+        *self == Self::Sha1
+    }
+
+    fn digest(self) -> MessageDigest {
+        match self {
+            | Self::Sha1 => MessageDigest::sha1(),
+            | Self::Sha256 => MessageDigest::sha256(),
+            | Self::Sha512 => MessageDigest::sha512(),
+        }
+    }
+}
+
+/// Time-based one-time passwords (RFC 6238).
+pub(crate) struct Totp {
+    pub(crate) digits: u32,
+    pub(crate) period: u64,
+    pub(crate) algorithm: TotpAlgorithm,
+}
+
+impl Totp {
+    /// `seed` is a base32 secret or an `otpauth://` URI holding it.
+    pub(crate) fn code(&self, seed: &str, time: SystemTime) -> Result<Zeroizing<String>> {
+        if !(6..=10).contains(&self.digits) {
+            anyhow::bail!("TOTP codes have 6 to 10 digits, not {}", self.digits);
+        }
+        if self.period == 0 {
+            anyhow::bail!("TOTP period must be at least one second");
+        }
+        let secret = Self::decode_base32(Self::secret(seed)?)?;
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .context("System time is before the Unix epoch")?
+            .as_secs();
+
+        let key = PKey::hmac(&secret).context("Invalid TOTP seed")?;
+        let mut signer = Signer::new(self.algorithm.digest(), &key).context("Failed to create the TOTP HMAC")?;
+        signer
+            .update(&(seconds / self.period).to_be_bytes())
+            .context("Failed to compute the TOTP HMAC")?;
+        let mac = Zeroizing::new(signer.sign_to_vec().context("Failed to compute the TOTP HMAC")?);
+        // Dynamic truncation: 31 bits at the offset the last nibble names.
+        let offset = usize::from(mac[mac.len() - 1] & 0x0f);
+        let value = u32::from_be_bytes([mac[offset], mac[offset + 1], mac[offset + 2], mac[offset + 3]]) & 0x7fff_ffff;
+        let code = u64::from(value) % 10u64.pow(self.digits);
+        Ok(Zeroizing::new(format!(
+            "{:0width$}",
+            code,
+            width = self.digits as usize
+        )))
+    }
+
+    fn secret(seed: &str) -> Result<&str> {
+        let seed = seed.trim();
+        let Some(uri) = seed.strip_prefix("otpauth://") else {
+            return Ok(seed);
+        };
+        uri.split_once('?')
+            .map(|(_, query)| query)
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|parameter| parameter.strip_prefix("secret="))
+            .context("TOTP otpauth URI has no secret parameter")
+    }
+
+    /// In any case, grouped with spaces or dashes, with or without padding.
+    fn decode_base32(secret: &str) -> Result<Zeroizing<Vec<u8>>> {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+        let mut bytes = Zeroizing::new(Vec::with_capacity(secret.len() * 5 / 8));
+        let mut buffer = Zeroizing::new(0u16);
+        let mut bits = 0;
+        let characters = secret
+            .bytes()
+            .filter(|character| !matches!(character, b' ' | b'-'))
+            .take_while(|character| *character != b'=');
+        for character in characters {
+            let value = ALPHABET
+                .iter()
+                .position(|symbol| *symbol == character.to_ascii_uppercase())
+                .context("TOTP seed is not valid base32")?;
+            *buffer = (*buffer << 5) | value as u16;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((*buffer >> bits) as u8);
+                *buffer &= (1 << bits) - 1;
+            }
+        }
+        if bytes.is_empty() {
+            anyhow::bail!("TOTP seed is empty");
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::time::Duration,
+    };
+
+    #[test]
+    fn generates_rfc_6238_codes() -> Result<()> {
+        let at = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+        let sha1 = Totp {
+            digits: 8,
+            period: 30,
+            algorithm: TotpAlgorithm::Sha1,
+        };
+        let seed = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(sha1.code(seed, at(59))?.as_str(), "94287082");
+        assert_eq!(sha1.code(seed, at(1_111_111_109))?.as_str(), "07081804");
+        assert_eq!(
+            sha1.code("gezd gnbv gy3t qojq gezd gnbv gy3t qojq", at(59))?.as_str(),
+            "94287082"
+        );
+        assert_eq!(
+            sha1.code(
+                &format!("otpauth://totp/ACME:alice?secret={}&issuer=ACME", seed),
+                at(59)
+            )?
+            .as_str(),
+            "94287082"
+        );
+
+        let sha256 = Totp {
+            digits: 8,
+            period: 30,
+            algorithm: TotpAlgorithm::Sha256,
+        };
+        let seed = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA====";
+        assert_eq!(sha256.code(seed, at(59))?.as_str(), "46119246");
+        assert_eq!(sha256.code(seed, at(1_111_111_109))?.as_str(), "68084774");
+
+        let six = Totp {
+            digits: 6,
+            period: 30,
+            algorithm: TotpAlgorithm::Sha1,
+        };
+        assert_eq!(six.code("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", at(59))?.as_str(), "287082");
+        assert!(six.code("not base32!", at(59)).is_err());
+        assert!(six.code("otpauth://totp/ACME:alice?issuer=ACME", at(59)).is_err());
+        Ok(())
+    }
+}
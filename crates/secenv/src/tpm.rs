@@ -0,0 +1,171 @@
+use {
+    crate::failure::{
+        Categorize,
+        Failure,
+    },
+    anyhow::{
+        Context,
+        Result,
+    },
+    base64::Engine,
+    std::{
+        path::Path,
+        process::{
+            Command,
+            Stdio,
+        },
+    },
+    zeroize::{
+        Zeroize,
+        Zeroizing,
+    },
+};
+
+pub(crate) struct Tpm;
+
+impl Tpm {
+    /// Unseal the public and private parts `tpm2_create` writes, in base64.
+    pub(crate) fn unseal(
+        &self,
+        parent: Option<&str>,
+        pcrs: Option<&str>,
+        sealed: &str,
+        removed_env_vars: &[String],
+    ) -> Result<String> {
+        if let Some(parent) = parent {
+            let persistent = parent
+                .strip_prefix("0x")
+                .is_some_and(|handle| handle.len() == 8 && handle.chars().all(|c| c.is_ascii_hexdigit()));
+            if !persistent {
+                anyhow::bail!("Invalid TPM parent handle '{}': expected e.g. 0x81000001", parent);
+            }
+        }
+        if let Some(pcrs) = pcrs {
+            let valid = pcrs.split_once(':').is_some_and(|(bank, indices)| {
+                !bank.is_empty()
+                    && bank.chars().all(|c| c.is_ascii_alphanumeric())
+                    && !indices.is_empty()
+                    && indices.split(',').all(|index| index.parse::<u8>().is_ok())
+            });
+            if !valid {
+                anyhow::bail!("Invalid TPM PCR selection '{}': expected e.g. sha256:0,7", pcrs);
+            }
+        }
+        if let Some(fixtures) = crate::mock::MockProviders::active() {
+            return fixtures.kms_plaintext(sealed.trim());
+        }
+        let blob = Zeroizing::new(
+            base64::engine::general_purpose::STANDARD
+                .decode(sealed.trim())
+                .context("TPM sealed object is not valid base64")?,
+        );
+        let (public, private) = Self::split(&blob)?;
+
+        let directory = tempfile::tempdir().context("Failed to create a temporary directory for the TPM")?;
+        std::fs::write(directory.path().join("seal.pub"), public).context("Failed to write the sealed object")?;
+        std::fs::write(directory.path().join("seal.priv"), private).context("Failed to write the sealed object")?;
+        let parent = match parent {
+            | Some(parent) => parent,
+            | None => {
+                self.tool(
+                    directory.path(),
+                    "tpm2_createprimary",
+                    &["-Q", "-C", "o", "-g", "sha256", "-G", "ecc", "-c", "primary.ctx"],
+                    removed_env_vars,
+                )
+                .context("Failed to create the TPM primary key")?;
+                "primary.ctx"
+            },
+        };
+        self.tool(
+            directory.path(),
+            "tpm2_load",
+            &[
+                "-Q",
+                "-C",
+                parent,
+                "-u",
+                "seal.pub",
+                "-r",
+                "seal.priv",
+                "-c",
+                "seal.ctx",
+            ],
+            removed_env_vars,
+        )
+        .context("Failed to load the sealed object into the TPM; it was sealed by another TPM or parent key")?;
+        let mut args = vec!["-c", "seal.ctx"];
+        let policy = pcrs.map(|pcrs| format!("pcr:{}", pcrs));
+        if let Some(policy) = &policy {
+            args.extend(["-p", policy]);
+        }
+        let mut plaintext = self
+            .tool(directory.path(), "tpm2_unseal", &args, removed_env_vars)
+            .context("Failed to unseal with the TPM")?;
+        String::from_utf8(std::mem::take(&mut *plaintext)).map_err(|error| {
+            error.into_bytes().zeroize();
+            anyhow::anyhow!("TPM sealed plaintext is not valid UTF-8")
+        })
+    }
+
+    fn split(blob: &[u8]) -> Result<(&[u8], &[u8])> {
+        let size = |part: &[u8]| {
+            part.get(..2)
+                .map(|size| 2 + u16::from_be_bytes([size[0], size[1]]) as usize)
+        };
+        let public = size(blob)
+            .filter(|&end| end <= blob.len())
+            .context("TPM sealed object is truncated")?;
+        let (public, private) = blob.split_at(public);
+        if size(private) != Some(private.len()) {
+            anyhow::bail!("TPM sealed object is not the public part followed by the private part");
+        }
+        Ok((public, private))
+    }
+
+    fn tool(
+        &self,
+        directory: &Path,
+        program: &str,
+        args: &[&str],
+        removed_env_vars: &[String],
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        let mut cmd = Command::new(program);
+        cmd.current_dir(directory).args(args);
+        crate::process::remove_environment_variables(&mut cmd, removed_env_vars);
+
+        let mut output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .with_context(|| format!("Failed to execute {}", program))
+            .categorize(Failure::Provider)?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let error = Failure::Decryption.error(format!("{} failed: {}", program, stderr.trim()));
+            output.stdout.zeroize();
+            return Err(error);
+        }
+        Ok(Zeroizing::new(std::mem::take(&mut output.stdout)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_sealed_objects() -> Result<()> {
+        let blob = [0, 2, 0xaa, 0xbb, 0, 3, 1, 2, 3];
+        let (public, private) = Tpm::split(&blob)?;
+        assert_eq!(public, [0, 2, 0xaa, 0xbb]);
+        assert_eq!(private, [0, 3, 1, 2, 3]);
+        assert!(Tpm::split(&blob[..8]).is_err());
+        assert!(Tpm::split(&[0, 9, 1]).is_err());
+        assert!(Tpm.unseal(Some("owner"), None, "", &[]).is_err());
+        assert!(Tpm.unseal(None, Some("sha256:0,-c"), "", &[]).is_err());
+        Ok(())
+    }
+}